@@ -0,0 +1,171 @@
+//! Throughput benchmarks for the app's own hot paths: checksum verification
+//! of a finished file, the history queries behind the downloads list and
+//! statistics page, and .torrent metainfo parsing and edits.
+//!
+//! Run with `cargo bench --features bench` (add `sha1-asm` to compare the
+//! assembly SHA-1 backend). Criterion keeps the previous run under
+//! `target/criterion`, so regressions show up as a change against it.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use gosh_fetch_lib::db::Database;
+use gosh_fetch_lib::types::{Download, DownloadState, TorrentEdits};
+use gosh_fetch_lib::verify::{hash_file, HashKind};
+use std::path::PathBuf;
+
+fn payload(len: usize) -> Vec<u8> {
+    let mut state: u32 = 0x9E37_79B9;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("gosh-fetch-bench-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn checksum_verification(c: &mut Criterion) {
+    const FILE_SIZE: usize = 32 * 1024 * 1024;
+    let dir = scratch_dir("verify");
+    let path = dir.join("file.bin");
+    std::fs::write(&path, payload(FILE_SIZE)).unwrap();
+
+    let mut group = c.benchmark_group("verify");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(20);
+    for kind in [
+        HashKind::Sha1,
+        HashKind::Sha256,
+        HashKind::Md5,
+        HashKind::Blake3,
+    ] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(kind.as_str()),
+            &kind,
+            |b, &kind| b.iter(|| hash_file(black_box(&path), kind, |_, _| {}).unwrap()),
+        );
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn finished_download(i: usize) -> Download {
+    Download {
+        gid: format!("{:032x}", i),
+        name: format!("file-{}.iso", i),
+        url: Some(format!(
+            "https://mirror{}.example.com/file-{}.iso",
+            i % 20,
+            i
+        )),
+        status: DownloadState::Complete,
+        total_size: 700 * 1024 * 1024,
+        completed_size: 700 * 1024 * 1024,
+        save_path: "/downloads".into(),
+        created_at: format!("2026-01-01T00:{:02}:{:02}+00:00", i / 60 % 60, i % 60),
+        completed_at: Some(format!(
+            "2026-01-01T01:{:02}:{:02}+00:00",
+            i / 60 % 60,
+            i % 60
+        )),
+        ..Default::default()
+    }
+}
+
+fn history_queries(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("history");
+    for rows in [100, 5000] {
+        let dir = scratch_dir(&format!("history-{}", rows));
+        let db = Database::new(&dir).unwrap();
+        for i in 0..rows {
+            db.queue_download(finished_download(i));
+        }
+        runtime.block_on(db.flush_downloads_async()).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("completed", rows), &db, |b, db| {
+            b.iter(|| {
+                runtime
+                    .block_on(db.get_completed_downloads_async())
+                    .unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("stats", rows), &db, |b, db| {
+            b.iter(|| runtime.block_on(db.get_history_stats_async()).unwrap())
+        });
+        // One progress tick's worth of snapshots, written as a batch
+        group.bench_with_input(BenchmarkId::new("flush", rows), &db, |b, db| {
+            b.iter(|| {
+                for i in 0..rows.min(100) {
+                    db.queue_download(finished_download(i));
+                }
+                runtime.block_on(db.flush_downloads_async()).unwrap()
+            })
+        });
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    group.finish();
+}
+
+/// Bencoded multi-file metainfo with `files` entries and one piece hash per MiB.
+fn synthetic_torrent(files: usize) -> Vec<u8> {
+    const FILE_LEN: usize = 3 * 1024 * 1024;
+    let mut out = b"d8:announce31:http://tracker.invalid/announce4:infod5:filesl".to_vec();
+    for i in 0..files {
+        let dir = format!("Season {:02}", i / 50);
+        let name = format!("Episode {:04}.mkv", i);
+        out.extend_from_slice(
+            format!(
+                "d6:lengthi{}e4:pathl{}:{}{}:{}ee",
+                FILE_LEN,
+                dir.len(),
+                dir,
+                name.len(),
+                name
+            )
+            .as_bytes(),
+        );
+    }
+    out.extend_from_slice(b"e4:name5:Bench12:piece lengthi1048576e6:pieces");
+    let pieces = payload(files * 3 * 20);
+    out.extend_from_slice(format!("{}:", pieces.len()).as_bytes());
+    out.extend_from_slice(&pieces);
+    out.extend_from_slice(b"ee");
+    out
+}
+
+fn metainfo_edits(c: &mut Criterion) {
+    let mut group = c.benchmark_group("metainfo");
+    let edits = TorrentEdits {
+        comment: Some("cross-seed".to_string()),
+        ..Default::default()
+    };
+    for files in [1, 100, 2000] {
+        let torrent = synthetic_torrent(files);
+        group.throughput(Throughput::Bytes(torrent.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", files), &torrent, |b, torrent| {
+            b.iter(|| gosh_dl::torrent::Metainfo::parse(black_box(torrent)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("edit", files), &torrent, |b, torrent| {
+            b.iter(|| gosh_fetch_lib::torrent_edit::edit(black_box(torrent), &edits).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    checksum_verification,
+    history_queries,
+    metainfo_edits
+);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+-- Per-download timing for completed history, used for host performance stats
+ALTER TABLE downloads ADD COLUMN elapsed_secs INTEGER;
+ALTER TABLE downloads ADD COLUMN avg_speed INTEGER;
+ALTER TABLE downloads ADD COLUMN host TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_downloads_host ON downloads(host);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (2);
@@ -0,0 +1,11 @@
+-- Automatic categorization rules, checked in `position` order. The default
+-- rule set is inserted by the app (categories::default_rules).
+CREATE TABLE IF NOT EXISTS category_rules (
+    position INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    directory TEXT NOT NULL,
+    extensions TEXT NOT NULL DEFAULT '',
+    mime_types TEXT NOT NULL DEFAULT ''
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (3);
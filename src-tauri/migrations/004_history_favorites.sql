@@ -0,0 +1,4 @@
+-- Favorite history entries are exempt from retention pruning
+ALTER TABLE downloads ADD COLUMN favorite INTEGER NOT NULL DEFAULT 0;
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (4);
@@ -0,0 +1,15 @@
+-- Named download locations offered when adding a download, in `position`
+-- order. The default download directory is always offered as well.
+CREATE TABLE IF NOT EXISTS download_locations (
+    position INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    path TEXT NOT NULL
+);
+
+-- Location last picked for each category ('' for downloads without one)
+CREATE TABLE IF NOT EXISTS location_usage (
+    category TEXT PRIMARY KEY,
+    location TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (5);
@@ -0,0 +1,12 @@
+-- HTTP downloads written to the temp directory, keyed by their staging
+-- folder. `final_path` is set once the file has been moved to `final_dir`.
+CREATE TABLE IF NOT EXISTS staged_downloads (
+    temp_dir TEXT PRIMARY KEY,
+    gid TEXT,
+    final_dir TEXT NOT NULL,
+    final_path TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_staged_downloads_gid ON staged_downloads(gid);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (6);
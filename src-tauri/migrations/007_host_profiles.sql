@@ -0,0 +1,12 @@
+-- Average speed of finished HTTP downloads per host and connection count,
+-- used to pick the connection count for new downloads from the host.
+CREATE TABLE IF NOT EXISTS host_profiles (
+    host TEXT NOT NULL,
+    connections INTEGER NOT NULL,
+    samples INTEGER NOT NULL,
+    avg_speed INTEGER NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (host, connections)
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (7);
@@ -0,0 +1,10 @@
+-- Upload totals per torrent, added up from sampled upload speeds. `size`
+-- is the downloaded byte count the share ratio is taken over.
+CREATE TABLE IF NOT EXISTS seed_stats (
+    gid TEXT PRIMARY KEY,
+    uploaded INTEGER NOT NULL,
+    seed_secs INTEGER NOT NULL,
+    size INTEGER NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (8);
@@ -0,0 +1,9 @@
+-- Named download option presets. `options` is the template's
+-- DownloadOptions as JSON; `trackers` is whitespace-separated.
+CREATE TABLE IF NOT EXISTS download_templates (
+    name TEXT PRIMARY KEY COLLATE NOCASE,
+    options TEXT NOT NULL,
+    trackers TEXT NOT NULL DEFAULT ''
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (9);
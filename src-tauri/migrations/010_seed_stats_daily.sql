@@ -0,0 +1,13 @@
+-- Seeding and bandwidth per torrent per UTC day, for reports. Filled from
+-- the growth of seed_stats each time its totals are saved; `downloaded`
+-- is the growth of its `size`.
+CREATE TABLE IF NOT EXISTS seed_stats_daily (
+    day TEXT NOT NULL,
+    gid TEXT NOT NULL,
+    uploaded INTEGER NOT NULL DEFAULT 0,
+    downloaded INTEGER NOT NULL DEFAULT 0,
+    seed_secs INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, gid)
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (10);
@@ -0,0 +1,9 @@
+-- Hosts found to honour a byte range from the start of a file but send the
+-- whole file for one further in. Their downloads use one connection until
+-- the entry is old enough to check again.
+CREATE TABLE IF NOT EXISTS host_ranges (
+    host TEXT PRIMARY KEY,
+    detected_at TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (11);
@@ -0,0 +1,11 @@
+-- How long HTTP servers took to answer the probe sent before each download
+-- is added, for latency percentiles. Only the newest rows are kept.
+CREATE TABLE IF NOT EXISTS download_latency (
+    gid TEXT PRIMARY KEY,
+    host TEXT NOT NULL,
+    ttfb_ms INTEGER NOT NULL,
+    redirect_ms INTEGER,
+    recorded_at TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (12);
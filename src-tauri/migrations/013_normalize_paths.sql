@@ -0,0 +1,5 @@
+-- Paths were stored as entered, so a "~" or relative download folder
+-- reached the engine and the file manager unexpanded. They are normalized
+-- on save now; the rows already stored are rewritten in Rust alongside
+-- this migration, since SQL can't see the home directory.
+INSERT OR IGNORE INTO schema_version (version) VALUES (13);
@@ -0,0 +1,7 @@
+-- Per-category archive policy. NULL archive_after_days follows the global
+-- setting, 0 never archives the category; an empty archive_directory uses
+-- the global archive folder, a relative one is taken from it.
+ALTER TABLE category_rules ADD COLUMN archive_after_days INTEGER;
+ALTER TABLE category_rules ADD COLUMN archive_directory TEXT NOT NULL DEFAULT '';
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (14);
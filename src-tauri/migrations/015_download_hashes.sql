@@ -0,0 +1,7 @@
+-- Hash of each finished HTTP download's file ("sha256:hex" unless it was
+-- added with a checksum of another kind) and when it was last checked,
+-- for integrity re-checks later on.
+ALTER TABLE downloads ADD COLUMN file_hash TEXT;
+ALTER TABLE downloads ADD COLUMN verified_at TEXT;
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (15);
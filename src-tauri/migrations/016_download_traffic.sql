@@ -0,0 +1,12 @@
+-- Traffic totals per download, added up from sampled download speeds and
+-- completed sizes. `completed` is the size at the last sample, which the
+-- next one is measured against.
+CREATE TABLE IF NOT EXISTS download_traffic (
+    gid TEXT PRIMARY KEY,
+    received INTEGER NOT NULL,
+    payload INTEGER NOT NULL,
+    discarded INTEGER NOT NULL,
+    completed INTEGER NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (16);
@@ -0,0 +1,8 @@
+-- Finished HTTP downloads renamed by a rename rule, with the path their
+-- file was renamed to, so they are reported there after a restart.
+CREATE TABLE IF NOT EXISTS download_renames (
+    gid TEXT PRIMARY KEY,
+    path TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (17);
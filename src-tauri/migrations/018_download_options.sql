@@ -0,0 +1,10 @@
+-- The options each download was added with, and its URL as given, so it
+-- can be added again the same way: a restart after a size mismatch, or a
+-- download the engine lost.
+CREATE TABLE IF NOT EXISTS download_options (
+    gid TEXT PRIMARY KEY,
+    url TEXT,
+    options TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (18);
@@ -0,0 +1,7 @@
+-- Priority, category and add order, which the engine doesn't keep, so the
+-- queue order and categories survive a restart
+ALTER TABLE downloads ADD COLUMN priority TEXT;
+ALTER TABLE downloads ADD COLUMN category TEXT NOT NULL DEFAULT '';
+ALTER TABLE downloads ADD COLUMN added_order INTEGER NOT NULL DEFAULT 0;
+
+INSERT OR IGNORE INTO schema_version (version) VALUES (19);
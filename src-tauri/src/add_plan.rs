@@ -0,0 +1,117 @@
+//! Dry runs of adding a download. `plan_download` and `plan_torrent_file`
+//! go through what `add_download` and `add_torrent_file` decide (template,
+//! settings defaults, category and location, the name the server
+//! suggests, sanitization, connection limits) and report the result
+//! without creating a download, a directory or a temp folder, along with
+//! warnings about anything the add would trip over.
+
+use crate::types::{Download, FileRotation};
+use std::path::Path;
+
+/// A download in the list or the history that adding `url` or a torrent
+/// with `info_hash` would duplicate.
+pub fn find_duplicate<'a>(
+    downloads: &'a [Download],
+    url: Option<&str>,
+    info_hash: Option<&str>,
+) -> Option<&'a Download> {
+    downloads.iter().find(|d| {
+        url.is_some_and(|url| d.url.as_deref() == Some(url))
+            || info_hash.is_some_and(|hash| {
+                d.info_hash
+                    .as_deref()
+                    .is_some_and(|h| h.eq_ignore_ascii_case(hash))
+            })
+    })
+}
+
+pub fn duplicate_warning(download: &Download) -> String {
+    match download.completed_at {
+        Some(_) => format!("Already downloaded as {}", download.name),
+        None => format!("Already in the download list as {}", download.name),
+    }
+}
+
+/// Warnings about saving `name` in `dir`: a name changed to be valid on
+/// disk, or a file already there.
+pub fn target_warnings(
+    dir: &Path,
+    name: &str,
+    requested: Option<&str>,
+    rotation: Option<&FileRotation>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some(requested) = requested.filter(|r| *r != name) {
+        warnings.push(format!(
+            "\"{}\" is not a valid file name; saving as \"{}\"",
+            requested, name
+        ));
+    }
+    let path = dir.join(name);
+    if path.exists() {
+        warnings.push(match rotation {
+            Some(FileRotation::Overwrite) => {
+                format!("{} already exists and will be replaced", path.display())
+            }
+            Some(_) => format!("{} already exists and will be moved aside", path.display()),
+            None => format!("{} already exists", path.display()),
+        });
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DownloadState;
+
+    fn download(name: &str, url: Option<&str>, info_hash: Option<&str>) -> Download {
+        Download {
+            gid: name.into(),
+            name: name.into(),
+            url: url.map(str::to_string),
+            info_hash: info_hash.map(str::to_string),
+            status: DownloadState::Active,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_duplicate() {
+        let downloads = vec![
+            download("a.iso", Some("https://example.com/a.iso"), None),
+            download(
+                "show",
+                None,
+                Some("0123456789ABCDEF0123456789ABCDEF01234567"),
+            ),
+        ];
+        let by_url = find_duplicate(&downloads, Some("https://example.com/a.iso"), None);
+        assert_eq!(by_url.map(|d| d.name.as_str()), Some("a.iso"));
+        let by_hash = find_duplicate(
+            &downloads,
+            None,
+            Some("0123456789abcdef0123456789abcdef01234567"),
+        );
+        assert_eq!(by_hash.map(|d| d.name.as_str()), Some("show"));
+        assert!(find_duplicate(&downloads, Some("https://example.com/b.iso"), None).is_none());
+        assert!(find_duplicate(&downloads, None, None).is_none());
+    }
+
+    #[test]
+    fn test_target_warnings() {
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-add-plan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.iso"), b"x").unwrap();
+
+        assert!(target_warnings(&dir, "b.iso", Some("b.iso"), None).is_empty());
+        assert_eq!(
+            target_warnings(&dir, "a_b.iso", Some("a:b.iso"), None).len(),
+            1
+        );
+        let existing = target_warnings(&dir, "a.iso", None, Some(&FileRotation::Overwrite));
+        assert_eq!(existing.len(), 1);
+        assert!(existing[0].ends_with("will be replaced"));
+    }
+}
@@ -4,8 +4,18 @@
 
 use crate::db::Settings;
 use crate::state::OpenRequest;
-use crate::types::{Download, DownloadFile, DownloadOptions, GlobalStat, MagnetInfo, TorrentInfo};
-use crate::validation::{validate_download_url, validate_torrent_path};
+use crate::tasks::TaskProgress;
+use crate::types::{
+    AddPlan, AddUrlsResult, Capabilities, CategoryRule, CrashRecoveryInfo, DbMaintenanceReport,
+    Download, DownloadChanges, DownloadDetails, DownloadFile, DownloadLocation,
+    DownloadLocationInfo, DownloadOptions, DownloadPreview, DownloadTemplate, EventFilter,
+    FileFilter, GlobalStat, HistoryStats, HostActivity, HostProfile, IntegrityCheck,
+    LoadGuardStatus, MagnetInfo, OrphanedPartial, ProgressSnapshot, RenamePreview, RenameRule,
+    SettingIssue, SwarmGeography, TorrentInfo, UiFilters, UpdateInfo, UrlListImport,
+};
+use crate::validation::{
+    validate_download_url, validate_http_url, validate_metalink_path, validate_torrent_path,
+};
 use crate::{commands, AppState, Error, Result};
 use tauri::State;
 
@@ -28,13 +38,42 @@ pub async fn add_urls(
     state: State<'_, AppState>,
     urls: Vec<String>,
     options: Option<DownloadOptions>,
-) -> Result<Vec<String>> {
+) -> Result<AddUrlsResult> {
     for url in &urls {
         validate_download_url(url)?;
     }
     commands::add_urls(&state, urls, options).await
 }
 
+#[tauri::command]
+pub async fn add_metalink_file(
+    state: State<'_, AppState>,
+    file_path: String,
+    options: Option<DownloadOptions>,
+) -> Result<Vec<String>> {
+    validate_metalink_path(&file_path)?;
+    commands::add_metalink_file(&state, file_path, options).await
+}
+
+#[tauri::command]
+pub async fn import_url_list(
+    state: State<'_, AppState>,
+    source: String,
+    extensions: Option<Vec<String>>,
+) -> Result<UrlListImport> {
+    commands::import_url_list(&state, source, extensions).await
+}
+
+#[tauri::command]
+pub async fn plan_download(
+    state: State<'_, AppState>,
+    url: String,
+    options: Option<DownloadOptions>,
+) -> Result<AddPlan> {
+    validate_download_url(&url)?;
+    commands::plan_download(&state, url, options).await
+}
+
 #[tauri::command]
 pub async fn pause_download(state: State<'_, AppState>, gid: String) -> Result<()> {
     commands::pause_download(&state, gid).await
@@ -77,6 +116,23 @@ pub async fn get_download_status(state: State<'_, AppState>, gid: String) -> Res
     commands::get_download_status(&state, gid).await
 }
 
+#[tauri::command]
+pub async fn get_download_details(
+    state: State<'_, AppState>,
+    gid: String,
+) -> Result<DownloadDetails> {
+    commands::get_download_details(&state, gid).await
+}
+
+#[tauri::command]
+pub async fn verify_download(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    gid: String,
+) -> Result<IntegrityCheck> {
+    commands::verify_download(&state, &app, gid).await
+}
+
 #[tauri::command]
 pub async fn get_all_downloads(state: State<'_, AppState>) -> Result<Vec<Download>> {
     commands::get_all_downloads(&state).await
@@ -92,6 +148,20 @@ pub async fn get_global_stats(state: State<'_, AppState>) -> Result<GlobalStat>
     commands::get_global_stats(&state).await
 }
 
+#[tauri::command]
+pub async fn get_host_stats(state: State<'_, AppState>) -> Result<Vec<HostActivity>> {
+    commands::get_host_stats(&state).await
+}
+
+#[tauri::command]
+pub async fn preview_download(
+    state: State<'_, AppState>,
+    gid: String,
+    bytes: Option<u64>,
+) -> Result<DownloadPreview> {
+    commands::preview_download(&state, gid, bytes).await
+}
+
 #[tauri::command]
 pub async fn set_speed_limit(
     state: State<'_, AppState>,
@@ -102,20 +172,19 @@ pub async fn set_speed_limit(
 }
 
 #[tauri::command]
-pub async fn set_priority(
-    state: State<'_, AppState>,
-    gid: String,
-    priority: String,
-) -> Result<()> {
-    let engine = state.get_engine().await?;
-    let id = crate::engine_adapter::parse_gid_public(&gid)?;
+pub async fn set_priority(state: State<'_, AppState>, gid: String, priority: String) -> Result<()> {
+    let adapter = state.get_adapter().await?;
     let priority: gosh_dl::DownloadPriority = priority.parse().map_err(|_| {
         Error::InvalidInput(format!(
             "Invalid priority: {}. Use low, normal, high, or critical.",
             priority
         ))
     })?;
-    engine.set_priority(id, priority)?;
+    adapter.set_priority(&gid, priority)?;
+    // Kept for the next session without waiting for a progress report
+    if let Some(download) = adapter.get_status(&gid) {
+        state.get_db().await?.queue_download(download);
+    }
     Ok(())
 }
 
@@ -130,9 +199,7 @@ pub async fn set_schedule_rules(
     state: State<'_, AppState>,
     rules: Vec<gosh_dl::ScheduleRule>,
 ) -> Result<()> {
-    let engine = state.get_engine().await?;
-    engine.set_schedule_rules(rules);
-    Ok(())
+    commands::set_schedule_rules(&state, rules).await
 }
 
 // ---------------------------------------------------------------------------
@@ -149,6 +216,26 @@ pub async fn add_torrent_file(
     commands::add_torrent_file(&state, file_path, options).await
 }
 
+#[tauri::command]
+pub async fn plan_torrent_file(
+    state: State<'_, AppState>,
+    file_path: String,
+    options: Option<DownloadOptions>,
+) -> Result<AddPlan> {
+    validate_torrent_path(&file_path)?;
+    commands::plan_torrent_file(&state, file_path, options).await
+}
+
+#[tauri::command]
+pub async fn add_torrent_url(
+    state: State<'_, AppState>,
+    url: String,
+    options: Option<DownloadOptions>,
+) -> Result<String> {
+    validate_http_url(&url)?;
+    commands::add_torrent_url(&state, url, options).await
+}
+
 #[tauri::command]
 pub async fn add_magnet(
     state: State<'_, AppState>,
@@ -175,6 +262,15 @@ pub async fn select_torrent_files(
     commands::select_torrent_files(&state, gid, file_indices).await
 }
 
+#[tauri::command]
+pub async fn apply_file_filter(
+    state: State<'_, AppState>,
+    gid: String,
+    rules: FileFilter,
+) -> Result<Vec<u32>> {
+    commands::apply_file_filter(&state, gid, rules).await
+}
+
 #[tauri::command]
 pub fn parse_torrent_file(file_path: String) -> Result<TorrentInfo> {
     validate_torrent_path(&file_path)?;
@@ -187,11 +283,16 @@ pub fn parse_magnet_uri(magnet_uri: String) -> Result<MagnetInfo> {
 }
 
 #[tauri::command]
-pub async fn get_peers(
+pub async fn get_peers(state: State<'_, AppState>, gid: String) -> Result<Vec<serde_json::Value>> {
+    commands::get_peers(&state, gid).await
+}
+
+#[tauri::command]
+pub async fn get_swarm_geography(
     state: State<'_, AppState>,
     gid: String,
-) -> Result<Vec<serde_json::Value>> {
-    commands::get_peers(&state, gid).await
+) -> Result<SwarmGeography> {
+    commands::get_swarm_geography(&state, gid).await
 }
 
 // ---------------------------------------------------------------------------
@@ -291,11 +392,91 @@ pub async fn apply_settings_to_engine(
     commands::apply_settings_to_engine(&state, settings).await
 }
 
+#[tauri::command]
+pub async fn validate_settings(settings: Settings) -> Vec<SettingIssue> {
+    commands::validate_settings(settings).await
+}
+
 #[tauri::command]
 pub fn get_user_agent_presets() -> Vec<(String, String)> {
     commands::get_user_agent_presets()
 }
 
+#[tauri::command]
+pub async fn get_categories(state: State<'_, AppState>) -> Result<Vec<CategoryRule>> {
+    commands::get_categories(&state).await
+}
+
+#[tauri::command]
+pub async fn set_categories(
+    state: State<'_, AppState>,
+    rules: Vec<CategoryRule>,
+) -> Result<Vec<CategoryRule>> {
+    commands::set_categories(&state, rules).await
+}
+
+#[tauri::command]
+pub async fn reset_categories(state: State<'_, AppState>) -> Result<Vec<CategoryRule>> {
+    commands::reset_categories(&state).await
+}
+
+#[tauri::command]
+pub async fn list_download_locations(
+    state: State<'_, AppState>,
+) -> Result<Vec<DownloadLocationInfo>> {
+    commands::list_download_locations(&state).await
+}
+
+#[tauri::command]
+pub async fn set_download_locations(
+    state: State<'_, AppState>,
+    locations: Vec<DownloadLocation>,
+) -> Result<Vec<DownloadLocationInfo>> {
+    commands::set_download_locations(&state, locations).await
+}
+
+#[tauri::command]
+pub async fn get_download_templates(state: State<'_, AppState>) -> Result<Vec<DownloadTemplate>> {
+    commands::get_download_templates(&state).await
+}
+
+#[tauri::command]
+pub async fn save_download_template(
+    state: State<'_, AppState>,
+    template: DownloadTemplate,
+) -> Result<DownloadTemplate> {
+    commands::save_download_template(&state, template).await
+}
+
+#[tauri::command]
+pub async fn delete_download_template(state: State<'_, AppState>, name: String) -> Result<()> {
+    commands::delete_download_template(&state, name).await
+}
+
+#[tauri::command]
+pub async fn get_rename_rules(state: State<'_, AppState>) -> Result<Vec<RenameRule>> {
+    commands::get_rename_rules(&state).await
+}
+
+#[tauri::command]
+pub async fn set_rename_rules(
+    state: State<'_, AppState>,
+    rules: Vec<RenameRule>,
+) -> Result<Vec<RenameRule>> {
+    commands::set_rename_rules(&state, rules).await
+}
+
+#[tauri::command]
+pub async fn preview_rename(
+    state: State<'_, AppState>,
+    name: String,
+    url: Option<String>,
+    category: Option<String>,
+    rules: Option<Vec<RenameRule>>,
+) -> Result<Option<RenamePreview>> {
+    commands::preview_rename(&state, name, url, category, rules).await
+}
+
 // ---------------------------------------------------------------------------
 // System
 // ---------------------------------------------------------------------------
@@ -306,13 +487,96 @@ pub async fn get_engine_version(state: State<'_, AppState>) -> Result<serde_json
 }
 
 #[tauri::command]
-pub fn open_download_folder(path: String) -> Result<()> {
-    commands::open_download_folder(path)
+pub async fn get_engine_health(state: State<'_, AppState>) -> Result<serde_json::Value> {
+    commands::get_engine_health(&state).await
+}
+
+#[tauri::command]
+pub async fn get_ui_snapshot(
+    state: State<'_, AppState>,
+    since_version: Option<u64>,
+) -> Result<serde_json::Value> {
+    commands::get_ui_snapshot(&state, since_version).await
+}
+
+#[tauri::command]
+pub async fn get_changes(
+    state: State<'_, AppState>,
+    since_version: Option<u64>,
+    wait_ms: Option<u64>,
+) -> Result<DownloadChanges> {
+    commands::get_changes(&state, since_version, wait_ms).await
+}
+
+#[tauri::command]
+pub fn get_progress_snapshots(
+    state: State<'_, AppState>,
+    gids: Option<Vec<String>>,
+) -> Vec<ProgressSnapshot> {
+    commands::get_progress_snapshots(&state, gids)
+}
+
+#[tauri::command]
+pub fn set_ui_filters(state: State<'_, AppState>, filters: UiFilters) {
+    commands::set_ui_filters(&state, filters)
+}
+
+#[tauri::command]
+pub fn list_tasks(state: State<'_, AppState>) -> Vec<TaskProgress> {
+    commands::list_tasks(&state)
+}
+
+#[tauri::command]
+pub fn cancel_task(state: State<'_, AppState>, id: String) -> Result<()> {
+    commands::cancel_task(&state, id)
+}
+
+#[tauri::command]
+pub fn subscribe_download_events(
+    state: State<'_, AppState>,
+    filter: EventFilter,
+) -> Result<String> {
+    commands::subscribe_download_events(&state, filter)
+}
+
+#[tauri::command]
+pub fn unsubscribe_download_events(state: State<'_, AppState>, id: String) -> Result<()> {
+    commands::unsubscribe_download_events(&state, id)
+}
+
+#[tauri::command]
+pub fn get_load_guard_status(state: State<'_, AppState>) -> LoadGuardStatus {
+    commands::get_load_guard_status(&state)
+}
+
+#[tauri::command]
+pub async fn restart_engine(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<()> {
+    commands::restart_engine(&state, app).await
+}
+
+#[tauri::command]
+pub async fn open_download_folder(state: State<'_, AppState>, path: String) -> Result<()> {
+    commands::open_download_folder(&state, path).await
 }
 
 #[tauri::command]
-pub fn open_file_location(file_path: String) -> Result<()> {
-    commands::open_file_location(file_path)
+pub async fn open_file_location(state: State<'_, AppState>, file_path: String) -> Result<()> {
+    commands::open_file_location(&state, file_path).await
+}
+
+#[tauri::command]
+pub async fn open_download_file(state: State<'_, AppState>, gid: String) -> Result<()> {
+    commands::open_download_file(&state, gid).await
+}
+
+#[tauri::command]
+pub async fn reveal_download(state: State<'_, AppState>, gid: String) -> Result<()> {
+    commands::reveal_download(&state, gid).await
+}
+
+#[tauri::command]
+pub async fn get_download_path(state: State<'_, AppState>, gid: String) -> Result<String> {
+    commands::get_download_path(&state, gid).await
 }
 
 #[tauri::command]
@@ -325,11 +589,27 @@ pub fn get_app_version(app: tauri::AppHandle) -> String {
     commands::get_app_version(&app)
 }
 
+#[tauri::command]
+pub async fn get_update_info(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<UpdateInfo> {
+    commands::get_update_info(&state, &app).await
+}
+
 #[tauri::command]
 pub fn get_app_info(app: tauri::AppHandle) -> serde_json::Value {
     commands::get_app_info(&app)
 }
 
+#[tauri::command]
+pub async fn get_capabilities(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Capabilities> {
+    Ok(commands::get_capabilities(&state, &app).await)
+}
+
 #[tauri::command]
 pub fn get_disk_space(path: Option<String>) -> Result<serde_json::Value> {
     commands::get_disk_space(path)
@@ -364,11 +644,35 @@ pub async fn db_remove_download(state: State<'_, AppState>, gid: String) -> Resu
     commands::db_remove_download(&state, gid).await
 }
 
+#[tauri::command]
+pub async fn db_set_favorite(
+    state: State<'_, AppState>,
+    gid: String,
+    favorite: bool,
+) -> Result<()> {
+    commands::db_set_favorite(&state, gid, favorite).await
+}
+
 #[tauri::command]
 pub async fn db_clear_history(state: State<'_, AppState>) -> Result<()> {
     commands::db_clear_history(&state).await
 }
 
+#[tauri::command]
+pub async fn archive_downloads(state: State<'_, AppState>) -> Result<u32> {
+    commands::archive_downloads(&state).await
+}
+
+#[tauri::command]
+pub async fn db_get_history_stats(state: State<'_, AppState>) -> Result<HistoryStats> {
+    commands::db_get_history_stats(&state).await
+}
+
+#[tauri::command]
+pub async fn get_host_profiles(state: State<'_, AppState>) -> Result<Vec<HostProfile>> {
+    commands::get_host_profiles(&state).await
+}
+
 #[tauri::command]
 pub async fn db_get_settings(state: State<'_, AppState>) -> Result<Settings> {
     commands::db_get_settings(&state).await
@@ -379,6 +683,36 @@ pub async fn db_save_settings(state: State<'_, AppState>, settings: Settings) ->
     commands::db_save_settings(&state, settings).await
 }
 
+#[tauri::command]
+pub async fn db_maintenance(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    report_only: Option<bool>,
+) -> Result<DbMaintenanceReport> {
+    commands::db_maintenance(&state, &app, report_only.unwrap_or(false)).await
+}
+
+#[tauri::command]
+pub async fn db_export_history(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    format: String,
+) -> Result<String> {
+    commands::db_export_history(&state, &app, path, format).await
+}
+
+#[tauri::command]
+pub async fn export_stats(
+    state: State<'_, AppState>,
+    path: String,
+    format: String,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<u32> {
+    commands::export_stats(&state, path, format, from, to).await
+}
+
 #[tauri::command]
 pub async fn db_load_incomplete(state: State<'_, AppState>) -> Result<Vec<Download>> {
     commands::db_load_incomplete(&state).await
@@ -391,3 +725,39 @@ pub async fn db_load_incomplete(state: State<'_, AppState>) -> Result<Vec<Downlo
 pub fn get_pending_open_requests(state: State<'_, AppState>) -> Vec<OpenRequest> {
     state.take_pending_open_requests()
 }
+
+#[tauri::command]
+pub fn get_crash_recovery_info(state: State<'_, AppState>) -> Option<CrashRecoveryInfo> {
+    commands::get_crash_recovery_info(&state)
+}
+
+#[tauri::command]
+pub async fn restore_incomplete(state: State<'_, AppState>) -> Result<Vec<String>> {
+    commands::restore_incomplete(&state).await
+}
+
+#[tauri::command]
+pub async fn restore_session(
+    state: State<'_, AppState>,
+    selection: Vec<String>,
+) -> Result<Vec<String>> {
+    commands::restore_session(&state, selection).await
+}
+
+#[tauri::command]
+pub async fn list_orphaned_partials(state: State<'_, AppState>) -> Result<Vec<OrphanedPartial>> {
+    commands::list_orphaned_partials(&state).await
+}
+
+#[tauri::command]
+pub async fn resume_orphaned_partial(state: State<'_, AppState>, path: String) -> Result<String> {
+    commands::resume_orphaned_partial(&state, path).await
+}
+
+#[tauri::command]
+pub async fn delete_orphaned_partials(
+    state: State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<u32> {
+    commands::delete_orphaned_partials(&state, paths).await
+}
@@ -0,0 +1,297 @@
+//! Archiving old finished downloads. Once a finished download is older
+//! than its category's `archive_after_days` (or the global one), its file
+//! or folder is moved to the archive folder and its history entry updated
+//! to point there. An archive folder that doesn't exist is taken as a
+//! drive that isn't mounted, and its downloads wait for a later run;
+//! subfolders of the global archive folder are created as needed.
+
+use crate::categories::{find_by_name, match_extension, resolve_directory};
+use crate::db::{expand_tilde, Settings};
+use crate::staging::{move_file, unique_destination};
+use crate::types::{CategoryRule, Download, DownloadType};
+use crate::{AppState, Error, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+/// Where and after how long a finished download is archived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivePolicy {
+    pub after_days: u32,
+    pub directory: PathBuf,
+    /// Must already exist for the policy to apply: the global archive
+    /// folder, or `directory` itself when a category names an absolute one
+    pub root: PathBuf,
+}
+
+/// The category rule a finished download was saved under: the one it
+/// reported, else the one whose folder it is in, else the one matching its
+/// extension.
+pub fn category_of<'a>(
+    download: &Download,
+    settings: &Settings,
+    rules: &'a [CategoryRule],
+) -> Option<&'a CategoryRule> {
+    if !download.category.is_empty() {
+        return find_by_name(rules, &download.category);
+    }
+    let save_path = Path::new(&download.save_path);
+    rules
+        .iter()
+        .find(|r| resolve_directory(&settings.download_path, &r.directory) == save_path)
+        .or_else(|| match_extension(rules, &download.name))
+}
+
+/// The archive policy for downloads of `rule`'s category, or for
+/// uncategorized ones. None when they aren't archived.
+pub fn policy_for(settings: &Settings, rule: Option<&CategoryRule>) -> Option<ArchivePolicy> {
+    let after_days = match rule.and_then(|r| r.archive_after_days) {
+        Some(days) => days,
+        None if settings.archive_enabled => settings.archive_after_days,
+        None => return None,
+    };
+    if after_days == 0 {
+        return None;
+    }
+    let archive_path = settings.archive_path.trim();
+    let global = (!archive_path.is_empty()).then(|| PathBuf::from(expand_tilde(archive_path)));
+    let directory = rule
+        .map(|r| r.archive_directory.trim())
+        .filter(|d| !d.is_empty());
+    let (directory, root) = match directory {
+        Some(directory) if Path::new(&expand_tilde(directory)).is_absolute() => {
+            let directory = PathBuf::from(expand_tilde(directory));
+            (directory.clone(), directory)
+        }
+        Some(directory) => {
+            let global = global?;
+            (global.join(directory), global)
+        }
+        None => (global.clone()?, global?),
+    };
+    Some(ArchivePolicy {
+        after_days,
+        directory,
+        root,
+    })
+}
+
+/// Whether `download` finished at least `after_days` days before `now`.
+pub fn is_due(download: &Download, after_days: u32, now: DateTime<Utc>) -> bool {
+    download
+        .completed_at
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|t| {
+            now.signed_duration_since(t) >= chrono::Duration::days(i64::from(after_days))
+        })
+}
+
+/// Move a file or folder into `dest_dir`, renaming it if the name is
+/// taken there. Folders on another drive are moved file by file.
+pub fn move_entry(source: &Path, dest_dir: &Path) -> Result<PathBuf> {
+    if !source.is_dir() {
+        return move_file(source, dest_dir, |_, _| true)?
+            .ok_or_else(|| Error::Io(std::io::Error::other("move was cancelled")));
+    }
+    let name = source
+        .file_name()
+        .ok_or_else(|| Error::InvalidInput(format!("Not a folder: {}", source.display())))?
+        .to_string_lossy()
+        .to_string();
+    let dest = unique_destination(dest_dir, &name);
+    if std::fs::rename(source, &dest).is_err() {
+        move_tree(source, &dest)?;
+    }
+    Ok(dest)
+}
+
+fn move_tree(source: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            move_tree(&path, &dest.join(entry.file_name()))?;
+        } else {
+            move_file(&path, dest, |_, _| true)?;
+        }
+    }
+    std::fs::remove_dir(source)?;
+    Ok(())
+}
+
+/// Archive the finished downloads that are due. Torrents the engine still
+/// has are skipped, since they seed from their files. Returns how many
+/// were moved.
+pub async fn run(state: &AppState) -> Result<u32> {
+    let db = state.get_db().await?;
+    let settings = db.get_settings_async().await?;
+    let rules = db.get_category_rules_async().await?;
+    let adapter = state.get_adapter().await?;
+    let now = Utc::now();
+    let mut archived = 0;
+    for mut download in db.get_completed_downloads_async().await? {
+        let in_engine = adapter.get_status(&download.gid);
+        if in_engine
+            .as_ref()
+            .is_some_and(|d| d.download_type != DownloadType::Http)
+        {
+            continue;
+        }
+        if let Some(current) = &in_engine {
+            download.category = current.category.clone();
+        }
+        let Some(policy) = policy_for(&settings, category_of(&download, &settings, &rules)) else {
+            continue;
+        };
+        let source = Path::new(&download.save_path).join(&download.name);
+        if !is_due(&download, policy.after_days, now)
+            || source.starts_with(&policy.directory)
+            || !source.exists()
+        {
+            continue;
+        }
+        if !policy.root.is_dir() {
+            log::debug!(
+                "Archive folder {} isn't available; skipping {}",
+                policy.root.display(),
+                download.name
+            );
+            continue;
+        }
+        let directory = policy.directory.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            crate::locations::prepare_directory(&directory)
+                .and_then(|()| move_entry(&source, &directory))
+        })
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        let dest = match result {
+            Ok(dest) => dest,
+            Err(e) => {
+                log::warn!(
+                    "Failed to archive {} to {}: {}",
+                    download.name,
+                    policy.directory.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        log::info!("Archived {} to {}", download.name, dest.display());
+        if in_engine.is_some() {
+            adapter.set_moved(&download.gid, dest.clone());
+        }
+        download.save_path = policy.directory.to_string_lossy().into_owned();
+        if let Some(name) = dest.file_name() {
+            download.name = name.to_string_lossy().into_owned();
+        }
+        db.save_download_async(download).await?;
+        archived += 1;
+    }
+    Ok(archived)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DownloadState;
+
+    fn download(name: &str, save_path: &str, completed_at: Option<DateTime<Utc>>) -> Download {
+        Download {
+            gid: name.into(),
+            name: name.into(),
+            status: DownloadState::Complete,
+            save_path: save_path.into(),
+            completed_at: completed_at.map(|t| t.to_rfc3339()),
+            ..Default::default()
+        }
+    }
+
+    fn rule(name: &str, archive_after_days: Option<u32>, archive_directory: &str) -> CategoryRule {
+        CategoryRule {
+            name: name.into(),
+            directory: name.into(),
+            extensions: vec![name.to_lowercase()],
+            mime_types: Vec::new(),
+            archive_after_days,
+            archive_directory: archive_directory.into(),
+        }
+    }
+
+    #[test]
+    fn test_policy_for() {
+        let archive = std::env::temp_dir().join("archive");
+        let mut settings = Settings {
+            download_path: "/downloads".into(),
+            archive_path: archive.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let follows = rule("Iso", None, "Images");
+        let never = rule("Mkv", Some(0), "");
+        let own = rule("Zip", Some(3), "/mnt/backup/zips");
+
+        assert_eq!(policy_for(&settings, Some(&follows)), None);
+        assert_eq!(
+            policy_for(&settings, Some(&own)),
+            Some(ArchivePolicy {
+                after_days: 3,
+                directory: "/mnt/backup/zips".into(),
+                root: "/mnt/backup/zips".into(),
+            })
+        );
+
+        settings.archive_enabled = true;
+        let policy = policy_for(&settings, Some(&follows)).unwrap();
+        assert_eq!(
+            (policy.after_days, &policy.directory, &policy.root),
+            (30, &archive.join("Images"), &archive)
+        );
+        assert_eq!(policy_for(&settings, None).unwrap().directory, archive);
+        assert_eq!(policy_for(&settings, Some(&never)), None);
+
+        settings.archive_path.clear();
+        assert_eq!(policy_for(&settings, Some(&follows)), None);
+
+        let rules = [follows, never, own];
+        let mut download = download("a.zip", "/downloads/Iso", None);
+        assert_eq!(
+            category_of(&download, &settings, &rules).unwrap().name,
+            "Iso"
+        );
+        download.save_path = "/elsewhere".into();
+        assert_eq!(
+            category_of(&download, &settings, &rules).unwrap().name,
+            "Zip"
+        );
+    }
+
+    #[test]
+    fn test_is_due_and_move_entry() {
+        let now = Utc::now();
+        let mut download = download(
+            "a.iso",
+            "/downloads",
+            Some(now - chrono::Duration::days(10)),
+        );
+        assert!(is_due(&download, 10, now));
+        assert!(!is_due(&download, 11, now));
+        download.completed_at = None;
+        assert!(!is_due(&download, 1, now));
+
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-archive-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("show/season")).unwrap();
+        std::fs::create_dir_all(dir.join("archive/show")).unwrap();
+        std::fs::write(dir.join("show/season/e1.mkv"), "x").unwrap();
+        let moved = move_entry(&dir.join("show"), &dir.join("archive")).unwrap();
+        assert_eq!(moved, dir.join("archive/show (1)"));
+        assert!(moved.join("season/e1.mkv").is_file());
+        assert!(!dir.join("show").exists());
+
+        move_tree(&moved, &dir.join("copied")).unwrap();
+        assert!(dir.join("copied/season/e1.mkv").is_file());
+        assert!(!moved.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
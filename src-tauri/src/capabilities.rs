@@ -0,0 +1,162 @@
+//! What this build can do, for frontends and browser extensions that
+//! adapt their UI to it instead of guessing from version numbers. The
+//! lists only name what a client can actually use through the command
+//! API; anything missing (FTP, SFTP) is not available in this build.
+//! Protocol encryption, uTP and web seeds are listed while the running
+//! engine has them turned on. Metalink files are read by the app and added
+//! as HTTP downloads with mirrors, so they are a feature rather than a
+//! protocol.
+
+use crate::constants::{API_VERSION, ENGINE_NAME, ENGINE_VERSION};
+use crate::types::{Capabilities, ProtocolSupport};
+use gosh_dl::config::{EncryptionConfig, EncryptionPolicy, TransportPolicy};
+use gosh_dl::EngineConfig;
+
+/// URL schemes and sources `add_download`/`add_torrent_file` accept, with
+/// the protocol version spoken where there is a choice.
+const PROTOCOLS: &[(&str, Option<&str>)] = &[
+    ("http", None),
+    ("https", None),
+    // Presigned with AWS Signature Version 4 and fetched over HTTPS
+    ("s3", Some("sigv4")),
+    // BitTorrent v1 info hashes (btih) only
+    ("magnet", Some("v1")),
+    ("torrent", Some("v1")),
+];
+
+/// Optional features of the engine and the app that clients can use.
+const FEATURES: &[&str] = &[
+    "dht",
+    "pex",
+    "lpd",
+    "private-torrents",
+    "selective-files",
+    "sequential",
+    "seeding-limits",
+    "peer-geoip",
+    "mirrors",
+    "checksum-sha256",
+    "checksum-md5",
+    "checksum-sha1",
+    "checksum-blake3",
+    "proxy-http",
+    "proxy-socks5",
+    "recursive-http",
+    "speed-schedule",
+    "priorities",
+    "file-rotation",
+    "event-subscriptions",
+    "metalink",
+];
+
+/// Features the engine's config turns on or off: protocol encryption
+/// (MSE/PE), uTP and web seeds (BEP 19).
+fn configured_features(config: &EngineConfig) -> Vec<&'static str> {
+    let torrent = &config.torrent;
+    let utp = torrent.utp.enabled && torrent.utp.policy != TransportPolicy::TcpOnly;
+    [
+        ("encryption", encryption_enabled(&torrent.encryption)),
+        ("utp", utp),
+        ("webseed", torrent.webseed.enabled),
+    ]
+    .into_iter()
+    .filter_map(|(feature, on)| on.then_some(feature))
+    .collect()
+}
+
+/// Whether the engine encrypts peer connections with `encryption`. It
+/// treats its untouched default (`Preferred` with every other field at its
+/// default) as plaintext, so only a changed config turns MSE on.
+pub fn encryption_enabled(encryption: &EncryptionConfig) -> bool {
+    let default = EncryptionConfig::default();
+    encryption.policy != EncryptionPolicy::Disabled
+        && !(encryption.policy == default.policy
+            && encryption.allow_plaintext == default.allow_plaintext
+            && encryption.allow_rc4 == default.allow_rc4
+            && encryption.min_padding == default.min_padding
+            && encryption.max_padding == default.max_padding)
+}
+
+/// The capabilities of this build. `config` is the running engine's; the
+/// features it controls are left out while the engine isn't running.
+pub fn capabilities(app_version: &str, config: Option<&EngineConfig>) -> Capabilities {
+    Capabilities {
+        app_version: app_version.to_string(),
+        engine: ENGINE_NAME.to_string(),
+        engine_version: ENGINE_VERSION.to_string(),
+        api_version: API_VERSION,
+        protocols: PROTOCOLS
+            .iter()
+            .map(|(name, version)| ProtocolSupport {
+                name: name.to_string(),
+                version: version.map(str::to_string),
+            })
+            .collect(),
+        features: FEATURES
+            .iter()
+            .copied()
+            .chain(config.map(configured_features).unwrap_or_default())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::validate_download_url;
+
+    #[test]
+    fn test_listed_schemes_are_accepted() {
+        let urls = [
+            ("http", "http://example.com/file.iso"),
+            ("https", "https://example.com/file.iso"),
+            ("s3", "s3://bucket/file.iso"),
+            (
+                "magnet",
+                "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567",
+            ),
+        ];
+        let caps = capabilities("3.0.0", None);
+        for (scheme, url) in urls {
+            assert!(
+                caps.protocols.iter().any(|p| p.name == scheme),
+                "{} not listed",
+                scheme
+            );
+            assert!(validate_download_url(url).is_ok(), "{} rejected", url);
+        }
+        assert!(!caps.protocols.iter().any(|p| p.name == "ftp"));
+        assert!(validate_download_url("ftp://example.com/file.iso").is_err());
+    }
+
+    #[test]
+    fn test_configured_features() {
+        let mut config = EngineConfig::default();
+        // The engine's defaults: web seeds on, uTP off, plaintext peers
+        assert_eq!(configured_features(&config), vec!["webseed"]);
+
+        config.torrent.utp.enabled = true;
+        config.torrent.webseed.enabled = false;
+        config.torrent.encryption.policy = EncryptionPolicy::Required;
+        assert_eq!(configured_features(&config), vec!["encryption", "utp"]);
+
+        config.torrent.utp.policy = TransportPolicy::TcpOnly;
+        config.torrent.encryption.policy = EncryptionPolicy::Disabled;
+        assert!(configured_features(&config).is_empty());
+
+        let caps = capabilities("3.0.0", Some(&config));
+        assert!(caps.features.iter().any(|f| f == "dht"));
+        assert!(!caps.features.iter().any(|f| f == "utp"));
+    }
+
+    #[test]
+    fn test_encryption_enabled() {
+        let mut encryption = EncryptionConfig::default();
+        assert!(!encryption_enabled(&encryption));
+        encryption.min_padding = 1;
+        assert!(encryption_enabled(&encryption));
+        encryption.policy = EncryptionPolicy::Disabled;
+        assert!(!encryption_enabled(&encryption));
+    }
+}
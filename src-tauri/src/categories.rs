@@ -0,0 +1,285 @@
+//! Automatic categories: downloads added without an explicit directory are
+//! saved under the directory of the first rule matching their file
+//! extension or, failing that, the MIME type the server reports.
+
+use crate::types::CategoryRule;
+use crate::{Error, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a HEAD request for the content type may hold up adding a URL.
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// The rule set a fresh install starts with, and what a reset restores.
+pub fn default_rules() -> Vec<CategoryRule> {
+    fn rule(name: &str, extensions: &[&str], mime_types: &[&str]) -> CategoryRule {
+        CategoryRule {
+            name: name.to_string(),
+            directory: name.to_string(),
+            extensions: extensions.iter().map(|s| s.to_string()).collect(),
+            mime_types: mime_types.iter().map(|s| s.to_string()).collect(),
+            archive_after_days: None,
+            archive_directory: String::new(),
+        }
+    }
+    vec![
+        rule(
+            "Movies",
+            &[
+                "mp4", "mkv", "avi", "mov", "webm", "m4v", "wmv", "flv", "mpg", "mpeg",
+            ],
+            &["video/*"],
+        ),
+        rule(
+            "Music",
+            &["mp3", "flac", "ogg", "opus", "wav", "m4a", "aac", "wma"],
+            &["audio/*"],
+        ),
+        rule(
+            "Software",
+            &[
+                "iso", "img", "exe", "msi", "dmg", "pkg", "deb", "rpm", "appimage", "apk",
+            ],
+            &[
+                "application/x-iso9660-image",
+                "application/x-msdownload",
+                "application/x-msi",
+                "application/vnd.microsoft.portable-executable",
+                "application/x-apple-diskimage",
+                "application/vnd.debian.binary-package",
+                "application/x-rpm",
+                "application/vnd.android.package-archive",
+            ],
+        ),
+        rule(
+            "Archives",
+            &["zip", "rar", "7z", "tar", "gz", "tgz", "xz", "bz2", "zst"],
+            &[
+                "application/zip",
+                "application/vnd.rar",
+                "application/x-7z-compressed",
+                "application/x-tar",
+                "application/gzip",
+                "application/x-xz",
+                "application/x-bzip2",
+                "application/zstd",
+            ],
+        ),
+        rule(
+            "Documents",
+            &[
+                "pdf", "epub", "doc", "docx", "odt", "xls", "xlsx", "ods", "ppt", "pptx",
+            ],
+            &[
+                "application/pdf",
+                "application/epub+zip",
+                "application/msword",
+            ],
+        ),
+    ]
+}
+
+/// Check a rule set from the frontend and bring it into canonical form:
+/// trimmed names, lowercase extensions without a leading dot, lowercase
+/// MIME types.
+pub fn normalize_rules(rules: Vec<CategoryRule>) -> Result<Vec<CategoryRule>> {
+    let mut names = HashSet::new();
+    rules
+        .into_iter()
+        .map(|rule| {
+            let name = rule.name.trim().to_string();
+            if name.is_empty() {
+                return Err(Error::InvalidInput("Category name cannot be empty".into()));
+            }
+            if !names.insert(name.to_lowercase()) {
+                return Err(Error::InvalidInput(format!("Duplicate category: {}", name)));
+            }
+            let directory = rule.directory.trim().to_string();
+            if directory.is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "Category '{}' needs a directory",
+                    name
+                )));
+            }
+            let extensions = rule
+                .extensions
+                .iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect();
+            let mime_types = rule
+                .mime_types
+                .iter()
+                .map(|m| m.trim().to_lowercase())
+                .filter(|m| !m.is_empty())
+                .collect();
+            Ok(CategoryRule {
+                name,
+                directory,
+                extensions,
+                mime_types,
+                archive_after_days: rule.archive_after_days,
+                archive_directory: rule.archive_directory.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The rule named `name`, ignoring case.
+pub fn find_by_name<'a>(rules: &'a [CategoryRule], name: &str) -> Option<&'a CategoryRule> {
+    rules
+        .iter()
+        .find(|r| r.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// First rule listing the extension of `filename`.
+pub fn match_extension<'a>(rules: &'a [CategoryRule], filename: &str) -> Option<&'a CategoryRule> {
+    let extension = Path::new(filename)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    rules.iter().find(|r| r.extensions.contains(&extension))
+}
+
+/// First rule listing `mime`, either exactly or through a `type/*` entry.
+/// Parameters such as `; charset=...` are ignored.
+pub fn match_mime<'a>(rules: &'a [CategoryRule], mime: &str) -> Option<&'a CategoryRule> {
+    let mime = mime.split(';').next()?.trim().to_lowercase();
+    let top_level = mime.split('/').next()?;
+    rules.iter().find(|r| {
+        r.mime_types
+            .iter()
+            .any(|pattern| match pattern.strip_suffix("/*") {
+                Some(prefix) => prefix == top_level,
+                None => *pattern == mime,
+            })
+    })
+}
+
+/// Where a category's downloads go: relative directories are taken from
+/// `download_dir`, absolute (or `~`) ones are used as they are.
+pub fn resolve_directory(download_dir: &str, directory: &str) -> PathBuf {
+    let expanded = crate::db::expand_tilde(directory);
+    let path = PathBuf::from(&expanded);
+    if path.is_absolute() {
+        path
+    } else {
+        Path::new(download_dir).join(path)
+    }
+}
+
+/// Content type the server reports for `url`, from a HEAD request.
+pub async fn probe_content_type(
+    url: &str,
+    proxy_url: &str,
+    accept_invalid_certs: bool,
+) -> Option<String> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .danger_accept_invalid_certs(accept_invalid_certs);
+    if !proxy_url.is_empty() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url).ok()?);
+    }
+    let response = match builder.build().ok()?.head(url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            log::debug!("Content type probe for {} failed: {}", url, e);
+            return None;
+        }
+    };
+    if !response.status().is_success() {
+        return None;
+    }
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)?
+        .to_str()
+        .ok()
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_extension_and_mime() {
+        let rules = default_rules();
+        assert_eq!(match_extension(&rules, "Film.MKV").unwrap().name, "Movies");
+        assert_eq!(
+            match_extension(&rules, "ubuntu-24.04.iso").unwrap().name,
+            "Software"
+        );
+        assert_eq!(
+            match_extension(&rules, "backup.tar.gz").unwrap().name,
+            "Archives"
+        );
+        assert!(match_extension(&rules, "notes").is_none());
+        assert!(match_extension(&rules, "page.html").is_none());
+
+        assert_eq!(match_mime(&rules, "video/mp4").unwrap().name, "Movies");
+        assert_eq!(
+            match_mime(&rules, "Audio/OGG; codecs=opus").unwrap().name,
+            "Music"
+        );
+        assert_eq!(
+            match_mime(&rules, "application/x-iso9660-image")
+                .unwrap()
+                .name,
+            "Software"
+        );
+        assert!(match_mime(&rules, "text/html; charset=utf-8").is_none());
+    }
+
+    #[test]
+    fn test_normalize_rules() {
+        let rules = normalize_rules(vec![CategoryRule {
+            name: " Books ".into(),
+            directory: "Books".into(),
+            extensions: vec![".EPUB".into(), " mobi".into(), "".into()],
+            mime_types: vec!["Application/EPUB+zip".into()],
+            archive_after_days: Some(0),
+            archive_directory: " Books ".into(),
+        }])
+        .unwrap();
+        assert_eq!(rules[0].name, "Books");
+        assert_eq!(rules[0].extensions, vec!["epub", "mobi"]);
+        assert_eq!(rules[0].mime_types, vec!["application/epub+zip"]);
+        assert_eq!(
+            (
+                rules[0].archive_after_days,
+                rules[0].archive_directory.as_str()
+            ),
+            (Some(0), "Books")
+        );
+
+        let duplicate = vec![
+            rules[0].clone(),
+            CategoryRule {
+                name: "books".into(),
+                ..rules[0].clone()
+            },
+        ];
+        assert!(normalize_rules(duplicate).is_err());
+        let no_dir = CategoryRule {
+            directory: " ".into(),
+            ..rules[0].clone()
+        };
+        assert!(normalize_rules(vec![no_dir]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_directory() {
+        assert_eq!(
+            resolve_directory("/home/u/Downloads", "Movies"),
+            Path::new("/home/u/Downloads").join("Movies")
+        );
+        let absolute = std::env::temp_dir().join("media");
+        assert_eq!(
+            resolve_directory("/home/u/Downloads", &absolute.to_string_lossy()),
+            absolute
+        );
+        assert!(resolve_directory("/home/u/Downloads", "~/Software").is_absolute());
+    }
+}
@@ -0,0 +1,176 @@
+//! Versioned record of the download list for `get_changes`. Each time the
+//! list is looked at, downloads whose content differs from last time get
+//! the next version, and downloads gone from the list are remembered as
+//! removed, so a caller that saw version N only needs what moved after N.
+
+use crate::types::Download;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Removals remembered for callers that are behind. Older ones are
+/// dropped, and a caller from before them gets the full list again.
+const MAX_REMOVED: usize = 1000;
+
+#[derive(Debug, Default)]
+pub struct ChangeLog {
+    version: u64,
+    /// Version of each download's last change and a hash of its content
+    seen: HashMap<String, (u64, u64)>,
+    /// Version at which each download left the list
+    removed: HashMap<String, u64>,
+    /// Callers from before this version can't be told what was removed
+    floor: u64,
+}
+
+/// What changed after a version, see [`ChangeLog::update`].
+#[derive(Debug, PartialEq)]
+pub struct Changed {
+    pub version: u64,
+    /// GIDs to send in full
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    /// The caller's version is unknown or too old: `changed` is every
+    /// download and anything not in it should be dropped
+    pub full: bool,
+}
+
+impl ChangeLog {
+    /// Record the current list and return what changed after `since`.
+    pub fn update(&mut self, downloads: &[Download], since: Option<u64>) -> Changed {
+        let mut bumped = false;
+        let mut present = HashSet::with_capacity(downloads.len());
+        for download in downloads {
+            let hash = content_hash(download);
+            present.insert(download.gid.as_str());
+            match self.seen.get(&download.gid) {
+                Some((_, previous)) if *previous == hash => {}
+                _ => {
+                    if !bumped {
+                        self.version += 1;
+                        bumped = true;
+                    }
+                    self.seen.insert(download.gid.clone(), (self.version, hash));
+                    self.removed.remove(&download.gid);
+                }
+            }
+        }
+        let gone: Vec<String> = self
+            .seen
+            .keys()
+            .filter(|gid| !present.contains(gid.as_str()))
+            .cloned()
+            .collect();
+        if !gone.is_empty() && !bumped {
+            self.version += 1;
+        }
+        for gid in gone {
+            self.seen.remove(&gid);
+            self.removed.insert(gid, self.version);
+        }
+        if self.removed.len() > MAX_REMOVED {
+            let mut versions: Vec<u64> = self.removed.values().copied().collect();
+            versions.sort_unstable();
+            let cutoff = versions[versions.len() - MAX_REMOVED];
+            self.removed.retain(|_, version| *version >= cutoff);
+            self.floor = cutoff;
+        }
+
+        // A version from an earlier session can be ahead of this one
+        let full = match since {
+            Some(since) => since < self.floor || since > self.version,
+            None => true,
+        };
+        let since = if full { 0 } else { since.unwrap_or(0) };
+        let changed = downloads
+            .iter()
+            .filter(|d| {
+                self.seen
+                    .get(&d.gid)
+                    .is_some_and(|(version, _)| *version > since)
+            })
+            .map(|d| d.gid.clone())
+            .collect();
+        let mut removed: Vec<String> = if full {
+            Vec::new()
+        } else {
+            self.removed
+                .iter()
+                .filter(|(_, version)| **version > since)
+                .map(|(gid, _)| gid.clone())
+                .collect()
+        };
+        removed.sort();
+        Changed {
+            version: self.version,
+            changed,
+            removed,
+            full,
+        }
+    }
+}
+
+fn content_hash(download: &Download) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(download)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DownloadState;
+
+    fn download(gid: &str, completed_size: u64) -> Download {
+        Download {
+            gid: gid.into(),
+            name: format!("{}.iso", gid),
+            url: Some(format!("https://example.com/{}.iso", gid)),
+            status: DownloadState::Active,
+            total_size: 100,
+            completed_size,
+            save_path: "/tmp".into(),
+            created_at: "2026-01-01T00:00:00Z".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut log = ChangeLog::default();
+        let first = log.update(&[download("a", 0), download("b", 0)], None);
+        assert!(first.full);
+        assert_eq!(first.changed, vec!["a", "b"]);
+
+        let same = log.update(&[download("a", 0), download("b", 0)], Some(first.version));
+        assert_eq!(
+            (same.version, same.changed.len(), same.full),
+            (first.version, 0, false)
+        );
+
+        let moved = log.update(&[download("a", 50)], Some(first.version));
+        assert_eq!(moved.changed, vec!["a"]);
+        assert_eq!(moved.removed, vec!["b"]);
+        assert!(moved.version > first.version);
+
+        // Behind by two updates: both the progress and the removal
+        let later = log.update(&[download("a", 60), download("c", 0)], Some(moved.version));
+        let caught_up = log.update(&[download("a", 60), download("c", 0)], Some(first.version));
+        assert_eq!(later.changed, vec!["a", "c"]);
+        assert!(later.removed.is_empty());
+        assert_eq!(
+            (caught_up.changed.len(), caught_up.removed.clone()),
+            (2, vec!["b".to_string()])
+        );
+
+        // A version this log never handed out gets everything
+        let unknown = log.update(
+            &[download("a", 60), download("c", 0)],
+            Some(later.version + 10),
+        );
+        assert!(unknown.full);
+        assert_eq!(unknown.changed.len(), 2);
+    }
+}
@@ -1,15 +1,21 @@
-use crate::db::Settings;
-use crate::types::Download;
-use crate::{AppState, Result};
+use crate::db::{HistoryRetention, Settings};
+use crate::tasks::TaskHandle;
+use crate::types::{DbMaintenanceReport, Download, HistoryStats, HostProfile, SeedStatsDay};
+use crate::validation::validate_time_of_day;
+use crate::{AppState, Error, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 pub async fn db_get_completed_history(state: &AppState) -> Result<Vec<Download>> {
     let db = state.get_db().await?;
     db.get_completed_downloads_async().await
 }
 
+/// Queue a download snapshot; it is written with the next batch.
 pub async fn db_save_download(state: &AppState, download: Download) -> Result<()> {
     let db = state.get_db().await?;
-    db.save_download_async(download).await
+    db.queue_download(download);
+    Ok(())
 }
 
 pub async fn db_remove_download(state: &AppState, gid: String) -> Result<()> {
@@ -17,22 +23,420 @@ pub async fn db_remove_download(state: &AppState, gid: String) -> Result<()> {
     db.remove_download_async(gid).await
 }
 
+/// Pin or unpin a history entry; favorites survive retention pruning.
+pub async fn db_set_favorite(state: &AppState, gid: String, favorite: bool) -> Result<()> {
+    let db = state.get_db().await?;
+    if !db.set_favorite_async(gid.clone(), favorite).await? {
+        return Err(Error::NotFound(format!("Download not found: {}", gid)));
+    }
+    Ok(())
+}
+
 pub async fn db_clear_history(state: &AppState) -> Result<()> {
     let db = state.get_db().await?;
     db.clear_history_async().await
 }
 
+/// Archive the finished downloads that are due now, rather than waiting
+/// for the hourly run. Returns how many were moved.
+pub async fn archive_downloads(state: &AppState) -> Result<u32> {
+    crate::archive::run(state).await
+}
+
+pub async fn db_get_history_stats(state: &AppState) -> Result<HistoryStats> {
+    let db = state.get_db().await?;
+    db.get_history_stats_async().await
+}
+
+/// Connection counts learned per host, with the one each would get next.
+pub async fn get_host_profiles(state: &AppState) -> Result<Vec<HostProfile>> {
+    let db = state.get_db().await?;
+    let settings = db.get_settings_async().await?;
+    let max = settings
+        .max_connections_per_server
+        .max(settings.split_count);
+    let profiles = db.get_host_profiles_async(None).await?;
+    Ok(profiles
+        .into_iter()
+        .map(|profile| crate::host_profiles::with_suggestion(profile, max))
+        .collect())
+}
+
 pub async fn db_get_settings(state: &AppState) -> Result<Settings> {
     let db = state.get_db().await?;
     db.get_settings_async().await
 }
 
 pub async fn db_save_settings(state: &AppState, settings: Settings) -> Result<()> {
+    validate_time_of_day(&settings.quiet_hours_start)?;
+    validate_time_of_day(&settings.quiet_hours_end)?;
+    validate_export_format(&settings.stats_report_format)?;
+    if settings.stats_report_enabled && !Path::new(&settings.stats_report_path).is_absolute() {
+        return Err(Error::InvalidInput(
+            "Stats report path must be absolute".into(),
+        ));
+    }
+    if settings.stats_report_interval_hours == 0 {
+        return Err(Error::InvalidInput(
+            "Stats report interval must be at least an hour".into(),
+        ));
+    }
     let db = state.get_db().await?;
     db.save_settings_async(settings).await
 }
 
+/// Settings-table key holding when maintenance last ran (RFC 3339).
+pub const LAST_MAINTENANCE_KEY: &str = "last_db_maintenance";
+
+/// Report the database's size and, unless `report_only`, prune history
+/// the retention policy no longer keeps and compact the file. Runs as a background
+/// task so the UI can show progress, but waits for the result.
+pub async fn db_maintenance(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    report_only: bool,
+) -> Result<DbMaintenanceReport> {
+    let started = std::time::Instant::now();
+    let db = state.get_db().await?;
+    let before = db.storage_info_async().await?;
+    if report_only {
+        return Ok(DbMaintenanceReport {
+            before,
+            after: None,
+            pruned: 0,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    let mut task = state
+        .tasks()
+        .start(app, "db-maintenance", "Database maintenance".into(), false);
+    let result = async {
+        let policy = HistoryRetention::from_settings(&db.get_settings_async().await?);
+        let pruned = if policy != HistoryRetention::Forever {
+            task.progress(0, 2, Some("Pruning old history".into()));
+            db.prune_history_async(policy).await?
+        } else {
+            0
+        };
+        task.progress(1, 2, Some("Compacting".into()));
+        db.compact_async().await?;
+        db.set_meta_async(LAST_MAINTENANCE_KEY, chrono::Utc::now().to_rfc3339())
+            .await?;
+        Ok(DbMaintenanceReport {
+            after: Some(db.storage_info_async().await?),
+            before,
+            pruned,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+    .await;
+    task.finish(&result);
+    if let Ok(report) = &result {
+        log::info!(
+            "Database maintenance: pruned {} entries, {} -> {} bytes",
+            report.pruned,
+            report.before.db_size + report.before.wal_size,
+            report.after.as_ref().map_or(0, |a| a.db_size + a.wal_size)
+        );
+    }
+    result
+}
+
+/// Start writing the download history to `path` as CSV or JSON in the
+/// background. Returns the task id; progress arrives as `task:progress`.
+pub async fn db_export_history(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    path: String,
+    format: String,
+) -> Result<String> {
+    let format = validate_export_format(&format)?;
+    let path = PathBuf::from(path);
+    if !path.is_absolute() {
+        return Err(Error::InvalidInput("Export path must be absolute".into()));
+    }
+    let db = state.get_db().await?;
+    let mut task = state.tasks().start(
+        app,
+        "history-export",
+        "Export download history".into(),
+        true,
+    );
+    let id = task.id().to_string();
+    tokio::spawn(async move {
+        let history = match db.get_completed_downloads_async().await {
+            Ok(history) => history,
+            Err(e) => return task.finish(&Err::<(), _>(e)),
+        };
+        let _ = tokio::task::spawn_blocking(move || {
+            let result = write_history(&mut task, &path, &format, &history);
+            task.finish(&result);
+        })
+        .await;
+    });
+    Ok(id)
+}
+
+pub(crate) fn validate_export_format(format: &str) -> Result<String> {
+    let format = format.to_lowercase();
+    if format != "csv" && format != "json" {
+        return Err(Error::InvalidInput(format!(
+            "Unsupported export format: {}",
+            format
+        )));
+    }
+    Ok(format)
+}
+
+/// Settings-table key holding when the scheduled stats report was last
+/// written (RFC 3339).
+pub const LAST_STATS_REPORT_KEY: &str = "last_stats_report";
+
+/// Write per-torrent upload, download and seed time for each day (UTC)
+/// from `from` to `to` (`YYYY-MM-DD`, inclusive, either open-ended) to
+/// `path` as CSV or JSON. Returns how many rows were written.
+pub async fn export_stats(
+    state: &AppState,
+    path: String,
+    format: String,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<u32> {
+    let format = validate_export_format(&format)?;
+    let path = PathBuf::from(path);
+    if !path.is_absolute() {
+        return Err(Error::InvalidInput("Export path must be absolute".into()));
+    }
+    let (from, to) = stats_range(from, to)?;
+    let db = state.get_db().await?;
+    let mut days = db.get_seed_stats_daily_async(from, to).await?;
+    let adapter = state.get_adapter().await?;
+    name_stats(
+        &mut days,
+        &super::download::known_downloads(state, &adapter).await?,
+    );
+    let rows = days.len() as u32;
+    tokio::task::spawn_blocking(move || write_stats(&path, &format, &days))
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))??;
+    Ok(rows)
+}
+
+/// Check a date range from the frontend, dropping empty bounds.
+fn stats_range(
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(Option<String>, Option<String>)> {
+    let parse = |date: Option<String>| -> Result<Option<chrono::NaiveDate>> {
+        match date.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            Some(d) => chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| {
+                    Error::InvalidInput(format!("Invalid date (expected YYYY-MM-DD): {}", d))
+                }),
+            None => Ok(None),
+        }
+    };
+    let (from, to) = (parse(from)?, parse(to)?);
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(Error::InvalidInput("Start date is after end date".into()));
+        }
+    }
+    let format = |date: chrono::NaiveDate| date.format("%Y-%m-%d").to_string();
+    Ok((from.map(format), to.map(format)))
+}
+
+/// Fill in names and info hashes from the downloads still known.
+fn name_stats(days: &mut [SeedStatsDay], downloads: &[Download]) {
+    for day in days {
+        if let Some(download) = downloads.iter().find(|d| d.gid == day.gid) {
+            day.name = download.name.clone();
+            day.info_hash = download.info_hash.clone();
+        }
+    }
+}
+
+/// Write `days` beside `path` and move the file into place, so a report
+/// being read by another program is never half-written.
+fn write_stats(path: &Path, format: &str, days: &[SeedStatsDay]) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", format));
+    let result = (|| {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        if format == "csv" {
+            writeln!(
+                out,
+                "date,name,info_hash,uploaded,downloaded,seed_time_secs,gid"
+            )?;
+            for day in days {
+                writeln!(out, "{}", stats_csv_row(day))?;
+            }
+        } else {
+            serde_json::to_writer_pretty(&mut out, days)?;
+            writeln!(out)?;
+        }
+        out.flush()?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+        return result;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn stats_csv_row(day: &SeedStatsDay) -> String {
+    [
+        day.date.clone(),
+        day.name.clone(),
+        day.info_hash.clone().unwrap_or_default(),
+        day.uploaded.to_string(),
+        day.downloaded.to_string(),
+        day.seed_time_secs.to_string(),
+        day.gid.clone(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Write `history` to a temporary file beside `path` and move it into
+/// place, so a failed or cancelled export never leaves a partial file.
+fn write_history(
+    task: &mut TaskHandle,
+    path: &Path,
+    format: &str,
+    history: &[Download],
+) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", format));
+    let result = (|| {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        if format == "csv" {
+            writeln!(
+                out,
+                "name,url,status,size,save_path,created_at,completed_at"
+            )?;
+        } else {
+            writeln!(out, "[")?;
+        }
+        for (i, download) in history.iter().enumerate() {
+            if task.is_cancelled() {
+                return Ok(());
+            }
+            if format == "csv" {
+                writeln!(out, "{}", history_csv_row(download))?;
+            } else {
+                let separator = if i + 1 < history.len() { "," } else { "" };
+                writeln!(out, "  {}{}", serde_json::to_string(download)?, separator)?;
+            }
+            task.progress(i as u64 + 1, history.len() as u64, None);
+        }
+        if format == "json" {
+            writeln!(out, "]")?;
+        }
+        out.flush()?;
+        Ok(())
+    })();
+    if result.is_err() || task.is_cancelled() {
+        let _ = std::fs::remove_file(&tmp);
+        return result;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn history_csv_row(download: &Download) -> String {
+    [
+        download.name.clone(),
+        download
+            .url
+            .clone()
+            .or_else(|| download.magnet_uri.clone())
+            .unwrap_or_default(),
+        download.status.to_string(),
+        download.total_size.to_string(),
+        download.save_path.clone(),
+        download.created_at.clone(),
+        download.completed_at.clone().unwrap_or_default(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Incomplete downloads to restore at startup. Downloads waiting on the
+/// crash recovery prompt are left out; `restore_session` handles them.
 pub async fn db_load_incomplete(state: &AppState) -> Result<Vec<Download>> {
     let db = state.get_db().await?;
-    db.get_incomplete_downloads_async().await
+    let mut downloads = db.get_incomplete_downloads_async().await?;
+    if let Some(recovery) = state.crash_recovery() {
+        downloads.retain(|d| !recovery.downloads.iter().any(|r| r.gid == d.gid));
+    }
+    Ok(downloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain.iso"), "plain.iso");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_stats_range() {
+        assert_eq!(stats_range(None, Some(" ".into())).unwrap(), (None, None));
+        assert_eq!(
+            stats_range(Some("2026-01-01".into()), Some("2026-01-31".into())).unwrap(),
+            (Some("2026-01-01".into()), Some("2026-01-31".into()))
+        );
+        assert!(stats_range(Some("2026-02-01".into()), Some("2026-01-31".into())).is_err());
+        assert!(stats_range(Some("01/02/2026".into()), None).is_err());
+    }
+
+    #[test]
+    fn test_write_stats() {
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-stats-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let days = vec![SeedStatsDay {
+            date: "2026-03-01".into(),
+            gid: "abc".into(),
+            name: "a, b".into(),
+            info_hash: None,
+            uploaded: 2048,
+            downloaded: 1024,
+            seed_time_secs: 3600,
+        }];
+
+        let csv = dir.join("stats.csv");
+        write_stats(&csv, "csv", &days).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv).unwrap(),
+            "date,name,info_hash,uploaded,downloaded,seed_time_secs,gid\n2026-03-01,\"a, b\",,2048,1024,3600,abc\n"
+        );
+        let json = dir.join("stats.json");
+        write_stats(&json, "json", &days).unwrap();
+        let read: Vec<SeedStatsDay> =
+            serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(read, days);
+        assert!(!dir.join("stats.json.tmp").exists());
+    }
 }
@@ -1,24 +1,594 @@
-use crate::types::{Download, DownloadOptions, GlobalStat};
-use crate::{AppState, Result};
+use crate::add_plan;
+use crate::categories::{
+    find_by_name, match_extension, match_mime, probe_content_type, resolve_directory,
+};
+use crate::db::Settings;
+use crate::engine_adapter::EngineAdapter;
+use crate::host_profiles::{suggest_connections, url_host};
+use crate::locations;
+use crate::metalink;
+use crate::naming::choose_filename;
+use crate::orphans;
+use crate::preview::{locate_file, read_prefix, sniff_mime, MAX_PREVIEW_BYTES};
+use crate::ranges::{probe_server, ProbeRequest, RedirectRules, ServerProbe};
+use crate::rename_rules::{self, RenameTarget};
+use crate::rotation::{self, rotate_existing, PendingRotation};
+use crate::s3::{self, S3Config};
+use crate::staging;
+use crate::state::lower_limit;
+use crate::templates;
+use crate::types::{
+    AddPlan, AddUrlsResult, Download, DownloadDetails, DownloadOptions, DownloadPreview,
+    DownloadState, DownloadType, FailedUrl, GlobalStat, HostActivity, IntegrityCheck,
+    UrlListImport,
+};
+use crate::url_list;
+use crate::validation::validate_http_url;
+use crate::{AppState, Error, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use std::path::Path;
+
+/// Largest URL list file `import_url_list` reads.
+const MAX_URL_LIST_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Move an existing file at the download's target path aside, per its
+/// rotation policy. The policy is settled once the download finishes.
+fn apply_rotation(
+    adapter: &EngineAdapter,
+    url: &str,
+    options: Option<&DownloadOptions>,
+) -> Result<Option<PendingRotation>> {
+    let Some(options) = options else {
+        return Ok(None);
+    };
+    let Some(policy) = &options.rotation else {
+        return Ok(None);
+    };
+    match adapter.resolve_target(url, options) {
+        Some((dir, filename)) => Ok(rotate_existing(&dir, &filename, policy)?),
+        None => Ok(None),
+    }
+}
+
+/// Start `options` from the download template it names, if any. Returns
+/// the template's trackers along with the merged options.
+pub(super) async fn with_template(
+    state: &AppState,
+    options: Option<DownloadOptions>,
+) -> Result<(Option<DownloadOptions>, Vec<String>)> {
+    let Some(name) = options.as_ref().and_then(|o| o.template.clone()) else {
+        return Ok((options, Vec::new()));
+    };
+    let template = state
+        .get_db()
+        .await?
+        .get_download_templates_async()
+        .await?
+        .into_iter()
+        .find(|t| t.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| Error::NotFound(format!("Download template not found: {}", name)))?;
+    let options = templates::apply(options.unwrap_or_default(), &template)?;
+    Ok((Some(options), template.trackers))
+}
+
+/// Fill in per-download options whose default comes from settings or the
+/// download template.
+async fn with_setting_defaults(
+    state: &AppState,
+    options: Option<DownloadOptions>,
+) -> Result<DownloadOptions> {
+    let (options, _) = with_template(state, options).await?;
+    let settings = state.get_db().await?.get_settings_async().await?;
+    let mut options = options.unwrap_or_default();
+    options
+        .identity_encoding
+        .get_or_insert(settings.http_identity_encoding);
+    Ok(options)
+}
+
+/// Point a download without an explicit `dir` at its location and category
+/// directory. The category is the named `category`, else the first rule
+/// matching `filename`'s extension, else (for HTTP) the first matching the
+/// server's content type. The location is the named `location`, which is
+/// remembered for the category, else the one last used for it; a
+/// category's relative directory is taken from the location. The category
+/// picked is left in `options.category` for the download to report. A
+/// `dry_run` neither remembers the location nor creates the directory.
+pub(super) async fn apply_category(
+    state: &AppState,
+    options: &mut DownloadOptions,
+    filename: Option<&str>,
+    probe_url: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    if options.dir.is_some() {
+        return Ok(());
+    }
+    let db = state.get_db().await?;
+    let rules = db.get_category_rules_async().await?;
+    let settings = db.get_settings_async().await?;
+    let rule = match options.category.as_deref() {
+        Some(name) => Some(
+            find_by_name(&rules, name)
+                .ok_or_else(|| Error::NotFound(format!("Category not found: {}", name)))?,
+        ),
+        None => match filename.and_then(|name| match_extension(&rules, name)) {
+            Some(rule) => Some(rule),
+            None if rules.iter().any(|r| !r.mime_types.is_empty()) => match probe_url {
+                Some(url) => {
+                    probe_content_type(url, &settings.proxy_url, settings.accept_invalid_certs)
+                        .await
+                        .and_then(|mime| match_mime(&rules, &mime))
+                }
+                None => None,
+            },
+            None => None,
+        },
+    };
+    let category = rule.map(|r| r.name.clone()).unwrap_or_default();
+
+    let locations = db.get_download_locations_async().await?;
+    let location = match options.location.as_deref() {
+        Some(name) => {
+            let path = locations::find_path(&settings.download_path, &locations, name)
+                .ok_or_else(|| Error::NotFound(format!("Download location not found: {}", name)))?;
+            let name = locations
+                .iter()
+                .find(|l| l.name.eq_ignore_ascii_case(name.trim()))
+                .map_or(locations::DEFAULT_LOCATION.to_string(), |l| l.name.clone());
+            if !dry_run {
+                db.record_location_usage_async(category.clone(), name)
+                    .await?;
+            }
+            Some(path)
+        }
+        None => db
+            .get_location_usage_async()
+            .await?
+            .get(&category)
+            .and_then(|name| locations::find_path(&settings.download_path, &locations, name)),
+    };
+
+    let dir = match (rule, location) {
+        (Some(rule), Some(base)) => {
+            Some(resolve_directory(&base.to_string_lossy(), &rule.directory))
+        }
+        (Some(rule), None) => Some(resolve_directory(&settings.download_path, &rule.directory)),
+        (None, location) => location,
+    };
+    if let Some(dir) = dir {
+        if !dry_run {
+            locations::prepare_directory(&dir)?;
+        }
+        log::debug!("Category {:?} -> {}", category, dir.display());
+        options.dir = Some(dir.to_string_lossy().to_string());
+    }
+    options.category = rule.map(|r| r.name.clone());
+    Ok(())
+}
+
+/// Per-URL options for an HTTP download, with its category applied.
+async fn categorized(
+    state: &AppState,
+    adapter: &EngineAdapter,
+    url: &str,
+    options: &DownloadOptions,
+    dry_run: bool,
+) -> Result<DownloadOptions> {
+    let mut options = options.clone();
+    let filename = adapter.resolve_target(url, &options).map(|(_, name)| name);
+    apply_category(state, &mut options, filename.as_deref(), Some(url), dry_run).await?;
+    Ok(options)
+}
+
+/// Resolve one HTTP download into the URL and options the engine gets:
+/// an `s3://` URL presigned, named from what the server says, categorized,
+/// and limited to one connection if the server ignores byte ranges or to
+/// the count learned for its host when auto-tuning.
+async fn resolve_http(
+    state: &AppState,
+    adapter: &EngineAdapter,
+    url: String,
+    options: &DownloadOptions,
+    dry_run: bool,
+) -> Result<(String, DownloadOptions, ServerProbe)> {
+    let db = state.get_db().await?;
+    let settings = db.get_settings_async().await?;
+    // Logged and reported in place of a presigned URL
+    let shown = url.clone();
+    let url = if s3::is_s3_url(&url) {
+        s3::resolve_url(
+            &S3Config::from_settings(&settings),
+            &url,
+            chrono::Utc::now(),
+        )?
+    } else {
+        url
+    };
+    let rules = RedirectRules::from_settings(&settings, options.max_redirects);
+    let host = url_host(&shown);
+    let known_broken = match &host {
+        Some(host) => db.has_broken_ranges_async(host.clone()).await?,
+        None => false,
+    };
+    let request = ProbeRequest::new(&settings, options);
+    let mut probe = probe_server(&url, &settings.proxy_url, &request, rules, !known_broken).await;
+    if let Some(reason) = &probe.redirect_error {
+        return Err(Error::Network(format!("{}: {}", shown, reason)));
+    }
+    if known_broken && probe.ranges == Some(true) {
+        log::info!(
+            "{} is known to break byte ranges further into a file",
+            shown
+        );
+        probe.ranges = Some(false);
+        probe.broken_ranges = true;
+    } else if let (true, false, Some(host)) = (probe.broken_ranges, dry_run, host) {
+        db.record_broken_ranges_async(host).await?;
+    }
+    let mut options = options.clone();
+    // Without anything new from the server the engine names it as before
+    if options.out.is_none() && (probe.final_url.is_some() || probe.content_disposition.is_some()) {
+        options.out = choose_filename(&settings.filename_priority, &url, &probe);
+    }
+    let mut options = categorized(state, adapter, &url, &options, dry_run).await?;
+    if probe.ranges == Some(false) {
+        log::info!(
+            "{} does not support byte ranges; using a single connection",
+            shown
+        );
+        options.split = Some("1".to_string());
+        options.max_connection_per_server = Some("1".to_string());
+    } else if settings.auto_tune_connections
+        && options.split.is_none()
+        && options.max_connection_per_server.is_none()
+    {
+        if let Some(connections) = tuned_connections(state, &settings, &shown).await? {
+            log::debug!(
+                "Using {} connections for {} from its host profile",
+                connections,
+                shown
+            );
+            options.split = Some(connections.to_string());
+            options.max_connection_per_server = Some(connections.to_string());
+        }
+    }
+    Ok((url, options, probe))
+}
+
+/// Add one HTTP download: resolve it, rotate an existing file at its
+/// destination, and write it to the temp directory if one is configured.
+async fn add_staged(
+    state: &AppState,
+    adapter: &EngineAdapter,
+    url: String,
+    options: &DownloadOptions,
+) -> Result<String> {
+    let (source, given) = (url.clone(), options);
+    let (url, mut options, probe) = resolve_http(state, adapter, url, options, false).await?;
+    let rotation = apply_rotation(adapter, &url, Some(&options))?;
+    let staged = match staging::stage(state, &mut options).await {
+        Ok(staged) => staged,
+        Err(e) => {
+            if let Some(mut rotation) = rotation {
+                rotation.restore()?;
+            }
+            return Err(e);
+        }
+    };
+    let part = adapter
+        .resolve_target(&url, &options)
+        .map(|(dir, name)| orphans::part_path(&dir, &name));
+    // The URL as given, so an S3 download is signed afresh if re-added
+    let mut sidecar = orphans::Sidecar {
+        url: source,
+        etag: probe.etag.clone(),
+        total_size: probe.total_size,
+        checksum: options.checksum.clone(),
+        added_at: chrono::Utc::now(),
+    };
+    let previous = part.as_deref().and_then(orphans::read_sidecar);
+    let changed = previous
+        .as_ref()
+        .and_then(|previous| sidecar.merge_previous(previous));
+    if options.checksum.is_none() {
+        options.checksum = sidecar.checksum.clone();
+    }
+    let result = adapter.add_download(url.clone(), Some(options)).await;
+    staging::attach(state, staged, result.as_ref().ok().map(String::as_str)).await?;
+    match (&result, rotation) {
+        (Ok(gid), Some(rotation)) => {
+            state
+                .rotations()
+                .lock()
+                .unwrap()
+                .insert(gid.clone(), rotation);
+        }
+        (Err(_), Some(mut rotation)) => rotation.restore()?,
+        (_, None) => {}
+    }
+    let gid = result?;
+    if s3::is_s3_url(&sidecar.url) {
+        adapter.set_source(&gid, sidecar.url.clone());
+    }
+    keep_options(state, &gid, Some(sidecar.url.clone()), given.clone()).await;
+    if let Some(part) = &part {
+        orphans::write_sidecar(part, &sidecar);
+    }
+    if let Some(warning) = changed {
+        adapter.flag_warning(&gid, warning);
+    }
+    record_latency(state, &gid, &sidecar.url, &probe).await;
+    adapter.set_server_probe(&gid, probe);
+    Ok(gid)
+}
+
+/// Keep the URL and options a download was added with, so it can be added
+/// again the same way. What only applies to the first add (the template,
+/// metainfo edits and file filter, already resolved into the other
+/// options, and starting paused) is left out.
+pub(crate) async fn keep_options(
+    state: &AppState,
+    gid: &str,
+    url: Option<String>,
+    options: DownloadOptions,
+) {
+    let kept = DownloadOptions {
+        template: None,
+        torrent_edits: None,
+        file_filter: None,
+        paused: None,
+        ..options
+    };
+    let result = match state.get_db().await {
+        Ok(db) => {
+            db.save_download_options_async(gid.to_string(), url, kept)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to keep the options of {}: {}", gid, e);
+    }
+}
+
+/// Keep how long `url`'s server took to answer its probe, for the
+/// latency percentiles in the history statistics.
+pub(crate) async fn record_latency(state: &AppState, gid: &str, url: &str, probe: &ServerProbe) {
+    let (Some(ttfb_ms), Some(host), Ok(db)) = (probe.ttfb_ms, url_host(url), state.get_db().await)
+    else {
+        return;
+    };
+    if let Err(e) = db
+        .record_latency_async(gid.to_string(), host, ttfb_ms, probe.redirect_ms)
+        .await
+    {
+        log::warn!("Failed to record latency of {}: {}", gid, e);
+    }
+}
+
+/// Connections suggested by the profile of `url`'s host, if it has one.
+async fn tuned_connections(
+    state: &AppState,
+    settings: &Settings,
+    url: &str,
+) -> Result<Option<u32>> {
+    let Some(host) = url_host(url) else {
+        return Ok(None);
+    };
+    let profiles = state
+        .get_db()
+        .await?
+        .get_host_profiles_async(Some(host))
+        .await?;
+    let max = settings
+        .max_connections_per_server
+        .max(settings.split_count);
+    Ok(profiles
+        .first()
+        .and_then(|profile| suggest_connections(&profile.connections, max)))
+}
 
 pub async fn add_download(
     state: &AppState,
     url: String,
     options: Option<DownloadOptions>,
 ) -> Result<String> {
+    let options = with_setting_defaults(state, options).await?;
     let adapter = state.get_adapter().await?;
-    let gid = adapter.add_download(url, options).await?;
+    let gid = add_staged(state, &adapter, url, &options).await?;
     log::info!("Added download with GID: {}", gid);
     Ok(gid)
 }
 
+/// Dry run of `add_download`: where the download would be saved and with
+/// which options, plus warnings, without adding it. The server is still
+/// asked for the file's name and type.
+pub async fn plan_download(
+    state: &AppState,
+    url: String,
+    options: Option<DownloadOptions>,
+) -> Result<AddPlan> {
+    let options = with_setting_defaults(state, options).await?;
+    let adapter = state.get_adapter().await?;
+    let (resolved, options, _) = resolve_http(state, &adapter, url.clone(), &options, true).await?;
+    let settings = state.get_db().await?.get_settings_async().await?;
+
+    let mut warnings = Vec::new();
+    let target = adapter.resolve_target(&resolved, &options);
+    match &target {
+        Some((dir, name)) => warnings.extend(add_plan::target_warnings(
+            dir,
+            name,
+            options.out.as_deref(),
+            options.rotation.as_ref(),
+        )),
+        None => warnings.push("The file name is only known once the download starts".to_string()),
+    }
+    let downloads = known_downloads(state, &adapter).await?;
+    if let Some(duplicate) = add_plan::find_duplicate(&downloads, Some(&url), None) {
+        warnings.push(add_plan::duplicate_warning(duplicate));
+    }
+
+    let directory = match &target {
+        Some((dir, _)) => dir.to_string_lossy().to_string(),
+        None => options.dir.clone().unwrap_or(settings.download_path),
+    };
+    let rules = rename_rules::load_rules(state).await?;
+    let renamed = target.as_ref().and_then(|(_, name)| {
+        let target = RenameTarget {
+            name,
+            url: Some(&url),
+            category: options.category.as_deref().unwrap_or_default(),
+            date: chrono::Local::now().date_naive(),
+        };
+        rename_rules::rename(&rules, &target)
+    });
+    let temp_root = settings.temp_download_path.trim();
+    Ok(AddPlan {
+        url: Some(url),
+        name: target.as_ref().map(|(_, name)| name.clone()),
+        path: target.map(|(dir, name)| dir.join(name).to_string_lossy().to_string()),
+        directory,
+        temp_directory: (!temp_root.is_empty()).then(|| temp_root.to_string()),
+        category: options.category.clone(),
+        renamed,
+        options,
+        warnings,
+    })
+}
+
+/// Downloads in the list and in history, for duplicate checks.
+pub(super) async fn known_downloads(
+    state: &AppState,
+    adapter: &EngineAdapter,
+) -> Result<Vec<Download>> {
+    let mut downloads = adapter.get_all();
+    downloads.extend(
+        state
+            .get_db()
+            .await?
+            .get_completed_downloads_async()
+            .await?,
+    );
+    Ok(downloads)
+}
+
+/// Add several URLs with the same options. Each may land in a different
+/// category, so they are added one by one; one that fails is reported and
+/// the rest still go in.
 pub async fn add_urls(
     state: &AppState,
     urls: Vec<String>,
     options: Option<DownloadOptions>,
-) -> Result<Vec<String>> {
+) -> Result<AddUrlsResult> {
+    let options = with_setting_defaults(state, options).await?;
     let adapter = state.get_adapter().await?;
-    let gids = adapter.add_urls(urls, options).await?;
+    let mut result = AddUrlsResult::default();
+    for url in urls {
+        match add_staged(state, &adapter, url.clone(), &options).await {
+            Ok(gid) => result.gids.push(gid),
+            Err(e) => {
+                log::warn!("Failed to add {}: {}", url, e);
+                result.failed.push(FailedUrl {
+                    url,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Read a list of URLs to add without adding anything: a text, CSV or HTML
+/// file, or the list itself when `source` isn't a file. `extensions`
+/// limits the links taken from HTML. Entries already downloaded or in the
+/// list carry a warning.
+pub async fn import_url_list(
+    state: &AppState,
+    source: String,
+    extensions: Option<Vec<String>>,
+) -> Result<UrlListImport> {
+    let path = Path::new(source.trim());
+    let (file_name, text) = if !source.contains('\n') && path.is_file() {
+        if std::fs::metadata(path)?.len() > MAX_URL_LIST_BYTES {
+            return Err(Error::InvalidInput(format!(
+                "URL list is larger than {} MiB",
+                MAX_URL_LIST_BYTES / (1024 * 1024)
+            )));
+        }
+        let text = String::from_utf8_lossy(&std::fs::read(path)?).into_owned();
+        (
+            path.file_name().map(|n| n.to_string_lossy().into_owned()),
+            text,
+        )
+    } else {
+        (None, source)
+    };
+    let format = url_list::detect_format(file_name.as_deref(), &text);
+    let mut list = url_list::parse(&text, format, &extensions.unwrap_or_default());
+
+    let adapter = state.get_adapter().await?;
+    let downloads = known_downloads(state, &adapter).await?;
+    for entry in &mut list.entries {
+        entry.warning = add_plan::find_duplicate(&downloads, Some(&entry.url), None)
+            .map(add_plan::duplicate_warning);
+    }
+    Ok(UrlListImport {
+        format: format.as_str().to_string(),
+        entries: list.entries,
+        duplicates: list.duplicates,
+        skipped: list.skipped,
+    })
+}
+
+/// Add a download for each file a metalink lists: from its best http(s)
+/// URL, with the others as mirrors and its SHA-256 or MD5 hash as the
+/// checksum. Files without a usable URL are skipped.
+pub async fn add_metalink_file(
+    state: &AppState,
+    file_path: String,
+    options: Option<DownloadOptions>,
+) -> Result<Vec<String>> {
+    let files = metalink::parse(&std::fs::read_to_string(&file_path)?)?;
+    let several = files.len() > 1;
+    let mut gids = Vec::with_capacity(files.len());
+    for file in files {
+        let mut urls = file
+            .urls
+            .iter()
+            .filter(|url| validate_http_url(url).is_ok())
+            .cloned();
+        let Some(url) = urls.next() else {
+            log::warn!(
+                "Skipping {} from {}: no usable http(s) URL",
+                file.name,
+                file_path
+            );
+            continue;
+        };
+        let mut file_options = options.clone().unwrap_or_default();
+        file_options
+            .mirrors
+            .get_or_insert_with(Vec::new)
+            .extend(urls);
+        if file_options.checksum.is_none() {
+            file_options.checksum = file.checksum();
+        }
+        // A name given with the download only makes sense for a single file
+        let name = file.name.rsplit(['/', '\\']).next().unwrap_or_default();
+        if !name.is_empty() && (several || file_options.out.is_none()) {
+            file_options.out = Some(name.to_string());
+        }
+        gids.push(add_download(state, url, Some(file_options)).await?);
+    }
+    if gids.is_empty() {
+        return Err(Error::InvalidInput(
+            "The metalink lists no file with an http(s) URL".into(),
+        ));
+    }
+    log::info!("Added {} downloads from metalink {}", gids.len(), file_path);
     Ok(gids)
 }
 
@@ -38,14 +608,35 @@ pub async fn pause_all(state: &AppState) -> Result<serde_json::Value> {
 
 pub async fn resume_download(state: &AppState, gid: String) -> Result<()> {
     let adapter = state.get_adapter().await?;
+    if adapter
+        .get_status(&gid)
+        .is_some_and(|d| d.download_type != DownloadType::Http)
+    {
+        state.ensure_torrents_allowed().await?;
+    }
+    let restarts = adapter
+        .get_status(&gid)
+        .is_some_and(|d| d.resumable == Some(false) && d.completed_size > 0);
+    rotation::resume(state, &gid)?;
     adapter.resume(&gid).await?;
-    log::info!("Resumed download: {}", gid);
+    state.size_guard().lock().unwrap().forget(&gid);
+    if restarts {
+        log::info!(
+            "Resumed download: {} (server ignores ranges, starting over)",
+            gid
+        );
+    } else {
+        log::info!("Resumed download: {}", gid);
+    }
     Ok(())
 }
 
 pub async fn resume_all(state: &AppState) -> Result<serde_json::Value> {
+    // Resuming everything would start the torrents the kill switch holds
+    state.ensure_torrents_allowed().await?;
     let adapter = state.get_adapter().await?;
     let result = adapter.resume_all().await;
+    state.size_guard().lock().unwrap().forget_all();
     log::info!("Resumed all downloads");
     Ok(result)
 }
@@ -57,13 +648,23 @@ pub async fn cancel_all(state: &AppState, delete_files: bool) -> Result<serde_js
     Ok(result)
 }
 
-pub async fn remove_download(
-    state: &AppState,
-    gid: String,
-    delete_files: bool,
-) -> Result<()> {
+pub async fn remove_download(state: &AppState, gid: String, delete_files: bool) -> Result<()> {
     let adapter = state.get_adapter().await?;
+    // The engine only knows the copy in the temp directory, or the file
+    // under the name it had before a rename rule applied
+    let moved = match delete_files {
+        true => match rename_rules::renamed_path(state, &gid).await? {
+            Some(path) => Some(path),
+            None => staging::moved_path(state, &gid).await?,
+        },
+        false => None,
+    };
     adapter.remove(&gid, delete_files).await?;
+    if let Some(path) = moved {
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Failed to delete {}: {}", path.display(), e);
+        }
+    }
     log::info!("Removed download: {} (delete_files: {})", gid, delete_files);
     Ok(())
 }
@@ -75,6 +676,39 @@ pub async fn get_download_status(state: &AppState, gid: String) -> Result<Downlo
         .ok_or_else(|| crate::Error::NotFound(format!("Download not found: {}", gid)))
 }
 
+/// A download's status with the redirects its server probe went through
+/// and how long the server took to answer it.
+pub async fn get_download_details(state: &AppState, gid: String) -> Result<DownloadDetails> {
+    let adapter = state.get_adapter().await?;
+    let download = adapter
+        .get_status(&gid)
+        .ok_or_else(|| crate::Error::NotFound(format!("Download not found: {}", gid)))?;
+    let probe = adapter.server_probe(&gid).unwrap_or_default();
+    let redirect_chain = match (&download.url, probe.redirects.is_empty()) {
+        (Some(url), false) => std::iter::once(url.clone())
+            .chain(probe.redirects)
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(DownloadDetails {
+        download,
+        redirect_chain,
+        redirect_error: probe.redirect_error,
+        ttfb_ms: probe.ttfb_ms,
+        redirect_ms: probe.redirect_ms,
+    })
+}
+
+/// Hash a finished HTTP download's file again and compare it with the
+/// hash recorded when it finished.
+pub async fn verify_download(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    gid: String,
+) -> Result<IntegrityCheck> {
+    crate::verify::recheck(state, app, gid).await
+}
+
 pub async fn get_all_downloads(state: &AppState) -> Result<Vec<Download>> {
     let adapter = state.get_adapter().await?;
     Ok(adapter.get_all())
@@ -90,12 +724,68 @@ pub async fn get_global_stats(state: &AppState) -> Result<GlobalStat> {
     Ok(adapter.get_global_stats())
 }
 
+/// Live figures for each host with running HTTP downloads: connections
+/// in use and allowed, speed, recent failures, and what throttles it.
+pub async fn get_host_stats(state: &AppState) -> Result<Vec<HostActivity>> {
+    let adapter = state.get_adapter().await?;
+    let limits = state.limits(&adapter);
+    let profiles = super::get_host_profiles(state).await?;
+    Ok(state.host_stats(&adapter.get_active(), limits, &profiles))
+}
+
 pub async fn set_speed_limit(
     state: &AppState,
     download_limit: Option<u64>,
     upload_limit: Option<u64>,
 ) -> Result<()> {
     let adapter = state.get_adapter().await?;
+    if state.is_battery_saver() {
+        // Schedule changes must not lift the battery caps
+        let settings = state.get_db().await?.get_settings_async().await?;
+        let download_limit = lower_limit(download_limit, settings.battery_download_limit);
+        let upload_limit = lower_limit(upload_limit, settings.battery_upload_limit);
+        adapter.set_speed_limit(download_limit, upload_limit)?;
+        return Ok(());
+    }
     adapter.set_speed_limit(download_limit, upload_limit)?;
     Ok(())
 }
+
+/// Return the first `bytes` (default 64 KiB, at most 1 MiB) of a download's
+/// file, running or finished, for previews and type sniffing.
+pub async fn preview_download(
+    state: &AppState,
+    gid: String,
+    bytes: Option<u64>,
+) -> Result<DownloadPreview> {
+    let adapter = state.get_adapter().await?;
+    let (download, torrent_files) = match adapter.get_status(&gid) {
+        Some(download) => {
+            let files = adapter.get_torrent_files(&gid).unwrap_or_default();
+            (download, files)
+        }
+        // Finished downloads may only be left in history
+        None => {
+            let download = state
+                .get_db()
+                .await?
+                .get_download_by_gid_async(gid.clone())
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("download {}", gid)))?;
+            (download, Vec::new())
+        }
+    };
+
+    let path = locate_file(&download, &torrent_files)
+        .ok_or_else(|| Error::NotFound(format!("no data on disk yet for {}", download.name)))?;
+    let max = bytes.unwrap_or(64 * 1024).clamp(1, MAX_PREVIEW_BYTES);
+    let data = read_prefix(&path, max)?;
+
+    Ok(DownloadPreview {
+        gid,
+        path: path.to_string_lossy().to_string(),
+        mime: sniff_mime(&data).map(str::to_string),
+        data: BASE64.encode(&data),
+        complete: download.status == DownloadState::Complete,
+    })
+}
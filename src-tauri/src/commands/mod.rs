@@ -1,13 +1,17 @@
 mod database;
 mod download;
+mod recovery;
 mod recursive;
 mod settings;
+mod snapshot;
 mod system;
 mod torrent;
 
 pub use database::*;
 pub use download::*;
+pub use recovery::*;
 pub use recursive::*;
 pub use settings::*;
+pub use snapshot::*;
 pub use system::*;
 pub use torrent::*;
@@ -0,0 +1,209 @@
+use super::{add_download, add_magnet};
+use crate::types::{
+    CrashRecoveryInfo, Download, DownloadOptions, DownloadState, DownloadType, OrphanedPartial,
+};
+use crate::{AppState, Error, Result};
+
+/// Downloads the previous session had running when it crashed, if the
+/// restore prompt hasn't been answered yet.
+pub fn get_crash_recovery_info(state: &AppState) -> Option<CrashRecoveryInfo> {
+    state.crash_recovery()
+}
+
+/// Answer the restore prompt. Selected downloads are resumed, or added
+/// again if the engine lost them; unselected ones the engine kept stay
+/// paused, and the rest are dropped. Returns the GIDs now running, which
+/// are new for downloads that had to be added again.
+pub async fn restore_session(state: &AppState, selection: Vec<String>) -> Result<Vec<String>> {
+    let info = state
+        .take_crash_recovery()
+        .ok_or_else(|| Error::NotFound("No interrupted session to restore".into()))?;
+    let adapter = state.get_adapter().await?;
+    let db = state.get_db().await?;
+    let stored = db.get_incomplete_downloads_async().await?;
+
+    let mut restored = Vec::new();
+    for item in info.downloads {
+        let selected = selection.contains(&item.gid);
+        if item.in_engine {
+            if selected && item.download_type != DownloadType::Http && state.is_vpn_down() {
+                // Stays paused for the user to resume once the VPN is back
+                log::warn!("Not resuming torrent {} while the VPN is down", item.name);
+            } else if selected {
+                match adapter.resume(&item.gid).await {
+                    Ok(()) => restored.push(item.gid),
+                    Err(e) => log::warn!("Failed to resume {}: {}", item.name, e),
+                }
+            }
+            continue;
+        }
+        if selected {
+            let Some(download) = stored.iter().find(|d| d.gid == item.gid) else {
+                continue;
+            };
+            match add_again(state, download).await {
+                Ok(gid) => restored.push(gid),
+                Err(e) => {
+                    // Keep the record so the startup restore can retry it
+                    log::warn!("Failed to restore {}: {}", item.name, e);
+                    continue;
+                }
+            }
+        }
+        db.remove_download_async(item.gid).await?;
+    }
+    log::info!("Restored {} interrupted download(s)", restored.len());
+    Ok(restored)
+}
+
+/// Bring back the downloads the app database has as incomplete but the
+/// engine didn't restore on its own, e.g. after a restart. Each is added
+/// again into its folder under its name, with the options it was first
+/// added with, and paused if it was paused. Ones the engine kept are left
+/// alone, as are ones held for the crash recovery prompt. Returns the GIDs
+/// added.
+pub async fn restore_incomplete(state: &AppState) -> Result<Vec<String>> {
+    let adapter = state.get_adapter().await?;
+    let db = state.get_db().await?;
+    let mut restored = Vec::new();
+    for download in super::db_load_incomplete(state).await? {
+        if adapter.get_status(&download.gid).is_some() {
+            continue;
+        }
+        let gid = match add_again(state, &download).await {
+            Ok(gid) => gid,
+            Err(e) => {
+                log::warn!("Failed to restore {}: {}", download.name, e);
+                continue;
+            }
+        };
+        db.remove_download_async(download.gid).await?;
+        restored.push(gid);
+    }
+    if !restored.is_empty() {
+        log::info!("Restored {} incomplete download(s)", restored.len());
+    }
+    Ok(restored)
+}
+
+/// Add a stored download to the engine again, into the same directory,
+/// with the options it was added with (headers, checksum, limits and so
+/// on) where they were kept, and paused if it was paused. An HTTP download
+/// keeps its name, so the engine finds its partial file.
+async fn add_again(state: &AppState, download: &Download) -> Result<String> {
+    let db = state.get_db().await?;
+    let (source, kept) = db
+        .get_download_options_async(download.gid.clone())
+        .await?
+        .unwrap_or_default();
+    let is_http = download.download_type == DownloadType::Http;
+    let selected_files = download
+        .selected_files
+        .as_ref()
+        .filter(|files| !is_http && !files.is_empty());
+    let options = Some(DownloadOptions {
+        dir: Some(download.save_path.clone()),
+        out: (is_http && !download.name.is_empty()).then(|| download.name.clone()),
+        priority: download.priority.clone().or(kept.priority),
+        select_file: selected_files
+            .map(|files| {
+                files
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .or(kept.select_file),
+        // Already resolved into `dir`
+        location: None,
+        // The existing file is the partial download to continue
+        rotation: None,
+        paused: (download.status == DownloadState::Paused).then_some(true),
+        ..kept
+    });
+    // The URL as given (an S3 download's is signed afresh), and a magnet
+    // link with the trackers it was added with
+    let (url, magnet) = match source {
+        Some(source) if is_http => (Some(source), None),
+        Some(source) => (None, Some(source)),
+        None => (None, None),
+    };
+    let url = url.or_else(|| download.url.clone());
+    let magnet = magnet.or_else(|| download.magnet_uri.clone()).or_else(|| {
+        download
+            .info_hash
+            .as_ref()
+            .map(|h| format!("magnet:?xt=urn:btih:{}", h))
+    });
+    let gid = match (download.download_type, magnet, url) {
+        (DownloadType::Http, _, Some(url)) => add_download(state, url, options).await?,
+        (DownloadType::Torrent | DownloadType::Magnet, Some(magnet), _) => {
+            add_magnet(state, magnet, options).await?
+        }
+        _ => {
+            return Err(Error::InvalidInput(format!(
+                "{} has no URL or magnet link to restore from",
+                download.name
+            )))
+        }
+    };
+    db.delete_download_options_async(download.gid.clone())
+        .await?;
+    Ok(gid)
+}
+
+/// `.part` files in the download folders that no download claims, as
+/// found at startup.
+pub async fn list_orphaned_partials(state: &AppState) -> Result<Vec<OrphanedPartial>> {
+    state.orphaned_partials().await
+}
+
+/// Add an orphaned partial download again from the URL its sidecar
+/// recorded, to the same folder and name so the engine picks up the file.
+pub async fn resume_orphaned_partial(state: &AppState, path: String) -> Result<String> {
+    let orphans = state.orphaned_partials().await?;
+    let orphan = orphans
+        .iter()
+        .find(|o| o.path == path)
+        .ok_or_else(|| Error::NotFound(format!("Not an orphaned partial download: {}", path)))?;
+    let url = orphan.url.clone().ok_or_else(|| {
+        Error::InvalidInput(format!(
+            "{} has no record of where it was downloaded from",
+            orphan.name
+        ))
+    })?;
+    let dir = std::path::Path::new(&path)
+        .parent()
+        .map(|p| p.to_string_lossy().into_owned());
+    let options = DownloadOptions {
+        dir,
+        out: Some(orphan.name.clone()),
+        ..Default::default()
+    };
+    let gid = add_download(state, url, Some(options)).await?;
+    state.forget_orphaned_partials(&[path]);
+    Ok(gid)
+}
+
+/// Delete orphaned partial downloads and their sidecars. Only paths from
+/// `list_orphaned_partials` are touched. Returns how many were deleted.
+pub async fn delete_orphaned_partials(state: &AppState, paths: Vec<String>) -> Result<u32> {
+    let orphans = state.orphaned_partials().await?;
+    let mut deleted = Vec::new();
+    for orphan in orphans.iter().filter(|o| paths.contains(&o.path)) {
+        let part = std::path::Path::new(&orphan.path);
+        match std::fs::remove_file(part) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                log::warn!("Failed to delete {}: {}", orphan.path, e);
+                continue;
+            }
+        }
+        crate::orphans::remove_sidecar(part);
+        deleted.push(orphan.path.clone());
+    }
+    state.forget_orphaned_partials(&deleted);
+    log::info!("Deleted {} orphaned partial download(s)", deleted.len());
+    Ok(deleted.len() as u32)
+}
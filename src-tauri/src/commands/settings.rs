@@ -1,6 +1,15 @@
+use crate::categories::{default_rules, normalize_rules};
 use crate::constants::DEFAULT_USER_AGENT;
 use crate::db::Settings;
-use crate::{AppState, Result};
+use crate::locations::{location_infos, normalize_locations};
+use crate::rename_rules::{self, RenameTarget, RENAME_RULES_KEY};
+use crate::state::{apply_battery_limits, apply_encryption, apply_peer_discovery, apply_web_seeds};
+use crate::templates::normalize_template;
+use crate::types::{
+    CategoryRule, DownloadLocation, DownloadLocationInfo, DownloadTemplate, RenamePreview,
+    RenameRule, SettingIssue,
+};
+use crate::{AppState, Error, Result};
 use std::path::PathBuf;
 
 pub async fn get_settings(state: &AppState) -> Result<Settings> {
@@ -8,14 +17,159 @@ pub async fn get_settings(state: &AppState) -> Result<Settings> {
     db.get_settings_async().await
 }
 
-pub async fn update_settings(
-    state: &AppState,
-    settings: Settings,
-) -> Result<()> {
+pub async fn update_settings(state: &AppState, settings: Settings) -> Result<()> {
     let db = state.get_db().await?;
     db.save_settings_async(settings).await
 }
 
+pub async fn get_categories(state: &AppState) -> Result<Vec<CategoryRule>> {
+    state.get_db().await?.get_category_rules_async().await
+}
+
+/// Replace the category rules; their order is the order they are checked in.
+pub async fn set_categories(
+    state: &AppState,
+    rules: Vec<CategoryRule>,
+) -> Result<Vec<CategoryRule>> {
+    let rules = normalize_rules(rules)?;
+    state
+        .get_db()
+        .await?
+        .save_category_rules_async(rules.clone())
+        .await?;
+    Ok(rules)
+}
+
+pub async fn reset_categories(state: &AppState) -> Result<Vec<CategoryRule>> {
+    let rules = default_rules();
+    state
+        .get_db()
+        .await?
+        .save_category_rules_async(rules.clone())
+        .await?;
+    Ok(rules)
+}
+
+/// The default download directory and the named locations, with the free
+/// space on each one's drive.
+pub async fn list_download_locations(state: &AppState) -> Result<Vec<DownloadLocationInfo>> {
+    let db = state.get_db().await?;
+    let settings = db.get_settings_async().await?;
+    let locations = db.get_download_locations_async().await?;
+    let usage = db.get_location_usage_async().await?;
+    Ok(location_infos(&settings.download_path, &locations, &usage))
+}
+
+/// Replace the named locations, in the order the add dialog lists them.
+pub async fn set_download_locations(
+    state: &AppState,
+    locations: Vec<DownloadLocation>,
+) -> Result<Vec<DownloadLocationInfo>> {
+    let locations = normalize_locations(locations)?;
+    state
+        .get_db()
+        .await?
+        .save_download_locations_async(locations)
+        .await?;
+    list_download_locations(state).await
+}
+
+pub async fn get_download_templates(state: &AppState) -> Result<Vec<DownloadTemplate>> {
+    state.get_db().await?.get_download_templates_async().await
+}
+
+/// Add a template, or replace the one with the same name.
+pub async fn save_download_template(
+    state: &AppState,
+    template: DownloadTemplate,
+) -> Result<DownloadTemplate> {
+    let template = normalize_template(template)?;
+    state
+        .get_db()
+        .await?
+        .save_download_template_async(template.clone())
+        .await?;
+    Ok(template)
+}
+
+pub async fn delete_download_template(state: &AppState, name: String) -> Result<()> {
+    if !state
+        .get_db()
+        .await?
+        .delete_download_template_async(name.clone())
+        .await?
+    {
+        return Err(Error::NotFound(format!(
+            "Download template not found: {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+pub async fn get_rename_rules(state: &AppState) -> Result<Vec<RenameRule>> {
+    rename_rules::load_rules(state).await
+}
+
+/// Replace the rename rules; their order is the order they are checked in.
+pub async fn set_rename_rules(state: &AppState, rules: Vec<RenameRule>) -> Result<Vec<RenameRule>> {
+    let rules = rename_rules::normalize_rules(rules)?;
+    state
+        .get_db()
+        .await?
+        .set_meta_async(RENAME_RULES_KEY, serde_json::to_string(&rules)?)
+        .await?;
+    Ok(rules)
+}
+
+/// The name a file would get on completion, by `rules` if given (say,
+/// while they are being edited) or else the saved ones.
+pub async fn preview_rename(
+    state: &AppState,
+    name: String,
+    url: Option<String>,
+    category: Option<String>,
+    rules: Option<Vec<RenameRule>>,
+) -> Result<Option<RenamePreview>> {
+    let rules = match rules {
+        Some(rules) => rename_rules::normalize_rules(rules)?,
+        None => rename_rules::load_rules(state).await?,
+    };
+    let target = RenameTarget {
+        name: &name,
+        url: url.as_deref(),
+        category: category.as_deref().unwrap_or_default(),
+        date: chrono::Local::now().date_naive(),
+    };
+    Ok(rename_rules::rename(&rules, &target))
+}
+
+/// Settings-table key holding the speed schedule rules (JSON), which the
+/// engine doesn't keep across restarts.
+pub const SCHEDULE_RULES_KEY: &str = "schedule_rules";
+
+/// Replace the engine's speed schedule and save it so it is re-applied
+/// when the engine next starts.
+pub async fn set_schedule_rules(state: &AppState, rules: Vec<gosh_dl::ScheduleRule>) -> Result<()> {
+    if let Some(rule) = rules.iter().find(|r| r.start_hour > 23 || r.end_hour > 23) {
+        return Err(Error::InvalidInput(format!(
+            "Schedule hours must be 0-23, got {}-{}",
+            rule.start_hour, rule.end_hour
+        )));
+    }
+    let engine = state.get_engine().await?;
+    let db = state.get_db().await?;
+    db.set_meta_async(SCHEDULE_RULES_KEY, serde_json::to_string(&rules)?)
+        .await?;
+    engine.set_schedule_rules(rules);
+    Ok(())
+}
+
+/// What is wrong with `settings`, field by field, without saving them.
+pub async fn validate_settings(settings: Settings) -> Vec<SettingIssue> {
+    crate::settings_check::check_settings_online(&settings).await
+}
+
 pub fn set_close_to_tray(state: &AppState, value: bool) {
     state.set_close_to_tray(value);
 }
@@ -53,12 +207,12 @@ pub async fn update_tracker_list(state: &AppState) -> Result<Vec<String>> {
     Ok(trackers)
 }
 
-pub async fn apply_settings_to_engine(
-    state: &AppState,
-    settings: Settings,
-) -> Result<()> {
+pub async fn apply_settings_to_engine(state: &AppState, settings: Settings) -> Result<()> {
     use gosh_dl::AllocationMode;
 
+    state.set_unit_system(&settings.unit_system);
+    state.set_delta_progress(settings.delta_progress_events);
+
     let engine = state.get_engine().await?;
     let mut config = engine.get_config();
 
@@ -80,12 +234,19 @@ pub async fn apply_settings_to_engine(
         config.global_upload_limit = None;
     }
 
+    apply_peer_discovery(&mut config, &settings, state.is_vpn_down());
+    apply_web_seeds(&mut config, &settings);
+    apply_encryption(&mut config, &settings);
+    if state.is_battery_saver() {
+        apply_battery_limits(&mut config, &settings);
+    }
+    if state.is_load_guarded() {
+        crate::load_guard::apply_load_limits(&mut config, &settings);
+    }
     config.user_agent = settings.user_agent;
-    config.enable_dht = settings.bt_enable_dht;
-    config.enable_pex = settings.bt_enable_pex;
-    config.enable_lpd = settings.bt_enable_lpd;
     config.max_peers = settings.bt_max_peers as usize;
     config.seed_ratio = settings.bt_seed_ratio;
+    state.apply_fd_budget(&mut config);
 
     // Proxy
     config.http.proxy_url = if settings.proxy_url.is_empty() {
@@ -98,6 +259,8 @@ pub async fn apply_settings_to_engine(
     config.http.connect_timeout = settings.connect_timeout;
     config.http.read_timeout = settings.read_timeout;
     config.http.max_retries = settings.max_retries as usize;
+    config.http.max_redirects = settings.max_redirects as usize;
+    config.http.accept_invalid_certs = settings.accept_invalid_certs;
 
     // File allocation mode
     config.torrent.allocation_mode = match settings.allocation_mode.as_str() {
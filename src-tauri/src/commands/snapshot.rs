@@ -0,0 +1,166 @@
+use crate::types::{DownloadChanges, ProgressSnapshot, UiFilters};
+use crate::{AppState, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Longest a `get_changes` call waits for something to change.
+const MAX_CHANGES_WAIT_MS: u64 = 30_000;
+
+/// How often a waiting `get_changes` looks at the list again.
+const CHANGES_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Everything the Downloads view renders, in one call: downloads with
+/// display-ready fields, the active filters, global stats and engine health.
+///
+/// The snapshot carries a version that only changes when its content does.
+/// Pass the last seen version as `since_version` to get `{ version,
+/// changed: false }` instead of the full payload when nothing moved.
+pub async fn get_ui_snapshot(
+    state: &AppState,
+    since_version: Option<u64>,
+) -> Result<serde_json::Value> {
+    let adapter = state.get_adapter().await?;
+    let fmt = state.number_format();
+    let filters = state.ui_filters();
+
+    let downloads: Vec<serde_json::Value> = adapter
+        .get_all()
+        .into_iter()
+        .map(|d| {
+            let progress = if d.total_size > 0 {
+                (d.completed_size as f64 / d.total_size as f64 * 100.0).round() as u32
+            } else {
+                0
+            };
+            let remaining = d.total_size.saturating_sub(d.completed_size);
+            let formatted = serde_json::json!({
+                "totalSize": fmt.bytes(d.total_size),
+                "completedSize": fmt.bytes(d.completed_size),
+                "downloadSpeed": fmt.speed(d.download_speed),
+                "uploadSpeed": fmt.speed(d.upload_speed),
+                "eta": fmt.eta(remaining, d.download_speed),
+                "progress": format!("{}%", progress),
+            });
+            let visible = filters.matches(&d);
+            let mut value = serde_json::to_value(&d).unwrap_or(serde_json::Value::Null);
+            if let Some(obj) = value.as_object_mut() {
+                obj.insert("formatted".into(), formatted);
+                obj.insert("visible".into(), visible.into());
+            }
+            value
+        })
+        .collect();
+
+    let stats = adapter.get_global_stats();
+    let body = serde_json::json!({
+        "downloads": downloads,
+        "filters": filters,
+        "stats": stats,
+        "formattedStats": {
+            "downloadSpeed": fmt.speed(stats.download_speed),
+            "uploadSpeed": fmt.speed(stats.upload_speed),
+        },
+        "health": super::get_engine_health(state).await?,
+    });
+
+    let version = state.ui_snapshot_version(content_hash(&body));
+    if since_version == Some(version) {
+        return Ok(serde_json::json!({ "version": version, "changed": false }));
+    }
+
+    let mut snapshot = body;
+    if let Some(obj) = snapshot.as_object_mut() {
+        obj.insert("version".into(), version.into());
+        obj.insert("changed".into(), true.into());
+    }
+    Ok(snapshot)
+}
+
+/// Downloads that changed after `since_version`, and the GIDs of those
+/// removed since, so a large list can be refreshed without sending it all.
+/// Without a version (or with one from an earlier session) the whole list
+/// comes back with `full` set.
+///
+/// With `wait_ms`, a call that would return nothing waits up to that long
+/// (capped at 30 seconds) for something to change, for long polling.
+pub async fn get_changes(
+    state: &AppState,
+    since_version: Option<u64>,
+    wait_ms: Option<u64>,
+) -> Result<DownloadChanges> {
+    let deadline =
+        Instant::now() + Duration::from_millis(wait_ms.unwrap_or(0).min(MAX_CHANGES_WAIT_MS));
+    loop {
+        let adapter = state.get_adapter().await?;
+        let downloads = adapter.get_all();
+        let changes = state.download_changes(&downloads, since_version);
+        let quiet = changes.changed.is_empty() && changes.removed.is_empty() && !changes.full;
+        if !quiet || Instant::now() >= deadline {
+            let changed: std::collections::HashSet<&String> = changes.changed.iter().collect();
+            return Ok(DownloadChanges {
+                version: changes.version,
+                downloads: downloads
+                    .into_iter()
+                    .filter(|d| changed.contains(&d.gid))
+                    .collect(),
+                removed: changes.removed,
+                full: changes.full,
+            });
+        }
+        tokio::time::sleep(CHANGES_POLL_INTERVAL).await;
+    }
+}
+
+/// The full progress last reported for `gids` (every download if None),
+/// with the sequence number of the `download:progress` that carried it, for
+/// clients of delta progress events that missed one.
+pub fn get_progress_snapshots(
+    state: &AppState,
+    gids: Option<Vec<String>>,
+) -> Vec<ProgressSnapshot> {
+    state.progress_snapshots(gids.as_deref())
+}
+
+/// Record the filters the Downloads view is showing.
+pub fn set_ui_filters(state: &AppState, filters: UiFilters) {
+    state.set_ui_filters(filters);
+}
+
+/// Hash of the snapshot content, ignoring the engine uptime (which would
+/// otherwise change the version every second).
+fn content_hash(body: &serde_json::Value) -> u64 {
+    let mut stable = body.clone();
+    if let Some(health) = stable.get_mut("health").and_then(|h| h.as_object_mut()) {
+        health.remove("uptimeSecs");
+    }
+    let mut hasher = DefaultHasher::new();
+    stable.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash_ignores_uptime() {
+        let a =
+            serde_json::json!({ "downloads": [], "health": { "running": true, "uptimeSecs": 1 } });
+        let b =
+            serde_json::json!({ "downloads": [], "health": { "running": true, "uptimeSecs": 99 } });
+        let c =
+            serde_json::json!({ "downloads": [1], "health": { "running": true, "uptimeSecs": 1 } });
+        assert_eq!(content_hash(&a), content_hash(&b));
+        assert_ne!(content_hash(&a), content_hash(&c));
+    }
+
+    #[test]
+    fn test_snapshot_version_changes_with_content() {
+        let state = AppState::new();
+        let v1 = state.ui_snapshot_version(1);
+        assert_eq!(state.ui_snapshot_version(1), v1);
+        let v2 = state.ui_snapshot_version(2);
+        assert!(v2 > v1);
+    }
+}
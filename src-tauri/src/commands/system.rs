@@ -1,6 +1,11 @@
 use crate::constants::{ENGINE_NAME, ENGINE_VERSION};
+use crate::reveal;
+use crate::tasks::TaskProgress;
+use crate::types::{Capabilities, EventFilter, LoadGuardStatus, UpdateInfo};
 use crate::{AppState, Error, Result};
+use chrono::Utc;
 use std::path::PathBuf;
+use tauri::Emitter;
 
 /// Validate and canonicalize a filesystem path.
 /// Rejects empty paths, URL schemes, and paths that don't exist on disk.
@@ -9,7 +14,9 @@ fn validate_path(path: &str) -> Result<PathBuf> {
         return Err(Error::InvalidInput("Path cannot be empty".into()));
     }
     if path.contains("://") {
-        return Err(Error::InvalidInput("URL schemes are not allowed in file paths".into()));
+        return Err(Error::InvalidInput(
+            "URL schemes are not allowed in file paths".into(),
+        ));
     }
     let p = PathBuf::from(path);
     let canonical = p.canonicalize().map_err(|_| {
@@ -27,78 +34,138 @@ pub async fn get_engine_version(state: &AppState) -> Result<serde_json::Value> {
     }))
 }
 
-pub fn open_download_folder(path: String) -> Result<()> {
-    let validated = validate_path(&path)?;
-    let path_str = validated.to_string_lossy();
-
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(path_str.as_ref())
-            .spawn()?;
-    }
+/// Engine health for the degraded-engine banner: whether it is running,
+/// how often it was restarted this session, the last startup error, how
+/// long the current instance has been up, and its open file budget.
+pub async fn get_engine_health(state: &AppState) -> Result<serde_json::Value> {
+    let started_at = state.engine_started_at();
+    Ok(serde_json::json!({
+        "running": state.is_engine_running().await && started_at.is_some(),
+        "restartCount": state.engine_restart_count(),
+        "lastError": state.engine_last_error(),
+        "startedAt": started_at.map(|t| t.to_rfc3339()),
+        "uptimeSecs": started_at.map(|t| (Utc::now() - t).num_seconds().max(0)),
+        "fdBudget": state.fd_budget(),
+    }))
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(path_str.as_ref())
-            .spawn()?;
-    }
+pub fn list_tasks(state: &AppState) -> Vec<TaskProgress> {
+    state.tasks().list()
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .arg(path_str.as_ref())
-            .spawn()?;
-    }
+pub fn cancel_task(state: &AppState, id: String) -> Result<()> {
+    state.tasks().cancel(&id)
+}
 
-    Ok(())
+/// Open a filtered event stream. Matching events are emitted on
+/// `download-events:<id>` until the subscription is closed.
+pub fn subscribe_download_events(state: &AppState, filter: EventFilter) -> Result<String> {
+    let id = state.subscriptions().lock().unwrap().subscribe(filter)?;
+    log::debug!("Opened event subscription {}", id);
+    Ok(id)
 }
 
-pub fn open_file_location(file_path: String) -> Result<()> {
-    let validated = validate_path(&file_path)?;
-    let folder = if validated.is_dir() {
-        validated.clone()
-    } else {
-        validated.parent().unwrap_or(&validated).to_path_buf()
-    };
+pub fn unsubscribe_download_events(state: &AppState, id: String) -> Result<()> {
+    state.subscriptions().lock().unwrap().unsubscribe(&id)
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&folder)
-            .spawn()?;
-    }
+/// Latest system load readings and whether the load guard is easing off.
+pub fn get_load_guard_status(state: &AppState) -> LoadGuardStatus {
+    state.load_guard_status()
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        if validated.exists() && !validated.is_dir() {
-            std::process::Command::new("open")
-                .arg("-R")
-                .arg(&validated)
-                .spawn()?;
-        } else {
-            std::process::Command::new("open")
-                .arg(&folder)
-                .spawn()?;
+/// Shut the engine down and start it again with the saved settings.
+pub async fn restart_engine(state: &AppState, app: tauri::AppHandle) -> Result<()> {
+    let _ = app.emit(
+        "engine-status",
+        serde_json::json!({ "connected": false, "restarting": true }),
+    );
+    match state.reinitialize(app.clone()).await {
+        Ok(()) => {
+            log::info!(
+                "Engine restarted (restart #{})",
+                state.engine_restart_count()
+            );
+            let _ = app.emit(
+                "engine-status",
+                serde_json::json!({ "connected": true, "restarting": false }),
+            );
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Engine restart failed: {}", e);
+            state.record_engine_error(e.to_string());
+            let _ = app.emit(
+                "engine-status",
+                serde_json::json!({ "connected": false, "restarting": false }),
+            );
+            Err(e)
         }
     }
+}
+
+/// Directories the open commands accept paths from.
+async fn download_roots(state: &AppState) -> Result<Vec<PathBuf>> {
+    let db = state.get_db().await?;
+    let settings = db.get_settings_async().await?;
+    let locations = db.get_download_locations_async().await?;
+    let rules = db.get_category_rules_async().await?;
+    Ok(reveal::download_roots(&settings, &locations, &rules))
+}
 
-    #[cfg(target_os = "windows")]
+/// A download's file (or torrent root folder), running or in history.
+async fn download_target(state: &AppState, gid: &str) -> Result<PathBuf> {
+    let download = match state
+        .get_adapter()
+        .await
+        .ok()
+        .and_then(|adapter| adapter.get_status(gid))
     {
-        if validated.exists() && !validated.is_dir() {
-            std::process::Command::new("explorer")
-                .arg("/select,")
-                .arg(&validated)
-                .spawn()?;
-        } else {
-            std::process::Command::new("explorer")
-                .arg(&folder)
-                .spawn()?;
-        }
-    }
+        Some(download) => download,
+        None => state
+            .get_db()
+            .await?
+            .get_completed_downloads_async()
+            .await?
+            .into_iter()
+            .find(|d| d.gid == gid)
+            .ok_or_else(|| Error::NotFound(format!("Download not found: {}", gid)))?,
+    };
+    let target = reveal::download_target(&download);
+    // Its own directory too, which the app recorded rather than the webview
+    let mut roots = download_roots(state).await?;
+    roots.extend(PathBuf::from(&download.save_path).canonicalize());
+    reveal::check_target(&target.to_string_lossy(), &roots)
+}
 
-    Ok(())
+pub async fn open_download_folder(state: &AppState, path: String) -> Result<()> {
+    let validated = reveal::check_target(&path, &download_roots(state).await?)?;
+    reveal::open(&validated)
+}
+
+pub async fn open_file_location(state: &AppState, file_path: String) -> Result<()> {
+    let validated = reveal::check_target(&file_path, &download_roots(state).await?)?;
+    reveal::reveal(&validated)
+}
+
+/// Open a download's file with the app the OS associates with it; a
+/// multi-file torrent's folder opens in the file manager.
+pub async fn open_download_file(state: &AppState, gid: String) -> Result<()> {
+    reveal::open(&download_target(state, &gid).await?)
+}
+
+/// Show a download in the file manager: the file, or the root folder of a
+/// multi-file torrent.
+pub async fn reveal_download(state: &AppState, gid: String) -> Result<()> {
+    reveal::reveal(&download_target(state, &gid).await?)
+}
+
+/// Full path of a download's file or torrent folder, for copying.
+pub async fn get_download_path(state: &AppState, gid: String) -> Result<String> {
+    Ok(download_target(state, &gid)
+        .await?
+        .to_string_lossy()
+        .to_string())
 }
 
 pub fn get_default_download_path() -> String {
@@ -117,6 +184,18 @@ pub fn get_app_version(app: &tauri::AppHandle) -> String {
     app.package_info().version.to_string()
 }
 
+/// Compare this build with the newest release on the configured channel.
+/// The opt-in setting only gates the background check; asking always checks.
+pub async fn get_update_info(state: &AppState, app: &tauri::AppHandle) -> Result<UpdateInfo> {
+    let settings = state.get_db().await?.get_settings_async().await?;
+    crate::updates::check(
+        &get_app_version(app),
+        &settings.update_channel,
+        &settings.proxy_url,
+    )
+    .await
+}
+
 /// Free/total disk space for a path (defaults to the user's download dir).
 pub fn get_disk_space(path: Option<String>) -> Result<serde_json::Value> {
     let target = match path {
@@ -148,10 +227,7 @@ pub fn perform_system_action(action: &str, force_close_apps: bool) -> Result<()>
             #[cfg(target_os = "linux")]
             return run_system_command("systemctl", &["suspend"]);
             #[cfg(target_os = "windows")]
-            return run_system_command(
-                "rundll32.exe",
-                &["powrprof.dll,SetSuspendState", "0,1,0"],
-            );
+            return run_system_command("rundll32.exe", &["powrprof.dll,SetSuspendState", "0,1,0"]);
             #[allow(unreachable_code)]
             Err(Error::InvalidInput("Unsupported platform".into()))
         }
@@ -191,12 +267,25 @@ pub fn perform_system_action(action: &str, force_close_apps: bool) -> Result<()>
 pub fn read_settings_json(path: String) -> Result<serde_json::Value> {
     let validated = validate_path(&path)?;
     if validated.extension().and_then(|e| e.to_str()) != Some("json") {
-        return Err(Error::InvalidInput("File must have a .json extension".into()));
+        return Err(Error::InvalidInput(
+            "File must have a .json extension".into(),
+        ));
     }
     let content = std::fs::read_to_string(&validated)?;
     Ok(serde_json::from_str(&content)?)
 }
 
+/// Protocols and optional features of this build, so clients can adapt
+/// their UI to it.
+pub async fn get_capabilities(state: &AppState, app: &tauri::AppHandle) -> Capabilities {
+    let config = state
+        .get_engine()
+        .await
+        .ok()
+        .map(|engine| engine.get_config());
+    crate::capabilities::capabilities(&get_app_version(app), config.as_ref())
+}
+
 pub fn get_app_info(app: &tauri::AppHandle) -> serde_json::Value {
     serde_json::json!({
         "name": "Gosh-Fetch",
@@ -204,6 +293,7 @@ pub fn get_app_info(app: &tauri::AppHandle) -> serde_json::Value {
         "description": "Gosh Fetch - the modern download manager powered by gosh-dl",
         "license": "AGPL-3.0",
         "repository": "https://github.com/goshitsarch-eng/Gosh-Fetch",
+        "portable": crate::paths::is_portable(),
         "engine": {
             "name": ENGINE_NAME,
             "version": ENGINE_VERSION,
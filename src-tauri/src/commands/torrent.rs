@@ -1,34 +1,335 @@
-use crate::engine_adapter::{PeerInfo, TorrentFileInfo};
-use crate::types::{DownloadFile, DownloadOptions, MagnetInfo, TorrentFile, TorrentInfo};
+use super::download::{apply_category, keep_options, known_downloads, with_template};
+use crate::add_plan;
+use crate::db::Settings;
+use crate::engine_adapter::{EngineAdapter, PeerInfo, TorrentFileInfo};
+use crate::file_filter::CompiledFilter;
+use crate::geoip::{self, GeoIpDb};
+use crate::templates;
+use crate::torrent_edit;
+use crate::types::{
+    AddPlan, DownloadFile, DownloadOptions, FileFilter, MagnetInfo, SwarmGeography, TorrentFile,
+    TorrentInfo,
+};
 use crate::{AppState, Error, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// Largest .torrent `add_torrent_url` will download.
+const MAX_TORRENT_BYTES: usize = 16 * 1024 * 1024;
+const TORRENT_FETCH_TIMEOUT_SECS: u64 = 30;
+/// How long adding a magnet link waits for the global tracker list
+const TRACKER_FETCH_WAIT: Duration = Duration::from_secs(5);
 
 pub async fn add_torrent_file(
     state: &AppState,
     file_path: String,
     options: Option<DownloadOptions>,
 ) -> Result<String> {
-    let adapter = state.get_adapter().await?;
+    state.ensure_torrents_allowed().await?;
+    let (options, _) = with_template(state, options).await?;
     let torrent_data = std::fs::read(&file_path)?;
-    let gid = adapter.add_torrent(&torrent_data, options).await?;
+    let gid = add_torrent_data(state, &torrent_data, options).await?;
     log::info!("Added torrent from file: {} with GID: {}", file_path, gid);
     Ok(gid)
 }
 
+/// Dry run of `add_torrent_file`: where the torrent would be saved and
+/// with which options, plus warnings, without adding it.
+pub async fn plan_torrent_file(
+    state: &AppState,
+    file_path: String,
+    options: Option<DownloadOptions>,
+) -> Result<AddPlan> {
+    state.ensure_torrents_allowed().await?;
+    let (mut options, _) = with_template(state, options).await?;
+    let torrent_data = std::fs::read(&file_path)?;
+    let settings = state.get_db().await?.get_settings_async().await?;
+    check_private_edits(&torrent_data, options.as_ref(), &settings)?;
+    let (torrent_data, edit_warning) = edit_torrent(&torrent_data, &mut options)?;
+    let metainfo = gosh_dl::torrent::Metainfo::parse(&torrent_data)
+        .map_err(|e| Error::InvalidInput(format!("Failed to parse torrent: {}", e)))?;
+    let mut options = apply_file_filter_option(&torrent_data, options)?.unwrap_or_default();
+    let name = torrent_category_name(&torrent_data);
+    apply_category(state, &mut options, name.as_deref(), None, true).await?;
+    let directory = options.dir.clone().unwrap_or(settings.download_path);
+
+    let mut warnings =
+        add_plan::target_warnings(Path::new(&directory), &metainfo.info.name, None, None);
+    warnings.extend(edit_warning);
+    let info_hash = hex::encode(metainfo.info_hash);
+    let adapter = state.get_adapter().await?;
+    let downloads = known_downloads(state, &adapter).await?;
+    if let Some(duplicate) = add_plan::find_duplicate(&downloads, None, Some(&info_hash)) {
+        warnings.push(add_plan::duplicate_warning(duplicate));
+    }
+    if metainfo.info.private {
+        warnings.push(
+            "Private torrent: peers come from its trackers only, not DHT, PEX or LPD".to_string(),
+        );
+    }
+
+    Ok(AddPlan {
+        url: None,
+        path: Some(
+            Path::new(&directory)
+                .join(&metainfo.info.name)
+                .to_string_lossy()
+                .to_string(),
+        ),
+        name: Some(metainfo.info.name),
+        directory,
+        temp_directory: None,
+        category: options.category.clone(),
+        // Torrents seed from their files, so they keep their names
+        renamed: None,
+        options,
+        warnings,
+    })
+}
+
+/// Download a .torrent over HTTP and add it. The request carries the
+/// options' `user_agent`, `referer` and `header` entries (cookies,
+/// `Authorization`), so torrents behind a login can be fetched.
+pub async fn add_torrent_url(
+    state: &AppState,
+    url: String,
+    options: Option<DownloadOptions>,
+) -> Result<String> {
+    state.ensure_torrents_allowed().await?;
+    let (options, _) = with_template(state, options).await?;
+    let settings = state.get_db().await?.get_settings_async().await?;
+    let torrent_data = fetch_torrent(
+        &url,
+        options.as_ref(),
+        &settings.user_agent,
+        &settings.proxy_url,
+        settings.accept_invalid_certs,
+    )
+    .await?;
+    gosh_dl::torrent::Metainfo::parse(&torrent_data).map_err(|e| {
+        Error::InvalidInput(format!("{} did not return a valid torrent: {}", url, e))
+    })?;
+    let gid = add_torrent_data(state, &torrent_data, options).await?;
+    log::info!("Added torrent from URL: {} with GID: {}", url, gid);
+    Ok(gid)
+}
+
+async fn fetch_torrent(
+    url: &str,
+    options: Option<&DownloadOptions>,
+    default_user_agent: &str,
+    proxy_url: &str,
+    accept_invalid_certs: bool,
+) -> Result<Vec<u8>> {
+    let network = |e: reqwest::Error| Error::Network(format!("Failed to fetch torrent: {}", e));
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(TORRENT_FETCH_TIMEOUT_SECS))
+        .user_agent(
+            options
+                .and_then(|o| o.user_agent.as_deref())
+                .unwrap_or(default_user_agent),
+        )
+        .danger_accept_invalid_certs(accept_invalid_certs);
+    if !proxy_url.is_empty() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url).map_err(network)?);
+    }
+    let mut request = builder.build().map_err(network)?.get(url);
+    if let Some(referer) = options.and_then(|o| o.referer.as_deref()) {
+        request = request.header(reqwest::header::REFERER, referer);
+    }
+    for header in options
+        .and_then(|o| o.header.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        if let Some((name, value)) = header.split_once(':') {
+            request = request.header(name.trim(), value.trim());
+        }
+    }
+
+    let mut response = request.send().await.map_err(network)?;
+    if !response.status().is_success() {
+        return Err(Error::Network(format!(
+            "Failed to fetch torrent: HTTP {}",
+            response.status()
+        )));
+    }
+    let too_large = || {
+        Error::InvalidInput(format!(
+            "Torrent at {} is larger than {} MiB",
+            url,
+            MAX_TORRENT_BYTES / (1024 * 1024)
+        ))
+    };
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_TORRENT_BYTES as u64)
+    {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(network)? {
+        if data.len() + chunk.len() > MAX_TORRENT_BYTES {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Add a torrent from its metainfo, applying metainfo edits, file filter,
+/// category and strict private mode.
+async fn add_torrent_data(
+    state: &AppState,
+    torrent_data: &[u8],
+    mut options: Option<DownloadOptions>,
+) -> Result<String> {
+    let adapter = state.get_adapter().await?;
+    let settings = state.get_db().await?.get_settings_async().await?;
+    check_private_edits(torrent_data, options.as_ref(), &settings)?;
+    let (torrent_data, edit_warning) = edit_torrent(torrent_data, &mut options)?;
+    if let Some(warning) = edit_warning {
+        log::warn!("{}", warning);
+    }
+    let torrent_data = torrent_data.as_slice();
+    let mut options = apply_file_filter_option(torrent_data, options)?.unwrap_or_default();
+    let name = torrent_category_name(torrent_data);
+    apply_category(state, &mut options, name.as_deref(), None, false).await?;
+    let kept = options.clone();
+
+    let gid = adapter.add_torrent(torrent_data, Some(options)).await?;
+    // Without a URL: it is added again from its info hash
+    keep_options(state, &gid, None, kept).await;
+    Ok(gid)
+}
+
+/// Strict private mode: refuse metainfo edits that clear a private
+/// torrent's `private` flag, which would put the tracker's torrent on DHT
+/// and PEX under a new info hash.
+fn check_private_edits(
+    torrent_data: &[u8],
+    options: Option<&DownloadOptions>,
+    settings: &Settings,
+) -> Result<()> {
+    let clears_private = options
+        .and_then(|o| o.torrent_edits.as_ref())
+        .is_some_and(|edits| edits.private == Some(false));
+    if !settings.bt_strict_private_mode || !clears_private {
+        return Ok(());
+    }
+    let private = gosh_dl::torrent::Metainfo::parse(torrent_data)
+        .map(|metainfo| metainfo.info.private)
+        .unwrap_or(false);
+    if !private {
+        return Ok(());
+    }
+    log::warn!("Strict private mode: refused to clear the private flag of a torrent");
+    Err(Error::InvalidInput(
+        "Strict private mode is on: a private torrent's private flag can't be cleared".to_string(),
+    ))
+}
+
 pub async fn add_magnet(
     state: &AppState,
     magnet_uri: String,
     options: Option<DownloadOptions>,
 ) -> Result<String> {
+    let (options, trackers) = with_template(state, options).await?;
+    if options.as_ref().is_some_and(|o| o.file_filter.is_some()) {
+        return Err(Error::InvalidInput(
+            "File filters need the torrent's file list, which a magnet link does not \
+             carry. Add the .torrent file instead."
+                .to_string(),
+        ));
+    }
+    if options.as_ref().is_some_and(|o| o.torrent_edits.is_some()) {
+        return Err(Error::InvalidInput(
+            "Metainfo edits need the .torrent file, which a magnet link does not carry."
+                .to_string(),
+        ));
+    }
+    state.ensure_torrents_allowed().await?;
+    let mut options = options.unwrap_or_default();
+    let name = gosh_dl::torrent::MagnetUri::parse(&magnet_uri)
+        .ok()
+        .and_then(|magnet| magnet.display_name);
+    apply_category(state, &mut options, name.as_deref(), None, false).await?;
+    let settings = state.get_db().await?.get_settings_async().await?;
     let adapter = state.get_adapter().await?;
-    let gid = adapter.add_magnet(&magnet_uri, options).await?;
+    let trackers = check_private_magnet(&magnet_uri, trackers, &settings, &adapter)?;
+    let mut magnet_uri = templates::with_trackers(&magnet_uri, &trackers);
+    if settings.bt_magnet_merge_trackers && options.skip_global_trackers != Some(true) {
+        let global = global_trackers(state, settings.auto_update_trackers).await;
+        let merged = crate::utils::trackers_to_merge(&magnet_uri, &global);
+        if !merged.is_empty() {
+            log::debug!(
+                "Adding {} trackers from the global list to the magnet link",
+                merged.len()
+            );
+            magnet_uri = templates::with_trackers(&magnet_uri, &merged);
+        }
+    }
+    let gid = adapter
+        .add_magnet(&magnet_uri, Some(options.clone()))
+        .await?;
+    keep_options(state, &gid, Some(magnet_uri), options).await;
     log::info!("Added magnet link with GID: {}", gid);
     Ok(gid)
 }
 
-pub async fn get_torrent_files(
-    state: &AppState,
-    gid: String,
-) -> Result<Vec<DownloadFile>> {
+/// Strict private mode for a magnet link that lists a private tracker. Its
+/// metadata isn't known yet, so the engine can't tell the torrent is
+/// private and would look it up on DHT: refused while DHT is on. Template
+/// trackers would get its info hash too, so they are dropped. Returns the
+/// trackers to add.
+fn check_private_magnet(
+    magnet_uri: &str,
+    trackers: Vec<String>,
+    settings: &Settings,
+    adapter: &EngineAdapter,
+) -> Result<Vec<String>> {
+    if !settings.bt_strict_private_mode || !crate::utils::lists_private_tracker(magnet_uri) {
+        return Ok(trackers);
+    }
+    if adapter.engine().get_config().enable_dht {
+        log::warn!("Strict private mode: refused a private tracker's magnet link while DHT is on");
+        return Err(Error::InvalidInput(
+            "Strict private mode is on and this magnet link is for a private tracker: it would \
+             be looked up on DHT before its metadata arrives. Add the .torrent file instead."
+                .to_string(),
+        ));
+    }
+    if !trackers.is_empty() {
+        log::warn!(
+            "Strict private mode: left {} template tracker(s) off a private tracker's magnet link",
+            trackers.len()
+        );
+    }
+    Ok(Vec::new())
+}
+
+/// The global tracker list for merging into a magnet link, fetched first
+/// if it is due and auto-update is on. A slow fetch doesn't hold up the
+/// add; the list loaded so far is used instead.
+async fn global_trackers(state: &AppState, auto_update: bool) -> Vec<String> {
+    if auto_update {
+        let fetch = super::settings::get_tracker_list(state);
+        match tokio::time::timeout(TRACKER_FETCH_WAIT, fetch).await {
+            Ok(Ok(trackers)) => return trackers,
+            Ok(Err(e)) => log::warn!("Could not fetch the tracker list: {}", e),
+            Err(_) => log::warn!("Tracker list fetch timed out; adding the magnet link without it"),
+        }
+    }
+    // Still being fetched elsewhere: go without
+    let updater = state.get_tracker_updater();
+    let trackers = updater
+        .try_read()
+        .map(|u| u.get_trackers().to_vec())
+        .unwrap_or_default();
+    trackers
+}
+
+pub async fn get_torrent_files(state: &AppState, gid: String) -> Result<Vec<DownloadFile>> {
     let adapter = state.get_adapter().await?;
     let files: Vec<TorrentFileInfo> = adapter.get_torrent_files(&gid).unwrap_or_default();
     Ok(files
@@ -55,10 +356,132 @@ pub async fn select_torrent_files(
         "Post-add file selection is not supported by the download engine. \
          File selection must be specified when adding the torrent using the \
          select_file option in DownloadOptions. To change file selection, \
-         remove the torrent and re-add it with the desired files selected.".to_string()
+         remove the torrent and re-add it with the desired files selected."
+            .to_string(),
     ))
 }
 
+/// Re-evaluate a file filter against a torrent that is already added and
+/// apply the resulting selection. Returns the selected indices.
+pub async fn apply_file_filter(
+    state: &AppState,
+    gid: String,
+    rules: FileFilter,
+) -> Result<Vec<u32>> {
+    let adapter = state.get_adapter().await?;
+    let files = adapter
+        .get_torrent_files(&gid)
+        .ok_or_else(|| Error::NotFound(format!("Torrent file list for {}", gid)))?;
+
+    let paths: Vec<String> = files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    let selected = CompiledFilter::new(&rules)?.select(paths.iter().map(String::as_str))?;
+    let indices: Vec<u32> = selected.iter().map(|&i| i as u32).collect();
+
+    let unchanged = files
+        .iter()
+        .enumerate()
+        .all(|(i, f)| f.selected == selected.contains(&i));
+    if !unchanged {
+        select_torrent_files(state, gid, indices.clone()).await?;
+    }
+    Ok(indices)
+}
+/// Apply the options' `torrent_edits` to a torrent's metainfo. Returns the
+/// torrent to add and, when its info hash changed, a warning saying so.
+fn edit_torrent(
+    torrent_data: &[u8],
+    options: &mut Option<DownloadOptions>,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let Some(edits) = options.as_mut().and_then(|o| o.torrent_edits.take()) else {
+        return Ok((torrent_data.to_vec(), None));
+    };
+    let edited = torrent_edit::edit(torrent_data, &edits)?;
+    let info_hash = |data: &[u8]| {
+        gosh_dl::torrent::Metainfo::parse(data)
+            .map(|metainfo| hex::encode(metainfo.info_hash))
+            .map_err(|e| Error::InvalidInput(format!("Failed to parse torrent: {}", e)))
+    };
+    let (before, after) = (info_hash(torrent_data)?, info_hash(&edited)?);
+    if before == after {
+        return Ok((edited, None));
+    }
+    if !edits.changes_info() {
+        // Re-encoding put a non-canonical info dictionary in order
+        return Err(Error::InvalidInput(
+            "This torrent is not canonically encoded, so editing its comment or creation date \
+             would change its info hash"
+                .to_string(),
+        ));
+    }
+    Ok((
+        edited,
+        Some(format!(
+            "Editing the torrent changed its info hash from {} to {}; it won't share peers with \
+             the original",
+            before, after
+        )),
+    ))
+}
+
+/// Resolve `file_filter` against the torrent's file list into `select_file`,
+/// intersecting with any indices the caller already chose.
+fn apply_file_filter_option(
+    torrent_data: &[u8],
+    options: Option<DownloadOptions>,
+) -> Result<Option<DownloadOptions>> {
+    let Some(mut opts) = options else {
+        return Ok(None);
+    };
+    let Some(filter) = opts.file_filter.take() else {
+        return Ok(Some(opts));
+    };
+
+    let metainfo = gosh_dl::torrent::Metainfo::parse(torrent_data)
+        .map_err(|e| Error::InvalidInput(format!("Failed to parse torrent: {}", e)))?;
+    let paths: Vec<String> = metainfo
+        .info
+        .files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    let mut selected = CompiledFilter::new(&filter)?.select(paths.iter().map(String::as_str))?;
+
+    if let Some(explicit) = opts.select_file.as_deref() {
+        let explicit: Vec<usize> = explicit
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+        selected.retain(|i| explicit.contains(i));
+        if selected.is_empty() {
+            return Err(Error::InvalidInput(
+                "File filter and select_file have no files in common".to_string(),
+            ));
+        }
+    }
+
+    opts.select_file = Some(
+        selected
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+    );
+    Ok(Some(opts))
+}
+
+/// Name a torrent is categorized by: its largest file, so a folder holding
+/// a film and a few extras is filed by the film.
+fn torrent_category_name(torrent_data: &[u8]) -> Option<String> {
+    let metainfo = gosh_dl::torrent::Metainfo::parse(torrent_data).ok()?;
+    match metainfo.info.files.iter().max_by_key(|f| f.length) {
+        Some(file) => Some(file.path.to_string_lossy().to_string()),
+        None => Some(metainfo.info.name),
+    }
+}
+
 pub fn parse_torrent_file(file_path: String) -> Result<TorrentInfo> {
     let torrent_data = std::fs::read(&file_path)?;
     match gosh_dl::torrent::Metainfo::parse(&torrent_data) {
@@ -85,7 +508,10 @@ pub fn parse_torrent_file(file_path: String) -> Result<TorrentInfo> {
                 announce_list: metainfo.announce_list.iter().flatten().cloned().collect(),
             })
         }
-        Err(e) => Err(Error::InvalidInput(format!("Failed to parse torrent: {}", e))),
+        Err(e) => Err(Error::InvalidInput(format!(
+            "Failed to parse torrent: {}",
+            e
+        ))),
     }
 }
 
@@ -96,13 +522,28 @@ pub fn parse_magnet_uri(magnet_uri: String) -> Result<MagnetInfo> {
             info_hash: hex::encode(magnet.info_hash),
             trackers: magnet.trackers.clone(),
         }),
-        Err(e) => Err(Error::InvalidInput(format!("Failed to parse magnet URI: {}", e))),
+        Err(e) => Err(Error::InvalidInput(format!(
+            "Failed to parse magnet URI: {}",
+            e
+        ))),
     }
 }
 
+/// Connected peers of a torrent, with their `country` when the GeoIP
+/// lookup is on (null otherwise, or for addresses the database lacks).
 pub async fn get_peers(state: &AppState, gid: String) -> Result<Vec<serde_json::Value>> {
     let adapter = state.get_adapter().await?;
     let peers: Vec<PeerInfo> = adapter.get_peers(&gid).unwrap_or_default();
+    let settings = match state.get_db().await {
+        Ok(db) => db.get_settings_async().await.ok(),
+        Err(_) => None,
+    };
+    let geoip = settings.and_then(|settings| {
+        state.geoip(&settings).unwrap_or_else(|e| {
+            log::warn!("GeoIP lookup unavailable: {}", e);
+            None
+        })
+    });
     Ok(peers
         .into_iter()
         .map(|p| {
@@ -112,7 +553,36 @@ pub async fn get_peers(state: &AppState, gid: String) -> Result<Vec<serde_json::
                 "client": p.client,
                 "downloadSpeed": p.download_speed,
                 "uploadSpeed": p.upload_speed,
+                "country": geoip.as_deref().and_then(|db| peer_country(db, &p.ip)),
             })
         })
         .collect())
 }
+
+/// Connected peers of a torrent counted by country. Fails if the GeoIP
+/// lookup is off or its database can't be read.
+pub async fn get_swarm_geography(state: &AppState, gid: String) -> Result<SwarmGeography> {
+    let settings = state.get_db().await?.get_settings_async().await?;
+    let geoip = state
+        .geoip(&settings)?
+        .ok_or_else(|| Error::InvalidInput("Peer GeoIP lookup is off".into()))?;
+    let adapter = state.get_adapter().await?;
+    let peers: Vec<(Option<String>, u64, u64)> = adapter
+        .get_peers(&gid)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| {
+            (
+                peer_country(&geoip, &p.ip),
+                p.download_speed,
+                p.upload_speed,
+            )
+        })
+        .collect();
+    Ok(geoip::aggregate(&peers))
+}
+
+fn peer_country(db: &GeoIpDb, ip: &str) -> Option<String> {
+    // The engine may report IPv6 peers in brackets
+    db.country(ip.trim_matches(['[', ']']).parse().ok()?)
+}
@@ -1,3 +1,6 @@
 pub const ENGINE_NAME: &str = "gosh-dl";
 pub const ENGINE_VERSION: &str = "0.5.0";
 pub const DEFAULT_USER_AGENT: &str = "gosh-dl/0.5.0";
+/// Version of the command and event API; bumped when a command or event
+/// changes in a way existing clients would break on.
+pub const API_VERSION: u32 = 1;
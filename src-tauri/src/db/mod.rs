@@ -1,14 +1,36 @@
-use crate::types::{Download, DownloadState, DownloadType};
 use crate::constants::DEFAULT_USER_AGENT;
+use crate::seeding::SeedTotals;
+use crate::staging::StagedDownload;
+use crate::traffic::TrafficTotals;
+use crate::types::{
+    CategoryRule, ConnectionProfile, DbStorageInfo, Download, DownloadLocation, DownloadOptions,
+    DownloadState, DownloadTemplate, DownloadType, HistoryStats, HostProfile, HostStats,
+    LatencyPercentiles, SeedStatsDay,
+};
 use crate::{Error, Result};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+/// Probe latencies kept for the history statistics.
+const LATENCY_SAMPLES: i64 = 2000;
+
+/// Settings holding a path, normalized when saved.
+const PATH_SETTINGS: [&str; 5] = [
+    "download_path",
+    "temp_download_path",
+    "stats_report_path",
+    "geoip_db_path",
+    "archive_path",
+];
+
 #[derive(Clone)]
 pub struct Database {
     conn: Arc<Mutex<Connection>>,
+    /// Download snapshots waiting for the next batched write, by GID
+    pending_downloads: Arc<Mutex<HashMap<String, Download>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,12 +62,255 @@ pub struct Settings {
     pub max_retries: u32,
     #[serde(default = "default_allocation_mode")]
     pub allocation_mode: String,
+    #[serde(default)]
+    pub bt_strict_private_mode: bool,
+    /// Add trackers from the global list to magnet links that list few
+    #[serde(default = "default_bt_magnet_merge_trackers")]
+    pub bt_magnet_merge_trackers: bool,
+    #[serde(default)]
+    pub bt_auto_stop_seeders: u32,
+    #[serde(default = "default_unit_system")]
+    pub unit_system: String,
+    #[serde(default = "default_failure_digest_threshold")]
+    pub failure_digest_threshold: u32,
+    #[serde(default = "default_pause_on_network_loss")]
+    pub pause_on_network_loss: bool,
+    #[serde(default)]
+    pub bt_vpn_interface: String,
+    #[serde(default)]
+    pub quiet_hours_enabled: bool,
+    #[serde(default = "default_quiet_hours_start")]
+    pub quiet_hours_start: String,
+    #[serde(default = "default_quiet_hours_end")]
+    pub quiet_hours_end: String,
+    #[serde(default)]
+    pub battery_mode_enabled: bool,
+    #[serde(default = "default_battery_threshold")]
+    pub battery_threshold: u32,
+    #[serde(default = "default_battery_max_connections")]
+    pub battery_max_connections: u32,
+    #[serde(default = "default_battery_download_limit")]
+    pub battery_download_limit: u64,
+    #[serde(default = "default_battery_upload_limit")]
+    pub battery_upload_limit: u64,
+    #[serde(default = "default_http_identity_encoding")]
+    pub http_identity_encoding: bool,
+    #[serde(default)]
+    pub delete_error_pages: bool,
+    /// "forever", "days" or "count"; see `HistoryRetention`
+    #[serde(default = "default_history_retention")]
+    pub history_retention: String,
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u32,
+    #[serde(default = "default_history_retention_count")]
+    pub history_retention_count: u32,
+    #[serde(default = "default_auto_db_maintenance")]
+    pub auto_db_maintenance: bool,
+    /// Look for new releases in the background; off unless the user opts in
+    #[serde(default)]
+    pub update_check_enabled: bool,
+    /// "stable" or "beta" (includes pre-releases)
+    #[serde(default = "default_update_channel")]
+    pub update_channel: String,
+    /// Where HTTP downloads are written until they finish; empty to write
+    /// them straight to their destination
+    #[serde(default)]
+    pub temp_download_path: String,
+    /// Restart an HTTP download from scratch once when its size changes
+    /// mid-download, instead of failing it straight away
+    #[serde(default = "default_retry_size_mismatch")]
+    pub retry_size_mismatch: bool,
+    /// Where HTTP downloads without an explicit name take it from first:
+    /// "content_disposition", "final_url" (after redirects) or "original_url"
+    #[serde(default = "default_filename_priority")]
+    pub filename_priority: String,
+    /// Redirects an HTTP download may follow
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: u32,
+    /// Follow redirects from https to plain http
+    #[serde(default)]
+    pub allow_insecure_redirects: bool,
+    /// Accept expired, self-signed or mismatched TLS certificates
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// S3-compatible endpoint for `s3://` URLs; empty for AWS
+    #[serde(default)]
+    pub s3_endpoint: String,
+    #[serde(default = "default_s3_region")]
+    pub s3_region: String,
+    /// Credentials for presigning `s3://` requests; empty for public buckets
+    #[serde(default)]
+    pub s3_access_key: String,
+    #[serde(default)]
+    pub s3_secret_key: String,
+    /// Give HTTP downloads without an explicit connection count the one
+    /// learned for their host
+    #[serde(default)]
+    pub auto_tune_connections: bool,
+    /// Minutes a finished torrent seeds before it is stopped; 0 for no limit
+    #[serde(default)]
+    pub bt_seed_time_limit: u32,
+    /// Download torrent pieces from the HTTP web seeds (BEP 19) a torrent lists
+    #[serde(default = "default_bt_webseed_enabled")]
+    pub bt_webseed_enabled: bool,
+    /// Web seed connections per torrent
+    #[serde(default = "default_bt_webseed_max_connections")]
+    pub bt_webseed_max_connections: u32,
+    /// Peer connection encryption (MSE/PE): "disabled", "prefer" or "require"
+    #[serde(default = "default_bt_encryption")]
+    pub bt_encryption: String,
+    /// Look up peer countries in the MaxMind DB at `geoip_db_path`
+    #[serde(default)]
+    pub peer_geoip: bool,
+    #[serde(default)]
+    pub geoip_db_path: String,
+    /// Ease off while CPU or disk load stays high
+    #[serde(default)]
+    pub load_guard_enabled: bool,
+    /// CPU usage (%) that counts as high; 0 ignores CPU
+    #[serde(default = "default_load_guard_cpu_percent")]
+    pub load_guard_cpu_percent: u32,
+    /// Disk I/O requests in flight that count as high; 0 ignores disks
+    #[serde(default = "default_load_guard_disk_queue")]
+    pub load_guard_disk_queue: u32,
+    #[serde(default = "default_load_guard_max_connections")]
+    pub load_guard_max_connections: u32,
+    /// Also pause low-priority downloads while the load is high
+    #[serde(default = "default_load_guard_pause_low_priority")]
+    pub load_guard_pause_low_priority: bool,
+    /// Write seeding statistics to `stats_report_path` on a schedule
+    #[serde(default)]
+    pub stats_report_enabled: bool,
+    #[serde(default)]
+    pub stats_report_path: String,
+    /// csv or json
+    #[serde(default = "default_stats_report_format")]
+    pub stats_report_format: String,
+    #[serde(default = "default_stats_report_interval_hours")]
+    pub stats_report_interval_hours: u32,
+    /// Seconds the app may spend saving state and stopping the engine on
+    /// exit before it quits anyway
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u32,
+    /// Move finished downloads older than `archive_after_days` to
+    /// `archive_path`; category rules can override both
+    #[serde(default)]
+    pub archive_enabled: bool,
+    #[serde(default = "default_archive_after_days")]
+    pub archive_after_days: u32,
+    #[serde(default)]
+    pub archive_path: String,
+    /// Send `download:progress` as the fields that changed since the last
+    /// one, with a per-download sequence number
+    #[serde(default)]
+    pub delta_progress_events: bool,
 }
 
-fn default_connect_timeout() -> u64 { 30 }
-fn default_read_timeout() -> u64 { 60 }
-fn default_max_retries() -> u32 { 3 }
-fn default_allocation_mode() -> String { "sparse".to_string() }
+fn default_connect_timeout() -> u64 {
+    30
+}
+fn default_read_timeout() -> u64 {
+    60
+}
+fn default_max_retries() -> u32 {
+    3
+}
+fn default_allocation_mode() -> String {
+    "sparse".to_string()
+}
+fn default_unit_system() -> String {
+    "binary".to_string()
+}
+fn default_failure_digest_threshold() -> u32 {
+    3
+}
+fn default_pause_on_network_loss() -> bool {
+    true
+}
+fn default_quiet_hours_start() -> String {
+    "22:00".to_string()
+}
+fn default_quiet_hours_end() -> String {
+    "07:00".to_string()
+}
+fn default_battery_threshold() -> u32 {
+    50
+}
+fn default_battery_max_connections() -> u32 {
+    2
+}
+fn default_battery_download_limit() -> u64 {
+    2 * 1024 * 1024
+}
+fn default_battery_upload_limit() -> u64 {
+    256 * 1024
+}
+fn default_load_guard_cpu_percent() -> u32 {
+    90
+}
+fn default_load_guard_disk_queue() -> u32 {
+    16
+}
+fn default_load_guard_max_connections() -> u32 {
+    2
+}
+fn default_load_guard_pause_low_priority() -> bool {
+    true
+}
+fn default_stats_report_format() -> String {
+    "csv".to_string()
+}
+fn default_stats_report_interval_hours() -> u32 {
+    24
+}
+fn default_shutdown_timeout_secs() -> u32 {
+    10
+}
+fn default_archive_after_days() -> u32 {
+    30
+}
+fn default_http_identity_encoding() -> bool {
+    true
+}
+fn default_history_retention() -> String {
+    "forever".to_string()
+}
+fn default_history_retention_days() -> u32 {
+    90
+}
+fn default_history_retention_count() -> u32 {
+    1000
+}
+fn default_auto_db_maintenance() -> bool {
+    true
+}
+fn default_update_channel() -> String {
+    "stable".to_string()
+}
+fn default_retry_size_mismatch() -> bool {
+    true
+}
+fn default_bt_magnet_merge_trackers() -> bool {
+    true
+}
+fn default_bt_webseed_enabled() -> bool {
+    true
+}
+fn default_bt_webseed_max_connections() -> u32 {
+    4
+}
+fn default_bt_encryption() -> String {
+    "prefer".to_string()
+}
+fn default_filename_priority() -> String {
+    "content_disposition".to_string()
+}
+fn default_max_redirects() -> u32 {
+    10
+}
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
 
 impl Default for Settings {
     fn default() -> Self {
@@ -78,12 +343,93 @@ impl Default for Settings {
             read_timeout: 60,
             max_retries: 3,
             allocation_mode: "sparse".to_string(),
+            bt_strict_private_mode: false,
+            bt_magnet_merge_trackers: true,
+            bt_auto_stop_seeders: 0,
+            unit_system: "binary".to_string(),
+            failure_digest_threshold: 3,
+            pause_on_network_loss: true,
+            bt_vpn_interface: String::new(),
+            quiet_hours_enabled: false,
+            quiet_hours_start: "22:00".to_string(),
+            quiet_hours_end: "07:00".to_string(),
+            battery_mode_enabled: false,
+            battery_threshold: 50,
+            battery_max_connections: 2,
+            battery_download_limit: 2 * 1024 * 1024,
+            battery_upload_limit: 256 * 1024,
+            http_identity_encoding: true,
+            delete_error_pages: false,
+            history_retention: "forever".to_string(),
+            history_retention_days: 90,
+            history_retention_count: 1000,
+            auto_db_maintenance: true,
+            update_check_enabled: false,
+            update_channel: "stable".to_string(),
+            temp_download_path: String::new(),
+            retry_size_mismatch: true,
+            filename_priority: default_filename_priority(),
+            max_redirects: 10,
+            allow_insecure_redirects: false,
+            accept_invalid_certs: false,
+            s3_endpoint: String::new(),
+            s3_region: default_s3_region(),
+            s3_access_key: String::new(),
+            s3_secret_key: String::new(),
+            auto_tune_connections: false,
+            bt_seed_time_limit: 0,
+            bt_webseed_enabled: true,
+            bt_webseed_max_connections: 4,
+            bt_encryption: default_bt_encryption(),
+            peer_geoip: false,
+            geoip_db_path: String::new(),
+            load_guard_enabled: false,
+            load_guard_cpu_percent: 90,
+            load_guard_disk_queue: 16,
+            load_guard_max_connections: 2,
+            load_guard_pause_low_priority: true,
+            stats_report_enabled: false,
+            stats_report_path: String::new(),
+            stats_report_format: "csv".to_string(),
+            stats_report_interval_hours: 24,
+            shutdown_timeout_secs: 10,
+            archive_enabled: false,
+            archive_after_days: 30,
+            archive_path: String::new(),
+            delta_progress_events: false,
+        }
+    }
+}
+
+/// How much finished history to keep. Favorites are never pruned and do
+/// not count towards the `Count` limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryRetention {
+    Forever,
+    /// Drop entries completed more than this many days ago
+    Days(u32),
+    /// Keep only this many of the most recently completed entries
+    Count(u32),
+}
+
+impl HistoryRetention {
+    /// Policy selected in `settings`; unknown modes keep everything. A limit
+    /// of zero would empty the history, so it turns pruning off instead.
+    pub fn from_settings(settings: &Settings) -> Self {
+        match settings.history_retention.as_str() {
+            "days" if settings.history_retention_days > 0 => {
+                HistoryRetention::Days(settings.history_retention_days)
+            }
+            "count" if settings.history_retention_count > 0 => {
+                HistoryRetention::Count(settings.history_retention_count)
+            }
+            _ => HistoryRetention::Forever,
         }
     }
 }
 
 /// Expand leading `~` in a path string to the user's home directory.
-fn expand_tilde(path: &str) -> String {
+pub(crate) fn expand_tilde(path: &str) -> String {
     if path.starts_with("~/") || path == "~" {
         if let Some(home) = dirs::home_dir() {
             return path.replacen("~", &home.to_string_lossy(), 1);
@@ -92,14 +438,50 @@ fn expand_tilde(path: &str) -> String {
     path.to_string()
 }
 
+/// A path as it should be stored: `~` expanded, a relative path taken from
+/// the portable data root (see `paths::data_root`) or else the home
+/// directory, and `.` and `..` resolved. Done on the components rather
+/// than with `canonicalize`, which needs the path to exist and would swap
+/// a symlinked folder for its target. Empty stays empty, for settings
+/// where that means "not set".
+pub(crate) fn normalize_path(path: &str) -> String {
+    normalize_path_from(path, crate::paths::data_root().or_else(dirs::home_dir))
+}
+
+fn normalize_path_from(path: &str, base: Option<PathBuf>) -> String {
+    let path = path.trim();
+    if path.is_empty() {
+        return String::new();
+    }
+    let mut absolute = PathBuf::from(expand_tilde(path));
+    if !absolute.is_absolute() {
+        match base {
+            Some(base) => absolute = base.join(absolute),
+            None => return path.to_string(),
+        }
+    }
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized.to_string_lossy().into_owned()
+}
+
 impl Database {
     pub fn new(data_dir: &Path) -> Result<Self> {
         std::fs::create_dir_all(data_dir)?;
-        let db_path = data_dir.join("gosh-fetch.db");
+        let db_path = crate::paths::app_db(data_dir);
         let conn = Connection::open(&db_path)?;
         conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
         let db = Self {
             conn: Arc::new(Mutex::new(conn)),
+            pending_downloads: Arc::default(),
         };
         db.run_migrations_sync()?;
         Ok(db)
@@ -124,7 +506,10 @@ impl Database {
     /// Synchronous migration -- called only once during Database::new() (not on Tokio runtime yet).
     /// Checks schema_version table to skip already-applied migrations.
     fn run_migrations_sync(&self) -> Result<()> {
-        let conn = self.conn.lock().map_err(|e| Error::Database(e.to_string()))?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| Error::Database(e.to_string()))?;
 
         // Check if schema_version table exists and what version we're at
         let current_version: i64 = conn
@@ -141,9 +526,127 @@ impl Database {
             log::info!("Applied migration 001_initial.sql");
         }
 
+        if current_version < 2 {
+            let sql = include_str!("../../migrations/002_history_timing.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 002_history_timing.sql");
+        }
+
+        if current_version < 3 {
+            let sql = include_str!("../../migrations/003_category_rules.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 003_category_rules.sql");
+        }
+
+        if current_version < 4 {
+            let sql = include_str!("../../migrations/004_history_favorites.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 004_history_favorites.sql");
+        }
+
+        if current_version < 5 {
+            let sql = include_str!("../../migrations/005_download_locations.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 005_download_locations.sql");
+        }
+
+        if current_version < 6 {
+            let sql = include_str!("../../migrations/006_staged_downloads.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 006_staged_downloads.sql");
+        }
+
+        if current_version < 7 {
+            let sql = include_str!("../../migrations/007_host_profiles.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 007_host_profiles.sql");
+        }
+
+        if current_version < 8 {
+            let sql = include_str!("../../migrations/008_seed_stats.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 008_seed_stats.sql");
+        }
+
+        if current_version < 9 {
+            let sql = include_str!("../../migrations/009_download_templates.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 009_download_templates.sql");
+        }
+
+        if current_version < 10 {
+            let sql = include_str!("../../migrations/010_seed_stats_daily.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 010_seed_stats_daily.sql");
+        }
+
+        if current_version < 11 {
+            let sql = include_str!("../../migrations/011_host_ranges.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 011_host_ranges.sql");
+        }
+
+        if current_version < 12 {
+            let sql = include_str!("../../migrations/012_download_latency.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 012_download_latency.sql");
+        }
+
+        if current_version < 13 {
+            let sql = include_str!("../../migrations/013_normalize_paths.sql");
+            conn.execute_batch(sql)?;
+            let fixed = Self::normalize_stored_paths(&conn)?;
+            log::info!(
+                "Applied migration 013_normalize_paths.sql ({} paths rewritten)",
+                fixed
+            );
+        }
+
+        if current_version < 14 {
+            let sql = include_str!("../../migrations/014_category_archive.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 014_category_archive.sql");
+        }
+
+        if current_version < 15 {
+            let sql = include_str!("../../migrations/015_download_hashes.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 015_download_hashes.sql");
+        }
+
+        if current_version < 16 {
+            let sql = include_str!("../../migrations/016_download_traffic.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 016_download_traffic.sql");
+        }
+
+        if current_version < 17 {
+            let sql = include_str!("../../migrations/017_download_renames.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 017_download_renames.sql");
+        }
+
+        if current_version < 18 {
+            let sql = include_str!("../../migrations/018_download_options.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 018_download_options.sql");
+        }
+
+        if current_version < 19 {
+            let sql = include_str!("../../migrations/019_download_queue.sql");
+            conn.execute_batch(sql)?;
+            log::info!("Applied migration 019_download_queue.sql");
+        }
+
+        // The default categories are written once the table has all of its
+        // columns
+        if current_version < 3 {
+            Self::replace_category_rules(&conn, &crate::categories::default_rules())?;
+        }
+
         // Future migrations go here:
-        // if current_version < 2 {
-        //     let sql = include_str!("../../migrations/002_xxx.sql");
+        // if current_version < 20 {
+        //     let sql = include_str!("../../migrations/020_xxx.sql");
         //     conn.execute_batch(sql)?;
         // }
 
@@ -151,7 +654,10 @@ impl Database {
     }
 
     pub fn get_settings(&self) -> Result<Settings> {
-        let conn = self.conn.lock().map_err(|e| Error::Database(e.to_string()))?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| Error::Database(e.to_string()))?;
         Self::get_settings_inner(&conn)
     }
 
@@ -181,9 +687,7 @@ impl Database {
                 "download_speed_limit" => {
                     settings.download_speed_limit = value.parse().unwrap_or(0)
                 }
-                "upload_speed_limit" => {
-                    settings.upload_speed_limit = value.parse().unwrap_or(0)
-                }
+                "upload_speed_limit" => settings.upload_speed_limit = value.parse().unwrap_or(0),
                 "user_agent" => settings.user_agent = value,
                 "enable_notifications" => settings.enable_notifications = value == "true",
                 "close_to_tray" => settings.close_to_tray = value == "true",
@@ -200,6 +704,88 @@ impl Database {
                 "read_timeout" => settings.read_timeout = value.parse().unwrap_or(60),
                 "max_retries" => settings.max_retries = value.parse().unwrap_or(3),
                 "allocation_mode" => settings.allocation_mode = value,
+                "bt_strict_private_mode" => settings.bt_strict_private_mode = value == "true",
+                "bt_magnet_merge_trackers" => settings.bt_magnet_merge_trackers = value == "true",
+                "bt_auto_stop_seeders" => {
+                    settings.bt_auto_stop_seeders = value.parse().unwrap_or(0)
+                }
+                "unit_system" => settings.unit_system = value,
+                "failure_digest_threshold" => {
+                    settings.failure_digest_threshold = value.parse().unwrap_or(3)
+                }
+                "pause_on_network_loss" => settings.pause_on_network_loss = value == "true",
+                "bt_vpn_interface" => settings.bt_vpn_interface = value,
+                "quiet_hours_enabled" => settings.quiet_hours_enabled = value == "true",
+                "quiet_hours_start" => settings.quiet_hours_start = value,
+                "quiet_hours_end" => settings.quiet_hours_end = value,
+                "battery_mode_enabled" => settings.battery_mode_enabled = value == "true",
+                "battery_threshold" => settings.battery_threshold = value.parse().unwrap_or(50),
+                "battery_max_connections" => {
+                    settings.battery_max_connections = value.parse().unwrap_or(2)
+                }
+                "battery_download_limit" => {
+                    settings.battery_download_limit = value.parse().unwrap_or(2 * 1024 * 1024)
+                }
+                "battery_upload_limit" => {
+                    settings.battery_upload_limit = value.parse().unwrap_or(256 * 1024)
+                }
+                "http_identity_encoding" => settings.http_identity_encoding = value == "true",
+                "delete_error_pages" => settings.delete_error_pages = value == "true",
+                "history_retention" => settings.history_retention = value,
+                "history_retention_days" => {
+                    settings.history_retention_days = value.parse().unwrap_or(90)
+                }
+                "history_retention_count" => {
+                    settings.history_retention_count = value.parse().unwrap_or(1000)
+                }
+                "auto_db_maintenance" => settings.auto_db_maintenance = value == "true",
+                "update_check_enabled" => settings.update_check_enabled = value == "true",
+                "update_channel" => settings.update_channel = value,
+                "temp_download_path" => settings.temp_download_path = expand_tilde(&value),
+                "retry_size_mismatch" => settings.retry_size_mismatch = value == "true",
+                "filename_priority" => settings.filename_priority = value,
+                "max_redirects" => settings.max_redirects = value.parse().unwrap_or(10),
+                "allow_insecure_redirects" => settings.allow_insecure_redirects = value == "true",
+                "accept_invalid_certs" => settings.accept_invalid_certs = value == "true",
+                "s3_endpoint" => settings.s3_endpoint = value,
+                "s3_region" => settings.s3_region = value,
+                "s3_access_key" => settings.s3_access_key = value,
+                "s3_secret_key" => settings.s3_secret_key = value,
+                "auto_tune_connections" => settings.auto_tune_connections = value == "true",
+                "bt_seed_time_limit" => settings.bt_seed_time_limit = value.parse().unwrap_or(0),
+                "bt_webseed_enabled" => settings.bt_webseed_enabled = value == "true",
+                "bt_webseed_max_connections" => {
+                    settings.bt_webseed_max_connections = value.parse().unwrap_or(4)
+                }
+                "bt_encryption" => settings.bt_encryption = value,
+                "peer_geoip" => settings.peer_geoip = value == "true",
+                "geoip_db_path" => settings.geoip_db_path = value,
+                "load_guard_enabled" => settings.load_guard_enabled = value == "true",
+                "load_guard_cpu_percent" => {
+                    settings.load_guard_cpu_percent = value.parse().unwrap_or(90)
+                }
+                "load_guard_disk_queue" => {
+                    settings.load_guard_disk_queue = value.parse().unwrap_or(16)
+                }
+                "load_guard_max_connections" => {
+                    settings.load_guard_max_connections = value.parse().unwrap_or(2)
+                }
+                "load_guard_pause_low_priority" => {
+                    settings.load_guard_pause_low_priority = value == "true"
+                }
+                "stats_report_enabled" => settings.stats_report_enabled = value == "true",
+                "stats_report_path" => settings.stats_report_path = expand_tilde(&value),
+                "stats_report_format" => settings.stats_report_format = value,
+                "stats_report_interval_hours" => {
+                    settings.stats_report_interval_hours = value.parse().unwrap_or(24)
+                }
+                "shutdown_timeout_secs" => {
+                    settings.shutdown_timeout_secs = value.parse().unwrap_or(10)
+                }
+                "archive_enabled" => settings.archive_enabled = value == "true",
+                "archive_after_days" => settings.archive_after_days = value.parse().unwrap_or(30),
+                "archive_path" => settings.archive_path = expand_tilde(&value),
+                "delta_progress_events" => settings.delta_progress_events = value == "true",
                 _ => {}
             }
         }
@@ -210,7 +796,7 @@ impl Database {
     pub async fn save_settings_async(&self, settings: Settings) -> Result<()> {
         self.with_conn(move |conn| {
             let pairs: Vec<(&str, String)> = vec![
-                ("download_path", settings.download_path.clone()),
+                ("download_path", normalize_path(&settings.download_path)),
                 ("max_concurrent_downloads", settings.max_concurrent_downloads.to_string()),
                 ("max_connections_per_server", settings.max_connections_per_server.to_string()),
                 ("split_count", settings.split_count.to_string()),
@@ -232,6 +818,63 @@ impl Database {
                 ("read_timeout", settings.read_timeout.to_string()),
                 ("max_retries", settings.max_retries.to_string()),
                 ("allocation_mode", settings.allocation_mode.clone()),
+                ("bt_strict_private_mode", settings.bt_strict_private_mode.to_string()),
+                ("bt_magnet_merge_trackers", settings.bt_magnet_merge_trackers.to_string()),
+                ("bt_auto_stop_seeders", settings.bt_auto_stop_seeders.to_string()),
+                ("unit_system", settings.unit_system.clone()),
+                ("failure_digest_threshold", settings.failure_digest_threshold.to_string()),
+                ("pause_on_network_loss", settings.pause_on_network_loss.to_string()),
+                ("bt_vpn_interface", settings.bt_vpn_interface.clone()),
+                ("quiet_hours_enabled", settings.quiet_hours_enabled.to_string()),
+                ("quiet_hours_start", settings.quiet_hours_start.clone()),
+                ("quiet_hours_end", settings.quiet_hours_end.clone()),
+                ("battery_mode_enabled", settings.battery_mode_enabled.to_string()),
+                ("battery_threshold", settings.battery_threshold.to_string()),
+                ("battery_max_connections", settings.battery_max_connections.to_string()),
+                ("battery_download_limit", settings.battery_download_limit.to_string()),
+                ("battery_upload_limit", settings.battery_upload_limit.to_string()),
+                ("http_identity_encoding", settings.http_identity_encoding.to_string()),
+                ("delete_error_pages", settings.delete_error_pages.to_string()),
+                ("history_retention", settings.history_retention.clone()),
+                ("history_retention_days", settings.history_retention_days.to_string()),
+                ("history_retention_count", settings.history_retention_count.to_string()),
+                ("auto_db_maintenance", settings.auto_db_maintenance.to_string()),
+                ("update_check_enabled", settings.update_check_enabled.to_string()),
+                ("update_channel", settings.update_channel.clone()),
+                ("temp_download_path", normalize_path(&settings.temp_download_path)),
+                ("retry_size_mismatch", settings.retry_size_mismatch.to_string()),
+                ("filename_priority", settings.filename_priority.clone()),
+                ("max_redirects", settings.max_redirects.to_string()),
+                ("allow_insecure_redirects", settings.allow_insecure_redirects.to_string()),
+                ("accept_invalid_certs", settings.accept_invalid_certs.to_string()),
+                ("s3_endpoint", settings.s3_endpoint.clone()),
+                ("s3_region", settings.s3_region.clone()),
+                ("s3_access_key", settings.s3_access_key.clone()),
+                ("s3_secret_key", settings.s3_secret_key.clone()),
+                ("auto_tune_connections", settings.auto_tune_connections.to_string()),
+                ("bt_seed_time_limit", settings.bt_seed_time_limit.to_string()),
+                ("bt_webseed_enabled", settings.bt_webseed_enabled.to_string()),
+                ("bt_webseed_max_connections", settings.bt_webseed_max_connections.to_string()),
+                ("bt_encryption", settings.bt_encryption.clone()),
+                ("peer_geoip", settings.peer_geoip.to_string()),
+                ("geoip_db_path", normalize_path(&settings.geoip_db_path)),
+                ("load_guard_enabled", settings.load_guard_enabled.to_string()),
+                ("load_guard_cpu_percent", settings.load_guard_cpu_percent.to_string()),
+                ("load_guard_disk_queue", settings.load_guard_disk_queue.to_string()),
+                ("load_guard_max_connections", settings.load_guard_max_connections.to_string()),
+                (
+                    "load_guard_pause_low_priority",
+                    settings.load_guard_pause_low_priority.to_string(),
+                ),
+                ("stats_report_enabled", settings.stats_report_enabled.to_string()),
+                ("stats_report_path", normalize_path(&settings.stats_report_path)),
+                ("stats_report_format", settings.stats_report_format.clone()),
+                ("stats_report_interval_hours", settings.stats_report_interval_hours.to_string()),
+                ("shutdown_timeout_secs", settings.shutdown_timeout_secs.to_string()),
+                ("archive_enabled", settings.archive_enabled.to_string()),
+                ("archive_after_days", settings.archive_after_days.to_string()),
+                ("archive_path", normalize_path(&settings.archive_path)),
+                ("delta_progress_events", settings.delta_progress_events.to_string()),
             ];
 
             let tx = conn.unchecked_transaction()?;
@@ -247,137 +890,1203 @@ impl Database {
         }).await
     }
 
-    pub async fn get_completed_downloads_async(&self) -> Result<Vec<Download>> {
+    pub async fn get_category_rules_async(&self) -> Result<Vec<CategoryRule>> {
         self.with_conn(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT * FROM downloads WHERE status = 'complete' ORDER BY completed_at DESC LIMIT 100",
+                "SELECT name, directory, extensions, mime_types, archive_after_days, archive_directory
+                 FROM category_rules ORDER BY position",
             )?;
-            let downloads = stmt
-                .query_map([], |row| Ok(row_to_download(row)))?
-                .filter_map(|r| r.ok())
-                .collect();
-            Ok(downloads)
+            let split = |list: String| -> Vec<String> {
+                list.split_whitespace().map(str::to_string).collect()
+            };
+            let rules = stmt
+                .query_map([], |row| {
+                    Ok(CategoryRule {
+                        name: row.get(0)?,
+                        directory: row.get(1)?,
+                        extensions: split(row.get(2)?),
+                        mime_types: split(row.get(3)?),
+                        archive_after_days: row.get(4)?,
+                        archive_directory: row.get(5)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(rules)
         }).await
     }
 
-    pub async fn save_download_async(&self, download: Download) -> Result<()> {
+    pub async fn save_category_rules_async(&self, rules: Vec<CategoryRule>) -> Result<()> {
         self.with_conn(move |conn| {
-            let selected_files_json = download
-                .selected_files
-                .as_ref()
-                .map(|f| serde_json::to_string(f).unwrap_or_default());
+            let tx = conn.unchecked_transaction()?;
+            Self::replace_category_rules(&tx, &rules)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Normalize the path settings and download save paths stored before
+    /// `normalize_path` ran on save. Returns how many values changed.
+    fn normalize_stored_paths(conn: &Connection) -> Result<usize> {
+        let settings_filter = format!("key IN ('{}')", PATH_SETTINGS.join("', '"));
+        let mut fixed = 0;
+        for (table, column, filter) in [
+            ("settings", "value", settings_filter.as_str()),
+            ("downloads", "save_path", "1"),
+        ] {
+            let stored: Vec<String> = conn
+                .prepare(&format!(
+                    "SELECT DISTINCT {} FROM {} WHERE {}",
+                    column, table, filter
+                ))?
+                .query_map([], |row| row.get(0))?
+                .collect::<std::result::Result<_, _>>()?;
+            for value in stored {
+                let normalized = normalize_path(&value);
+                if normalized != value {
+                    fixed += conn.execute(
+                        &format!(
+                            "UPDATE {} SET {} = ?1 WHERE {} = ?2 AND {}",
+                            table, column, column, filter
+                        ),
+                        params![normalized, value],
+                    )?;
+                }
+            }
+        }
+        Ok(fixed)
+    }
 
+    fn replace_category_rules(conn: &Connection, rules: &[CategoryRule]) -> Result<()> {
+        conn.execute("DELETE FROM category_rules", [])?;
+        for (position, rule) in rules.iter().enumerate() {
             conn.execute(
-                "INSERT OR REPLACE INTO downloads
-                 (gid, name, url, magnet_uri, info_hash, download_type, status, total_size, completed_size,
-                  download_speed, upload_speed, save_path, created_at, completed_at, error_message, selected_files)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                "INSERT INTO category_rules
+                 (position, name, directory, extensions, mime_types, archive_after_days, archive_directory)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
                 params![
-                    download.gid,
-                    download.name,
-                    download.url,
-                    download.magnet_uri,
-                    download.info_hash,
-                    download.download_type.to_string(),
-                    download.status.to_string(),
-                    download.total_size as i64,
-                    download.completed_size as i64,
-                    download.download_speed as i64,
-                    download.upload_speed as i64,
-                    download.save_path,
-                    download.created_at,
-                    download.completed_at,
-                    download.error_message,
-                    selected_files_json,
+                    position as i64,
+                    rule.name,
+                    rule.directory,
+                    rule.extensions.join(" "),
+                    rule.mime_types.join(" "),
+                    rule.archive_after_days,
+                    rule.archive_directory,
                 ],
             )?;
-            Ok(())
-        }).await
+        }
+        Ok(())
     }
 
-    pub async fn remove_download_async(&self, gid: String) -> Result<()> {
+    pub async fn get_download_locations_async(&self) -> Result<Vec<DownloadLocation>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT name, path FROM download_locations ORDER BY position")?;
+            let locations = stmt
+                .query_map([], |row| {
+                    Ok(DownloadLocation {
+                        name: row.get(0)?,
+                        path: row.get(1)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(locations)
+        })
+        .await
+    }
+
+    /// Replace the named locations. Remembered choices pointing at a
+    /// location that is gone are forgotten.
+    pub async fn save_download_locations_async(
+        &self,
+        locations: Vec<DownloadLocation>,
+    ) -> Result<()> {
         self.with_conn(move |conn| {
-            conn.execute("DELETE FROM downloads WHERE gid = ?1", params![gid])?;
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM download_locations", [])?;
+            for (position, location) in locations.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO download_locations (position, name, path) VALUES (?1, ?2, ?3)",
+                    params![position as i64, location.name, location.path],
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM location_usage WHERE location <> ?1 COLLATE NOCASE
+                 AND location NOT IN (SELECT name FROM download_locations)",
+                params![crate::locations::DEFAULT_LOCATION],
+            )?;
+            tx.commit()?;
             Ok(())
-        }).await
+        })
+        .await
     }
 
-    pub async fn clear_history_async(&self) -> Result<()> {
+    /// Location last picked for each category (`""` for uncategorized).
+    pub async fn get_location_usage_async(&self) -> Result<HashMap<String, String>> {
         self.with_conn(|conn| {
-            conn.execute("DELETE FROM downloads WHERE status = 'complete'", [])?;
+            let mut stmt = conn.prepare("SELECT category, location FROM location_usage")?;
+            let usage = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+            Ok(usage)
+        })
+        .await
+    }
+
+    pub async fn record_location_usage_async(
+        &self,
+        category: String,
+        location: String,
+    ) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO location_usage (category, location) VALUES (?1, ?2)
+                 ON CONFLICT(category) DO UPDATE SET location = excluded.location",
+                params![category, location],
+            )?;
             Ok(())
-        }).await
+        })
+        .await
     }
 
-    pub async fn get_incomplete_downloads_async(&self) -> Result<Vec<Download>> {
+    pub async fn get_download_templates_async(&self) -> Result<Vec<DownloadTemplate>> {
         self.with_conn(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT * FROM downloads
-                 WHERE status NOT IN ('complete', 'error')
-                 AND (total_size <= 0 OR completed_size < total_size)
-                 ORDER BY created_at ASC",
+                "SELECT name, options, trackers FROM download_templates ORDER BY name COLLATE NOCASE",
             )?;
-            let downloads = stmt
-                .query_map([], |row| Ok(row_to_download(row)))?
-                .filter_map(|r| r.ok())
-                .collect();
-            Ok(downloads)
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((row.get(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+                })?
+                .collect::<std::result::Result<Vec<(String, _, _)>, _>>()?;
+            rows.into_iter()
+                .map(|(name, options, trackers)| {
+                    Ok(DownloadTemplate {
+                        name,
+                        options: serde_json::from_str(&options)?,
+                        trackers: trackers.split_whitespace().map(str::to_string).collect(),
+                    })
+                })
+                .collect()
         }).await
     }
-}
 
-fn row_to_download(row: &rusqlite::Row) -> Download {
-    let status_str: String = row.get::<_, String>("status").unwrap_or_default();
-    let dl_type_str: String = row.get::<_, String>("download_type").unwrap_or_default();
-    let selected_files_str: Option<String> = row.get::<_, Option<String>>("selected_files").unwrap_or(None);
+    /// Add a template, or replace the one with the same name (ignoring case).
+    pub async fn save_download_template_async(&self, template: DownloadTemplate) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO download_templates (name, options, trackers) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET
+                     name = excluded.name, options = excluded.options, trackers = excluded.trackers",
+                params![
+                    template.name,
+                    serde_json::to_string(&template.options)?,
+                    template.trackers.join(" ")
+                ],
+            )?;
+            Ok(())
+        }).await
+    }
 
-    Download {
-        id: row.get::<_, i64>("id").unwrap_or(0),
-        gid: row.get::<_, String>("gid").unwrap_or_default(),
-        name: row.get::<_, String>("name").unwrap_or_default(),
-        url: row.get::<_, Option<String>>("url").unwrap_or(None),
-        magnet_uri: row.get::<_, Option<String>>("magnet_uri").unwrap_or(None),
-        info_hash: row.get::<_, Option<String>>("info_hash").unwrap_or(None),
-        download_type: match dl_type_str.as_str() {
-            "torrent" => DownloadType::Torrent,
-            "magnet" => DownloadType::Magnet,
-            _ => DownloadType::Http,
-        },
-        status: DownloadState::from(status_str.as_str()),
-        total_size: row.get::<_, i64>("total_size").unwrap_or(0) as u64,
-        completed_size: row.get::<_, i64>("completed_size").unwrap_or(0) as u64,
-        download_speed: row.get::<_, i64>("download_speed").unwrap_or(0) as u64,
-        upload_speed: row.get::<_, i64>("upload_speed").unwrap_or(0) as u64,
-        save_path: row.get::<_, String>("save_path").unwrap_or_default(),
-        created_at: row.get::<_, String>("created_at").unwrap_or_default(),
-        completed_at: row.get::<_, Option<String>>("completed_at").unwrap_or(None),
-        error_message: row.get::<_, Option<String>>("error_message").unwrap_or(None),
-        connections: 0,
-        seeders: 0,
-        selected_files: selected_files_str.and_then(|s| serde_json::from_str(&s).ok()),
+    /// Returns false if there was no template of that name.
+    pub async fn delete_download_template_async(&self, name: String) -> Result<bool> {
+        self.with_conn(move |conn| {
+            let removed = conn.execute(
+                "DELETE FROM download_templates WHERE name = ?1",
+                params![name],
+            )?;
+            Ok(removed > 0)
+        })
+        .await
     }
-}
 
-pub fn download_type_from_url(url: &str) -> DownloadType {
-    let lower = url.to_lowercase();
-    if lower.starts_with("magnet:") {
-        DownloadType::Magnet
-    } else if lower.ends_with(".torrent") || lower.contains("torrent") {
-        DownloadType::Torrent
-    } else {
-        DownloadType::Http
+    pub async fn save_staged_async(&self, staged: StagedDownload) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO staged_downloads (temp_dir, gid, final_dir, final_path)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    staged.temp_dir,
+                    staged.gid,
+                    staged.final_dir,
+                    staged.final_path
+                ],
+            )?;
+            Ok(())
+        })
+        .await
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub async fn get_staged_downloads_async(&self) -> Result<Vec<StagedDownload>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT temp_dir, gid, final_dir, final_path FROM staged_downloads")?;
+            let staged = stmt
+                .query_map([], |row| {
+                    Ok(StagedDownload {
+                        temp_dir: row.get(0)?,
+                        gid: row.get(1)?,
+                        final_dir: row.get(2)?,
+                        final_path: row.get(3)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(staged)
+        })
+        .await
+    }
 
-    fn test_db() -> Database {
-        let conn = Connection::open_in_memory().unwrap();
-        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;").unwrap();
+    pub async fn delete_staged_async(&self, temp_dir: String) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM staged_downloads WHERE temp_dir = ?1",
+                params![temp_dir],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn save_rename_async(&self, gid: String, path: String) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO download_renames (gid, path) VALUES (?1, ?2)",
+                params![gid, path],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// GIDs of renamed downloads with the paths they were renamed to.
+    pub async fn get_renames_async(&self) -> Result<Vec<(String, String)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT gid, path FROM download_renames ORDER BY gid")?;
+            let renames = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(renames)
+        })
+        .await
+    }
+
+    pub async fn delete_rename_async(&self, gid: String) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM download_renames WHERE gid = ?1", params![gid])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Keep the URL and options a download was added with.
+    pub async fn save_download_options_async(
+        &self,
+        gid: String,
+        url: Option<String>,
+        options: DownloadOptions,
+    ) -> Result<()> {
+        let options = serde_json::to_string(&options)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO download_options (gid, url, options) VALUES (?1, ?2, ?3)",
+                params![gid, url, options],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The URL and options a download was added with, if they were kept.
+    pub async fn get_download_options_async(
+        &self,
+        gid: String,
+    ) -> Result<Option<(Option<String>, DownloadOptions)>> {
+        self.with_conn(move |conn| {
+            let found = conn
+                .query_row(
+                    "SELECT url, options FROM download_options WHERE gid = ?1",
+                    params![gid],
+                    |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()?;
+            Ok(found.and_then(|(url, options)| Some((url, serde_json::from_str(&options).ok()?))))
+        })
+        .await
+    }
+
+    /// The URLs downloads were added with, by GID, where one was kept.
+    pub async fn get_download_urls_async(&self) -> Result<HashMap<String, String>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT gid, url FROM download_options WHERE url IS NOT NULL")?;
+            let urls = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            Ok(urls)
+        })
+        .await
+    }
+
+    pub async fn delete_download_options_async(&self, gid: String) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM download_options WHERE gid = ?1", params![gid])?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_completed_downloads_async(&self) -> Result<Vec<Download>> {
+        self.with_downloads(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM downloads WHERE status IN ('complete', 'warning') ORDER BY completed_at DESC",
+            )?;
+            let downloads = stmt
+                .query_map([], |row| Ok(row_to_download(row)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            Ok(downloads)
+        }).await
+    }
+
+    /// Write a download's row now, after any queued snapshots.
+    pub async fn save_download_async(&self, download: Download) -> Result<()> {
+        self.with_downloads(move |conn| upsert_download(conn, &download))
+            .await
+    }
+
+    /// Queue a download snapshot for the next batched write. A newer
+    /// snapshot of the same download replaces the queued one, so a burst of
+    /// progress reports costs a single row write.
+    pub fn queue_download(&self, download: Download) {
+        self.pending_downloads
+            .lock()
+            .unwrap()
+            .insert(download.gid.clone(), download);
+    }
+
+    /// Write the queued snapshots in one transaction. Returns how many.
+    pub async fn flush_downloads_async(&self) -> Result<usize> {
+        let pending = self.pending_downloads.clone();
+        self.with_conn(move |conn| write_pending(conn, &pending))
+            .await
+    }
+
+    /// Like `with_conn`, but queued snapshots are written first, so `f`
+    /// sees every download as last reported.
+    async fn with_downloads<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Connection) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let pending = self.pending_downloads.clone();
+        self.with_conn(move |conn| {
+            write_pending(conn, &pending)?;
+            f(conn)
+        })
+        .await
+    }
+
+    /// Aggregate timing over completed history: overall throughput, the
+    /// median completion time, and per-host averages for HTTP downloads.
+    pub async fn get_history_stats_async(&self) -> Result<HistoryStats> {
+        self.with_downloads(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT elapsed_secs, total_size FROM downloads
+                 WHERE status = 'complete' AND elapsed_secs IS NOT NULL
+                 ORDER BY elapsed_secs",
+            )?;
+            let timed: Vec<(i64, i64)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .filter_map(|r| r.ok())
+                .collect();
+
+            let total_bytes: i64 = timed.iter().map(|(_, size)| size).sum();
+            let total_secs: i64 = timed.iter().map(|(elapsed, _)| elapsed).sum();
+            let median_elapsed_secs = match timed.len() {
+                0 => 0,
+                n if n % 2 == 1 => timed[n / 2].0,
+                n => (timed[n / 2 - 1].0 + timed[n / 2].0) / 2,
+            };
+
+            // Bytes over seconds rather than AVG(avg_speed), so a host's many
+            // tiny files don't outweigh its large ones.
+            let mut stmt = conn.prepare(
+                "SELECT host, COUNT(*), SUM(total_size), SUM(elapsed_secs), AVG(elapsed_secs)
+                 FROM downloads
+                 WHERE status = 'complete' AND elapsed_secs IS NOT NULL AND host IS NOT NULL
+                 GROUP BY host
+                 ORDER BY SUM(total_size) * 1.0 / MAX(SUM(elapsed_secs), 1) DESC
+                 LIMIT 20",
+            )?;
+            let mut hosts = stmt
+                .query_map([], |row| {
+                    let bytes: i64 = row.get(2)?;
+                    let secs: i64 = row.get(3)?;
+                    let avg_elapsed: f64 = row.get(4)?;
+                    Ok(HostStats {
+                        host: row.get(0)?,
+                        downloads: row.get::<_, i64>(1)? as u32,
+                        total_bytes: bytes as u64,
+                        average_speed: (bytes / secs.max(1)) as u64,
+                        average_elapsed_secs: avg_elapsed.round() as u64,
+                        median_ttfb_ms: None,
+                    })
+                })?
+                .filter_map(|r| r.ok())
+                .collect::<Vec<_>>();
+
+            let mut stmt =
+                conn.prepare("SELECT host, ttfb_ms FROM download_latency ORDER BY ttfb_ms")?;
+            let latencies: Vec<(String, u64)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?
+                .collect::<std::result::Result<_, _>>()?;
+            let all: Vec<u64> = latencies.iter().map(|(_, ttfb)| *ttfb).collect();
+            for host in &mut hosts {
+                let own: Vec<u64> = latencies
+                    .iter()
+                    .filter(|(h, _)| *h == host.host)
+                    .map(|(_, ttfb)| *ttfb)
+                    .collect();
+                host.median_ttfb_ms = (!own.is_empty()).then(|| percentile(&own, 50));
+            }
+
+            Ok(HistoryStats {
+                downloads: timed.len() as u32,
+                total_bytes: total_bytes as u64,
+                average_speed: (total_bytes / total_secs.max(1)) as u64,
+                median_elapsed_secs: median_elapsed_secs as u64,
+                ttfb: (!all.is_empty()).then(|| LatencyPercentiles {
+                    samples: all.len() as u32,
+                    p50_ms: percentile(&all, 50),
+                    p90_ms: percentile(&all, 90),
+                    p99_ms: percentile(&all, 99),
+                }),
+                hosts,
+            })
+        })
+        .await
+    }
+
+    /// Keep a download's probe latency, dropping all but the newest
+    /// `LATENCY_SAMPLES` rows.
+    pub async fn record_latency_async(
+        &self,
+        gid: String,
+        host: String,
+        ttfb_ms: u64,
+        redirect_ms: Option<u64>,
+    ) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO download_latency (gid, host, ttfb_ms, redirect_ms, recorded_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    gid,
+                    host,
+                    ttfb_ms as i64,
+                    redirect_ms.map(|ms| ms as i64),
+                    chrono::Utc::now().to_rfc3339()
+                ],
+            )?;
+            conn.execute(
+                "DELETE FROM download_latency WHERE rowid NOT IN
+                 (SELECT rowid FROM download_latency ORDER BY recorded_at DESC LIMIT ?1)",
+                params![LATENCY_SAMPLES],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    pub async fn get_seed_totals_async(&self) -> Result<HashMap<String, SeedTotals>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT gid, uploaded, seed_secs, size FROM seed_stats")?;
+            let totals = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        SeedTotals {
+                            uploaded: row.get::<_, i64>(1)? as u64,
+                            seed_secs: row.get::<_, i64>(2)? as u64,
+                            size: row.get::<_, i64>(3)? as u64,
+                        },
+                    ))
+                })?
+                .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+            Ok(totals)
+        })
+        .await
+    }
+
+    /// Save torrents' totals, adding what they grew by to today's row in
+    /// the daily statistics.
+    pub async fn save_seed_totals_async(&self, totals: Vec<(String, SeedTotals)>) -> Result<()> {
+        let day = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        self.with_conn(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            for (gid, t) in totals {
+                let previous = tx
+                    .query_row(
+                        "SELECT uploaded, seed_secs, size FROM seed_stats WHERE gid = ?1",
+                        params![gid],
+                        |row| {
+                            Ok(SeedTotals {
+                                uploaded: row.get::<_, i64>(0)? as u64,
+                                seed_secs: row.get::<_, i64>(1)? as u64,
+                                size: row.get::<_, i64>(2)? as u64,
+                            })
+                        },
+                    )
+                    .optional()?
+                    .unwrap_or_default();
+                let grown = t.since(&previous);
+                if grown != SeedTotals::default() {
+                    tx.execute(
+                        "INSERT INTO seed_stats_daily (day, gid, uploaded, downloaded, seed_secs)
+                         VALUES (?1, ?2, ?3, ?4, ?5)
+                         ON CONFLICT(day, gid) DO UPDATE SET
+                             uploaded = uploaded + excluded.uploaded,
+                             downloaded = downloaded + excluded.downloaded,
+                             seed_secs = seed_secs + excluded.seed_secs",
+                        params![
+                            day,
+                            gid,
+                            grown.uploaded as i64,
+                            grown.size as i64,
+                            grown.seed_secs as i64
+                        ],
+                    )?;
+                }
+                tx.execute(
+                    "INSERT OR REPLACE INTO seed_stats (gid, uploaded, seed_secs, size) VALUES (?1, ?2, ?3, ?4)",
+                    params![gid, t.uploaded as i64, t.seed_secs as i64, t.size as i64],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        }).await
+    }
+
+    pub async fn get_traffic_totals_async(&self) -> Result<HashMap<String, TrafficTotals>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT gid, received, payload, discarded, completed FROM download_traffic",
+            )?;
+            let totals = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        TrafficTotals {
+                            received: row.get::<_, i64>(1)? as u64,
+                            payload: row.get::<_, i64>(2)? as u64,
+                            discarded: row.get::<_, i64>(3)? as u64,
+                            completed: row.get::<_, i64>(4)? as u64,
+                        },
+                    ))
+                })?
+                .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+            Ok(totals)
+        })
+        .await
+    }
+
+    pub async fn save_traffic_totals_async(
+        &self,
+        totals: Vec<(String, TrafficTotals)>,
+    ) -> Result<()> {
+        self.with_conn(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            for (gid, t) in totals {
+                tx.execute(
+                    "INSERT OR REPLACE INTO download_traffic (gid, received, payload, discarded, completed)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        gid,
+                        t.received as i64,
+                        t.payload as i64,
+                        t.discarded as i64,
+                        t.completed as i64
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        }).await
+    }
+
+    /// Daily seeding statistics between `from` and `to` (`YYYY-MM-DD`,
+    /// both inclusive and optional), oldest first. Names are left for the
+    /// caller to fill in.
+    pub async fn get_seed_stats_daily_async(
+        &self,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<Vec<SeedStatsDay>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT day, gid, uploaded, downloaded, seed_secs FROM seed_stats_daily
+                 WHERE (?1 IS NULL OR day >= ?1) AND (?2 IS NULL OR day <= ?2)
+                 ORDER BY day, gid",
+            )?;
+            let rows = stmt
+                .query_map(params![from, to], |row| {
+                    Ok(SeedStatsDay {
+                        date: row.get(0)?,
+                        gid: row.get(1)?,
+                        name: String::new(),
+                        info_hash: None,
+                        uploaded: row.get::<_, i64>(2)? as u64,
+                        downloaded: row.get::<_, i64>(3)? as u64,
+                        seed_time_secs: row.get::<_, i64>(4)? as u64,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await
+    }
+
+    /// Add a finished download's speed at `connections` to the host's
+    /// running average.
+    pub async fn record_host_speed_async(
+        &self,
+        host: String,
+        connections: u32,
+        speed: u64,
+    ) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO host_profiles (host, connections, samples, avg_speed, updated_at)
+                 VALUES (?1, ?2, 1, ?3, ?4)
+                 ON CONFLICT(host, connections) DO UPDATE SET
+                     avg_speed = (avg_speed * samples + excluded.avg_speed) / (samples + 1),
+                     samples = samples + 1,
+                     updated_at = excluded.updated_at",
+                params![
+                    host,
+                    connections,
+                    speed as i64,
+                    chrono::Utc::now().to_rfc3339()
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Learned profiles by host, or only `host`'s. Suggestions are left
+    /// for the caller, which knows the connection limit.
+    pub async fn get_host_profiles_async(&self, host: Option<String>) -> Result<Vec<HostProfile>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT host, connections, samples, avg_speed, updated_at FROM host_profiles
+                 WHERE ?1 IS NULL OR host = ?1
+                 ORDER BY host, connections",
+            )?;
+            let rows = stmt
+                .query_map(params![host], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        ConnectionProfile {
+                            connections: row.get(1)?,
+                            samples: row.get(2)?,
+                            average_speed: row.get::<_, i64>(3)? as u64,
+                            updated_at: row.get(4)?,
+                        },
+                    ))
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let mut profiles: Vec<HostProfile> = Vec::new();
+            for (host, measured) in rows {
+                match profiles.last_mut() {
+                    Some(profile) if profile.host == host => profile.connections.push(measured),
+                    _ => profiles.push(HostProfile {
+                        host,
+                        connections: vec![measured],
+                        suggested_connections: None,
+                        broken_ranges: false,
+                    }),
+                }
+            }
+            for host in broken_range_hosts(conn, host.as_deref())? {
+                match profiles.iter_mut().find(|p| p.host == host) {
+                    Some(profile) => profile.broken_ranges = true,
+                    None => profiles.push(HostProfile {
+                        host,
+                        connections: Vec::new(),
+                        suggested_connections: None,
+                        broken_ranges: true,
+                    }),
+                }
+            }
+            profiles.sort_by(|a, b| a.host.cmp(&b.host));
+            Ok(profiles)
+        })
+        .await
+    }
+
+    /// Remember that `host` breaks byte ranges past the first byte.
+    pub async fn record_broken_ranges_async(&self, host: String) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO host_ranges (host, detected_at) VALUES (?1, ?2)",
+                params![host, chrono::Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Whether `host` was found to break byte ranges recently enough that
+    /// it isn't worth checking again.
+    pub async fn has_broken_ranges_async(&self, host: String) -> Result<bool> {
+        self.with_conn(move |conn| Ok(!broken_range_hosts(conn, Some(&host))?.is_empty()))
+            .await
+    }
+
+    pub async fn remove_download_async(&self, gid: String) -> Result<()> {
+        self.with_downloads(move |conn| {
+            conn.execute("DELETE FROM downloads WHERE gid = ?1", params![gid])?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn clear_history_async(&self) -> Result<()> {
+        self.with_downloads(|conn| {
+            conn.execute(
+                "DELETE FROM downloads WHERE status IN ('complete', 'warning')",
+                [],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// File sizes and row counts, for the maintenance report.
+    pub async fn storage_info_async(&self) -> Result<DbStorageInfo> {
+        self.with_downloads(|conn| {
+            let pragma = |name: &str| -> Result<u64> {
+                let value: i64 =
+                    conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))?;
+                Ok(value.max(0) as u64)
+            };
+            let page_size = pragma("page_size")?;
+            let db_size = page_size * pragma("page_count")?;
+            let free_bytes = page_size * pragma("freelist_count")?;
+            let wal_size = conn
+                .path()
+                .filter(|path| !path.is_empty())
+                .and_then(|path| std::fs::metadata(format!("{}-wal", path)).ok())
+                .map(|meta| meta.len())
+                .unwrap_or(0);
+
+            let mut rows = std::collections::BTreeMap::new();
+            let tables = [
+                "downloads",
+                "settings",
+                "trackers",
+                "category_rules",
+                "download_locations",
+                "host_profiles",
+                "host_ranges",
+                "seed_stats",
+                "seed_stats_daily",
+                "download_templates",
+                "download_latency",
+            ];
+            for table in tables {
+                let count: i64 =
+                    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                        row.get(0)
+                    })?;
+                rows.insert(table.to_string(), count as u64);
+            }
+            Ok(DbStorageInfo {
+                db_size,
+                wal_size,
+                free_bytes,
+                rows,
+            })
+        })
+        .await
+    }
+
+    /// Remove finished downloads the retention policy no longer keeps.
+    /// Favorites are left alone. Returns how many were removed.
+    pub async fn prune_history_async(&self, policy: HistoryRetention) -> Result<u64> {
+        self.with_downloads(move |conn| {
+            let removed = match policy {
+                HistoryRetention::Forever => 0,
+                HistoryRetention::Days(days) => {
+                    let cutoff = (chrono::Utc::now() - chrono::Duration::days(days as i64))
+                        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+                    conn.execute(
+                        "DELETE FROM downloads
+                         WHERE status IN ('complete', 'warning') AND favorite = 0
+                         AND completed_at IS NOT NULL AND completed_at < ?1",
+                        params![cutoff],
+                    )?
+                }
+                HistoryRetention::Count(keep) => conn.execute(
+                    "DELETE FROM downloads
+                     WHERE status IN ('complete', 'warning') AND favorite = 0
+                     AND id NOT IN (
+                         SELECT id FROM downloads
+                         WHERE status IN ('complete', 'warning') AND favorite = 0
+                         ORDER BY completed_at DESC LIMIT ?1
+                     )",
+                    params![keep as i64],
+                )?,
+            };
+            Ok(removed as u64)
+        })
+        .await
+    }
+
+    /// Mark a history entry as a favorite, exempting it from pruning.
+    /// Returns false if no download has that gid.
+    pub async fn set_favorite_async(&self, gid: String, favorite: bool) -> Result<bool> {
+        self.with_downloads(move |conn| {
+            let updated = conn.execute(
+                "UPDATE downloads SET favorite = ?2 WHERE gid = ?1",
+                params![gid, favorite as i64],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Save a finished download with the hash of its file, checked now.
+    pub async fn record_file_hash_async(&self, download: Download, hash: String) -> Result<()> {
+        self.with_downloads(move |conn| {
+            upsert_download(conn, &download)?;
+            conn.execute(
+                "UPDATE downloads SET file_hash = ?2, verified_at = ?3 WHERE gid = ?1",
+                params![download.gid, hash, chrono::Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The saved row of one download, if it has one.
+    pub async fn get_download_by_gid_async(&self, gid: String) -> Result<Option<Download>> {
+        self.with_downloads(move |conn| {
+            let found = conn
+                .query_row(
+                    "SELECT * FROM downloads WHERE gid = ?1",
+                    params![gid],
+                    |row| Ok(row_to_download(row)),
+                )
+                .optional()?;
+            Ok(found)
+        })
+        .await
+    }
+
+    /// A saved download and the file hash recorded for it, if any.
+    pub async fn get_file_hash_async(
+        &self,
+        gid: String,
+    ) -> Result<Option<(Download, Option<String>)>> {
+        self.with_downloads(move |conn| {
+            let found = conn
+                .query_row(
+                    "SELECT * FROM downloads WHERE gid = ?1",
+                    params![gid],
+                    |row| {
+                        Ok((
+                            row_to_download(row),
+                            row.get::<_, Option<String>>("file_hash")?,
+                        ))
+                    },
+                )
+                .optional()?;
+            Ok(found)
+        })
+        .await
+    }
+
+    /// Give free pages back to the filesystem, fold the WAL into the main
+    /// file and refresh the query planner's statistics. The first run
+    /// switches the file to incremental auto-vacuum, which needs one full
+    /// VACUUM; later runs only release free pages.
+    pub async fn compact_async(&self) -> Result<()> {
+        self.with_conn(|conn| {
+            let auto_vacuum: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+            if auto_vacuum == 2 {
+                conn.execute_batch("PRAGMA incremental_vacuum;")?;
+            } else {
+                conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")?;
+            }
+            conn.execute_batch("ANALYZE; PRAGMA optimize;")?;
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Value stored under an internal key in the settings table (kept out
+    /// of `Settings`, which only holds user options).
+    pub async fn get_meta_async(&self, key: &'static str) -> Result<Option<String>> {
+        self.with_conn(move |conn| {
+            let value = conn
+                .query_row(
+                    "SELECT value FROM settings WHERE key = ?1",
+                    params![key],
+                    |row| row.get(0),
+                )
+                .ok();
+            Ok(value)
+        })
+        .await
+    }
+
+    pub async fn set_meta_async(&self, key: &'static str, value: String) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES (?1, ?2, datetime('now'))",
+                params![key, value],
+            )?;
+            Ok(())
+        }).await
+    }
+
+    /// Priority, category and add order of every download that has one,
+    /// by GID.
+    pub async fn get_download_queue_async(
+        &self,
+    ) -> Result<Vec<(String, Option<String>, String, u64)>> {
+        self.with_downloads(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT gid, priority, category, added_order FROM downloads WHERE added_order > 0",
+            )?;
+            let entries = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get::<_, i64>(3)? as u64,
+                    ))
+                })?
+                .filter_map(|r| r.ok())
+                .collect();
+            Ok(entries)
+        })
+        .await
+    }
+
+    pub async fn get_incomplete_downloads_async(&self) -> Result<Vec<Download>> {
+        self.with_downloads(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM downloads
+                 WHERE status NOT IN ('complete', 'warning', 'error')
+                 AND (total_size <= 0 OR completed_size < total_size)
+                 ORDER BY created_at ASC",
+            )?;
+            let downloads = stmt
+                .query_map([], |row| Ok(row_to_download(row)))?
+                .filter_map(|r| r.ok())
+                .collect();
+            Ok(downloads)
+        })
+        .await
+    }
+}
+
+fn row_to_download(row: &rusqlite::Row) -> Download {
+    let status_str: String = row.get::<_, String>("status").unwrap_or_default();
+    let dl_type_str: String = row.get::<_, String>("download_type").unwrap_or_default();
+    let selected_files_str: Option<String> = row
+        .get::<_, Option<String>>("selected_files")
+        .unwrap_or(None);
+
+    Download {
+        id: row.get::<_, i64>("id").unwrap_or(0),
+        gid: row.get::<_, String>("gid").unwrap_or_default(),
+        name: row.get::<_, String>("name").unwrap_or_default(),
+        url: row.get::<_, Option<String>>("url").unwrap_or(None),
+        magnet_uri: row.get::<_, Option<String>>("magnet_uri").unwrap_or(None),
+        info_hash: row.get::<_, Option<String>>("info_hash").unwrap_or(None),
+        download_type: match dl_type_str.as_str() {
+            "torrent" => DownloadType::Torrent,
+            "magnet" => DownloadType::Magnet,
+            _ => DownloadType::Http,
+        },
+        status: DownloadState::from(status_str.as_str()),
+        total_size: row.get::<_, i64>("total_size").unwrap_or(0) as u64,
+        completed_size: row.get::<_, i64>("completed_size").unwrap_or(0) as u64,
+        download_speed: row.get::<_, i64>("download_speed").unwrap_or(0) as u64,
+        upload_speed: row.get::<_, i64>("upload_speed").unwrap_or(0) as u64,
+        save_path: row.get::<_, String>("save_path").unwrap_or_default(),
+        created_at: row.get::<_, String>("created_at").unwrap_or_default(),
+        completed_at: row.get::<_, Option<String>>("completed_at").unwrap_or(None),
+        error_message: row
+            .get::<_, Option<String>>("error_message")
+            .unwrap_or(None),
+        connections: 0,
+        seeders: 0,
+        swarm_seeders: 0,
+        peers: 0,
+        leechers: 0,
+        selected_files: selected_files_str.and_then(|s| serde_json::from_str(&s).ok()),
+        favorite: row.get::<_, i64>("favorite").unwrap_or(0) != 0,
+        resumable: None,
+        queue_position: None,
+        priority: row.get::<_, Option<String>>("priority").unwrap_or(None),
+        category: row.get::<_, String>("category").unwrap_or_default(),
+        added_order: row.get::<_, i64>("added_order").unwrap_or(0) as u64,
+        seeding: None,
+        traffic: None,
+    }
+}
+
+/// Wall-clock seconds from creation to completion (queued and paused time
+/// included) and the average speed over that span. None unless complete.
+fn completion_timing(download: &Download) -> Option<(i64, i64)> {
+    if download.status != DownloadState::Complete {
+        return None;
+    }
+    let created = chrono::DateTime::parse_from_rfc3339(&download.created_at).ok()?;
+    let completed = chrono::DateTime::parse_from_rfc3339(download.completed_at.as_deref()?).ok()?;
+    // Sub-second downloads count as one second so the speed stays finite
+    let elapsed = (completed - created).num_seconds().max(1);
+    let size = download.completed_size.max(download.total_size) as i64;
+    Some((elapsed, size / elapsed))
+}
+
+/// Host an HTTP download was fetched from; torrents have none.
+fn download_host(download: &Download) -> Option<String> {
+    if download.download_type != DownloadType::Http {
+        return None;
+    }
+    crate::host_profiles::url_host(download.url.as_deref()?)
+}
+
+/// Nearest-rank `percent`ile of ascending `sorted`, which must not be
+/// empty.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Insert or replace a download's row, keeping its favorite flag and
+/// recorded file hash.
+fn upsert_download(conn: &Connection, download: &Download) -> Result<()> {
+    let selected_files_json = download
+        .selected_files
+        .as_ref()
+        .map(|f| serde_json::to_string(f).unwrap_or_default());
+    let timing = completion_timing(download);
+    let host = download_host(download);
+
+    conn.execute(
+        "INSERT OR REPLACE INTO downloads
+         (gid, name, url, magnet_uri, info_hash, download_type, status, total_size, completed_size,
+          download_speed, upload_speed, save_path, created_at, completed_at, error_message, selected_files,
+          elapsed_secs, avg_speed, host, priority, category, added_order, favorite, file_hash, verified_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19,
+                 ?20, ?21, ?22,
+                 COALESCE((SELECT favorite FROM downloads WHERE gid = ?1), 0),
+                 (SELECT file_hash FROM downloads WHERE gid = ?1),
+                 (SELECT verified_at FROM downloads WHERE gid = ?1))",
+        params![
+            download.gid,
+            download.name,
+            download.url,
+            download.magnet_uri,
+            download.info_hash,
+            download.download_type.to_string(),
+            download.status.to_string(),
+            download.total_size as i64,
+            download.completed_size as i64,
+            download.download_speed as i64,
+            download.upload_speed as i64,
+            normalize_path(&download.save_path),
+            download.created_at,
+            download.completed_at,
+            download.error_message,
+            selected_files_json,
+            timing.map(|(elapsed, _)| elapsed),
+            timing.map(|(_, speed)| speed),
+            host,
+            download.priority,
+            download.category,
+            download.added_order as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Write and clear the queued download snapshots. On failure they are put
+/// back, unless a newer snapshot was queued meanwhile.
+fn write_pending(conn: &Connection, pending: &Mutex<HashMap<String, Download>>) -> Result<usize> {
+    let downloads: Vec<Download> = pending.lock().unwrap().drain().map(|(_, d)| d).collect();
+    if downloads.is_empty() {
+        return Ok(0);
+    }
+    let result = (|| {
+        let tx = conn.unchecked_transaction()?;
+        for download in &downloads {
+            upsert_download(&tx, download)?;
+        }
+        tx.commit()?;
+        Ok(downloads.len())
+    })();
+    if result.is_err() {
+        let mut pending = pending.lock().unwrap();
+        for download in downloads {
+            pending.entry(download.gid.clone()).or_insert(download);
+        }
+    }
+    result
+}
+
+/// Hosts, or only `host`, found to break byte ranges within
+/// `BROKEN_RANGES_MEMORY_DAYS`.
+fn broken_range_hosts(conn: &Connection, host: Option<&str>) -> Result<Vec<String>> {
+    let cutoff =
+        chrono::Utc::now() - chrono::Duration::days(crate::ranges::BROKEN_RANGES_MEMORY_DAYS);
+    let mut stmt =
+        conn.prepare("SELECT host, detected_at FROM host_ranges WHERE ?1 IS NULL OR host = ?1")?;
+    let rows = stmt
+        .query_map(params![host], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, detected_at)| {
+            chrono::DateTime::parse_from_rfc3339(detected_at).is_ok_and(|t| t >= cutoff)
+        })
+        .map(|(host, _)| host)
+        .collect())
+}
+
+pub fn download_type_from_url(url: &str) -> DownloadType {
+    let lower = url.to_lowercase();
+    if lower.starts_with("magnet:") {
+        DownloadType::Magnet
+    } else if lower.ends_with(".torrent") || lower.contains("torrent") {
+        DownloadType::Torrent
+    } else {
+        DownloadType::Http
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")
+            .unwrap();
         let db = Database {
             conn: Arc::new(Mutex::new(conn)),
+            pending_downloads: Arc::default(),
         };
         db.run_migrations_sync().unwrap();
         db
@@ -385,11 +2094,26 @@ mod tests {
 
     #[test]
     fn test_download_type_from_url() {
-        assert_eq!(download_type_from_url("https://example.com/file.zip"), DownloadType::Http);
-        assert_eq!(download_type_from_url("https://example.com/file.torrent"), DownloadType::Torrent);
-        assert_eq!(download_type_from_url("magnet:?xt=urn:btih:abc"), DownloadType::Magnet);
-        assert_eq!(download_type_from_url("MAGNET:?xt=urn:btih:abc"), DownloadType::Magnet);
-        assert_eq!(download_type_from_url("https://example.com/torrent/details"), DownloadType::Torrent);
+        assert_eq!(
+            download_type_from_url("https://example.com/file.zip"),
+            DownloadType::Http
+        );
+        assert_eq!(
+            download_type_from_url("https://example.com/file.torrent"),
+            DownloadType::Torrent
+        );
+        assert_eq!(
+            download_type_from_url("magnet:?xt=urn:btih:abc"),
+            DownloadType::Magnet
+        );
+        assert_eq!(
+            download_type_from_url("MAGNET:?xt=urn:btih:abc"),
+            DownloadType::Magnet
+        );
+        assert_eq!(
+            download_type_from_url("https://example.com/torrent/details"),
+            DownloadType::Torrent
+        );
     }
 
     #[test]
@@ -402,6 +2126,74 @@ mod tests {
         assert_eq!(expand_tilde("relative/path"), "relative/path");
     }
 
+    #[test]
+    fn test_normalize_path() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            normalize_path("~/Downloads"),
+            home.join("Downloads").to_string_lossy()
+        );
+        assert_eq!(
+            normalize_path("Downloads/isos/"),
+            home.join("Downloads/isos").to_string_lossy()
+        );
+        assert_eq!(normalize_path(" /srv/./data/../isos "), "/srv/isos");
+        assert_eq!(normalize_path(""), "");
+
+        let stick = PathBuf::from("/media/stick/gosh-fetch");
+        assert_eq!(
+            normalize_path_from("Downloads/../isos", Some(stick.clone())),
+            stick.join("isos").to_string_lossy()
+        );
+        assert_eq!(
+            normalize_path_from("~/Downloads", Some(stick)),
+            home.join("Downloads").to_string_lossy()
+        );
+        assert_eq!(normalize_path_from("Downloads", None), "Downloads");
+    }
+
+    #[test]
+    fn test_normalize_stored_paths() {
+        let db = test_db();
+        let conn = db.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE settings SET value = '~/Downloads' WHERE key = 'download_path'",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('user_agent', '~/not-a-path')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO downloads (gid, name, download_type, status, save_path, created_at)
+             VALUES ('a', 'a.iso', 'http', 'complete', 'isos', '2024-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(Database::normalize_stored_paths(&conn).unwrap(), 2);
+        let home = dirs::home_dir().unwrap();
+        let value = |sql: &str| {
+            conn.query_row(sql, [], |row| row.get::<_, String>(0))
+                .unwrap()
+        };
+        assert_eq!(
+            value("SELECT value FROM settings WHERE key = 'download_path'"),
+            home.join("Downloads").to_string_lossy()
+        );
+        assert_eq!(
+            value("SELECT value FROM settings WHERE key = 'user_agent'"),
+            "~/not-a-path"
+        );
+        assert_eq!(
+            value("SELECT save_path FROM downloads WHERE gid = 'a'"),
+            home.join("isos").to_string_lossy()
+        );
+        assert_eq!(Database::normalize_stored_paths(&conn).unwrap(), 0);
+    }
+
     #[test]
     fn test_default_settings() {
         let settings = Settings::default();
@@ -433,6 +2225,10 @@ mod tests {
         settings.max_concurrent_downloads = 10;
         settings.theme = "light".to_string();
         settings.proxy_url = "http://proxy:8080".to_string();
+        settings.accept_invalid_certs = true;
+        settings.bt_webseed_enabled = false;
+        settings.bt_webseed_max_connections = 8;
+        settings.bt_encryption = "require".to_string();
 
         db.save_settings_async(settings).await.unwrap();
 
@@ -440,31 +2236,29 @@ mod tests {
         assert_eq!(loaded.max_concurrent_downloads, 10);
         assert_eq!(loaded.theme, "light");
         assert_eq!(loaded.proxy_url, "http://proxy:8080");
+        assert!(loaded.accept_invalid_certs);
+        assert!(!loaded.bt_webseed_enabled);
+        assert_eq!(loaded.bt_webseed_max_connections, 8);
+        assert_eq!(loaded.bt_encryption, "require");
     }
 
     #[tokio::test]
     async fn test_save_and_load_download() {
         let db = test_db();
         let download = Download {
-            id: 0,
             gid: "test-gid-123".to_string(),
             name: "test-file.zip".to_string(),
             url: Some("https://example.com/file.zip".to_string()),
-            magnet_uri: None,
-            info_hash: None,
-            download_type: DownloadType::Http,
             status: DownloadState::Complete,
             total_size: 1024,
             completed_size: 1024,
-            download_speed: 0,
-            upload_speed: 0,
             save_path: "/tmp/downloads".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
             completed_at: Some("2026-01-01T00:01:00Z".to_string()),
-            error_message: None,
-            connections: 0,
-            seeders: 0,
-            selected_files: None,
+            priority: Some("high".to_string()),
+            category: "Archives".to_string(),
+            added_order: 7,
+            ..Default::default()
         };
 
         db.save_download_async(download).await.unwrap();
@@ -474,37 +2268,43 @@ mod tests {
         assert_eq!(completed[0].gid, "test-gid-123");
         assert_eq!(completed[0].name, "test-file.zip");
         assert_eq!(completed[0].total_size, 1024);
+        assert_eq!(completed[0].priority.as_deref(), Some("high"));
+        assert_eq!(completed[0].category, "Archives");
+
+        let queue = db.get_download_queue_async().await.unwrap();
+        assert_eq!(
+            queue,
+            vec![(
+                "test-gid-123".to_string(),
+                Some("high".to_string()),
+                "Archives".to_string(),
+                7
+            )]
+        );
     }
 
     #[tokio::test]
     async fn test_remove_download() {
         let db = test_db();
         let download = Download {
-            id: 0,
             gid: "remove-me".to_string(),
             name: "to-remove.zip".to_string(),
             url: Some("https://example.com/file.zip".to_string()),
-            magnet_uri: None,
-            info_hash: None,
-            download_type: DownloadType::Http,
             status: DownloadState::Complete,
             total_size: 512,
             completed_size: 512,
-            download_speed: 0,
-            upload_speed: 0,
             save_path: "/tmp".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
             completed_at: Some("2026-01-01T00:01:00Z".to_string()),
-            error_message: None,
-            connections: 0,
-            seeders: 0,
-            selected_files: None,
+            ..Default::default()
         };
 
         db.save_download_async(download).await.unwrap();
         assert_eq!(db.get_completed_downloads_async().await.unwrap().len(), 1);
 
-        db.remove_download_async("remove-me".to_string()).await.unwrap();
+        db.remove_download_async("remove-me".to_string())
+            .await
+            .unwrap();
         assert_eq!(db.get_completed_downloads_async().await.unwrap().len(), 0);
     }
 
@@ -513,25 +2313,16 @@ mod tests {
         let db = test_db();
         for i in 0..3 {
             let download = Download {
-                id: 0,
                 gid: format!("gid-{}", i),
                 name: format!("file-{}.zip", i),
                 url: Some("https://example.com/file.zip".to_string()),
-                magnet_uri: None,
-                info_hash: None,
-                download_type: DownloadType::Http,
                 status: DownloadState::Complete,
                 total_size: 100,
                 completed_size: 100,
-                download_speed: 0,
-                upload_speed: 0,
                 save_path: "/tmp".to_string(),
                 created_at: "2026-01-01T00:00:00Z".to_string(),
                 completed_at: Some("2026-01-01T00:01:00Z".to_string()),
-                error_message: None,
-                connections: 0,
-                seeders: 0,
-                selected_files: None,
+                ..Default::default()
             };
             db.save_download_async(download).await.unwrap();
         }
@@ -546,49 +2337,30 @@ mod tests {
         let db = test_db();
         // Save an active download
         let active = Download {
-            id: 0,
             gid: "active-1".to_string(),
             name: "downloading.zip".to_string(),
             url: Some("https://example.com/file.zip".to_string()),
-            magnet_uri: None,
-            info_hash: None,
-            download_type: DownloadType::Http,
             status: DownloadState::Active,
             total_size: 1000,
             completed_size: 500,
-            download_speed: 0,
-            upload_speed: 0,
             save_path: "/tmp".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
-            completed_at: None,
-            error_message: None,
-            connections: 0,
-            seeders: 0,
-            selected_files: None,
+            ..Default::default()
         };
         db.save_download_async(active).await.unwrap();
 
         // Save a completed download
         let complete = Download {
-            id: 0,
             gid: "complete-1".to_string(),
             name: "done.zip".to_string(),
             url: Some("https://example.com/done.zip".to_string()),
-            magnet_uri: None,
-            info_hash: None,
-            download_type: DownloadType::Http,
             status: DownloadState::Complete,
             total_size: 100,
             completed_size: 100,
-            download_speed: 0,
-            upload_speed: 0,
             save_path: "/tmp".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
             completed_at: Some("2026-01-01T00:01:00Z".to_string()),
-            error_message: None,
-            connections: 0,
-            seeders: 0,
-            selected_files: None,
+            ..Default::default()
         };
         db.save_download_async(complete).await.unwrap();
 
@@ -597,6 +2369,608 @@ mod tests {
         assert_eq!(incomplete[0].gid, "active-1");
     }
 
+    fn completed(gid: &str, url: &str, size: u64, elapsed_secs: i64) -> Download {
+        let created = chrono::DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap();
+        let completed = created + chrono::Duration::seconds(elapsed_secs);
+        Download {
+            gid: gid.to_string(),
+            name: format!("{}.bin", gid),
+            url: Some(url.to_string()),
+            download_type: download_type_from_url(url),
+            status: DownloadState::Complete,
+            total_size: size,
+            completed_size: size,
+            save_path: "/tmp".to_string(),
+            created_at: created.to_rfc3339(),
+            completed_at: Some(completed.to_rfc3339()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_stats() {
+        let db = test_db();
+        db.save_download_async(completed("a", "https://fast.example/a", 1000, 10))
+            .await
+            .unwrap();
+        db.save_download_async(completed("b", "https://FAST.example/b", 3000, 10))
+            .await
+            .unwrap();
+        db.save_download_async(completed("c", "https://slow.example/c", 1000, 100))
+            .await
+            .unwrap();
+        db.save_download_async(completed("d", "magnet:?xt=urn:btih:abc", 600, 30))
+            .await
+            .unwrap();
+        // Not complete: no timing recorded
+        let mut active = completed("e", "https://slow.example/e", 1000, 1);
+        active.status = DownloadState::Active;
+        active.completed_at = None;
+        db.save_download_async(active).await.unwrap();
+
+        let stats = db.get_history_stats_async().await.unwrap();
+        assert_eq!(stats.downloads, 4);
+        assert_eq!(stats.total_bytes, 5600);
+        assert_eq!(stats.average_speed, 5600 / 150);
+        assert_eq!(stats.median_elapsed_secs, 20);
+
+        assert_eq!(stats.hosts.len(), 2);
+        assert_eq!(stats.hosts[0].host, "fast.example");
+        assert_eq!(stats.hosts[0].downloads, 2);
+        assert_eq!(stats.hosts[0].average_speed, 200);
+        assert_eq!(stats.hosts[0].average_elapsed_secs, 10);
+        assert_eq!(stats.hosts[1].host, "slow.example");
+        assert_eq!(stats.hosts[1].average_speed, 10);
+    }
+
+    #[test]
+    fn test_completion_timing() {
+        let download = completed("t", "https://example.com/t", 500, 0);
+        assert_eq!(completion_timing(&download), Some((1, 500)));
+        let mut download = completed("t", "https://example.com/t", 500, 50);
+        assert_eq!(completion_timing(&download), Some((50, 10)));
+        download.completed_at = Some("not a date".to_string());
+        assert_eq!(completion_timing(&download), None);
+    }
+
+    #[tokio::test]
+    async fn test_category_rules() {
+        let db = test_db();
+        let rules = db.get_category_rules_async().await.unwrap();
+        assert_eq!(rules, crate::categories::default_rules());
+
+        let custom = vec![CategoryRule {
+            name: "Books".into(),
+            directory: "/srv/books".into(),
+            extensions: vec!["epub".into(), "mobi".into()],
+            mime_types: vec![],
+            archive_after_days: Some(7),
+            archive_directory: "Books".into(),
+        }];
+        db.save_category_rules_async(custom.clone()).await.unwrap();
+        assert_eq!(db.get_category_rules_async().await.unwrap(), custom);
+
+        // Re-running migrations leaves edited rules alone
+        db.run_migrations_sync().unwrap();
+        assert_eq!(db.get_category_rules_async().await.unwrap(), custom);
+    }
+
+    #[tokio::test]
+    async fn test_download_locations() {
+        let db = test_db();
+        assert!(db.get_download_locations_async().await.unwrap().is_empty());
+
+        let locations = vec![
+            DownloadLocation {
+                name: "NAS".into(),
+                path: "/mnt/nas".into(),
+            },
+            DownloadLocation {
+                name: "Scratch".into(),
+                path: "/mnt/scratch".into(),
+            },
+        ];
+        db.save_download_locations_async(locations.clone())
+            .await
+            .unwrap();
+        assert_eq!(db.get_download_locations_async().await.unwrap(), locations);
+
+        db.record_location_usage_async("Movies".into(), "NAS".into())
+            .await
+            .unwrap();
+        db.record_location_usage_async("Music".into(), "Scratch".into())
+            .await
+            .unwrap();
+        db.record_location_usage_async("".into(), "Default".into())
+            .await
+            .unwrap();
+        db.record_location_usage_async("Movies".into(), "Scratch".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_location_usage_async().await.unwrap()["Movies"],
+            "Scratch"
+        );
+
+        // Dropping a location forgets the categories that used it
+        db.save_download_locations_async(locations[..1].to_vec())
+            .await
+            .unwrap();
+        let usage = db.get_location_usage_async().await.unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[""], "Default");
+    }
+
+    #[tokio::test]
+    async fn test_download_templates() {
+        let db = test_db();
+        let template = DownloadTemplate {
+            name: "Linux ISOs".into(),
+            options: crate::types::DownloadOptions {
+                dir: Some("/srv/isos".into()),
+                sequential: Some(true),
+                ..Default::default()
+            },
+            trackers: vec![
+                "udp://a.example:6969".into(),
+                "https://b.example/announce".into(),
+            ],
+        };
+        db.save_download_template_async(template.clone())
+            .await
+            .unwrap();
+        let saved = db.get_download_templates_async().await.unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].name, "Linux ISOs");
+        assert_eq!(saved[0].options.dir.as_deref(), Some("/srv/isos"));
+        assert_eq!(saved[0].options.sequential, Some(true));
+        assert_eq!(saved[0].trackers, template.trackers);
+
+        // Same name in another case replaces it
+        let renamed = DownloadTemplate {
+            name: "linux isos".into(),
+            trackers: Vec::new(),
+            ..template
+        };
+        db.save_download_template_async(renamed).await.unwrap();
+        let saved = db.get_download_templates_async().await.unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].name, "linux isos");
+        assert!(saved[0].trackers.is_empty());
+
+        assert!(db
+            .delete_download_template_async("LINUX ISOS".into())
+            .await
+            .unwrap());
+        assert!(!db
+            .delete_download_template_async("Linux ISOs".into())
+            .await
+            .unwrap());
+        assert!(db.get_download_templates_async().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_staged_downloads() {
+        let db = test_db();
+        let mut staged = StagedDownload {
+            temp_dir: "/ssd/tmp/a".into(),
+            gid: None,
+            final_dir: "/hdd/Movies".into(),
+            final_path: None,
+        };
+        db.save_staged_async(staged.clone()).await.unwrap();
+        staged.gid = Some("gid-a".into());
+        staged.final_path = Some("/hdd/Movies/film.mkv".into());
+        db.save_staged_async(staged.clone()).await.unwrap();
+        assert_eq!(db.get_staged_downloads_async().await.unwrap(), vec![staged]);
+
+        db.delete_staged_async("/ssd/tmp/a".into()).await.unwrap();
+        assert!(db.get_staged_downloads_async().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_renames() {
+        let db = test_db();
+        db.save_rename_async("b".into(), "/dl/old.mkv".into())
+            .await
+            .unwrap();
+        db.save_rename_async("b".into(), "/dl/Show - S01E02.mkv".into())
+            .await
+            .unwrap();
+        db.save_rename_async("a".into(), "/dl/report.pdf".into())
+            .await
+            .unwrap();
+        db.delete_rename_async("a".into()).await.unwrap();
+        assert_eq!(
+            db.get_renames_async().await.unwrap(),
+            vec![("b".to_string(), "/dl/Show - S01E02.mkv".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_options() {
+        let db = test_db();
+        let options = DownloadOptions {
+            header: Some(vec!["Authorization: Bearer t".into()]),
+            priority: Some("high".into()),
+            ..Default::default()
+        };
+        db.save_download_options_async("a".into(), Some("https://example.com/f".into()), options)
+            .await
+            .unwrap();
+        let (url, options) = db
+            .get_download_options_async("a".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(url.as_deref(), Some("https://example.com/f"));
+        assert_eq!(
+            options.header,
+            Some(vec!["Authorization: Bearer t".to_string()])
+        );
+        assert_eq!(options.priority.as_deref(), Some("high"));
+        db.save_download_options_async("b".into(), None, DownloadOptions::default())
+            .await
+            .unwrap();
+        let urls = db.get_download_urls_async().await.unwrap();
+        assert_eq!(urls.len(), 1);
+        assert_eq!(urls["a"], "https://example.com/f");
+
+        db.delete_download_options_async("a".into()).await.unwrap();
+        assert!(db
+            .get_download_options_async("a".into())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_seed_totals() {
+        let db = test_db();
+        let a = SeedTotals {
+            uploaded: 1500,
+            seed_secs: 600,
+            size: 1000,
+        };
+        db.save_seed_totals_async(vec![("a".into(), a), ("b".into(), SeedTotals::default())])
+            .await
+            .unwrap();
+        let updated = SeedTotals {
+            uploaded: 2000,
+            ..a
+        };
+        db.save_seed_totals_async(vec![("a".into(), updated)])
+            .await
+            .unwrap();
+
+        let totals = db.get_seed_totals_async().await.unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["a"], updated);
+    }
+
+    #[tokio::test]
+    async fn test_traffic_totals() {
+        let db = test_db();
+        let a = TrafficTotals {
+            received: 1500,
+            payload: 1000,
+            discarded: 200,
+            completed: 800,
+        };
+        db.save_traffic_totals_async(vec![("a".into(), a)])
+            .await
+            .unwrap();
+        let updated = TrafficTotals {
+            received: 2000,
+            ..a
+        };
+        db.save_traffic_totals_async(vec![("a".into(), updated)])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_traffic_totals_async().await.unwrap(),
+            HashMap::from([("a".to_string(), updated)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latency_percentiles() {
+        let db = test_db();
+        db.save_download_async(completed("a", "https://fast.example/a", 1000, 10))
+            .await
+            .unwrap();
+        for (i, ttfb) in (1..=10).map(|i| i * 10).enumerate() {
+            db.record_latency_async(format!("gid{}", i), "fast.example".into(), ttfb, None)
+                .await
+                .unwrap();
+        }
+        db.record_latency_async("slow".into(), "slow.example".into(), 900, Some(400))
+            .await
+            .unwrap();
+
+        let stats = db.get_history_stats_async().await.unwrap();
+        let ttfb = stats.ttfb.unwrap();
+        assert_eq!(
+            (ttfb.samples, ttfb.p50_ms, ttfb.p90_ms, ttfb.p99_ms),
+            (11, 60, 100, 900)
+        );
+        assert_eq!(stats.hosts[0].median_ttfb_ms, Some(50));
+        assert_eq!(percentile(&[7], 99), 7);
+    }
+
+    #[tokio::test]
+    async fn test_file_hash_survives_snapshots() {
+        let db = test_db();
+        let download = completed("a", "https://example.com/a", 100, 10);
+        db.record_file_hash_async(download.clone(), "sha256:ab".into())
+            .await
+            .unwrap();
+        // A later snapshot of the same download keeps the hash
+        db.save_download_async(download).await.unwrap();
+        let (saved, hash) = db.get_file_hash_async("a".into()).await.unwrap().unwrap();
+        assert_eq!(
+            (saved.gid.as_str(), hash.as_deref()),
+            ("a", Some("sha256:ab"))
+        );
+        assert!(db.get_file_hash_async("b".into()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queued_downloads() {
+        let db = test_db();
+        let mut first = completed("a", "https://example.com/a", 100, 10);
+        first.name = "old.bin".into();
+        db.queue_download(first);
+        db.queue_download(completed("a", "https://example.com/a", 100, 10));
+        db.queue_download(completed("b", "https://example.com/b", 100, 10));
+
+        // Reads see queued snapshots, the newest of each download
+        let history = db.get_completed_downloads_async().await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().any(|d| d.name == "a.bin"));
+        assert_eq!(db.flush_downloads_async().await.unwrap(), 0);
+
+        db.queue_download(completed("c", "https://example.com/c", 100, 10));
+        assert_eq!(db.flush_downloads_async().await.unwrap(), 1);
+        assert_eq!(db.get_completed_downloads_async().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_seed_stats_daily() {
+        let db = test_db();
+        let first = SeedTotals {
+            uploaded: 1000,
+            seed_secs: 60,
+            size: 500,
+        };
+        db.save_seed_totals_async(vec![("a".into(), first)])
+            .await
+            .unwrap();
+        let second = SeedTotals {
+            uploaded: 1500,
+            seed_secs: 120,
+            size: 500,
+        };
+        db.save_seed_totals_async(vec![
+            ("a".into(), second),
+            ("b".into(), SeedTotals::default()),
+        ])
+        .await
+        .unwrap();
+
+        let days = db.get_seed_stats_daily_async(None, None).await.unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!((days[0].gid.as_str(), days[0].uploaded), ("a", 1500));
+        assert_eq!((days[0].downloaded, days[0].seed_time_secs), (500, 120));
+
+        let today = days[0].date.clone();
+        assert_eq!(
+            db.get_seed_stats_daily_async(Some(today.clone()), Some(today))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        let none = db
+            .get_seed_stats_daily_async(Some("2000-01-01".into()), Some("2000-01-31".into()))
+            .await;
+        assert!(none.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_host_profiles() {
+        let db = test_db();
+        db.record_host_speed_async("cdn.example".into(), 8, 100)
+            .await
+            .unwrap();
+        db.record_host_speed_async("cdn.example".into(), 8, 200)
+            .await
+            .unwrap();
+        db.record_host_speed_async("cdn.example".into(), 4, 90)
+            .await
+            .unwrap();
+        db.record_host_speed_async("other.example".into(), 2, 10)
+            .await
+            .unwrap();
+
+        let profiles = db.get_host_profiles_async(None).await.unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].host, "cdn.example");
+        let counts: Vec<(u32, u32, u64)> = profiles[0]
+            .connections
+            .iter()
+            .map(|c| (c.connections, c.samples, c.average_speed))
+            .collect();
+        assert_eq!(counts, vec![(4, 1, 90), (8, 2, 150)]);
+
+        let other = db
+            .get_host_profiles_async(Some("other.example".into()))
+            .await
+            .unwrap();
+        assert_eq!(other.len(), 1);
+        assert_eq!(other[0].connections[0].connections, 2);
+    }
+
+    #[tokio::test]
+    async fn test_broken_ranges() {
+        let db = test_db();
+        db.record_host_speed_async("cdn.example".into(), 8, 100)
+            .await
+            .unwrap();
+        db.record_broken_ranges_async("cdn.example".into())
+            .await
+            .unwrap();
+        db.record_broken_ranges_async("a.example".into())
+            .await
+            .unwrap();
+        assert!(db
+            .has_broken_ranges_async("cdn.example".into())
+            .await
+            .unwrap());
+        assert!(!db
+            .has_broken_ranges_async("other.example".into())
+            .await
+            .unwrap());
+
+        let profiles = db.get_host_profiles_async(None).await.unwrap();
+        let hosts: Vec<(&str, bool, usize)> = profiles
+            .iter()
+            .map(|p| (p.host.as_str(), p.broken_ranges, p.connections.len()))
+            .collect();
+        assert_eq!(
+            hosts,
+            vec![("a.example", true, 0), ("cdn.example", true, 1)]
+        );
+
+        // Old enough to be checked again
+        let old = (chrono::Utc::now()
+            - chrono::Duration::days(crate::ranges::BROKEN_RANGES_MEMORY_DAYS + 1))
+        .to_rfc3339();
+        db.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE host_ranges SET detected_at = ?1 WHERE host = 'a.example'",
+                params![old],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert!(!db
+            .has_broken_ranges_async("a.example".into())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prune_and_compact() {
+        let db = test_db();
+        let mut old = completed("old", "https://example.com/a.iso", 100, 10);
+        old.completed_at = Some("2020-01-01T00:00:00+00:00".into());
+        let mut recent = completed("recent", "https://example.com/b.iso", 100, 10);
+        recent.completed_at = Some(chrono::Utc::now().to_rfc3339());
+        db.save_download_async(old).await.unwrap();
+        db.save_download_async(recent).await.unwrap();
+
+        assert_eq!(
+            db.prune_history_async(HistoryRetention::Forever)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            db.prune_history_async(HistoryRetention::Days(30))
+                .await
+                .unwrap(),
+            1
+        );
+        let history = db.get_completed_downloads_async().await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].gid, "recent");
+
+        db.compact_async().await.unwrap();
+        let info = db.storage_info_async().await.unwrap();
+        assert!(info.db_size > 0);
+        assert_eq!(info.rows["downloads"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_prune_by_count_keeps_favorites() {
+        let db = test_db();
+        for (i, gid) in ["a", "b", "c", "d"].iter().enumerate() {
+            let mut download = completed(gid, "https://example.com/f.zip", 100, 10);
+            download.completed_at = Some(format!("2024-01-0{}T00:00:00+00:00", i + 1));
+            db.save_download_async(download).await.unwrap();
+        }
+        assert!(db.set_favorite_async("a".into(), true).await.unwrap());
+        assert!(!db.set_favorite_async("missing".into(), true).await.unwrap());
+
+        // Saving a download again keeps its favorite flag
+        let mut resaved = completed("a", "https://example.com/f.zip", 100, 10);
+        resaved.completed_at = Some("2024-01-01T00:00:00+00:00".into());
+        db.save_download_async(resaved).await.unwrap();
+
+        assert_eq!(
+            db.prune_history_async(HistoryRetention::Count(2))
+                .await
+                .unwrap(),
+            1
+        );
+        let gids: Vec<String> = db
+            .get_completed_downloads_async()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|d| d.gid)
+            .collect();
+        assert_eq!(gids, vec!["d", "c", "a"]);
+        assert_eq!(
+            db.prune_history_async(HistoryRetention::Days(1))
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(db.get_completed_downloads_async().await.unwrap()[0].favorite);
+    }
+
+    #[test]
+    fn test_history_retention_from_settings() {
+        let mut settings = Settings::default();
+        assert_eq!(
+            HistoryRetention::from_settings(&settings),
+            HistoryRetention::Forever
+        );
+        settings.history_retention = "count".into();
+        assert_eq!(
+            HistoryRetention::from_settings(&settings),
+            HistoryRetention::Count(1000)
+        );
+        settings.history_retention = "days".into();
+        settings.history_retention_days = 7;
+        assert_eq!(
+            HistoryRetention::from_settings(&settings),
+            HistoryRetention::Days(7)
+        );
+        settings.history_retention = "bogus".into();
+        assert_eq!(
+            HistoryRetention::from_settings(&settings),
+            HistoryRetention::Forever
+        );
+
+        // Zero limits would delete everything
+        settings.history_retention = "days".into();
+        settings.history_retention_days = 0;
+        assert_eq!(
+            HistoryRetention::from_settings(&settings),
+            HistoryRetention::Forever
+        );
+        settings.history_retention = "count".into();
+        settings.history_retention_count = 0;
+        assert_eq!(
+            HistoryRetention::from_settings(&settings),
+            HistoryRetention::Forever
+        );
+    }
+
     #[test]
     fn test_migration_idempotent() {
         let db = test_db();
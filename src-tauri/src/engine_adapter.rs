@@ -4,13 +4,19 @@
 //! command interface, maintaining backwards compatibility
 //! with the Electron frontend.
 
-use crate::types::{Download, DownloadOptions as FrontendOptions, DownloadState, DownloadType, GlobalStat};
+use crate::ranges::ServerProbe;
+use crate::seeding::{SeedLedger, SeedTotals};
+use crate::traffic::{TrafficLedger, TrafficTotals};
+use crate::types::{
+    Download, DownloadOptions as FrontendOptions, DownloadState, DownloadType, GlobalStat,
+};
 use gosh_dl::{
-    DownloadEngine, DownloadId, DownloadOptions, DownloadState as EngineState, DownloadStatus,
-    PeerInfo as EnginePeerInfo, TorrentFile,
+    DownloadEngine, DownloadId, DownloadOptions, DownloadPriority, DownloadState as EngineState,
+    DownloadStatus, PeerInfo as EnginePeerInfo, TorrentFile,
 };
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 /// Torrent file info for frontend compatibility
 #[derive(Debug, Clone)]
@@ -31,16 +37,121 @@ pub struct PeerInfo {
     pub upload_speed: u64,
 }
 
+/// Why the app, rather than the user, paused a download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hold {
+    /// The network went away
+    Network,
+    /// The VPN kill switch tripped (torrents only)
+    Vpn,
+    /// The system load guard eased off (low-priority downloads only)
+    Load,
+}
+
+/// Priority and category of a download, and when it was added.
+#[derive(Debug, Clone, Default)]
+struct QueueEntry {
+    priority: DownloadPriority,
+    category: String,
+    added_order: u64,
+}
+
+#[derive(Debug, Default)]
+struct QueueBook {
+    entries: HashMap<String, QueueEntry>,
+    last_order: u64,
+}
+
+impl QueueBook {
+    fn insert(&mut self, gid: &str, priority: DownloadPriority, category: String) {
+        self.last_order += 1;
+        let added_order = self.last_order;
+        self.entries.insert(
+            gid.to_string(),
+            QueueEntry {
+                priority,
+                category,
+                added_order,
+            },
+        );
+    }
+
+    /// Entries kept from an earlier session; later downloads are ordered
+    /// after them.
+    fn load(&mut self, entries: Vec<(String, Option<String>, String, u64)>) {
+        for (gid, priority, category, added_order) in entries {
+            let priority = priority.and_then(|p| p.parse().ok()).unwrap_or_default();
+            self.last_order = self.last_order.max(added_order);
+            self.entries.insert(
+                gid,
+                QueueEntry {
+                    priority,
+                    category,
+                    added_order,
+                },
+            );
+        }
+    }
+
+    /// The entry for `gid`; one seen for the first time (restored from an
+    /// earlier session without a kept entry) is taken to have been added
+    /// now.
+    fn entry(&mut self, gid: &str) -> &mut QueueEntry {
+        let last_order = &mut self.last_order;
+        self.entries.entry(gid.to_string()).or_insert_with(|| {
+            *last_order += 1;
+            QueueEntry {
+                added_order: *last_order,
+                ..Default::default()
+            }
+        })
+    }
+}
+
 /// Adapter to convert between gosh-dl types and existing frontend types
 #[derive(Clone)]
 pub struct EngineAdapter {
     engine: Arc<DownloadEngine>,
+    /// Downloads paused by `hold`, reported as WaitingForNetwork until they
+    /// are released or the user takes over.
+    held: Arc<Mutex<HashMap<String, Hold>>>,
+    /// Finished downloads whose content looked wrong, reported as Warning
+    /// with this message.
+    warnings: Arc<Mutex<HashMap<String, String>>>,
+    /// Downloads stopped by the app with an error the engine doesn't know
+    /// about (a size mismatch), reported as Error with this message.
+    failures: Arc<Mutex<HashMap<String, String>>>,
+    /// Finished downloads moved out of the temp directory, by final path.
+    moved: Arc<Mutex<HashMap<String, PathBuf>>>,
+    /// What the server of an HTTP download said about byte ranges and the
+    /// file's size when probed.
+    probes: Arc<Mutex<HashMap<String, ServerProbe>>>,
+    /// The `s3://` URLs of downloads the engine fetches presigned, reported
+    /// in place of the signed URL.
+    sources: Arc<Mutex<HashMap<String, String>>>,
+    /// Priority, category and add order, which the engine doesn't report.
+    queue: Arc<Mutex<QueueBook>>,
+    /// Upload totals of torrents, sampled from their upload speeds.
+    seeding: Arc<Mutex<SeedLedger>>,
+    /// Bytes received and wasted per download, sampled the same way.
+    traffic: Arc<Mutex<TrafficLedger>>,
 }
 
 impl EngineAdapter {
     /// Create a new adapter with the given engine
     pub fn new(engine: Arc<DownloadEngine>) -> Self {
-        Self { engine }
+        Self {
+            engine,
+            held: Arc::new(Mutex::new(HashMap::new())),
+            warnings: Arc::new(Mutex::new(HashMap::new())),
+            failures: Arc::new(Mutex::new(HashMap::new())),
+            moved: Arc::new(Mutex::new(HashMap::new())),
+            probes: Arc::new(Mutex::new(HashMap::new())),
+            sources: Arc::new(Mutex::new(HashMap::new())),
+            queue: Arc::new(Mutex::new(QueueBook::default())),
+            seeding: Arc::new(Mutex::new(SeedLedger::default())),
+            traffic: Arc::new(Mutex::new(TrafficLedger::default())),
+        }
     }
 
     /// Get a reference to the engine
@@ -54,9 +165,51 @@ impl EngineAdapter {
         url: String,
         options: Option<FrontendOptions>,
     ) -> Result<String, gosh_dl::EngineError> {
+        let (priority, category) = queue_details(options.as_ref());
+        let paused = starts_paused(options.as_ref());
         let opts = options.map(convert_options).unwrap_or_default();
         let id = self.engine.add_http(&url, opts).await?;
-        Ok(id.as_uuid().to_string())
+        let gid = id.as_uuid().to_string();
+        self.queue.lock().unwrap().insert(&gid, priority, category);
+        if paused {
+            self.pause_added(id).await;
+        }
+        Ok(gid)
+    }
+
+    /// Pause a download added with `paused`. A failure is only logged: the
+    /// download was added, and its GID is what the caller needs.
+    async fn pause_added(&self, id: DownloadId) {
+        if let Err(e) = self.engine.pause(id).await {
+            log::warn!("Failed to pause {} after adding it: {}", id.as_uuid(), e);
+        }
+    }
+
+    /// Resolve the directory and filename an HTTP download will be saved
+    /// under, when it can be known before the request is made (an explicit
+    /// `out`, or the last segment of the URL path).
+    pub fn resolve_target(
+        &self,
+        url: &str,
+        options: &FrontendOptions,
+    ) -> Option<(PathBuf, String)> {
+        let dir = options
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.engine.get_config().download_dir);
+        let name = match &options.out {
+            Some(out) => out.clone(),
+            None => {
+                let parsed = url::Url::parse(url).ok()?;
+                let segment = parsed.path_segments()?.next_back()?.to_string();
+                percent_decode(&segment)
+            }
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some((dir, sanitize_filename(&name)))
     }
 
     /// Add multiple downloads
@@ -65,11 +218,21 @@ impl EngineAdapter {
         urls: Vec<String>,
         options: Option<FrontendOptions>,
     ) -> Result<Vec<String>, gosh_dl::EngineError> {
+        let (priority, category) = queue_details(options.as_ref());
+        let paused = starts_paused(options.as_ref());
         let opts = options.map(convert_options).unwrap_or_default();
         let mut gids = Vec::new();
         for url in urls {
             let id = self.engine.add_http(&url, opts.clone()).await?;
-            gids.push(id.as_uuid().to_string());
+            let gid = id.as_uuid().to_string();
+            self.queue
+                .lock()
+                .unwrap()
+                .insert(&gid, priority, category.clone());
+            if paused {
+                self.pause_added(id).await;
+            }
+            gids.push(gid);
         }
         Ok(gids)
     }
@@ -77,24 +240,284 @@ impl EngineAdapter {
     /// Pause a download
     pub async fn pause(&self, gid: &str) -> Result<(), gosh_dl::EngineError> {
         let id = parse_gid(gid)?;
+        // An explicit pause overrides a hold: don't auto-resume it
+        self.held.lock().unwrap().remove(gid);
         self.engine.pause(id).await
     }
 
     /// Pause all active and queued downloads
     pub async fn pause_all(&self) -> serde_json::Value {
+        self.held.lock().unwrap().clear();
         batch_to_json(self.engine.pause_all().await)
     }
 
     /// Resume a download
     pub async fn resume(&self, gid: &str) -> Result<(), gosh_dl::EngineError> {
         let id = parse_gid(gid)?;
+        self.held.lock().unwrap().remove(gid);
+        self.failures.lock().unwrap().remove(gid);
         self.engine.resume(id).await
     }
 
+    /// Pause every running or queued download (only torrents and magnets
+    /// for `Hold::Vpn`, only low-priority ones for `Hold::Load`) and
+    /// remember why. Returns the GIDs that were paused.
+    pub async fn hold(&self, reason: Hold) -> Vec<String> {
+        let mut paused = Vec::new();
+        for status in self.engine.list() {
+            if matches!(
+                status.state,
+                EngineState::Paused | EngineState::Completed | EngineState::Error { .. }
+            ) {
+                continue;
+            }
+            if reason == Hold::Vpn && matches!(status.kind, gosh_dl::DownloadKind::Http) {
+                continue;
+            }
+            let gid = status.id.as_uuid().to_string();
+            if reason == Hold::Load
+                && self.queue.lock().unwrap().entry(&gid).priority != DownloadPriority::Low
+            {
+                continue;
+            }
+            match self.engine.pause(status.id).await {
+                Ok(()) => {
+                    self.held.lock().unwrap().insert(gid.clone(), reason);
+                    paused.push(gid);
+                }
+                Err(e) => log::warn!("Failed to pause {} ({:?} hold): {}", gid, reason, e),
+            }
+        }
+        paused
+    }
+
+    /// Resume the downloads held for `reason`. With `keep_torrents`, held
+    /// torrents are moved to a VPN hold instead of resumed. Returns the GIDs
+    /// that were resumed.
+    pub async fn release(&self, reason: Hold, keep_torrents: bool) -> Vec<String> {
+        let gids: Vec<String> = {
+            let mut held = self.held.lock().unwrap();
+            let gids: Vec<String> = held
+                .iter()
+                .filter(|(_, r)| **r == reason)
+                .map(|(gid, _)| gid.clone())
+                .collect();
+            for gid in &gids {
+                held.remove(gid);
+            }
+            gids
+        };
+
+        let mut resumed = Vec::new();
+        for gid in gids {
+            let Ok(id) = parse_gid(&gid) else { continue };
+            if keep_torrents {
+                let is_torrent = self
+                    .engine
+                    .status(id)
+                    .is_some_and(|s| !matches!(s.kind, gosh_dl::DownloadKind::Http));
+                if is_torrent {
+                    self.held.lock().unwrap().insert(gid, Hold::Vpn);
+                    continue;
+                }
+            }
+            match self.engine.resume(id).await {
+                Ok(()) => resumed.push(gid),
+                Err(e) => log::warn!("Failed to resume {} ({:?} hold): {}", gid, reason, e),
+            }
+        }
+        resumed
+    }
+
+    /// The downloads currently held for `reason`.
+    pub fn held(&self, reason: Hold) -> Vec<String> {
+        self.held
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, r)| **r == reason)
+            .map(|(gid, _)| gid.clone())
+            .collect()
+    }
+
+    /// Report a finished download as Warning with `message`.
+    pub fn flag_warning(&self, gid: &str, message: String) {
+        self.warnings
+            .lock()
+            .unwrap()
+            .insert(gid.to_string(), message);
+    }
+
+    /// Report a stopped download as failed with `message` until it is
+    /// resumed or removed.
+    pub fn fail(&self, gid: &str, message: String) {
+        self.failures
+            .lock()
+            .unwrap()
+            .insert(gid.to_string(), message);
+    }
+
+    /// Report a finished download at the place it was moved to.
+    pub fn set_moved(&self, gid: &str, path: PathBuf) {
+        self.moved.lock().unwrap().insert(gid.to_string(), path);
+    }
+
+    /// Record what probing the server of `gid` found.
+    pub fn set_server_probe(&self, gid: &str, probe: ServerProbe) {
+        self.probes.lock().unwrap().insert(gid.to_string(), probe);
+    }
+
+    pub fn server_probe(&self, gid: &str) -> Option<ServerProbe> {
+        self.probes.lock().unwrap().get(gid).cloned()
+    }
+
+    pub fn is_probed(&self, gid: &str) -> bool {
+        self.probes.lock().unwrap().contains_key(gid)
+    }
+
+    /// Report `gid` under the URL it was added with instead of the
+    /// presigned one the engine got.
+    pub fn set_source(&self, gid: &str, url: String) {
+        self.sources.lock().unwrap().insert(gid.to_string(), url);
+    }
+
+    /// Restore the priority, category and add order kept from an earlier
+    /// session.
+    pub fn load_queue(&self, entries: Vec<(String, Option<String>, String, u64)>) {
+        self.queue.lock().unwrap().load(entries);
+    }
+
+    /// Change a download's priority in the engine and in what the app
+    /// reports for it.
+    pub fn set_priority(
+        &self,
+        gid: &str,
+        priority: DownloadPriority,
+    ) -> Result<(), gosh_dl::EngineError> {
+        let id = parse_gid(gid)?;
+        self.engine.set_priority(id, priority)?;
+        self.queue.lock().unwrap().entry(gid).priority = priority;
+        Ok(())
+    }
+
+    /// Start the upload totals from what an earlier session saved.
+    pub fn load_seed_totals(&self, totals: HashMap<String, SeedTotals>) {
+        self.seeding.lock().unwrap().load(totals);
+    }
+
+    /// Add `elapsed_secs` of uploading at the torrents' current speeds.
+    /// Returns the totals that changed, for saving.
+    pub fn sample_seeding(&self, elapsed_secs: u64) -> Vec<(String, SeedTotals)> {
+        let downloads = self.get_all();
+        let mut ledger = self.seeding.lock().unwrap();
+        ledger.sample(&downloads, elapsed_secs);
+        ledger.take_dirty()
+    }
+
+    /// Every upload total, for a final save at shutdown. Totals already
+    /// taken by `sample_seeding` may not have been saved yet.
+    pub fn seed_totals(&self) -> Vec<(String, SeedTotals)> {
+        self.seeding.lock().unwrap().all()
+    }
+
+    /// Start the traffic totals from what an earlier session saved.
+    pub fn load_traffic_totals(&self, totals: HashMap<String, TrafficTotals>) {
+        self.traffic.lock().unwrap().load(totals);
+    }
+
+    /// Add `elapsed_secs` of downloading at the current speeds. Returns
+    /// the totals that changed, for saving.
+    pub fn sample_traffic(&self, elapsed_secs: u64) -> Vec<(String, TrafficTotals)> {
+        let downloads = self.get_all();
+        let mut ledger = self.traffic.lock().unwrap();
+        ledger.sample(&downloads, elapsed_secs);
+        ledger.take_dirty()
+    }
+
+    /// Every traffic total, for a final save at shutdown.
+    pub fn traffic_totals(&self) -> Vec<(String, TrafficTotals)> {
+        self.traffic.lock().unwrap().all()
+    }
+
+    fn convert(&self, status: DownloadStatus) -> Download {
+        let entry = self
+            .queue
+            .lock()
+            .unwrap()
+            .entry(&status.id.as_uuid().to_string())
+            .clone();
+        convert_status(status, &entry)
+    }
+
+    /// Apply app-side state the engine doesn't know about: holds, content
+    /// warnings, app-detected failures, moves out of the temp directory,
+    /// what the server probe found, upload and traffic totals, and the
+    /// unsigned URL of S3 downloads.
+    fn annotate(&self, mut download: Download) -> Download {
+        if download.download_type != DownloadType::Http {
+            let totals = self
+                .seeding
+                .lock()
+                .unwrap()
+                .get(&download.gid)
+                .unwrap_or_default();
+            download.seeding = Some(totals.stats());
+        }
+        let traffic = self
+            .traffic
+            .lock()
+            .unwrap()
+            .get(&download.gid)
+            .unwrap_or_default();
+        download.traffic = Some(traffic.stats());
+        if let Some(source) = self.sources.lock().unwrap().get(&download.gid) {
+            download.url = Some(source.clone());
+        } else if let Some(unsigned) = download.url.as_deref().and_then(crate::s3::unsigned_url) {
+            // Added before its source was kept
+            download.url = Some(unsigned.to_string());
+        }
+        if let Some(probe) = self.probes.lock().unwrap().get(&download.gid) {
+            download.resumable = probe.ranges;
+            if download.total_size == 0 {
+                download.total_size = probe.total_size.unwrap_or(0);
+            }
+        }
+        if let Some(path) = self.moved.lock().unwrap().get(&download.gid) {
+            if let (Some(dir), Some(name)) = (path.parent(), path.file_name()) {
+                download.save_path = dir.to_string_lossy().to_string();
+                download.name = name.to_string_lossy().to_string();
+            }
+        }
+        if download.status == DownloadState::Paused {
+            match self.held.lock().unwrap().get(&download.gid) {
+                Some(Hold::Network | Hold::Vpn) => {
+                    download.status = DownloadState::WaitingForNetwork
+                }
+                // Queued again once the load subsides
+                Some(Hold::Load) => download.status = DownloadState::Waiting,
+                None => {}
+            }
+        }
+        if let Some(message) = self.failures.lock().unwrap().get(&download.gid) {
+            download.status = DownloadState::Error;
+            download.error_message = Some(message.clone());
+            return download;
+        }
+        if download.status == DownloadState::Complete {
+            if let Some(message) = self.warnings.lock().unwrap().get(&download.gid) {
+                download.status = DownloadState::Warning;
+                download.error_message = Some(message.clone());
+            }
+        }
+        download
+    }
+
     /// Resume all paused downloads, and retry errored ones
     /// (the engine's resume_all only covers Paused; errored retry is a
     /// Gosh-Fetch behavior carried over from the pre-0.5.0 adapter)
     pub async fn resume_all(&self) -> serde_json::Value {
+        self.held.lock().unwrap().clear();
+        self.failures.lock().unwrap().clear();
         let mut result = self.engine.resume_all().await;
         for status in self.engine.stopped() {
             if matches!(status.state, EngineState::Error { .. }) {
@@ -114,34 +537,73 @@ impl EngineAdapter {
     }
 
     /// Remove a download
-    pub async fn remove(
-        &self,
-        gid: &str,
-        delete_files: bool,
-    ) -> Result<(), gosh_dl::EngineError> {
+    pub async fn remove(&self, gid: &str, delete_files: bool) -> Result<(), gosh_dl::EngineError> {
         let id = parse_gid(gid)?;
+        self.held.lock().unwrap().remove(gid);
+        self.warnings.lock().unwrap().remove(gid);
+        self.moved.lock().unwrap().remove(gid);
+        self.failures.lock().unwrap().remove(gid);
+        self.probes.lock().unwrap().remove(gid);
+        self.sources.lock().unwrap().remove(gid);
+        self.queue.lock().unwrap().entries.remove(gid);
         self.engine.cancel(id, delete_files).await
     }
 
     /// Get status of a single download
     pub fn get_status(&self, gid: &str) -> Option<Download> {
         let id = parse_gid(gid).ok()?;
-        self.engine.status(id).map(convert_status)
+        let mut download = self.annotate(self.convert(self.engine.status(id)?));
+        if download.status == DownloadState::Waiting {
+            // Waiting downloads are the engine's queued ones and those the
+            // load guard holds
+            let others: Vec<String> = self
+                .engine
+                .waiting()
+                .into_iter()
+                .map(|status| status.id.as_uuid().to_string())
+                .chain(self.held(Hold::Load))
+                .filter(|other| *other != download.gid)
+                .collect();
+            let mut queue = self.queue.lock().unwrap();
+            let key = |entry: &QueueEntry| (queue_rank(entry.priority), entry.added_order);
+            let own = key(queue.entry(&download.gid));
+            let ahead = others
+                .iter()
+                .filter(|other| key(queue.entry(other)) < own)
+                .count();
+            download.queue_position = Some(ahead as u32 + 1);
+        }
+        Some(download)
     }
 
     /// Get all downloads
     pub fn get_all(&self) -> Vec<Download> {
-        self.engine.list().into_iter().map(convert_status).collect()
+        let mut statuses = self.engine.list();
+        // Downloads restored from an earlier session get their add order
+        // from when they were first added
+        statuses.sort_by_key(|status| status.created_at);
+        let mut downloads: Vec<Download> = statuses
+            .into_iter()
+            .map(|status| self.annotate(self.convert(status)))
+            .collect();
+        assign_queue_positions(&mut downloads);
+        downloads
     }
 
     /// Get active downloads
     pub fn get_active(&self) -> Vec<Download> {
-        self.engine.active().into_iter().map(convert_status).collect()
+        self.engine
+            .active()
+            .into_iter()
+            .map(|status| self.annotate(self.convert(status)))
+            .collect()
     }
 
     /// Get global stats
     pub fn get_global_stats(&self) -> GlobalStat {
         let stats = self.engine.global_stats();
+        let (total_uploaded, share_ratio) = self.seeding.lock().unwrap().overall();
+        let traffic = self.traffic.lock().unwrap().overall();
         GlobalStat {
             download_speed: stats.download_speed,
             upload_speed: stats.upload_speed,
@@ -149,6 +611,10 @@ impl EngineAdapter {
             num_waiting: stats.num_waiting as u32,
             num_stopped: stats.num_stopped as u32,
             num_stopped_total: stats.num_stopped as u32,
+            total_uploaded,
+            share_ratio,
+            total_received: traffic.received,
+            total_wasted: traffic.wasted,
         }
     }
 
@@ -170,9 +636,16 @@ impl EngineAdapter {
         torrent_data: &[u8],
         options: Option<FrontendOptions>,
     ) -> Result<String, gosh_dl::EngineError> {
+        let (priority, category) = queue_details(options.as_ref());
+        let paused = starts_paused(options.as_ref());
         let opts = options.map(convert_options).unwrap_or_default();
         let id = self.engine.add_torrent(torrent_data, opts).await?;
-        Ok(id.as_uuid().to_string())
+        let gid = id.as_uuid().to_string();
+        self.queue.lock().unwrap().insert(&gid, priority, category);
+        if paused {
+            self.pause_added(id).await;
+        }
+        Ok(gid)
     }
 
     /// Add a magnet link
@@ -181,9 +654,16 @@ impl EngineAdapter {
         magnet_uri: &str,
         options: Option<FrontendOptions>,
     ) -> Result<String, gosh_dl::EngineError> {
+        let (priority, category) = queue_details(options.as_ref());
+        let paused = starts_paused(options.as_ref());
         let opts = options.map(convert_options).unwrap_or_default();
         let id = self.engine.add_magnet(magnet_uri, opts).await?;
-        Ok(id.as_uuid().to_string())
+        let gid = id.as_uuid().to_string();
+        self.queue.lock().unwrap().insert(&gid, priority, category);
+        if paused {
+            self.pause_added(id).await;
+        }
+        Ok(gid)
     }
 
     /// Get torrent files
@@ -273,6 +753,61 @@ fn batch_to_json(result: gosh_dl::BatchResult) -> serde_json::Value {
     })
 }
 
+/// Priority and category a download is added with.
+fn queue_details(options: Option<&FrontendOptions>) -> (DownloadPriority, String) {
+    let priority = options
+        .and_then(|o| o.priority.as_deref())
+        .and_then(|p| p.parse().ok())
+        .unwrap_or_default();
+    let category = options.and_then(|o| o.category.clone()).unwrap_or_default();
+    (priority, category)
+}
+
+/// Whether a download is to be added paused.
+fn starts_paused(options: Option<&FrontendOptions>) -> bool {
+    options.and_then(|o| o.paused).unwrap_or(false)
+}
+
+fn priority_name(priority: DownloadPriority) -> &'static str {
+    match priority {
+        DownloadPriority::Low => "low",
+        DownloadPriority::Normal => "normal",
+        DownloadPriority::High => "high",
+        DownloadPriority::Critical => "critical",
+    }
+}
+
+/// Where a priority starts in the queue, 0 first.
+fn queue_rank(priority: DownloadPriority) -> u8 {
+    match priority {
+        DownloadPriority::Critical => 0,
+        DownloadPriority::High => 1,
+        DownloadPriority::Normal => 2,
+        DownloadPriority::Low => 3,
+    }
+}
+
+/// Number the waiting downloads in the order the engine starts them:
+/// higher priority first, then the ones added earlier.
+fn assign_queue_positions(downloads: &mut [Download]) {
+    let rank = |download: &Download| {
+        let priority = download
+            .priority
+            .as_deref()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or_default();
+        queue_rank(priority)
+    };
+    let mut waiting: Vec<&mut Download> = downloads
+        .iter_mut()
+        .filter(|d| d.status == DownloadState::Waiting)
+        .collect();
+    waiting.sort_by_key(|d| (rank(d), d.added_order));
+    for (position, download) in waiting.into_iter().enumerate() {
+        download.queue_position = Some(position as u32 + 1);
+    }
+}
+
 /// Public wrapper for parse_gid, used by RPC handlers
 pub fn parse_gid_public(gid: &str) -> crate::Result<DownloadId> {
     parse_gid(gid).map_err(crate::Error::from)
@@ -286,9 +821,15 @@ fn parse_gid(gid: &str) -> Result<DownloadId, gosh_dl::EngineError> {
         return Ok(DownloadId::from_uuid(uuid));
     }
     // Fall back to legacy 16-char GID format
-    DownloadId::from_gid(gid).ok_or_else(|| {
-        gosh_dl::EngineError::NotFound(format!("Invalid GID: {}", gid))
-    })
+    DownloadId::from_gid(gid)
+        .ok_or_else(|| gosh_dl::EngineError::NotFound(format!("Invalid GID: {}", gid)))
+}
+
+/// Decode %XX escapes in a URL path segment (invalid escapes are kept as-is).
+pub(crate) fn percent_decode(segment: &str) -> String {
+    percent_encoding::percent_decode_str(segment)
+        .decode_utf8_lossy()
+        .into_owned()
 }
 
 /// Sanitize a filename for cross-platform compatibility.
@@ -297,18 +838,25 @@ fn parse_gid(gid: &str) -> Result<DownloadId, gosh_dl::EngineError> {
 fn sanitize_filename(name: &str) -> String {
     const ILLEGAL: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
     const RESERVED: &[&str] = &[
-        "CON", "PRN", "AUX", "NUL",
-        "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
-        "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
     ];
 
     let mut sanitized: String = name
         .chars()
-        .map(|c| if ILLEGAL.contains(&c) || c.is_control() { '_' } else { c })
+        .map(|c| {
+            if ILLEGAL.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
         .collect();
 
     // Strip trailing dots and spaces (Windows doesn't allow them)
-    sanitized = sanitized.trim_end_matches(|c: char| c == '.' || c == ' ').to_string();
+    sanitized = sanitized
+        .trim_end_matches(|c: char| c == '.' || c == ' ')
+        .to_string();
 
     // Prefix Windows reserved device names
     let stem = sanitized.split('.').next().unwrap_or("");
@@ -325,8 +873,8 @@ fn sanitize_filename(name: &str) -> String {
 
 /// Convert frontend options to gosh-dl options
 fn convert_options(opts: FrontendOptions) -> DownloadOptions {
-    use gosh_dl::DownloadPriority;
     use gosh_dl::http::ExpectedChecksum;
+    use gosh_dl::DownloadPriority;
 
     let mut headers = Vec::new();
 
@@ -339,16 +887,19 @@ fn convert_options(opts: FrontendOptions) -> DownloadOptions {
         }
     }
 
+    if opts.identity_encoding == Some(true) {
+        request_identity_encoding(&mut headers);
+    }
+
     // Priority: parse from string
-    let priority = opts.priority
+    let priority = opts
+        .priority
         .as_deref()
         .and_then(|s| s.parse::<DownloadPriority>().ok())
         .unwrap_or_default();
 
     // Checksum: parse "sha256:hex" or "md5:hex" format
-    let checksum = opts.checksum
-        .as_deref()
-        .and_then(ExpectedChecksum::parse);
+    let checksum = opts.checksum.as_deref().and_then(ExpectedChecksum::parse);
 
     // Mirrors
     let mirrors = opts.mirrors.unwrap_or_default();
@@ -360,18 +911,17 @@ fn convert_options(opts: FrontendOptions) -> DownloadOptions {
         user_agent: opts.user_agent,
         referer: opts.referer,
         headers,
-        max_connections: opts.split
+        max_connections: opts
+            .split
             .as_ref()
             .and_then(|s| s.parse().ok())
             .or(opts.max_connection_per_server.and_then(|s| s.parse().ok())),
         max_download_speed: opts.max_download_limit.and_then(|s| parse_speed(&s)),
         max_upload_speed: opts.max_upload_limit.and_then(|s| parse_speed(&s)),
         seed_ratio: opts.seed_ratio.and_then(|s| s.parse().ok()),
-        selected_files: opts.select_file.map(|s| {
-            s.split(',')
-                .filter_map(|n| n.parse().ok())
-                .collect()
-        }),
+        selected_files: opts
+            .select_file
+            .map(|s| s.split(',').filter_map(|n| n.parse().ok()).collect()),
         checksum,
         mirrors,
         sequential: opts.sequential,
@@ -379,13 +929,28 @@ fn convert_options(opts: FrontendOptions) -> DownloadOptions {
     }
 }
 
+/// Add `Accept-Encoding: identity` unless the caller set Accept-Encoding
+/// themselves. Servers that compress on the fly otherwise leave a gzip or
+/// zstd body on disk whose size doesn't match what the user asked for.
+fn request_identity_encoding(headers: &mut Vec<(String, String)>) {
+    if !headers
+        .iter()
+        .any(|(k, _)| k.eq_ignore_ascii_case("accept-encoding"))
+    {
+        headers.push(("Accept-Encoding".to_string(), "identity".to_string()));
+    }
+}
+
 /// Parse a speed string like "1M" or "500K" to bytes/sec
 fn parse_speed(s: &str) -> Option<u64> {
     let s = s.trim().to_uppercase();
     if s.ends_with('K') {
         s[..s.len() - 1].parse::<u64>().ok().map(|n| n * 1024)
     } else if s.ends_with('M') {
-        s[..s.len() - 1].parse::<u64>().ok().map(|n| n * 1024 * 1024)
+        s[..s.len() - 1]
+            .parse::<u64>()
+            .ok()
+            .map(|n| n * 1024 * 1024)
     } else if s.ends_with('G') {
         s[..s.len() - 1]
             .parse::<u64>()
@@ -397,7 +962,7 @@ fn parse_speed(s: &str) -> Option<u64> {
 }
 
 /// Convert gosh-dl status to frontend Download type
-fn convert_status(status: DownloadStatus) -> Download {
+fn convert_status(status: DownloadStatus, queue: &QueueEntry) -> Download {
     use gosh_dl::DownloadKind;
 
     let download_type = match status.kind {
@@ -417,10 +982,29 @@ fn convert_status(status: DownloadStatus) -> Download {
     };
 
     let error_message = match &status.state {
-        EngineState::Error { message, .. } => Some(message.clone()),
+        EngineState::Error { message, .. } => Some(crate::fd_budget::explain_error(message)),
         _ => None,
     };
 
+    // Connected seeders are the peers holding every piece. The engine's own
+    // seeder count is the swarm total a tracker reported on announce
+    let (peers, seeders) = match (&download_type, status.peers.as_ref()) {
+        (DownloadType::Http, _) => (0, 0),
+        (_, Some(list)) => (
+            list.len() as u32,
+            list.iter().filter(|p| p.progress >= 1.0).count() as u32,
+        ),
+        (_, None) => (status.progress.connections, 0),
+    };
+    let leechers = peers.saturating_sub(seeders);
+
+    // A body of unknown length has its size once it is all there
+    let total_size = match (status.progress.total_size, &state) {
+        (Some(total), _) => total,
+        (None, DownloadState::Complete) => status.progress.completed_size,
+        (None, _) => 0,
+    };
+
     Download {
         id: 0, // Frontend uses database ID, we don't have one yet
         gid: status.id.as_uuid().to_string(),
@@ -430,7 +1014,7 @@ fn convert_status(status: DownloadStatus) -> Download {
         info_hash: status.metadata.info_hash.clone(),
         download_type,
         status: state,
-        total_size: status.progress.total_size.unwrap_or(0),
+        total_size,
         completed_size: status.progress.completed_size,
         download_speed: status.progress.download_speed,
         upload_speed: status.progress.upload_speed,
@@ -439,7 +1023,10 @@ fn convert_status(status: DownloadStatus) -> Download {
         completed_at: status.completed_at.map(|t| t.to_rfc3339()),
         error_message,
         connections: status.progress.connections,
-        seeders: status.progress.seeders,
+        seeders,
+        swarm_seeders: status.progress.seeders,
+        peers,
+        leechers,
         selected_files: status.torrent_info.as_ref().map(|info| {
             info.files
                 .iter()
@@ -447,6 +1034,14 @@ fn convert_status(status: DownloadStatus) -> Download {
                 .map(|f| f.index)
                 .collect()
         }),
+        favorite: false,
+        resumable: None,
+        queue_position: None,
+        priority: Some(priority_name(queue.priority).to_string()),
+        category: queue.category.clone(),
+        added_order: queue.added_order,
+        seeding: None,
+        traffic: None,
     }
 }
 
@@ -462,6 +1057,28 @@ mod tests {
         assert_eq!(parse_speed("2G"), Some(2 * 1024 * 1024 * 1024));
     }
 
+    #[test]
+    fn test_request_identity_encoding() {
+        let mut headers = vec![("Referer".to_string(), "https://example.com".to_string())];
+        request_identity_encoding(&mut headers);
+        assert_eq!(
+            headers[1],
+            ("Accept-Encoding".to_string(), "identity".to_string())
+        );
+
+        // An explicit Accept-Encoding header wins
+        let mut headers = vec![("accept-encoding".to_string(), "gzip".to_string())];
+        request_identity_encoding(&mut headers);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("my%20file.zip"), "my file.zip");
+        assert_eq!(percent_decode("plain.iso"), "plain.iso");
+        assert_eq!(percent_decode("bad%zz%2"), "bad%zz%2");
+    }
+
     #[test]
     fn test_sanitize_filename_illegal_chars() {
         assert_eq!(sanitize_filename("file<>name"), "file__name");
@@ -495,4 +1112,63 @@ mod tests {
         assert_eq!(sanitize_filename("my-file.zip"), "my-file.zip");
         assert_eq!(sanitize_filename("photo (1).jpg"), "photo (1).jpg");
     }
+
+    fn queued(gid: &str, status: DownloadState, priority: &str, added_order: u64) -> Download {
+        Download {
+            gid: gid.into(),
+            name: gid.into(),
+            status,
+            priority: Some(priority.into()),
+            added_order,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_assign_queue_positions() {
+        let mut downloads = vec![
+            queued("first", DownloadState::Waiting, "normal", 1),
+            queued("running", DownloadState::Active, "critical", 2),
+            queued("low", DownloadState::Waiting, "low", 3),
+            queued("urgent", DownloadState::Waiting, "critical", 4),
+            queued("second", DownloadState::Waiting, "normal", 5),
+        ];
+        assign_queue_positions(&mut downloads);
+        let positions: Vec<(&str, Option<u32>)> = downloads
+            .iter()
+            .map(|d| (d.gid.as_str(), d.queue_position))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("first", Some(2)),
+                ("running", None),
+                ("low", Some(4)),
+                ("urgent", Some(1)),
+                ("second", Some(3))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_queue_book() {
+        let mut book = QueueBook::default();
+        book.insert("a", DownloadPriority::High, "Video".into());
+        assert_eq!(book.entry("restored").added_order, 2);
+        assert_eq!(book.entry("restored").added_order, 2);
+        let a = book.entry("a").clone();
+        assert_eq!(
+            (a.priority, a.category.as_str(), a.added_order),
+            (DownloadPriority::High, "Video", 1)
+        );
+
+        let mut book = QueueBook::default();
+        book.load(vec![("kept".into(), Some("low".into()), "Music".into(), 5)]);
+        let kept = book.entry("kept").clone();
+        assert_eq!(
+            (kept.priority, kept.category.as_str(), kept.added_order),
+            (DownloadPriority::Low, "Music", 5)
+        );
+        assert_eq!(book.entry("new").added_order, 6);
+    }
 }
@@ -29,6 +29,12 @@ pub enum Error {
 
     #[error("network error: {0}")]
     Network(String),
+
+    #[error("size mismatch: expected {expected} bytes, got {observed}")]
+    SizeMismatch { expected: u64, observed: u64 },
+
+    #[error("file missing: {0} was moved or deleted")]
+    FileMissing(String),
 }
 
 impl Error {
@@ -43,6 +49,8 @@ impl Error {
             Error::InvalidInput(_) => -7,
             Error::NotFound(_) => -8,
             Error::Network(_) => -9,
+            Error::SizeMismatch { .. } => -10,
+            Error::FileMissing(_) => -11,
         }
     }
 }
@@ -84,6 +92,15 @@ mod tests {
         assert_eq!(Error::InvalidInput("test".into()).code(), -7);
         assert_eq!(Error::NotFound("test".into()).code(), -8);
         assert_eq!(Error::Network("test".into()).code(), -9);
+        assert_eq!(
+            Error::SizeMismatch {
+                expected: 1,
+                observed: 2
+            }
+            .code(),
+            -10
+        );
+        assert_eq!(Error::FileMissing("a.iso".into()).code(), -11);
     }
 
     #[test]
@@ -1,24 +1,64 @@
-//! Background event emitters: 1s global stats + tray data feed.
+//! Background event emitters: 1s global stats + tray data feed, seeding
+//! totals, the periodic seeding auto-stop check, batched failure digests,
+//! the network monitor that pauses downloads while offline, the system load
+//! guard, idle-time database maintenance, history retention and archiving
+//! of old finished downloads.
 
+use crate::engine_adapter::Hold;
+use crate::types::{Download, DownloadState};
 use crate::AppState;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use tauri::{AppHandle, Emitter, Manager};
 
-/// Format a speed in bytes/sec for the tray tooltip.
-pub fn format_speed(bytes_per_sec: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-
-    if bytes_per_sec >= GB {
-        format!("{:.1} GB/s", bytes_per_sec as f64 / GB as f64)
-    } else if bytes_per_sec >= MB {
-        format!("{:.1} MB/s", bytes_per_sec as f64 / MB as f64)
-    } else if bytes_per_sec >= KB {
-        format!("{:.1} KB/s", bytes_per_sec as f64 / KB as f64)
-    } else {
-        format!("{} B/s", bytes_per_sec)
-    }
-}
+/// How often completed torrents are checked against the auto-stop rules.
+const SEED_AUTO_STOP_INTERVAL_SECS: u64 = 300;
+
+/// How often torrent upload speeds are added to the seeding totals, and
+/// how often changed totals are saved.
+const SEED_SAMPLE_INTERVAL_SECS: u64 = 5;
+const SEED_SAVE_INTERVAL_SECS: u64 = 60;
+
+/// Queued download snapshots are written in one transaction this often.
+const DOWNLOAD_FLUSH_INTERVAL_SECS: u64 = 2;
+
+/// How long failures are collected before the frontend is told about them.
+const FAILURE_DIGEST_WINDOW_SECS: u64 = 5;
+
+const NETWORK_PROBE_INTERVAL_SECS: u64 = 5;
+
+/// Consecutive failed probes before the network counts as lost, so a brief
+/// flap (DHCP renewal, Wi-Fi roaming) doesn't pause everything.
+const NETWORK_LOSS_PROBES: u32 = 2;
+
+/// Shorter than the network probe: a VPN drop should stop torrents quickly.
+const VPN_CHECK_INTERVAL_SECS: u64 = 2;
+
+const POWER_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// How often the system load is sampled for the load guard.
+const LOAD_CHECK_INTERVAL_SECS: u64 = 10;
+
+const MAINTENANCE_CHECK_INTERVAL_SECS: u64 = 15 * 60;
+
+/// Automatic database maintenance runs at most this often.
+const MAINTENANCE_MIN_AGE_HOURS: i64 = 24;
+
+const RETENTION_CHECK_INTERVAL_SECS: u64 = 60 * 60;
+
+const ARCHIVE_CHECK_INTERVAL_SECS: u64 = 60 * 60;
+
+const STATS_REPORT_CHECK_INTERVAL_SECS: u64 = 15 * 60;
+
+const UPDATE_CHECK_INTERVAL_SECS: u64 = 60 * 60;
+
+/// The background update check hits the release feed at most this often.
+const UPDATE_CHECK_MIN_AGE_HOURS: i64 = 24;
+
+/// Settings-table keys: when the feed was last checked, and the newest
+/// version already announced, so each release is announced once.
+const LAST_UPDATE_CHECK_KEY: &str = "last_update_check";
+const ANNOUNCED_UPDATE_KEY: &str = "announced_update_version";
 
 /// Spawn the 1-second global stats emitter. Emits `global-stats` to all
 /// windows, updates the tray tooltip, and pushes `tray-update` with active
@@ -41,15 +81,20 @@ pub fn spawn_stats_emitter(app: AppHandle) {
                     "numActive": stats.num_active,
                     "numWaiting": stats.num_waiting,
                     "numStopped": stats.num_stopped,
+                    "totalUploaded": stats.total_uploaded,
+                    "shareRatio": stats.share_ratio,
+                    "totalReceived": stats.total_received,
+                    "totalWasted": stats.total_wasted,
                 }),
             );
 
             // Tray tooltip (no-op on Linux where libappindicator has no tooltip)
             if let Some(tray) = app.tray_by_id("main-tray") {
+                let fmt = state.number_format();
                 let tooltip = format!(
                     "Gosh-Fetch\n↓ {}  ↑ {}\n{} active",
-                    format_speed(stats.download_speed),
-                    format_speed(stats.upload_speed),
+                    fmt.speed(stats.download_speed),
+                    fmt.speed(stats.upload_speed),
                     stats.num_active
                 );
                 let _ = tray.set_tooltip(Some(&tooltip));
@@ -77,15 +122,819 @@ pub fn spawn_stats_emitter(app: AppHandle) {
     });
 }
 
+/// True if `download` is a finished torrent still seeding into a swarm that
+/// already has more than `threshold` seeders. The swarm count comes from the
+/// trackers, not from the peers we happen to be connected to; a torrent no
+/// tracker has reported on (DHT-only, say) is never considered well seeded.
+pub fn is_well_seeded(download: &Download, threshold: u32) -> bool {
+    threshold > 0 && crate::seeding::is_seeding(download) && download.swarm_seeders > threshold
+}
+
+/// Spawn the seeding and traffic totals sampler: torrents' upload speeds
+/// and downloads' speeds and sizes are added up every few seconds, and
+/// changed totals saved every minute.
+pub fn spawn_seed_stats(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut pending = HashMap::new();
+        let mut pending_traffic = HashMap::new();
+        let mut last_sample = std::time::Instant::now();
+        let mut last_save = std::time::Instant::now();
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(SEED_SAMPLE_INTERVAL_SECS)).await;
+            let state = app.state::<AppState>();
+            let Ok(adapter) = state.get_adapter().await else {
+                continue;
+            };
+            let elapsed = last_sample.elapsed().as_secs();
+            if elapsed == 0 {
+                continue;
+            }
+            // Whole seconds only; the remainder carries over
+            last_sample += std::time::Duration::from_secs(elapsed);
+            pending.extend(adapter.sample_seeding(elapsed));
+            pending_traffic.extend(adapter.sample_traffic(elapsed));
+
+            if (pending.is_empty() && pending_traffic.is_empty())
+                || last_save.elapsed().as_secs() < SEED_SAVE_INTERVAL_SECS
+            {
+                continue;
+            }
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let totals: Vec<_> = pending.drain().collect();
+            if let Err(e) = db.save_seed_totals_async(totals).await {
+                log::warn!("Failed to save seeding totals: {}", e);
+            }
+            let traffic: Vec<_> = pending_traffic.drain().collect();
+            if let Err(e) = db.save_traffic_totals_async(traffic).await {
+                log::warn!("Failed to save traffic totals: {}", e);
+            }
+            last_save = std::time::Instant::now();
+        }
+    });
+}
+
+/// Spawn the download snapshot writer. Snapshots the frontend saves on
+/// every state change are queued and written here in batches, so many
+/// busy downloads don't cost a SQLite transaction each. Anything still
+/// queued at exit is written by `AppState::shutdown`.
+pub fn spawn_download_writer(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                DOWNLOAD_FLUSH_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            match db.flush_downloads_async().await {
+                Ok(0) => {}
+                Ok(written) => log::trace!("Wrote {} download snapshots", written),
+                Err(e) => log::warn!("Failed to write download snapshots: {}", e),
+            }
+        }
+    });
+}
+
+/// Spawn the seeding auto-stop check. Every few minutes, completed torrents
+/// are paused once they reach the share ratio (`bt_seed_ratio`) or seeding
+/// time (`bt_seed_time_limit`) limit, or their swarm has more seeders than
+/// `bt_auto_stop_seeders`, so upload goes to rarer torrents, and
+/// `download:auto-stopped` is emitted. A torrent is stopped at most once,
+/// so resuming it by hand sticks; the record goes once it is removed.
+pub fn spawn_seed_auto_stop(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut stopped: HashSet<String> = HashSet::new();
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                SEED_AUTO_STOP_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            let (Ok(adapter), Ok(db)) = (state.get_adapter().await, state.get_db().await) else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+            let threshold = settings.bt_auto_stop_seeders;
+            let time_limit_secs = settings.bt_seed_time_limit as u64 * 60;
+            if threshold == 0 && settings.bt_seed_ratio <= 0.0 && time_limit_secs == 0 {
+                continue;
+            }
+
+            let downloads = adapter.get_all();
+            stopped.retain(|gid| downloads.iter().any(|d| &d.gid == gid));
+            for download in downloads {
+                if stopped.contains(&download.gid) {
+                    continue;
+                }
+                let reason = if is_well_seeded(&download, threshold) {
+                    format!(
+                        "Swarm has {} seeders (limit {})",
+                        download.swarm_seeders, threshold
+                    )
+                } else if let Some(reason) = crate::seeding::limit_reached(
+                    &download,
+                    settings.bt_seed_ratio,
+                    time_limit_secs,
+                ) {
+                    reason
+                } else {
+                    continue;
+                };
+                if let Err(e) = adapter.pause(&download.gid).await {
+                    log::warn!("Failed to auto-stop seeding {}: {}", download.gid, e);
+                    continue;
+                }
+                log::info!("Auto-stopped seeding {}: {}", download.name, reason);
+                let _ = app.emit(
+                    "download:auto-stopped",
+                    serde_json::json!({
+                        "gid": download.gid,
+                        "name": download.name,
+                        "seeders": download.swarm_seeders,
+                        "threshold": threshold,
+                        "reason": reason,
+                    }),
+                );
+                stopped.insert(download.gid);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedDownload {
+    pub gid: String,
+    pub name: String,
+    pub error: String,
+}
+
+/// Failures seen during the current digest window.
+#[derive(Debug, Default)]
+pub struct FailureBatch {
+    failures: Vec<FailedDownload>,
+}
+
+impl FailureBatch {
+    /// Add a failure. Returns true if it opened a new window.
+    pub fn push(&mut self, failure: FailedDownload) -> bool {
+        self.failures.push(failure);
+        self.failures.len() == 1
+    }
+
+    /// Close the window and build the `download:failure-digest` payload.
+    /// `grouped` tells the frontend to show one notification instead of one
+    /// per download; a threshold of 0 never groups.
+    pub fn take_digest(&mut self, threshold: u32) -> Option<serde_json::Value> {
+        let failures = std::mem::take(&mut self.failures);
+        if failures.is_empty() {
+            return None;
+        }
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for failure in &failures {
+            *counts.entry(failure.error.as_str()).or_default() += 1;
+        }
+        // Most frequent error; ties go to the one seen first
+        let mut reason = "";
+        let mut best = 0;
+        for failure in &failures {
+            let count = counts[failure.error.as_str()];
+            if count > best {
+                best = count;
+                reason = &failure.error;
+            }
+        }
+        let reason = reason.to_string();
+
+        Some(serde_json::json!({
+            "count": failures.len(),
+            "grouped": threshold > 0 && failures.len() >= threshold as usize,
+            "reason": reason,
+            "failures": failures,
+        }))
+    }
+}
+
+/// Queue a failed download for the next `download:failure-digest`. The first
+/// failure in a window schedules the flush, so a burst of failures (e.g. a
+/// network drop) is reported once with the full list attached.
+pub fn queue_failure(app: &AppHandle, state: &AppState, failure: FailedDownload) {
+    if !state.failure_batch().lock().unwrap().push(failure) {
+        return;
+    }
+    let app = app.clone();
+    let batch = state.failure_batch();
+    let state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(tokio::time::Duration::from_secs(FAILURE_DIGEST_WINDOW_SECS)).await;
+        let threshold = match state.get_db().await {
+            Ok(db) => db
+                .get_settings_async()
+                .await
+                .map(|s| s.failure_digest_threshold)
+                .unwrap_or(3),
+            Err(_) => 3,
+        };
+        let digest = batch.lock().unwrap().take_digest(threshold);
+        if let Some(digest) = digest {
+            let _ = app.emit("download:failure-digest", digest);
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkChange {
+    Lost,
+    Restored,
+}
+
+/// Debounces probe results into lost/restored transitions.
+#[derive(Debug, Default)]
+pub struct NetworkWatch {
+    failures: u32,
+    offline: bool,
+}
+
+impl NetworkWatch {
+    pub fn observe(&mut self, reachable: bool) -> Option<NetworkChange> {
+        if reachable {
+            self.failures = 0;
+            if self.offline {
+                self.offline = false;
+                return Some(NetworkChange::Restored);
+            }
+            return None;
+        }
+        self.failures += 1;
+        if !self.offline && self.failures >= NETWORK_LOSS_PROBES {
+            self.offline = true;
+            return Some(NetworkChange::Lost);
+        }
+        None
+    }
+}
+
+/// Spawn the network monitor. When the route to the internet disappears,
+/// active and queued downloads are paused into WaitingForNetwork; when it
+/// comes back, those (and only those) are resumed. Emits `network:status`
+/// on each transition.
+pub fn spawn_network_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut watch = NetworkWatch::default();
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                NETWORK_PROBE_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            let (Ok(adapter), Ok(db)) = (state.get_adapter().await, state.get_db().await) else {
+                continue;
+            };
+            let enabled = db
+                .get_settings_async()
+                .await
+                .map(|s| s.pause_on_network_loss)
+                .unwrap_or(true);
+
+            let change = if enabled {
+                watch.observe(!crate::utils::internet_route_sources().is_empty())
+            } else if watch.offline {
+                // Turned off while offline: hand the paused downloads back
+                watch = NetworkWatch::default();
+                Some(NetworkChange::Restored)
+            } else {
+                None
+            };
+
+            let (online, gids) = match change {
+                Some(NetworkChange::Lost) => (false, adapter.hold(Hold::Network).await),
+                Some(NetworkChange::Restored) => (
+                    true,
+                    adapter.release(Hold::Network, state.is_vpn_down()).await,
+                ),
+                None => continue,
+            };
+            log::info!(
+                "Network {}: {} download(s) {}",
+                if online { "restored" } else { "lost" },
+                gids.len(),
+                if online { "resumed" } else { "paused" }
+            );
+            let _ = app.emit(
+                "network:status",
+                serde_json::json!({ "online": online, "gids": gids }),
+            );
+        }
+    });
+}
+
+/// Spawn the VPN kill switch. While the configured interface is missing or
+/// not carrying the default route, DHT/PEX/LPD are off and every torrent is
+/// held; HTTP downloads are left alone. Emits `vpn:down` / `vpn:up` on each
+/// transition, including a drop found at startup once the webview is
+/// listening.
+pub fn spawn_vpn_guard(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut reported = false;
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(VPN_CHECK_INTERVAL_SECS)).await;
+            let state = app.state::<AppState>();
+            let (Ok(adapter), Ok(db)) = (state.get_adapter().await, state.get_db().await) else {
+                continue;
+            };
+            let interface = db
+                .get_settings_async()
+                .await
+                .map(|s| s.bt_vpn_interface)
+                .unwrap_or_default();
+
+            let down = !interface.is_empty() && !crate::vpn::routes_through_interface(&interface);
+            if down {
+                // Re-hold every tick: catches torrents started since the drop
+                adapter.hold(Hold::Vpn).await;
+                if !state.is_vpn_down() {
+                    state.set_vpn_down(true);
+                    if let Err(e) = state.sync_peer_discovery().await {
+                        log::warn!(
+                            "Failed to disable peer discovery for VPN kill switch: {}",
+                            e
+                        );
+                    }
+                }
+                if reported || !state.is_frontend_ready() {
+                    continue;
+                }
+                reported = true;
+                let gids = adapter.held(Hold::Vpn);
+                log::warn!(
+                    "VPN interface {} is down: {} torrent(s) paused",
+                    interface,
+                    gids.len()
+                );
+                let _ = app.emit(
+                    "vpn:down",
+                    serde_json::json!({ "interface": interface, "gids": gids }),
+                );
+            } else if state.is_vpn_down() {
+                state.set_vpn_down(false);
+                if let Err(e) = state.sync_peer_discovery().await {
+                    log::warn!(
+                        "Failed to restore peer discovery after VPN reconnect: {}",
+                        e
+                    );
+                }
+                let gids = adapter.release(Hold::Vpn, false).await;
+                log::info!(
+                    "VPN interface {} is up: {} torrent(s) resumed",
+                    interface,
+                    gids.len()
+                );
+                if reported {
+                    reported = false;
+                    let _ = app.emit(
+                        "vpn:up",
+                        serde_json::json!({ "interface": interface, "gids": gids }),
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// Spawn the power watcher. On battery below the configured threshold the
+/// battery limits are laid over the engine settings; back on AC (or above
+/// the threshold) the saved settings are re-applied in full. Emits
+/// `power:battery-saver` on each transition.
+pub fn spawn_power_watcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(POWER_CHECK_INTERVAL_SECS)).await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+
+            let power = crate::utils::power_state();
+            let active = crate::state::battery_saver_applies(&settings, power);
+            if active == state.is_battery_saver() {
+                continue;
+            }
+            state.set_battery_saver(active);
+            if let Err(e) = crate::commands::apply_settings_to_engine(&state, settings).await {
+                log::warn!("Failed to apply battery mode: {}", e);
+                // Try again next tick
+                state.set_battery_saver(!active);
+                continue;
+            }
+            let percent = power.map(|p| p.percent);
+            log::info!(
+                "Battery saver {} (charge {:?}%)",
+                if active { "on" } else { "off" },
+                percent
+            );
+            let _ = app.emit(
+                "power:battery-saver",
+                serde_json::json!({ "active": active, "percent": percent }),
+            );
+        }
+    });
+}
+
+/// Spawn the system load guard. While CPU usage or the disk queue stays
+/// above its threshold, connections per download are cut to
+/// `load_guard_max_connections` and low-priority downloads are paused
+/// (with `load_guard_pause_low_priority`); once the load has stayed low
+/// as long, the saved settings are re-applied and the paused downloads
+/// resumed. Emits `load-guard:changed` on each transition.
+pub fn spawn_load_guard(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut guard = crate::load_guard::LoadGuard::default();
+        let mut last_cpu = crate::load_guard::cpu_times();
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(LOAD_CHECK_INTERVAL_SECS)).await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+
+            let cpu = crate::load_guard::cpu_times();
+            let sample = crate::load_guard::LoadSample {
+                cpu_percent: last_cpu
+                    .zip(cpu)
+                    .and_then(|(last, now)| crate::load_guard::cpu_percent(last, now)),
+                disk_queue: crate::load_guard::disk_queue(),
+            };
+            last_cpu = cpu;
+            state.update_load_guard(|status| {
+                status.enabled = settings.load_guard_enabled;
+                status.cpu_percent = sample.cpu_percent;
+                status.disk_queue = sample.disk_queue;
+            });
+
+            let change = if settings.load_guard_enabled {
+                guard.observe(sample.is_over(&settings))
+            } else {
+                // Switched off while active: restore right away
+                let was_active = guard.is_active();
+                guard.reset();
+                was_active.then_some(false)
+            };
+            let Some(active) = change else { continue };
+            let Ok(adapter) = state.get_adapter().await else {
+                guard.reset();
+                continue;
+            };
+
+            state.update_load_guard(|status| {
+                status.active = active;
+                status.active_since = active.then(|| chrono::Utc::now().to_rfc3339());
+            });
+            if let Err(e) =
+                crate::commands::apply_settings_to_engine(&state, settings.clone()).await
+            {
+                log::warn!("Failed to apply load guard limits: {}", e);
+            }
+            let gids = if active && settings.load_guard_pause_low_priority {
+                adapter.hold(Hold::Load).await
+            } else if !active {
+                adapter.release(Hold::Load, state.is_vpn_down()).await
+            } else {
+                Vec::new()
+            };
+            state.update_load_guard(|status| {
+                status.paused = if active { gids.clone() } else { Vec::new() }
+            });
+            log::info!(
+                "Load guard {} (CPU {:?}%, disk queue {:?}), {} download(s) {}",
+                if active { "on" } else { "off" },
+                sample.cpu_percent,
+                sample.disk_queue,
+                gids.len(),
+                if active { "paused" } else { "resumed" }
+            );
+            let _ = app.emit(
+                "load-guard:changed",
+                serde_json::json!({
+                    "active": active,
+                    "cpuPercent": sample.cpu_percent,
+                    "diskQueue": sample.disk_queue,
+                    "gids": gids,
+                }),
+            );
+        }
+    });
+}
+
+/// Spawn the automatic database maintenance. Every 15 minutes, if it is
+/// switched on, nothing is downloading, and the last run was a day or more
+/// ago, history is pruned and the database compacted.
+pub fn spawn_db_maintenance(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                MAINTENANCE_CHECK_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+            if !settings.auto_db_maintenance {
+                continue;
+            }
+            let last_run = db
+                .get_meta_async(crate::commands::LAST_MAINTENANCE_KEY)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok());
+            if last_run.is_some_and(|t| {
+                chrono::Utc::now().signed_duration_since(t)
+                    < chrono::Duration::hours(MAINTENANCE_MIN_AGE_HOURS)
+            }) {
+                continue;
+            }
+            let Ok(adapter) = state.get_adapter().await else {
+                continue;
+            };
+            let busy = adapter
+                .get_all()
+                .iter()
+                .any(|d| matches!(d.status, DownloadState::Active | DownloadState::Waiting));
+            if busy {
+                continue;
+            }
+            if let Err(e) = crate::commands::db_maintenance(&state, &app, false).await {
+                log::warn!("Automatic database maintenance failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Spawn the scheduled stats report. Every `stats_report_interval_hours`
+/// the seeding statistics of every day recorded are written to
+/// `stats_report_path`, replacing the last report.
+pub fn spawn_stats_report(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                STATS_REPORT_CHECK_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+            if !settings.stats_report_enabled || settings.stats_report_path.is_empty() {
+                continue;
+            }
+            let last_run = db
+                .get_meta_async(crate::commands::LAST_STATS_REPORT_KEY)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok());
+            let interval =
+                chrono::Duration::hours(i64::from(settings.stats_report_interval_hours.max(1)));
+            if last_run.is_some_and(|t| chrono::Utc::now().signed_duration_since(t) < interval) {
+                continue;
+            }
+            let result = crate::commands::export_stats(
+                &state,
+                settings.stats_report_path.clone(),
+                settings.stats_report_format.clone(),
+                None,
+                None,
+            )
+            .await;
+            match result {
+                Ok(rows) => {
+                    log::info!(
+                        "Wrote stats report ({} rows) to {}",
+                        rows,
+                        settings.stats_report_path
+                    );
+                    let _ = db
+                        .set_meta_async(
+                            crate::commands::LAST_STATS_REPORT_KEY,
+                            chrono::Utc::now().to_rfc3339(),
+                        )
+                        .await;
+                }
+                Err(e) => log::warn!("Scheduled stats report failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Spawn the history retention enforcer. Hourly, finished downloads the
+/// configured policy no longer keeps are removed from history. Unlike the
+/// full maintenance run this is cheap, so it doesn't wait for idle time.
+pub fn spawn_history_retention(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                RETENTION_CHECK_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+            let policy = crate::db::HistoryRetention::from_settings(&settings);
+            if policy == crate::db::HistoryRetention::Forever {
+                continue;
+            }
+            match db.prune_history_async(policy).await {
+                Ok(0) => {}
+                Ok(removed) => log::info!("History retention removed {} entries", removed),
+                Err(e) => log::warn!("History retention failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Spawn the archiver. Hourly, finished downloads older than their
+/// category's (or the global) archive age are moved to the archive folder.
+pub fn spawn_archive(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                ARCHIVE_CHECK_INTERVAL_SECS,
+            ))
+            .await;
+            let state = app.state::<AppState>();
+            match crate::archive::run(&state).await {
+                Ok(0) => {}
+                Ok(archived) => log::info!("Archived {} finished download(s)", archived),
+                Err(e) => log::warn!("Archiving finished downloads failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Spawn the opt-in update check. Hourly, if it is switched on and the
+/// feed wasn't checked in the last day, look for a newer release and emit
+/// `update:available` the first time each one is seen.
+pub fn spawn_update_check(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(UPDATE_CHECK_INTERVAL_SECS)).await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.get_db().await else {
+                continue;
+            };
+            let Ok(settings) = db.get_settings_async().await else {
+                continue;
+            };
+            if !settings.update_check_enabled {
+                continue;
+            }
+            let last_check = db
+                .get_meta_async(LAST_UPDATE_CHECK_KEY)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok());
+            if last_check.is_some_and(|t| {
+                chrono::Utc::now().signed_duration_since(t)
+                    < chrono::Duration::hours(UPDATE_CHECK_MIN_AGE_HOURS)
+            }) {
+                continue;
+            }
+            let info = match crate::commands::get_update_info(&state, &app).await {
+                Ok(info) => info,
+                Err(e) => {
+                    log::warn!("Update check failed: {}", e);
+                    continue;
+                }
+            };
+            let _ = db
+                .set_meta_async(LAST_UPDATE_CHECK_KEY, chrono::Utc::now().to_rfc3339())
+                .await;
+            let announced = db.get_meta_async(ANNOUNCED_UPDATE_KEY).await.ok().flatten();
+            if info.update_available && announced.as_deref() != Some(info.latest_version.as_str()) {
+                log::info!("Update available: {}", info.latest_version);
+                let _ = db
+                    .set_meta_async(ANNOUNCED_UPDATE_KEY, info.latest_version.clone())
+                    .await;
+                let _ = app.emit("update:available", &info);
+            }
+        }
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::DownloadType;
+
+    fn seeding_torrent(swarm_seeders: u32) -> Download {
+        Download {
+            gid: "gid".into(),
+            name: "linux.iso".into(),
+            download_type: DownloadType::Torrent,
+            status: DownloadState::Active,
+            total_size: 100,
+            completed_size: 100,
+            swarm_seeders,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_well_seeded() {
+        assert!(is_well_seeded(&seeding_torrent(51), 50));
+        assert!(!is_well_seeded(&seeding_torrent(50), 50));
+        assert!(!is_well_seeded(&seeding_torrent(500), 0));
+
+        let mut downloading = seeding_torrent(100);
+        downloading.completed_size = 50;
+        assert!(!is_well_seeded(&downloading, 50));
+
+        let mut http = seeding_torrent(100);
+        http.download_type = DownloadType::Http;
+        assert!(!is_well_seeded(&http, 50));
+
+        let mut untracked = seeding_torrent(0);
+        untracked.seeders = 80;
+        assert!(!is_well_seeded(&untracked, 50));
+    }
+
+    fn failure(gid: &str, error: &str) -> FailedDownload {
+        FailedDownload {
+            gid: gid.into(),
+            name: format!("{}.zip", gid),
+            error: error.into(),
+        }
+    }
+
+    #[test]
+    fn test_failure_batch_window() {
+        let mut batch = FailureBatch::default();
+        assert!(batch.take_digest(3).is_none());
+        assert!(batch.push(failure("a", "timeout")));
+        assert!(!batch.push(failure("b", "timeout")));
+
+        let digest = batch.take_digest(3).unwrap();
+        assert_eq!(digest["count"], 2);
+        assert_eq!(digest["grouped"], false);
+        assert_eq!(digest["failures"][1]["gid"], "b");
+
+        // Taking the digest closes the window
+        assert!(batch.push(failure("c", "timeout")));
+    }
+
+    #[test]
+    fn test_failure_digest_groups_and_picks_common_reason() {
+        let mut batch = FailureBatch::default();
+        batch.push(failure("a", "Disk full"));
+        batch.push(failure("b", "Network unreachable"));
+        batch.push(failure("c", "Network unreachable"));
+        let digest = batch.take_digest(3).unwrap();
+        assert_eq!(digest["grouped"], true);
+        assert_eq!(digest["reason"], "Network unreachable");
+
+        batch.push(failure("a", "Disk full"));
+        batch.push(failure("b", "Network unreachable"));
+        assert_eq!(batch.take_digest(0).unwrap()["grouped"], false);
+        batch.push(failure("a", "Disk full"));
+        batch.push(failure("b", "Network unreachable"));
+        assert_eq!(batch.take_digest(2).unwrap()["reason"], "Disk full");
+    }
 
     #[test]
-    fn test_format_speed() {
-        assert_eq!(format_speed(512), "512 B/s");
-        assert_eq!(format_speed(2048), "2.0 KB/s");
-        assert_eq!(format_speed(3 * 1024 * 1024), "3.0 MB/s");
-        assert_eq!(format_speed(2 * 1024 * 1024 * 1024), "2.0 GB/s");
+    fn test_network_watch_debounces_loss() {
+        let mut watch = NetworkWatch::default();
+        assert_eq!(watch.observe(true), None);
+        assert_eq!(watch.observe(false), None);
+        assert_eq!(watch.observe(true), None);
+        assert_eq!(watch.observe(false), None);
+        assert_eq!(watch.observe(false), Some(NetworkChange::Lost));
+        assert_eq!(watch.observe(false), None);
+        assert_eq!(watch.observe(true), Some(NetworkChange::Restored));
+        assert_eq!(watch.observe(true), None);
     }
 }
@@ -0,0 +1,188 @@
+//! Open file descriptor budget. Every peer socket, HTTP connection and
+//! file being written takes a descriptor, and past the OS limit the engine
+//! fails with a bare "Too many open files". The limit is read when the
+//! engine starts, and the engine's concurrency, peer and connection caps
+//! are lowered until all downloads running at once fit in it.
+
+use crate::types::FdBudget;
+use gosh_dl::EngineConfig;
+
+/// Descriptors left for everything besides downloads: the databases, log
+/// files, the webview's IPC, DHT and LPD sockets.
+const RESERVED_FDS: u64 = 128;
+/// Connections a download keeps at least; below this, fewer downloads run
+/// at once instead.
+const MIN_CONNECTIONS_PER_DOWNLOAD: usize = 4;
+
+/// Descriptors the engine can need with `config`'s caps: every download
+/// slot at its peer or connection cap, plus the file it writes.
+pub fn worst_case(config: &EngineConfig) -> u64 {
+    let per_download = config.max_peers.max(config.max_connections_per_download) as u64 + 1;
+    config.max_concurrent_downloads as u64 * per_download
+}
+
+/// Lower `config`'s caps so `worst_case` fits in `limit`. Returns what was
+/// lowered, or None if it already fit.
+pub fn fit_to_limit(config: &mut EngineConfig, limit: u64) -> Option<String> {
+    let usable = limit.saturating_sub(RESERVED_FDS);
+    if worst_case(config) <= usable {
+        return None;
+    }
+    let usable = usable as usize;
+    let fitting = (usable / (MIN_CONNECTIONS_PER_DOWNLOAD + 1)).max(1);
+    let concurrent = config.max_concurrent_downloads.clamp(1, fitting);
+    let cap = (usable / concurrent).saturating_sub(1).max(1);
+    config.max_concurrent_downloads = concurrent;
+    config.max_peers = config.max_peers.min(cap);
+    config.max_connections_per_download = config.max_connections_per_download.min(cap);
+    Some(format!(
+        "Open file limit {} allows {} downloads at once with up to {} peers or connections each",
+        limit, concurrent, cap
+    ))
+}
+
+/// The budget for the engine about to run with `config`, which is lowered
+/// to fit the process's open file limit.
+pub fn apply(config: &mut EngineConfig) -> FdBudget {
+    let limit = open_file_limit();
+    let clamped = limit.and_then(|limit| fit_to_limit(config, limit));
+    if let Some(clamped) = &clamped {
+        log::warn!("{}", clamped);
+    }
+    FdBudget {
+        limit,
+        worst_case: worst_case(config),
+        open: None,
+        clamped,
+    }
+}
+
+/// A clearer message for an engine error caused by running out of
+/// descriptors; other errors are returned unchanged.
+pub fn explain_error(error: &str) -> String {
+    if error.contains("Too many open files") || error.contains("os error 24") {
+        format!(
+            "Out of file handles: the system's open file limit is used up. Lower the concurrent \
+             download or peer limits, or raise the limit (ulimit -n). ({})",
+            error
+        )
+    } else {
+        error.to_string()
+    }
+}
+
+/// Soft limit on open files, None if unlimited or unknown.
+#[cfg(target_os = "linux")]
+pub fn open_file_limit() -> Option<u64> {
+    parse_proc_limits(&std::fs::read_to_string("/proc/self/limits").ok()?)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn open_file_limit() -> Option<u64> {
+    let output = std::process::Command::new("sh")
+        .args(["-c", "ulimit -n"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Windows has no per-process socket limit worth budgeting for.
+#[cfg(not(unix))]
+pub fn open_file_limit() -> Option<u64> {
+    None
+}
+
+/// Descriptors the process has open now, where the OS tells.
+pub fn open_fds() -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        std::fs::read_dir("/proc/self/fd")
+            .ok()
+            .map(|dir| dir.count() as u64)
+    } else {
+        None
+    }
+}
+
+/// Soft limit from the `Max open files` row of `/proc/self/limits`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_limits(limits: &str) -> Option<u64> {
+    let row = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?;
+    row["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(concurrent: usize, peers: usize, connections: usize) -> EngineConfig {
+        EngineConfig {
+            max_concurrent_downloads: concurrent,
+            max_peers: peers,
+            max_connections_per_download: connections,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fit_to_limit() {
+        let mut roomy = config(5, 55, 16);
+        assert_eq!(worst_case(&roomy), 5 * 56);
+        assert_eq!(fit_to_limit(&mut roomy, 65536), None);
+        assert_eq!((roomy.max_concurrent_downloads, roomy.max_peers), (5, 55));
+
+        // 256 - 128 reserved = 128 usable: 5 downloads with 24 each
+        let mut tight = config(5, 55, 16);
+        assert!(fit_to_limit(&mut tight, 256).is_some());
+        assert_eq!(
+            (
+                tight.max_concurrent_downloads,
+                tight.max_peers,
+                tight.max_connections_per_download
+            ),
+            (5, 24, 16)
+        );
+        assert!(worst_case(&tight) <= 128);
+
+        // Too few for 20 downloads of 4 connections: fewer run at once
+        let mut starved = config(20, 55, 16);
+        fit_to_limit(&mut starved, 178);
+        assert_eq!(
+            (starved.max_concurrent_downloads, starved.max_peers),
+            (10, 4)
+        );
+
+        let mut minimal = config(3, 55, 16);
+        fit_to_limit(&mut minimal, 64);
+        assert_eq!(
+            (minimal.max_concurrent_downloads, minimal.max_peers),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn test_parse_proc_limits() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units\n\
+                      Max processes             63344                63344                processes\n\
+                      Max open files            1024                 524288               files\n";
+        assert_eq!(parse_proc_limits(limits), Some(1024));
+        assert_eq!(
+            parse_proc_limits("Max open files            unlimited   unlimited  files"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_explain_error() {
+        assert!(
+            explain_error("Failed to open file: Too many open files (os error 24)")
+                .starts_with("Out of file handles")
+        );
+        assert_eq!(explain_error("Connection refused"), "Connection refused");
+    }
+}
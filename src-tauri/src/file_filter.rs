@@ -0,0 +1,139 @@
+//! Pattern-based torrent file selection.
+//!
+//! Patterns are globs by default (`*` matches any run of characters,
+//! including `/`; `?` matches one character) and are matched
+//! case-insensitively against each file's path inside the torrent.
+//! A `re:` prefix switches a pattern to a regular expression.
+
+use crate::types::FileFilter;
+use crate::{Error, Result};
+use regex::{Regex, RegexBuilder};
+
+/// Compiled form of a [`FileFilter`].
+pub struct CompiledFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl CompiledFilter {
+    pub fn new(filter: &FileFilter) -> Result<Self> {
+        Ok(Self {
+            include: filter
+                .include
+                .iter()
+                .map(|p| compile(p))
+                .collect::<Result<_>>()?,
+            exclude: filter
+                .exclude
+                .iter()
+                .map(|p| compile(p))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// A path is selected if it matches any include pattern (or there are
+    /// none) and no exclude pattern.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.replace('\\', "/");
+        let included = self.include.is_empty() || self.include.iter().any(|re| re.is_match(&path));
+        included && !self.exclude.iter().any(|re| re.is_match(&path))
+    }
+
+    /// Indices of the selected paths. Errors if the filter selects nothing,
+    /// since the engine treats an empty selection as "download everything".
+    pub fn select<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> Result<Vec<usize>> {
+        let selected: Vec<usize> = paths
+            .into_iter()
+            .enumerate()
+            .filter(|(_, path)| self.matches(path))
+            .map(|(i, _)| i)
+            .collect();
+        if selected.is_empty() {
+            return Err(Error::InvalidInput(
+                "File filter does not match any file in the torrent".into(),
+            ));
+        }
+        Ok(selected)
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    let source = match pattern.strip_prefix("re:") {
+        Some(re) => re.to_string(),
+        None => glob_to_regex(pattern),
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Error::InvalidInput(format!("Invalid file pattern '{}': {}", pattern, e)))
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> CompiledFilter {
+        CompiledFilter::new(&FileFilter {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_glob_include_exclude() {
+        let f = filter(&["*.mkv"], &["*sample*"]);
+        assert!(f.matches("Show/S01E01.mkv"));
+        assert!(f.matches("MOVIE.MKV"));
+        assert!(!f.matches("Show/Sample/S01E01.mkv"));
+        assert!(!f.matches("Show/S01E01.nfo"));
+        assert!(f.matches("Show\\S01E02.mkv"));
+    }
+
+    #[test]
+    fn test_glob_escapes_regex_syntax() {
+        let f = filter(&["file[1].txt"], &[]);
+        assert!(f.matches("file[1].txt"));
+        assert!(!f.matches("file1.txt"));
+        assert!(filter(&["disc?.iso"], &[]).matches("disc2.iso"));
+    }
+
+    #[test]
+    fn test_regex_patterns() {
+        let f = filter(&[r"re:S\d{2}E\d{2}"], &[]);
+        assert!(f.matches("Show/S01E02.mkv"));
+        assert!(!f.matches("Show/extras.mkv"));
+        assert!(CompiledFilter::new(&FileFilter {
+            include: vec!["re:(".into()],
+            exclude: vec![],
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let paths = ["a.mkv", "a.sample.mkv", "a.nfo", "b.mkv"];
+        assert_eq!(
+            filter(&["*.mkv"], &["*sample*"]).select(paths).unwrap(),
+            vec![0, 3]
+        );
+        assert_eq!(
+            filter(&[], &["*.nfo"]).select(paths).unwrap(),
+            vec![0, 1, 3]
+        );
+        assert!(filter(&["*.iso"], &[]).select(paths).is_err());
+    }
+}
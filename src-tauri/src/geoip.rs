@@ -0,0 +1,515 @@
+//! Country lookups for torrent peers from a user-supplied MaxMind DB
+//! (`.mmdb`, e.g. GeoLite2-Country or DB-IP Country Lite). Off by default;
+//! the database is read the first time peers are listed with the lookup
+//! on, and lookups never leave the machine.
+//!
+//! Only what a country lookup needs of the MMDB format is implemented: the
+//! binary search tree with 24-, 28- and 32-bit records and the data types
+//! a record can hold. The file comes from the user, so every size and
+//! offset read from it is checked before use: arithmetic on them is
+//! checked, allocations are capped by the bytes left to read, and a record
+//! may decode only so many values, which stops pointer loops and records
+//! that expand one map into millions of copies.
+
+use crate::types::{CountryPeers, SwarmGeography};
+use crate::{Error, Result};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Marks the start of the metadata map near the end of the file.
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+/// Zero bytes between the search tree and the data section.
+const DATA_SEPARATOR_LEN: usize = 16;
+/// Nesting a record may have; deeper means a pointer loop.
+const MAX_DEPTH: usize = 32;
+/// Values one record may decode to. A country record has a few dozen.
+const MAX_VALUES: usize = 1 << 16;
+
+/// A value from the data section.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Uint(u64),
+    Int(i32),
+    Double(f64),
+    Bool(bool),
+    Bytes(Vec<u8>),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_uint(&self) -> Option<u64> {
+        match self {
+            Value::Uint(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GeoIpDb {
+    bytes: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u64,
+    /// Offset of the data section in `bytes`
+    data_start: usize,
+}
+
+impl GeoIpDb {
+    pub fn open(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(bytes).map_err(|e| {
+            Error::InvalidInput(format!("Invalid GeoIP database {}: {}", path.display(), e))
+        })
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> std::result::Result<Self, String> {
+        let marker = bytes
+            .windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+            .ok_or("no MaxMind DB metadata")?;
+        let metadata_start = marker + METADATA_MARKER.len();
+        let (metadata, _) = Decoder::new(&bytes[metadata_start..]).decode(0)?;
+        let field = |key| {
+            metadata
+                .get(key)
+                .and_then(Value::as_uint)
+                .ok_or(format!("metadata lacks {}", key))
+        };
+        let node_count = field("node_count")? as usize;
+        let record_size = field("record_size")? as usize;
+        let ip_version = field("ip_version")?;
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported record size {}", record_size));
+        }
+        let data_start = node_count
+            .checked_mul(record_size / 4)
+            .and_then(|tree_len| tree_len.checked_add(DATA_SEPARATOR_LEN))
+            .ok_or("search tree size overflows")?;
+        if data_start > marker {
+            return Err("search tree runs past the data section".into());
+        }
+        Ok(Self {
+            bytes,
+            node_count,
+            record_size,
+            ip_version,
+            data_start,
+        })
+    }
+
+    /// Two-letter ISO code of the country `ip` is in, falling back to the
+    /// country its network is registered in. None if the database doesn't
+    /// know the address.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let record = self.lookup(ip)?;
+        ["country", "registered_country"].iter().find_map(|key| {
+            record
+                .get(key)?
+                .get("iso_code")?
+                .as_str()
+                .map(str::to_string)
+        })
+    }
+
+    fn lookup(&self, ip: IpAddr) -> Option<Value> {
+        let bits: Vec<bool> = match (ip, self.ip_version) {
+            (IpAddr::V4(v4), 4) => bits_of(&v4.octets()),
+            // IPv4 addresses live under ::/96 in IPv6 databases
+            (IpAddr::V4(v4), _) => bits_of(&v4.to_ipv6_compatible().octets()),
+            (IpAddr::V6(v6), 4) => bits_of(&v6.to_ipv4_mapped()?.octets()),
+            (IpAddr::V6(v6), _) => bits_of(&v6.octets()),
+        };
+        let mut node = 0;
+        for bit in bits {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, bit)?;
+        }
+        if node <= self.node_count {
+            // Equal to the node count means the address isn't in the tree
+            return None;
+        }
+        // Records pointing into the separator are invalid
+        let offset = (node - self.node_count).checked_sub(DATA_SEPARATOR_LEN)?;
+        let data = Decoder::new(self.bytes.get(self.data_start..)?);
+        data.decode(offset).ok().map(|(value, _)| value)
+    }
+
+    /// Left (`right == false`) or right record of a search tree node.
+    fn record(&self, node: usize, right: bool) -> Option<usize> {
+        let size = self.record_size / 4;
+        let start = node.checked_mul(size)?;
+        let b = self.bytes.get(start..start.checked_add(size)?)?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+        Some(match (self.record_size, right) {
+            (24, false) => be(&b[0..3]),
+            (24, true) => be(&b[3..6]),
+            // The middle byte holds the high nibble of each record
+            (28, false) => (b[3] as usize >> 4) << 24 | be(&b[0..3]),
+            (28, true) => (b[3] as usize & 0x0f) << 24 | be(&b[4..7]),
+            (_, false) => be(&b[0..4]),
+            (_, true) => be(&b[4..8]),
+        })
+    }
+}
+
+fn bits_of(octets: &[u8]) -> Vec<bool> {
+    octets
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1))
+        .collect()
+}
+
+/// Reads values from a data section (or the metadata, which uses the same
+/// encoding). Pointers are offsets from the start of `bytes`.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    /// Values left to decode before giving up
+    budget: Cell<usize>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            budget: Cell::new(MAX_VALUES),
+        }
+    }
+
+    /// The value at `offset` and the offset just past it.
+    fn decode(&self, offset: usize) -> std::result::Result<(Value, usize), String> {
+        self.value(offset, 0)
+    }
+
+    fn value(&self, offset: usize, depth: usize) -> std::result::Result<(Value, usize), String> {
+        if depth > MAX_DEPTH {
+            return Err("data nested too deeply".into());
+        }
+        let budget = self
+            .budget
+            .get()
+            .checked_sub(1)
+            .ok_or("record decodes to too many values")?;
+        self.budget.set(budget);
+        let control = *self.byte(offset)?;
+        let mut pos = offset + 1;
+        let mut kind = control >> 5;
+        if kind == 1 {
+            let (target, next) = self.pointer(control, pos)?;
+            let (value, _) = self.value(target, depth + 1)?;
+            return Ok((value, next));
+        }
+        if kind == 0 {
+            kind = self
+                .byte(pos)?
+                .checked_add(7)
+                .ok_or("unsupported extended data type")?;
+            pos += 1;
+        }
+        let (size, pos) = self.size(control & 0x1f, pos)?;
+        let end = pos.checked_add(size).ok_or("data size overflows")?;
+        // Every entry takes at least a byte, so no more can follow
+        let capacity = size.min(self.bytes.len().saturating_sub(pos));
+        match kind {
+            2 => Ok((
+                Value::String(String::from_utf8_lossy(self.slice(pos, end)?).into_owned()),
+                end,
+            )),
+            3 => {
+                let raw: [u8; 8] = self
+                    .slice(pos, end)?
+                    .try_into()
+                    .map_err(|_| "bad double size")?;
+                Ok((Value::Double(f64::from_be_bytes(raw)), end))
+            }
+            4 => Ok((Value::Bytes(self.slice(pos, end)?.to_vec()), end)),
+            5 | 6 | 9 | 10 => {
+                let raw = self.slice(pos, end)?;
+                // uint128 values beyond 64 bits keep their low half
+                let n = raw.iter().fold(0u64, |n, &b| n << 8 | b as u64);
+                Ok((Value::Uint(n), end))
+            }
+            7 => {
+                let mut entries = Vec::with_capacity(capacity);
+                let mut pos = pos;
+                for _ in 0..size {
+                    let (key, next) = self.value(pos, depth + 1)?;
+                    let (value, next) = self.value(next, depth + 1)?;
+                    let key = key.as_str().ok_or("map key is not a string")?.to_string();
+                    entries.push((key, value));
+                    pos = next;
+                }
+                Ok((Value::Map(entries), pos))
+            }
+            8 => {
+                let raw = self.slice(pos, end)?;
+                let n = raw.iter().fold(0u32, |n, &b| n << 8 | b as u32);
+                Ok((Value::Int(n as i32), end))
+            }
+            11 => {
+                let mut items = Vec::with_capacity(capacity);
+                let mut pos = pos;
+                for _ in 0..size {
+                    let (item, next) = self.value(pos, depth + 1)?;
+                    items.push(item);
+                    pos = next;
+                }
+                Ok((Value::Array(items), pos))
+            }
+            // The size is the value
+            14 => Ok((Value::Bool(size != 0), pos)),
+            15 => {
+                let raw: [u8; 4] = self
+                    .slice(pos, end)?
+                    .try_into()
+                    .map_err(|_| "bad float size")?;
+                Ok((Value::Double(f32::from_be_bytes(raw) as f64), end))
+            }
+            other => Err(format!("unsupported data type {}", other)),
+        }
+    }
+
+    fn pointer(&self, control: u8, pos: usize) -> std::result::Result<(usize, usize), String> {
+        let high = (control & 0x07) as usize;
+        let len = ((control >> 3) & 0x03) as usize + 1;
+        let raw = self.slice(pos, pos + len)?;
+        let be = raw.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+        // At most 35 bits, so none of this overflows
+        let target = match len {
+            1 => high << 8 | be,
+            2 => (high << 16 | be) + 2048,
+            3 => (high << 24 | be) + 526_336,
+            _ => be,
+        };
+        Ok((target, pos + len))
+    }
+
+    fn size(&self, low: u8, pos: usize) -> std::result::Result<(usize, usize), String> {
+        let extra = |len: usize| -> std::result::Result<usize, String> {
+            Ok(self
+                .slice(pos, pos + len)?
+                .iter()
+                .fold(0usize, |n, &b| n << 8 | b as usize))
+        };
+        Ok(match low {
+            29 => (29 + extra(1)?, pos + 1),
+            30 => (285 + extra(2)?, pos + 2),
+            31 => (65_821 + extra(3)?, pos + 3),
+            n => (n as usize, pos),
+        })
+    }
+
+    fn byte(&self, pos: usize) -> std::result::Result<&u8, String> {
+        self.bytes
+            .get(pos)
+            .ok_or_else(|| "data runs past the end".to_string())
+    }
+
+    fn slice(&self, start: usize, end: usize) -> std::result::Result<&[u8], String> {
+        self.bytes
+            .get(start..end)
+            .ok_or_else(|| "data runs past the end".to_string())
+    }
+}
+
+/// Peers grouped by country, most peers first. Each peer is given as its
+/// country code (None when unknown) and download and upload speeds.
+pub fn aggregate(peers: &[(Option<String>, u64, u64)]) -> SwarmGeography {
+    let mut by_country: HashMap<&str, CountryPeers> = HashMap::new();
+    let mut unknown = 0;
+    for (country, download_speed, upload_speed) in peers {
+        let Some(country) = country else {
+            unknown += 1;
+            continue;
+        };
+        let entry = by_country.entry(country).or_insert_with(|| CountryPeers {
+            country: country.clone(),
+            ..Default::default()
+        });
+        entry.peers += 1;
+        entry.download_speed += download_speed;
+        entry.upload_speed += upload_speed;
+    }
+    let mut countries: Vec<CountryPeers> = by_country.into_values().collect();
+    countries.sort_by(|a, b| {
+        b.peers
+            .cmp(&a.peers)
+            .then_with(|| a.country.cmp(&b.country))
+    });
+    SwarmGeography { countries, unknown }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a map of string keys (control byte type 7) for test data.
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![0xe0 | entries.len() as u8];
+        for (key, value) in entries {
+            out.extend(string(key));
+            out.extend(value);
+        }
+        out
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = vec![0x40 | s.len() as u8];
+        out.extend(s.as_bytes());
+        out
+    }
+
+    fn uint16(n: u16) -> Vec<u8> {
+        vec![0xa2, (n >> 8) as u8, n as u8]
+    }
+
+    fn country(code: &str) -> Vec<u8> {
+        map(&[("country", map(&[("iso_code", string(code))]))])
+    }
+
+    /// An IPv6 database with 24-bit records: ::/96 leads to node 96, where
+    /// 0.0.0.0/1 is "US", 128.0.0.0/2 is "DE" and the rest unknown. The DE
+    /// record is reached through a pointer to the country map.
+    fn database() -> Vec<u8> {
+        let node_count = 98usize;
+        let us = country("US");
+        let de_offset = us.len();
+        let mut data = us;
+        data.extend(country("DE"));
+        let pointer_offset = data.len();
+        // Pointer with a one-byte offset
+        data.extend([0x20, de_offset as u8]);
+
+        let data_record = |offset: usize| node_count + DATA_SEPARATOR_LEN + offset;
+        let mut tree = Vec::new();
+        let mut node = |left: usize, right: usize| {
+            for n in [left, right] {
+                tree.extend(&(n as u32).to_be_bytes()[1..]);
+            }
+        };
+        for i in 0..96 {
+            node(i + 1, node_count);
+        }
+        node(data_record(0), 97);
+        node(data_record(pointer_offset), node_count);
+
+        let mut bytes = tree;
+        bytes.extend([0u8; DATA_SEPARATOR_LEN]);
+        bytes.extend(data);
+        bytes.extend(METADATA_MARKER);
+        bytes.extend(map(&[
+            ("node_count", uint16(node_count as u16)),
+            ("record_size", uint16(24)),
+            ("ip_version", uint16(6)),
+        ]));
+        bytes
+    }
+
+    #[test]
+    fn test_country_lookup() {
+        let db = GeoIpDb::from_bytes(database()).unwrap();
+        assert_eq!(
+            db.country("8.8.8.8".parse().unwrap()).as_deref(),
+            Some("US")
+        );
+        assert_eq!(
+            db.country("130.1.2.3".parse().unwrap()).as_deref(),
+            Some("DE")
+        );
+        assert_eq!(db.country("200.1.2.3".parse().unwrap()), None);
+        assert_eq!(db.country("2001:db8::1".parse().unwrap()), None);
+        assert!(GeoIpDb::from_bytes(b"not a database".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_hostile_database() {
+        // A node count whose tree size overflows
+        let mut bytes = METADATA_MARKER.to_vec();
+        bytes.extend(map(&[
+            (
+                "node_count",
+                vec![0xa8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            ("record_size", uint16(32)),
+            ("ip_version", uint16(4)),
+        ]));
+        assert!(GeoIpDb::from_bytes(bytes).is_err());
+
+        // A map claiming 65 821 entries in a few bytes
+        assert!(Decoder::new(&[0xff, 0x00, 0x00, 0x00]).decode(0).is_err());
+        // An extended type byte that would overflow
+        assert!(Decoder::new(&[0x00, 0xff]).decode(0).is_err());
+        // Six maps, each pointing eight times to the one before: 8^6 values
+        let mut nested = string("x");
+        let mut previous = 0;
+        for _ in 0..6 {
+            let offset = nested.len();
+            nested.push(0xe8);
+            for key in ["a", "b", "c", "d", "e", "f", "g", "h"] {
+                nested.extend(string(key));
+                nested.extend([0x20, previous as u8]);
+            }
+            previous = offset;
+        }
+        assert_eq!(
+            Decoder::new(&nested).decode(previous).unwrap_err(),
+            "record decodes to too many values"
+        );
+    }
+
+    #[test]
+    fn test_record_sizes() {
+        let db = |record_size, bytes: Vec<u8>| GeoIpDb {
+            bytes,
+            node_count: 1,
+            record_size,
+            ip_version: 4,
+            data_start: 0,
+        };
+        let b28 = db(28, vec![0x12, 0x34, 0x56, 0xab, 0x65, 0x43, 0x21]);
+        assert_eq!(b28.record(0, false), Some(0x0a12_3456));
+        assert_eq!(b28.record(0, true), Some(0x0b65_4321));
+        let b32 = db(32, vec![0, 0, 1, 0, 0, 0, 2, 0]);
+        assert_eq!(
+            (b32.record(0, false), b32.record(0, true)),
+            (Some(256), Some(512))
+        );
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let geography = aggregate(&[
+            (Some("DE".into()), 10, 1),
+            (Some("US".into()), 5, 0),
+            (None, 1, 1),
+            (Some("DE".into()), 20, 2),
+        ]);
+        assert_eq!(geography.unknown, 1);
+        let summary: Vec<(&str, u32, u64)> = geography
+            .countries
+            .iter()
+            .map(|c| (c.country.as_str(), c.peers, c.download_speed))
+            .collect();
+        assert_eq!(summary, vec![("DE", 2, 30), ("US", 1, 5)]);
+    }
+}
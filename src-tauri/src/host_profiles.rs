@@ -0,0 +1,199 @@
+//! Learns how many connections each host is worth. While an HTTP download
+//! runs, its speed is averaged along with the most connections the engine
+//! opened for it; a finished download adds that measurement to the host's
+//! profile in the database. New downloads from a profiled host get the
+//! fewest connections that came close to the best measured speed, so more
+//! connections are only used where they made a difference. Speeds
+//! reported while a global speed limit, battery saver or the load guard
+//! holds downloads back are left out, since they measure the app's own
+//! throttle rather than the host.
+
+use crate::types::{ConnectionProfile, HostProfile};
+use std::collections::HashMap;
+
+/// Progress reports a download needs before its speed counts; shorter
+/// downloads are mostly connection setup.
+const MIN_REPORTS: u32 = 10;
+/// Downloads measured at a connection count before it is trusted.
+const MIN_SAMPLES: u32 = 2;
+/// A lower connection count is preferred if it reaches this share of the
+/// best speed, in percent.
+const CLOSE_ENOUGH_PERCENT: u64 = 90;
+
+#[derive(Debug, Default)]
+struct Measurement {
+    host: String,
+    connections: u32,
+    speed_sum: u64,
+    reports: u32,
+}
+
+/// Running HTTP downloads being measured, by GID.
+#[derive(Debug, Default)]
+pub struct ThroughputTracker {
+    /// None for downloads that aren't measured (torrents, no host)
+    running: HashMap<String, Option<Measurement>>,
+}
+
+impl ThroughputTracker {
+    pub fn is_tracked(&self, gid: &str) -> bool {
+        self.running.contains_key(gid)
+    }
+
+    /// Start (or decline) measuring a download seen for the first time.
+    pub fn track(&mut self, gid: &str, host: Option<String>) {
+        let measurement = host.map(|host| Measurement {
+            host,
+            ..Default::default()
+        });
+        self.running.insert(gid.to_string(), measurement);
+    }
+
+    /// Record a progress report. Reports without transfer (queued,
+    /// connecting, paused) are skipped, and so are those made while the app
+    /// holds speed down (`throttled`), which say nothing about the host.
+    pub fn observe(&mut self, gid: &str, speed: u64, connections: u32, throttled: bool) {
+        let Some(Some(measurement)) = self.running.get_mut(gid) else {
+            return;
+        };
+        if speed == 0 || connections == 0 || throttled {
+            return;
+        }
+        measurement.connections = measurement.connections.max(connections);
+        measurement.speed_sum += speed;
+        measurement.reports += 1;
+    }
+
+    /// Stop measuring a finished download. Returns `(host, connections,
+    /// average speed)` if it ran long enough to tell.
+    pub fn finish(&mut self, gid: &str) -> Option<(String, u32, u64)> {
+        let measurement = self.running.remove(gid)??;
+        (measurement.reports >= MIN_REPORTS).then(|| {
+            let speed = measurement.speed_sum / measurement.reports as u64;
+            (measurement.host, measurement.connections, speed)
+        })
+    }
+
+    pub fn forget(&mut self, gid: &str) {
+        self.running.remove(gid);
+    }
+}
+
+/// Connections the next download from a host should use, at most `max`.
+/// Among counts measured often enough, the fewest that reach
+/// `CLOSE_ENOUGH_PERCENT` of the best speed win; if that is the lowest one
+/// measured, half of it is tried next. None without any trusted
+/// measurement, which leaves the configured default in place.
+pub fn suggest_connections(connections: &[ConnectionProfile], max: u32) -> Option<u32> {
+    let trusted: Vec<&ConnectionProfile> = connections
+        .iter()
+        .filter(|c| c.samples >= MIN_SAMPLES && c.connections <= max)
+        .collect();
+    let best = trusted.iter().map(|c| c.average_speed).max()?;
+    let choice = trusted
+        .iter()
+        .filter(|c| c.average_speed * 100 >= best * CLOSE_ENOUGH_PERCENT)
+        .map(|c| c.connections)
+        .min()?;
+    let fewer = choice / 2;
+    let lowest = trusted.iter().map(|c| c.connections).min() == Some(choice);
+    let untried = !trusted.iter().any(|c| c.connections == fewer);
+    if lowest && fewer >= 1 && untried {
+        return Some(fewer);
+    }
+    Some(choice)
+}
+
+/// Host a profile is kept under, lowercased.
+pub fn url_host(url: &str) -> Option<String> {
+    url::Url::parse(url).ok()?.host_str().map(str::to_lowercase)
+}
+
+/// Attach the suggestion to a profile read from the database.
+pub fn with_suggestion(mut profile: HostProfile, max: u32) -> HostProfile {
+    profile.suggested_connections = suggest_connections(&profile.connections, max);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured(connections: u32, samples: u32, average_speed: u64) -> ConnectionProfile {
+        ConnectionProfile {
+            connections,
+            samples,
+            average_speed,
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = ThroughputTracker::default();
+        tracker.track("a", Some("example.com".into()));
+        tracker.track("t", None);
+        assert!(tracker.is_tracked("t"));
+        for _ in 0..MIN_REPORTS {
+            tracker.observe("a", 100, 4, false);
+            tracker.observe("a", 0, 0, false);
+            tracker.observe("a", 10, 16, true);
+            tracker.observe("t", 100, 4, false);
+        }
+        tracker.observe("a", 400, 8, false);
+        let (host, connections, speed) = tracker.finish("a").unwrap();
+        assert_eq!((host.as_str(), connections), ("example.com", 8));
+        assert_eq!(
+            speed,
+            (100 * MIN_REPORTS as u64 + 400) / (MIN_REPORTS as u64 + 1)
+        );
+        assert_eq!(tracker.finish("t"), None);
+
+        tracker.track("short", Some("example.com".into()));
+        tracker.observe("short", 100, 2, false);
+        assert_eq!(tracker.finish("short"), None);
+        assert!(!tracker.is_tracked("short"));
+    }
+
+    #[test]
+    fn test_suggest_connections() {
+        assert_eq!(suggest_connections(&[], 8), None);
+        assert_eq!(suggest_connections(&[measured(8, 1, 100)], 8), None);
+        // Only the default measured: try half
+        assert_eq!(suggest_connections(&[measured(8, 2, 100)], 8), Some(4));
+        // Half was nearly as fast: keep halving
+        assert_eq!(
+            suggest_connections(&[measured(8, 2, 100), measured(4, 2, 95)], 8),
+            Some(2)
+        );
+        // Half was clearly slower: stay with more
+        assert_eq!(
+            suggest_connections(&[measured(8, 2, 100), measured(4, 2, 60)], 8),
+            Some(8)
+        );
+        assert_eq!(
+            suggest_connections(
+                &[measured(8, 3, 100), measured(4, 2, 60), measured(2, 2, 30)],
+                8
+            ),
+            Some(8)
+        );
+        // One connection is as low as it goes
+        assert_eq!(
+            suggest_connections(&[measured(2, 2, 100), measured(1, 2, 100)], 8),
+            Some(1)
+        );
+        // Counts above the limit are ignored
+        assert_eq!(
+            suggest_connections(
+                &[
+                    measured(16, 2, 500),
+                    measured(8, 2, 100),
+                    measured(4, 2, 50)
+                ],
+                8
+            ),
+            Some(8)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Live per-host view of running HTTP downloads, so the UI can say at a
+//! glance why a host is slow: how many connections its downloads hold
+//! against how many they may open, how fast they go together, how many of
+//! its recent downloads failed, and what is holding it back. Alongside
+//! the learned host profiles this is what per-host connection tuning is
+//! judged by.
+
+use crate::host_profiles::url_host;
+use crate::types::{Download, DownloadState, DownloadType, HostActivity, HostProfile};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How far back finished downloads count toward a host's error rate.
+pub const RECENT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Finished HTTP downloads per host within `RECENT_WINDOW`.
+#[derive(Debug, Default)]
+pub struct HostOutcomes {
+    /// When each finished and whether it failed, oldest first
+    outcomes: HashMap<String, VecDeque<(Instant, bool)>>,
+}
+
+impl HostOutcomes {
+    pub fn record(&mut self, host: String, failed: bool, now: Instant) {
+        self.prune(now);
+        self.outcomes
+            .entry(host)
+            .or_default()
+            .push_back((now, failed));
+    }
+
+    /// Downloads from `host` that finished within the window, and how
+    /// many of them failed.
+    pub fn recent(&self, host: &str, now: Instant) -> (u32, u32) {
+        let Some(outcomes) = self.outcomes.get(host) else {
+            return (0, 0);
+        };
+        outcomes
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= RECENT_WINDOW)
+            .fold((0, 0), |(finished, failed), (_, f)| {
+                (finished + 1, failed + u32::from(*f))
+            })
+    }
+
+    fn prune(&mut self, now: Instant) {
+        for outcomes in self.outcomes.values_mut() {
+            while outcomes
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > RECENT_WINDOW)
+            {
+                outcomes.pop_front();
+            }
+        }
+        self.outcomes.retain(|_, outcomes| !outcomes.is_empty());
+    }
+}
+
+/// What the engine is held to at the moment, for every host alike.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Connections each download may open
+    pub connections_per_download: u32,
+    /// A global download speed limit is in effect (set by hand, by a
+    /// schedule rule or by battery saver)
+    pub global_limit: bool,
+    pub battery_saver: bool,
+    pub load_guard: bool,
+}
+
+impl Limits {
+    /// Whether downloads are held below the speed they could reach.
+    pub fn slows_downloads(&self) -> bool {
+        self.global_limit || self.battery_saver || self.load_guard
+    }
+}
+
+/// Figures for each host with running HTTP downloads, fastest first.
+/// `profiles` should carry their suggestions.
+pub fn host_stats(
+    downloads: &[Download],
+    outcomes: &HostOutcomes,
+    limits: Limits,
+    profiles: &[HostProfile],
+    now: Instant,
+) -> Vec<HostActivity> {
+    let mut hosts: BTreeMap<String, HostActivity> = BTreeMap::new();
+    let running = downloads
+        .iter()
+        .filter(|d| d.download_type == DownloadType::Http && d.status == DownloadState::Active);
+    for download in running {
+        let Some(host) = download.url.as_deref().and_then(url_host) else {
+            continue;
+        };
+        let stats = hosts.entry(host.clone()).or_insert_with(|| HostActivity {
+            host,
+            downloads: 0,
+            connections: 0,
+            connection_limit: 0,
+            download_speed: 0,
+            recent_finished: 0,
+            recent_failed: 0,
+            error_rate: None,
+            throttled: Vec::new(),
+            suggested_connections: None,
+        });
+        let single = download.resumable == Some(false);
+        stats.downloads += 1;
+        stats.connections += download.connections;
+        stats.connection_limit += if single {
+            1
+        } else {
+            limits.connections_per_download
+        };
+        stats.download_speed += download.download_speed;
+        if single && !stats.throttled.iter().any(|r| r == "no-ranges") {
+            stats.throttled.push("no-ranges".to_string());
+        }
+    }
+
+    let mut hosts: Vec<HostActivity> = hosts.into_values().collect();
+    for stats in &mut hosts {
+        let (finished, failed) = outcomes.recent(&stats.host, now);
+        stats.recent_finished = finished;
+        stats.recent_failed = failed;
+        stats.error_rate = (finished > 0).then(|| f64::from(failed) / f64::from(finished));
+        if stats.connection_limit > 0 && stats.connections >= stats.connection_limit {
+            stats.throttled.push("connection-limit".to_string());
+        }
+        for (applies, reason) in [
+            (limits.global_limit, "global-limit"),
+            (limits.battery_saver, "battery-saver"),
+            (limits.load_guard, "load-guard"),
+        ] {
+            if applies {
+                stats.throttled.push(reason.to_string());
+            }
+        }
+        stats.suggested_connections = profiles
+            .iter()
+            .find(|p| p.host == stats.host)
+            .and_then(|p| p.suggested_connections);
+    }
+    hosts.sort_by_key(|stats| std::cmp::Reverse(stats.download_speed));
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running(url: &str, connections: u32, speed: u64, resumable: Option<bool>) -> Download {
+        Download {
+            gid: url.into(),
+            name: url.into(),
+            url: Some(url.into()),
+            status: DownloadState::Active,
+            download_speed: speed,
+            connections,
+            resumable,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recent_outcomes() {
+        let start = Instant::now();
+        let mut outcomes = HostOutcomes::default();
+        outcomes.record("a.example".into(), true, start);
+        outcomes.record("a.example".into(), false, start + Duration::from_secs(60));
+        assert_eq!(
+            outcomes.recent("a.example", start + Duration::from_secs(120)),
+            (2, 1)
+        );
+        // The failure has aged out
+        let later = start + RECENT_WINDOW + Duration::from_secs(30);
+        assert_eq!(outcomes.recent("a.example", later), (1, 0));
+        assert_eq!(outcomes.recent("b.example", later), (0, 0));
+    }
+
+    #[test]
+    fn test_host_stats() {
+        let now = Instant::now();
+        let mut outcomes = HostOutcomes::default();
+        outcomes.record("a.example".into(), true, now);
+        outcomes.record("a.example".into(), false, now);
+        let downloads = vec![
+            running("https://a.example/1.iso", 8, 100, Some(true)),
+            running("https://A.example/2.iso", 8, 200, None),
+            running("https://b.example/3.iso", 1, 500, Some(false)),
+        ];
+        let limits = Limits {
+            connections_per_download: 8,
+            load_guard: true,
+            ..Default::default()
+        };
+        let profiles = vec![HostProfile {
+            host: "a.example".into(),
+            connections: Vec::new(),
+            suggested_connections: Some(4),
+            broken_ranges: false,
+        }];
+        let hosts = host_stats(&downloads, &outcomes, limits, &profiles, now);
+
+        assert_eq!(hosts.len(), 2);
+        let (b, a) = (&hosts[0], &hosts[1]);
+        assert_eq!(
+            (b.host.as_str(), b.connections, b.connection_limit),
+            ("b.example", 1, 1)
+        );
+        assert_eq!(
+            b.throttled,
+            vec!["no-ranges", "connection-limit", "load-guard"]
+        );
+        assert_eq!(b.error_rate, None);
+        assert_eq!(
+            (
+                a.downloads,
+                a.connections,
+                a.connection_limit,
+                a.download_speed
+            ),
+            (2, 16, 16, 300)
+        );
+        assert_eq!(a.error_rate, Some(0.5));
+        assert_eq!(a.throttled, vec!["connection-limit", "load-guard"]);
+        assert_eq!(a.suggested_connections, Some(4));
+    }
+}
@@ -1,15 +1,52 @@
+pub mod add_plan;
 pub mod api;
+pub mod archive;
+pub mod capabilities;
+pub mod categories;
+pub mod changes;
 pub mod commands;
 pub mod constants;
 pub mod db;
 pub mod engine_adapter;
 pub mod error;
 pub mod events;
+pub mod fd_budget;
+pub mod file_filter;
+pub mod geoip;
+pub mod host_profiles;
+pub mod host_stats;
+pub mod load_guard;
+pub mod locations;
+pub mod metalink;
+pub mod naming;
+pub mod orphans;
+pub mod paths;
+pub mod preview;
+pub mod progress_delta;
+pub mod ranges;
+pub mod recovery;
+pub mod rename_rules;
+pub mod reveal;
+pub mod rotation;
+pub mod s3;
+pub mod seeding;
+pub mod settings_check;
+pub mod size_guard;
+pub mod staging;
 pub mod state;
+pub mod subscriptions;
+pub mod tasks;
+pub mod templates;
+pub mod torrent_edit;
+pub mod traffic;
 pub mod tray;
 pub mod types;
+pub mod updates;
+pub mod url_list;
 pub mod utils;
 pub mod validation;
+pub mod verify;
+pub mod vpn;
 
 pub use error::{Error, Result};
 pub use state::AppState;
@@ -34,6 +71,24 @@ fn open_requests_from_args<I: IntoIterator<Item = String>>(args: I) -> Vec<OpenR
         .collect()
 }
 
+/// Logs go to stdout and the platform log directory, or to `data/logs`
+/// beside the executable in portable mode.
+fn log_plugin<R: tauri::Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    use tauri_plugin_log::{Target, TargetKind};
+    let builder = tauri_plugin_log::Builder::new();
+    match paths::portable_log_dir() {
+        Some(path) => builder
+            .clear_targets()
+            .target(Target::new(TargetKind::Stdout))
+            .target(Target::new(TargetKind::Folder {
+                path,
+                file_name: None,
+            }))
+            .build(),
+        None => builder.build(),
+    }
+}
+
 fn deliver_requests(app: &AppHandle, requests: Vec<OpenRequest>) {
     let state = app.state::<AppState>();
     for request in requests {
@@ -51,6 +106,7 @@ fn show_main_window(app: &AppHandle) {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    paths::init(std::env::args());
     let mut builder = tauri::Builder::default();
 
     // Single instance must be the first plugin registered
@@ -63,7 +119,7 @@ pub fn run() {
     }
 
     builder = builder
-        .plugin(tauri_plugin_log::Builder::new().build())
+        .plugin(log_plugin())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_opener::init());
@@ -89,16 +145,13 @@ pub fn run() {
             // Initialize database + download engine in the background;
             // commands return EngineNotInitialized until this completes.
             let state = app.state::<AppState>().inner().clone();
-            let data_dir = app
-                .path()
-                .app_data_dir()
-                .unwrap_or_else(|_| {
-                    // Fallback reproducing the legacy sidecar's resolution
-                    dirs::data_dir()
-                        .expect("Could not determine platform data directory")
-                        .join("com.gosh.fetch")
-                });
-            log::info!("App data dir: {} (exists: {})", data_dir.display(), data_dir.exists());
+            let data_dir = paths::data_dir(&handle);
+            log::info!(
+                "App data dir: {} (exists: {}, portable: {})",
+                data_dir.display(),
+                data_dir.exists(),
+                paths::is_portable()
+            );
             let init_handle = handle.clone();
             tauri::async_runtime::spawn(async move {
                 match state.initialize(data_dir, init_handle.clone()).await {
@@ -110,6 +163,7 @@ pub fn run() {
                     }
                     Err(e) => {
                         log::error!("Engine initialization failed: {}", e);
+                        state.record_engine_error(e.to_string());
                         let _ = init_handle.emit(
                             "engine-status",
                             serde_json::json!({ "connected": false, "restarting": false }),
@@ -120,6 +174,18 @@ pub fn run() {
 
             tray::create_tray(&handle)?;
             events::spawn_stats_emitter(handle.clone());
+            events::spawn_seed_stats(handle.clone());
+            events::spawn_download_writer(handle.clone());
+            events::spawn_seed_auto_stop(handle.clone());
+            events::spawn_network_monitor(handle.clone());
+            events::spawn_vpn_guard(handle.clone());
+            events::spawn_power_watcher(handle.clone());
+            events::spawn_load_guard(handle.clone());
+            events::spawn_db_maintenance(handle.clone());
+            events::spawn_history_retention(handle.clone());
+            events::spawn_archive(handle.clone());
+            events::spawn_stats_report(handle.clone());
+            events::spawn_update_check(handle.clone());
 
             // magnet: deep links (registered while the app runs)
             #[cfg(desktop)]
@@ -168,6 +234,9 @@ pub fn run() {
             // Downloads
             api::add_download,
             api::add_urls,
+            api::add_metalink_file,
+            api::import_url_list,
+            api::plan_download,
             api::pause_download,
             api::pause_all,
             api::resume_download,
@@ -175,21 +244,29 @@ pub fn run() {
             api::cancel_all,
             api::remove_download,
             api::get_download_status,
+            api::get_download_details,
+            api::verify_download,
             api::get_all_downloads,
             api::get_active_downloads,
             api::get_global_stats,
+            api::get_host_stats,
+            api::preview_download,
             api::set_speed_limit,
             api::set_priority,
             api::get_schedule_rules,
             api::set_schedule_rules,
             // Torrents
             api::add_torrent_file,
+            api::plan_torrent_file,
+            api::add_torrent_url,
             api::add_magnet,
             api::get_torrent_files,
             api::select_torrent_files,
+            api::apply_file_filter,
             api::parse_torrent_file,
             api::parse_magnet_uri,
             api::get_peers,
+            api::get_swarm_geography,
             // Recursive mirroring
             api::discover_recursive,
             api::add_recursive,
@@ -200,31 +277,72 @@ pub fn run() {
             // Settings
             api::get_settings,
             api::update_settings,
+            api::validate_settings,
             api::set_close_to_tray,
             api::set_user_agent,
             api::get_tracker_list,
             api::update_tracker_list,
             api::apply_settings_to_engine,
             api::get_user_agent_presets,
+            api::get_categories,
+            api::set_categories,
+            api::reset_categories,
+            api::list_download_locations,
+            api::set_download_locations,
+            api::get_download_templates,
+            api::save_download_template,
+            api::delete_download_template,
+            api::get_rename_rules,
+            api::set_rename_rules,
+            api::preview_rename,
             // System
             api::get_engine_version,
+            api::get_engine_health,
+            api::restart_engine,
+            api::list_tasks,
+            api::cancel_task,
+            api::subscribe_download_events,
+            api::unsubscribe_download_events,
+            api::get_load_guard_status,
+            api::get_ui_snapshot,
+            api::get_changes,
+            api::get_progress_snapshots,
+            api::set_ui_filters,
             api::open_download_folder,
             api::open_file_location,
+            api::open_download_file,
+            api::reveal_download,
+            api::get_download_path,
             api::get_default_download_path,
             api::get_app_version,
             api::get_app_info,
+            api::get_capabilities,
+            api::get_update_info,
             api::get_disk_space,
             api::perform_system_action,
             api::read_settings_json,
             api::get_pending_open_requests,
+            api::get_crash_recovery_info,
+            api::restore_session,
+            api::restore_incomplete,
+            api::list_orphaned_partials,
+            api::resume_orphaned_partial,
+            api::delete_orphaned_partials,
             // Database
             api::db_get_completed_history,
             api::db_save_download,
             api::db_remove_download,
+            api::db_set_favorite,
             api::db_clear_history,
+            api::archive_downloads,
+            api::db_get_history_stats,
+            api::get_host_profiles,
             api::db_get_settings,
             api::db_save_settings,
             api::db_load_incomplete,
+            api::db_export_history,
+            api::export_stats,
+            api::db_maintenance,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
@@ -259,11 +377,21 @@ pub fn run() {
                 }
             }
             tauri::RunEvent::Exit => {
-                // Persist final history snapshot and stop the engine cleanly
+                // Checkpoint downloads and stop the engine cleanly, but don't
+                // let a stuck engine or tracker hold the process open
                 let state = app.state::<AppState>().inner().clone();
                 tauri::async_runtime::block_on(async move {
-                    if let Err(e) = state.shutdown().await {
-                        log::error!("Failed to shut down app state cleanly: {}", e);
+                    let limit = state.shutdown_timeout().await;
+                    match tokio::time::timeout(limit, state.shutdown()).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => log::error!("Failed to shut down app state cleanly: {}", e),
+                        Err(_) => {
+                            log::error!(
+                                "Shutdown took longer than {}s, exiting anyway",
+                                limit.as_secs()
+                            );
+                            std::process::exit(1);
+                        }
                     }
                 });
             }
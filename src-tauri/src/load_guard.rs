@@ -0,0 +1,328 @@
+//! System load guard. While the machine is busy with something else (a
+//! game, a build), downloads shouldn't compete for CPU and disk. The load
+//! is sampled every few seconds; once CPU usage or the disk queue stays
+//! above its threshold for a minute, connections per download are cut and
+//! low-priority downloads can be paused, until the load has stayed below
+//! the thresholds for a minute too.
+//!
+//! The app's own CPU time (hashing, disk writes, TLS) is taken out of the
+//! busy time, so a fast download doesn't trip the guard by itself and the
+//! guard doesn't keep itself on once it has slowed downloads down. The
+//! disk queue can't be told apart per process, so it still counts the
+//! app's own writes; its threshold is meant to sit above what downloads
+//! alone cause.
+
+use crate::db::Settings;
+use gosh_dl::EngineConfig;
+
+/// Samples in a row that must agree before the guard switches, so a short
+/// spike doesn't flip it.
+pub const SUSTAINED_SAMPLES: u32 = 6;
+
+/// Cumulative CPU time since boot, in the OS's units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub busy: u64,
+    pub total: u64,
+    /// Spent by this process, part of `busy`
+    pub own: u64,
+}
+
+/// One reading of the system load; None where the OS doesn't tell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadSample {
+    pub cpu_percent: Option<u32>,
+    /// I/O requests in flight on the system's disks
+    pub disk_queue: Option<u64>,
+}
+
+impl LoadSample {
+    /// Whether either reading is above its threshold; a zero threshold
+    /// ignores that reading.
+    pub fn is_over(&self, settings: &Settings) -> bool {
+        let cpu = settings.load_guard_cpu_percent;
+        let disk = settings.load_guard_disk_queue;
+        (cpu > 0 && self.cpu_percent.is_some_and(|p| p >= cpu))
+            || (disk > 0 && self.disk_queue.is_some_and(|q| q >= disk as u64))
+    }
+}
+
+/// Share of CPU time spent busy on other processes between two readings.
+pub fn cpu_percent(previous: CpuTimes, now: CpuTimes) -> Option<u32> {
+    let total = now.total.checked_sub(previous.total)?;
+    let busy = now.busy.checked_sub(previous.busy)?;
+    // The two are read at slightly different moments
+    let own = now.own.saturating_sub(previous.own).min(busy);
+    (total > 0).then(|| ((busy - own) * 100 / total).min(100) as u32)
+}
+
+/// Decides when the guard switches.
+#[derive(Debug, Default)]
+pub struct LoadGuard {
+    active: bool,
+    /// Samples in a row that disagree with the current state
+    streak: u32,
+}
+
+impl LoadGuard {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Record whether the load is over the thresholds. Returns the new
+    /// state when it switches.
+    pub fn observe(&mut self, over: bool) -> Option<bool> {
+        if over == self.active {
+            self.streak = 0;
+            return None;
+        }
+        self.streak += 1;
+        if self.streak < SUSTAINED_SAMPLES {
+            return None;
+        }
+        self.streak = 0;
+        self.active = over;
+        Some(over)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Cut connections per download to the load guard's limit. Applied on top
+/// of the normal settings, like the battery limits.
+pub fn apply_load_limits(config: &mut EngineConfig, settings: &Settings) {
+    config.max_connections_per_download = config
+        .max_connections_per_download
+        .min(settings.load_guard_max_connections.max(1) as usize);
+}
+
+#[cfg(target_os = "linux")]
+pub fn cpu_times() -> Option<CpuTimes> {
+    let times = parse_proc_stat(&std::fs::read_to_string("/proc/stat").ok()?)?;
+    // Both in clock ticks
+    let own = std::fs::read_to_string("/proc/self/stat")
+        .ok()
+        .and_then(|stat| parse_own_stat(&stat))
+        .unwrap_or(0);
+    Some(CpuTimes { own, ..times })
+}
+
+#[cfg(target_os = "windows")]
+pub fn cpu_times() -> Option<CpuTimes> {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, GetProcessTimes, GetSystemTimes,
+    };
+
+    // SAFETY: GetSystemTimes only writes into the structs we pass
+    let mut idle: FILETIME = unsafe { std::mem::zeroed() };
+    let mut kernel: FILETIME = unsafe { std::mem::zeroed() };
+    let mut user: FILETIME = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemTimes(&mut idle, &mut kernel, &mut user) } == 0 {
+        return None;
+    }
+    let ticks = |t: FILETIME| (t.dwHighDateTime as u64) << 32 | t.dwLowDateTime as u64;
+    // Kernel time includes idle time
+    let total = ticks(kernel) + ticks(user);
+
+    // SAFETY: as above; the pseudo handle needs no closing
+    let mut created: FILETIME = unsafe { std::mem::zeroed() };
+    let mut exited: FILETIME = unsafe { std::mem::zeroed() };
+    let mut own_kernel: FILETIME = unsafe { std::mem::zeroed() };
+    let mut own_user: FILETIME = unsafe { std::mem::zeroed() };
+    let own = match unsafe {
+        GetProcessTimes(
+            GetCurrentProcess(),
+            &mut created,
+            &mut exited,
+            &mut own_kernel,
+            &mut own_user,
+        )
+    } {
+        0 => 0,
+        _ => ticks(own_kernel) + ticks(own_user),
+    };
+    Some(CpuTimes {
+        busy: total - ticks(idle),
+        total,
+        own,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn cpu_times() -> Option<CpuTimes> {
+    None
+}
+
+/// I/O requests in flight on whole disks (partitions would count twice).
+#[cfg(target_os = "linux")]
+pub fn disk_queue() -> Option<u64> {
+    let stats = std::fs::read_to_string("/proc/diskstats").ok()?;
+    parse_diskstats(&stats, |name| {
+        !name.starts_with("loop")
+            && !name.starts_with("ram")
+            && std::path::Path::new("/sys/block").join(name).exists()
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn disk_queue() -> Option<u64> {
+    None
+}
+
+/// Busy and total time from the aggregate `cpu` line of `/proc/stat`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_stat(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|f| f.parse().ok())
+        .collect();
+    // user nice system idle iowait irq softirq steal; guest time is
+    // already counted in user
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields.get(3)? + fields.get(4).unwrap_or(&0);
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+        own: 0,
+    })
+}
+
+/// User plus system time from `/proc/self/stat`. The fields are counted
+/// after the command name, which may itself hold spaces and parentheses.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_own_stat(stat: &str) -> Option<u64> {
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    // utime and stime are fields 14 and 15; the state (field 3) comes first
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Sum of the "I/Os currently in progress" column over disks `is_disk`
+/// accepts.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_diskstats(stats: &str, is_disk: impl Fn(&str) -> bool) -> Option<u64> {
+    let mut any = false;
+    let mut in_flight = 0;
+    for line in stats.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(name), Some(count)) = (fields.get(2), fields.get(11)) else {
+            continue;
+        };
+        if !is_disk(name) {
+            continue;
+        }
+        any = true;
+        in_flight += count.parse::<u64>().unwrap_or(0);
+    }
+    any.then_some(in_flight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_needs_sustained_load() {
+        let mut guard = LoadGuard::default();
+        for _ in 1..SUSTAINED_SAMPLES {
+            assert_eq!(guard.observe(true), None);
+        }
+        // A quiet sample restarts the count
+        assert_eq!(guard.observe(false), None);
+        for _ in 1..SUSTAINED_SAMPLES {
+            assert_eq!(guard.observe(true), None);
+        }
+        assert_eq!(guard.observe(true), Some(true));
+        assert!(guard.is_active());
+        assert_eq!(guard.observe(true), None);
+
+        for _ in 1..SUSTAINED_SAMPLES {
+            assert_eq!(guard.observe(false), None);
+        }
+        assert_eq!(guard.observe(false), Some(false));
+        assert!(!guard.is_active());
+    }
+
+    #[test]
+    fn test_is_over() {
+        let settings = Settings {
+            load_guard_cpu_percent: 90,
+            load_guard_disk_queue: 16,
+            ..Default::default()
+        };
+        let sample = |cpu, disk| LoadSample {
+            cpu_percent: cpu,
+            disk_queue: disk,
+        };
+        assert!(sample(Some(95), None).is_over(&settings));
+        assert!(sample(Some(10), Some(20)).is_over(&settings));
+        assert!(!sample(Some(89), Some(15)).is_over(&settings));
+        assert!(!sample(None, None).is_over(&settings));
+
+        let cpu_only = Settings {
+            load_guard_disk_queue: 0,
+            ..settings
+        };
+        assert!(!sample(Some(10), Some(100)).is_over(&cpu_only));
+    }
+
+    #[test]
+    fn test_cpu_percent() {
+        let stat = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 50 0 25 400 25 0 0 0 0 0\n";
+        let first = parse_proc_stat(stat).unwrap();
+        assert_eq!(
+            first,
+            CpuTimes {
+                busy: 150,
+                total: 1000,
+                own: 0
+            }
+        );
+        let second = CpuTimes {
+            busy: 240,
+            total: 1100,
+            own: 0,
+        };
+        assert_eq!(cpu_percent(first, second), Some(90));
+        assert_eq!(cpu_percent(first, first), None);
+
+        // Busy with downloads only
+        let own = CpuTimes {
+            busy: 240,
+            total: 1100,
+            own: 80,
+        };
+        assert_eq!(cpu_percent(first, own), Some(10));
+        let own = CpuTimes {
+            busy: 160,
+            total: 1100,
+            own: 500,
+        };
+        assert_eq!(cpu_percent(first, own), Some(0));
+
+        let self_stat =
+            "4242 (gosh (fetch) x) S 1 4242 4242 0 -1 4194304 900 0 0 0 120 35 0 0 20 0 12 0 \
+                         8000 123456 789 18446744073709551615";
+        assert_eq!(parse_own_stat(self_stat), Some(155));
+        assert_eq!(parse_own_stat("4242 (truncated"), None);
+    }
+
+    #[test]
+    fn test_parse_diskstats() {
+        let stats = "   8       0 sda 100 0 0 0 0 0 0 0 3 0 0\n\
+                     8       1 sda1 100 0 0 0 0 0 0 0 3 0 0\n\
+                     259       0 nvme0n1 5 0 0 0 0 0 0 0 4 0 0\n";
+        let whole = |name: &str| name == "sda" || name == "nvme0n1";
+        assert_eq!(parse_diskstats(stats, whole), Some(7));
+        assert_eq!(parse_diskstats(stats, |_| false), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! Named download locations. Besides the default download directory the
+//! user can define several places to save into (another drive, a NAS
+//! mount); the add dialog lists them with the free space on their drive,
+//! and the location last picked for a category is used again for it.
+
+use crate::types::{DownloadLocation, DownloadLocationInfo};
+use crate::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Name under which the default download directory is offered.
+pub const DEFAULT_LOCATION: &str = "Default";
+
+/// Check a location list from the frontend: trimmed, unique names that
+/// don't shadow the default, and absolute paths (`~` allowed).
+pub fn normalize_locations(locations: Vec<DownloadLocation>) -> Result<Vec<DownloadLocation>> {
+    let mut names = HashSet::new();
+    locations
+        .into_iter()
+        .map(|location| {
+            let name = location.name.trim().to_string();
+            if name.is_empty() {
+                return Err(Error::InvalidInput("Location name cannot be empty".into()));
+            }
+            if name.eq_ignore_ascii_case(DEFAULT_LOCATION) {
+                return Err(Error::InvalidInput(format!(
+                    "'{}' is reserved for the default download directory",
+                    DEFAULT_LOCATION
+                )));
+            }
+            if !names.insert(name.to_lowercase()) {
+                return Err(Error::InvalidInput(format!("Duplicate location: {}", name)));
+            }
+            let path = location.path.trim().to_string();
+            if !PathBuf::from(crate::db::expand_tilde(&path)).is_absolute() {
+                return Err(Error::InvalidInput(format!(
+                    "Location '{}' needs an absolute path",
+                    name
+                )));
+            }
+            Ok(DownloadLocation { name, path })
+        })
+        .collect()
+}
+
+/// Path of the location named `name` (ignoring case), the default one included.
+pub fn find_path(
+    default_path: &str,
+    locations: &[DownloadLocation],
+    name: &str,
+) -> Option<PathBuf> {
+    let name = name.trim();
+    let path = if name.eq_ignore_ascii_case(DEFAULT_LOCATION) {
+        default_path
+    } else {
+        &locations
+            .iter()
+            .find(|l| l.name.eq_ignore_ascii_case(name))?
+            .path
+    };
+    Some(PathBuf::from(crate::db::expand_tilde(path)))
+}
+
+/// Make sure `path` can be saved into, creating it if needed.
+pub fn prepare_directory(path: &Path) -> Result<()> {
+    let expanded = PathBuf::from(crate::db::expand_tilde(&path.to_string_lossy()));
+    if !expanded.is_absolute() {
+        return Err(Error::InvalidInput(format!(
+            "Download directory must be absolute: {}",
+            path.display()
+        )));
+    }
+    if expanded.exists() && !expanded.is_dir() {
+        return Err(Error::InvalidInput(format!(
+            "Download directory is a file: {}",
+            expanded.display()
+        )));
+    }
+    std::fs::create_dir_all(&expanded)?;
+    Ok(())
+}
+
+/// Free and total bytes on the drive holding `path`. A directory that
+/// doesn't exist yet is measured at its nearest existing parent.
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    Some((
+        fs4::available_space(existing).ok()?,
+        fs4::total_space(existing).ok()?,
+    ))
+}
+
+/// The default directory followed by the named locations, with drive space
+/// and the categories (`""` for uncategorized) that last saved to each.
+pub fn location_infos(
+    default_path: &str,
+    locations: &[DownloadLocation],
+    usage: &HashMap<String, String>,
+) -> Vec<DownloadLocationInfo> {
+    let default = DownloadLocation {
+        name: DEFAULT_LOCATION.to_string(),
+        path: default_path.to_string(),
+    };
+    std::iter::once(&default)
+        .chain(locations)
+        .map(|location| {
+            let path = PathBuf::from(crate::db::expand_tilde(&location.path));
+            let space = disk_space(&path);
+            let mut last_used_for: Vec<String> = usage
+                .iter()
+                .filter(|(_, used)| used.eq_ignore_ascii_case(&location.name))
+                .map(|(category, _)| category.clone())
+                .collect();
+            last_used_for.sort();
+            DownloadLocationInfo {
+                name: location.name.clone(),
+                path: location.path.clone(),
+                is_default: std::ptr::eq(location, &default),
+                exists: path.is_dir(),
+                free_space: space.map(|(free, _)| free),
+                total_space: space.map(|(_, total)| total),
+                last_used_for,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(name: &str, path: &str) -> DownloadLocation {
+        DownloadLocation {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_locations() {
+        let absolute = std::env::temp_dir()
+            .join("media")
+            .to_string_lossy()
+            .to_string();
+        let locations = normalize_locations(vec![
+            location(" Media ", &format!(" {} ", absolute)),
+            location("Home", "~/Videos"),
+        ])
+        .unwrap();
+        assert_eq!(locations[0], location("Media", &absolute));
+
+        assert!(normalize_locations(vec![location("default", &absolute)]).is_err());
+        assert!(normalize_locations(vec![location("NAS", "nas/share")]).is_err());
+        assert!(
+            normalize_locations(vec![location("A", &absolute), location("a", &absolute)]).is_err()
+        );
+        assert!(normalize_locations(vec![location(" ", &absolute)]).is_err());
+    }
+
+    #[test]
+    fn test_location_infos() {
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-locations-{}", std::process::id()));
+        let missing = dir.join("not-yet");
+        let locations = vec![location("Scratch", &missing.to_string_lossy())];
+        let usage = HashMap::from([
+            ("Movies".to_string(), "scratch".to_string()),
+            (String::new(), "Default".to_string()),
+        ]);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let infos = location_infos(&dir.to_string_lossy(), &locations, &usage);
+        assert_eq!(infos.len(), 2);
+        assert!(infos[0].is_default && infos[0].exists);
+        assert_eq!(infos[0].last_used_for, vec![String::new()]);
+        assert!(!infos[1].is_default && !infos[1].exists);
+        assert_eq!(infos[1].last_used_for, vec!["Movies".to_string()]);
+        // Space is measured on the drive the directory will be created on
+        assert!(infos[1].free_space.is_some());
+
+        assert_eq!(
+            find_path("/dl", &locations, "SCRATCH"),
+            Some(missing.clone())
+        );
+        assert_eq!(
+            find_path("/dl", &locations, "default"),
+            Some(PathBuf::from("/dl"))
+        );
+        assert_eq!(find_path("/dl", &locations, "NAS"), None);
+
+        prepare_directory(&missing).unwrap();
+        assert!(missing.is_dir());
+        std::fs::write(dir.join("file"), "").unwrap();
+        assert!(prepare_directory(&dir.join("file")).is_err());
+        assert!(prepare_directory(Path::new("relative/dir")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
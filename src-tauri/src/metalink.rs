@@ -0,0 +1,298 @@
+//! Minimal reader for Metalink files: RFC 5854 `.meta4` and the older 3.0
+//! `.metalink`. Each file described becomes one HTTP download, from its
+//! best URL with the others as mirrors and its SHA-256 or MD5 hash as the
+//! checksum the engine verifies. Only the elements that needs are read;
+//! piece hashes, metaurls (torrents) and unknown elements are skipped.
+
+use crate::{Error, Result};
+
+/// One `<file>` of a metalink.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetalinkFile {
+    pub name: String,
+    pub size: Option<u64>,
+    /// http(s) URLs, most preferred first
+    pub urls: Vec<String>,
+    /// `(type, hex digest)`, with the type lowercased and without dashes
+    /// ("sha256", "md5")
+    pub hashes: Vec<(String, String)>,
+    /// Detached signature (usually PGP), kept as given
+    pub signature: Option<String>,
+}
+
+impl MetalinkFile {
+    /// The checksum in `DownloadOptions` form ("sha256:hex" or "md5:hex"),
+    /// preferring SHA-256. Other hash types can't be checked by the engine.
+    pub fn checksum(&self) -> Option<String> {
+        ["sha256", "md5"].iter().find_map(|wanted| {
+            self.hashes
+                .iter()
+                .find(|(kind, _)| kind == wanted)
+                .map(|(kind, digest)| format!("{}:{}", kind, digest.to_ascii_lowercase()))
+        })
+    }
+}
+
+/// The files described by a metalink document, in document order.
+pub fn parse(xml: &str) -> Result<Vec<MetalinkFile>> {
+    let mut files = Vec::new();
+    let mut saw_root = false;
+    // The file being read, with its URLs and their priority (lower first)
+    let mut current: Option<(MetalinkFile, Vec<(i64, String)>)> = None;
+    // The element whose text is being collected, with its attributes
+    let mut leaf: Option<(String, Vec<(String, String)>)> = None;
+    let mut text = String::new();
+    // Nesting inside <pieces>, whose hashes are per piece
+    let mut in_pieces = 0;
+
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..start]));
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after
+                .find("-->")
+                .map(|end| &after[end + 3..])
+                .ok_or_else(|| invalid("unterminated comment"))?;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after
+                .find("]]>")
+                .ok_or_else(|| invalid("unterminated CDATA section"))?;
+            text.push_str(&after[..end]);
+            rest = &after[end + 3..];
+            continue;
+        }
+        let end = rest.find('>').ok_or_else(|| invalid("unterminated tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = local_name(name.trim());
+            if name == "pieces" {
+                in_pieces -= 1;
+            }
+            if let (Some((open, attributes)), Some((file, urls))) = (&leaf, &mut current) {
+                if *open == name {
+                    read_leaf(file, urls, &name, attributes, text.trim());
+                    leaf = None;
+                }
+            }
+            if name == "file" {
+                if let Some((mut file, mut urls)) = current.take() {
+                    urls.sort_by_key(|(priority, _)| *priority);
+                    file.urls = urls.into_iter().map(|(_, url)| url).collect();
+                    files.push(file);
+                }
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attributes) = split_tag(tag);
+        match name.as_str() {
+            "metalink" => saw_root = true,
+            "file" if !self_closing => {
+                let name = attribute(&attributes, "name")
+                    .unwrap_or_default()
+                    .to_string();
+                current = Some((
+                    MetalinkFile {
+                        name,
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                ));
+            }
+            "pieces" if !self_closing => in_pieces += 1,
+            "url" | "size" | "signature" | "hash" if !self_closing && in_pieces == 0 => {
+                leaf = Some((name, attributes));
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    if !saw_root {
+        return Err(invalid("no <metalink> element"));
+    }
+    if files.is_empty() {
+        return Err(invalid("no files listed"));
+    }
+    Ok(files)
+}
+
+/// Record the text of a finished `url`, `size`, `signature` or `hash`.
+fn read_leaf(
+    file: &mut MetalinkFile,
+    urls: &mut Vec<(i64, String)>,
+    name: &str,
+    attributes: &[(String, String)],
+    text: &str,
+) {
+    match name {
+        "url" => {
+            let lower = text.to_ascii_lowercase();
+            if !lower.starts_with("http://") && !lower.starts_with("https://") {
+                return;
+            }
+            // 4.0 ranks by `priority` (1 first); 3.0 by `preference` (100 first)
+            let priority = match (
+                attribute(attributes, "priority"),
+                attribute(attributes, "preference"),
+            ) {
+                (Some(priority), _) => priority.parse().unwrap_or(i64::MAX),
+                (None, Some(preference)) => preference.parse().map_or(i64::MAX, |p: i64| 1000 - p),
+                (None, None) => i64::MAX,
+            };
+            urls.push((priority, text.to_string()));
+        }
+        "size" => file.size = text.parse().ok(),
+        "signature" => file.signature = Some(text.to_string()).filter(|s| !s.is_empty()),
+        "hash" => {
+            if let Some(kind) = attribute(attributes, "type") {
+                let kind = kind.to_ascii_lowercase().replace('-', "");
+                file.hashes.push((kind, text.to_string()));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// An element's name without its namespace prefix, and its attributes.
+fn split_tag(tag: &str) -> (String, Vec<(String, String)>) {
+    let tag = tag.trim();
+    let (name, mut rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let mut attributes = Vec::new();
+    while let Some((key, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = after[1..].find(quote) else {
+            break;
+        };
+        attributes.push((local_name(key.trim()), decode_entities(&after[1..end + 1])));
+        rest = &after[end + 2..];
+    }
+    (local_name(name), attributes)
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn local_name(name: &str) -> String {
+    name.rsplit(':').next().unwrap_or(name).to_string()
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidInput(format!("Malformed metalink: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const META4: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <!-- two mirrors and a torrent -->
+  <file name="example.iso">
+    <size>14471447</size>
+    <hash type="md5">0123456789ABCDEF0123456789ABCDEF</hash>
+    <hash type="sha-256">f0ad929cd259957e160ea442eb80986b5f01a2cb00000000000000000000000</hash>
+    <pieces length="262144" type="sha-256">
+      <hash>aaaa</hash>
+    </pieces>
+    <url location="de" priority="2">https://mirror.example.de/example.iso?a=1&amp;b=2</url>
+    <url priority="1">http://example.com/example.iso</url>
+    <url priority="3">ftp://ftp.example.com/example.iso</url>
+    <metaurl mediatype="torrent">http://example.com/example.iso.torrent</metaurl>
+    <signature mediatype="application/pgp-signature"><![CDATA[-----BEGIN PGP SIGNATURE-----
+x
+-----END PGP SIGNATURE-----]]></signature>
+  </file>
+  <file name="notes.txt">
+    <url>https://example.com/notes.txt</url>
+  </file>
+</metalink>"#;
+
+    #[test]
+    fn test_parse_meta4() {
+        let files = parse(META4).unwrap();
+        assert_eq!(files.len(), 2);
+        let iso = &files[0];
+        assert_eq!(iso.name, "example.iso");
+        assert_eq!(iso.size, Some(14471447));
+        assert_eq!(
+            iso.urls,
+            vec![
+                "http://example.com/example.iso",
+                "https://mirror.example.de/example.iso?a=1&b=2"
+            ]
+        );
+        assert_eq!(iso.hashes.len(), 2);
+        assert_eq!(
+            iso.checksum().as_deref(),
+            Some("sha256:f0ad929cd259957e160ea442eb80986b5f01a2cb00000000000000000000000")
+        );
+        assert!(iso
+            .signature
+            .as_deref()
+            .unwrap()
+            .starts_with("-----BEGIN PGP SIGNATURE-----"));
+        assert_eq!(files[1].urls, vec!["https://example.com/notes.txt"]);
+        assert_eq!(files[1].checksum(), None);
+    }
+
+    #[test]
+    fn test_parse_metalink3() {
+        let xml = r#"<metalink version="3.0" xmlns="http://www.metalinker.org/">
+          <files>
+            <file name='old.tar.gz'>
+              <verification><hash type="md5">0123456789abcdef0123456789abcdef</hash></verification>
+              <resources>
+                <url type="http" preference="10">http://slow.example/old.tar.gz</url>
+                <url type="http" preference="100">http://fast.example/old.tar.gz</url>
+              </resources>
+            </file>
+          </files>
+        </metalink>"#;
+        let files = parse(xml).unwrap();
+        assert_eq!(
+            files[0].urls,
+            vec![
+                "http://fast.example/old.tar.gz",
+                "http://slow.example/old.tar.gz"
+            ]
+        );
+        assert_eq!(
+            files[0].checksum().as_deref(),
+            Some("md5:0123456789abcdef0123456789abcdef")
+        );
+
+        assert!(parse("<html><file name=\"x\"/></html>").is_err());
+        assert!(parse("<metalink></metalink>").is_err());
+        assert!(parse("<metalink><file name=\"x\"").is_err());
+    }
+}
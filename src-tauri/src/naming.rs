@@ -0,0 +1,185 @@
+//! Filenames for HTTP downloads without an explicit `out`. The original
+//! URL often names a redirect endpoint (`/download?id=42`) rather than the
+//! file, so the server probe's final URL and `Content-Disposition` are
+//! consulted too, in the order the `filename_priority` setting picks.
+
+use crate::ranges::ServerProbe;
+
+/// Name sources in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    ContentDisposition,
+    FinalUrl,
+    OriginalUrl,
+}
+
+fn precedence(priority: &str) -> &'static [Source] {
+    match priority {
+        "final_url" => &[
+            Source::FinalUrl,
+            Source::ContentDisposition,
+            Source::OriginalUrl,
+        ],
+        "original_url" => &[Source::OriginalUrl],
+        _ => &[
+            Source::ContentDisposition,
+            Source::FinalUrl,
+            Source::OriginalUrl,
+        ],
+    }
+}
+
+/// Pick a filename for `url` following `priority` (`content_disposition`,
+/// `final_url` or `original_url`). None leaves naming to the engine.
+pub fn choose_filename(priority: &str, url: &str, probe: &ServerProbe) -> Option<String> {
+    precedence(priority).iter().find_map(|source| match source {
+        Source::ContentDisposition => probe
+            .content_disposition
+            .as_deref()
+            .and_then(disposition_filename),
+        Source::FinalUrl => probe.final_url.as_deref().and_then(url_filename),
+        Source::OriginalUrl => url_filename(url),
+    })
+}
+
+/// Last segment of the URL's path, decoded.
+fn url_filename(url: &str) -> Option<String> {
+    let parsed = url::Url::parse(url).ok()?;
+    let segment = parsed.path_segments()?.next_back()?;
+    base_name(&crate::engine_adapter::percent_decode(segment))
+}
+
+/// Filename from a `Content-Disposition` header. `filename*` (RFC 5987,
+/// percent-encoded UTF-8) wins over plain `filename`.
+fn disposition_filename(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+    for param in split_params(value).into_iter().skip(1) {
+        let Some((key, raw)) = param.split_once('=') else {
+            continue;
+        };
+        let raw = raw.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                // charset'language'value
+                let encoded = raw.splitn(3, '\'').nth(2).unwrap_or(raw);
+                extended = Some(crate::engine_adapter::percent_decode(
+                    encoded.trim_matches('"'),
+                ));
+            }
+            "filename" => plain = Some(unquote(raw)),
+            _ => {}
+        }
+    }
+    extended.or(plain).as_deref().and_then(base_name)
+}
+
+/// Split header parameters on `;` outside quoted strings.
+fn split_params(value: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    params.push(current);
+    params
+}
+
+fn unquote(raw: &str) -> String {
+    match raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => raw.to_string(),
+    }
+}
+
+/// The final path component, so a name can't point outside the download
+/// directory. None for empty names and `.`/`..`.
+fn base_name(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next()?.trim();
+    (!base.is_empty() && base != "." && base != "..").then(|| base.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(final_url: Option<&str>, disposition: Option<&str>) -> ServerProbe {
+        ServerProbe {
+            final_url: final_url.map(str::to_string),
+            content_disposition: disposition.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disposition_filename() {
+        assert_eq!(
+            disposition_filename("attachment; filename=report.pdf").as_deref(),
+            Some("report.pdf")
+        );
+        assert_eq!(
+            disposition_filename(r#"attachment; filename="a; \"b\".zip""#).as_deref(),
+            Some(r#"a; "b".zip"#)
+        );
+        assert_eq!(
+            disposition_filename(
+                "attachment; filename=\"x.bin\"; filename*=UTF-8''na%C3%AFve%20file.txt"
+            )
+            .as_deref(),
+            Some("naïve file.txt")
+        );
+        assert_eq!(
+            disposition_filename("attachment; filename=\"../../etc/passwd\"").as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(disposition_filename("attachment; filename=\"..\""), None);
+        assert_eq!(disposition_filename("inline"), None);
+    }
+
+    #[test]
+    fn test_choose_filename() {
+        let url = "https://example.com/download?id=42";
+        let both = probe(
+            Some("https://cdn.example.net/files/setup-1.2.exe"),
+            Some("attachment; filename=Setup.exe"),
+        );
+        assert_eq!(
+            choose_filename("content_disposition", url, &both).as_deref(),
+            Some("Setup.exe")
+        );
+        assert_eq!(
+            choose_filename("final_url", url, &both).as_deref(),
+            Some("setup-1.2.exe")
+        );
+        assert_eq!(
+            choose_filename("original_url", url, &both).as_deref(),
+            Some("download")
+        );
+
+        let redirect_only = probe(Some("https://cdn.example.net/files/setup-1.2.exe"), None);
+        assert_eq!(
+            choose_filename("content_disposition", url, &redirect_only).as_deref(),
+            Some("setup-1.2.exe")
+        );
+        assert_eq!(
+            choose_filename(
+                "content_disposition",
+                "https://example.com/",
+                &probe(None, None)
+            ),
+            None
+        );
+    }
+}
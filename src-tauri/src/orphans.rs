@@ -0,0 +1,258 @@
+//! Partial files left in the download folders by downloads the app no
+//! longer knows about, e.g. after the database was reset or a download was
+//! removed while the engine was down. HTTP downloads get a small
+//! `.goshmeta` sidecar next to their `.part` file recording the URL, the
+//! server's ETag and size, and the checksum, so an orphan found later (or
+//! copied to another machine) can be added again instead of only deleted,
+//! and adding the same URL again picks up what was known about it.
+
+use crate::db::{expand_tilde, Database};
+use crate::engine_adapter::EngineAdapter;
+use crate::types::OrphanedPartial;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const PART_SUFFIX: &str = ".part";
+
+/// Takes the place of `.part` in the sidecar's name.
+const SIDECAR_SUFFIX: &str = ".goshmeta";
+
+/// What a `.goshmeta` sidecar records about a partial download.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sidecar {
+    /// The URL as it was added (an `s3://` URL rather than a signed one)
+    pub url: String,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub total_size: Option<u64>,
+    /// "sha256:hex" or "md5:hex"
+    #[serde(default)]
+    pub checksum: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+impl Sidecar {
+    /// Take what an earlier attempt at the same URL recorded: its checksum
+    /// unless this one has its own. Returns a warning if the server's file
+    /// is no longer the one the partial data came from.
+    pub fn merge_previous(&mut self, previous: &Sidecar) -> Option<String> {
+        if previous.url != self.url {
+            return None;
+        }
+        if self.checksum.is_none() {
+            self.checksum = previous.checksum.clone();
+        }
+        let etag_changed =
+            matches!((&previous.etag, &self.etag), (Some(old), Some(new)) if old != new);
+        let size_changed =
+            matches!((previous.total_size, self.total_size), (Some(old), Some(new)) if old != new);
+        let changed = etag_changed || size_changed;
+        changed.then(|| {
+            "The file changed on the server since the partial download was started".to_string()
+        })
+    }
+}
+
+/// The partial file an HTTP download named `name` writes in `dir`. Mirrors
+/// gosh-dl, which names it `save_path.with_extension("<ext>.part")`
+/// (`partial_path_for` in src/http/mod.rs, `part_path` in
+/// src/http/segment.rs): the full name plus `.part`, with or without an
+/// extension.
+pub fn part_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}{}", name, PART_SUFFIX))
+}
+
+fn sidecar_path(part: &Path) -> PathBuf {
+    let name = part
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let stem = name.strip_suffix(PART_SUFFIX).unwrap_or(&name);
+    part.with_file_name(format!("{}{}", stem, SIDECAR_SUFFIX))
+}
+
+/// Record what is known about the download writing `part`.
+pub fn write_sidecar(part: &Path, sidecar: &Sidecar) {
+    let written = serde_json::to_vec(sidecar)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(sidecar_path(part), json));
+    if let Err(e) = written {
+        log::debug!("Failed to write sidecar for {}: {}", part.display(), e);
+    }
+}
+
+/// The sidecar of `part`, if there is a readable one.
+pub fn read_sidecar(part: &Path) -> Option<Sidecar> {
+    let json = std::fs::read(sidecar_path(part)).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Remove the sidecar of `part`, once the download finished or went away.
+pub fn remove_sidecar(part: &Path) {
+    match std::fs::remove_file(sidecar_path(part)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::debug!("Failed to remove sidecar of {}: {}", part.display(), e),
+    }
+}
+
+/// `.part` files directly in `dirs` that are not in `known`, with the URL
+/// from their sidecar where there is one. Sidecars whose `.part` file is
+/// gone are removed along the way.
+pub fn scan(dirs: &[PathBuf], known: &HashSet<PathBuf>) -> Vec<OrphanedPartial> {
+    let mut orphans = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if let Some(stem) = file_name.strip_suffix(SIDECAR_SUFFIX) {
+                let part = part_path(dir, stem);
+                if !part.exists() && !known.contains(&part) {
+                    remove_sidecar(&part);
+                }
+                continue;
+            }
+            let Some(name) = file_name.strip_suffix(PART_SUFFIX) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() || known.contains(&path) {
+                continue;
+            }
+            let sidecar = read_sidecar(&path);
+            orphans.push(OrphanedPartial {
+                path: path.to_string_lossy().into_owned(),
+                name: name.to_string(),
+                size: metadata.len(),
+                modified_at: metadata
+                    .modified()
+                    .ok()
+                    .map(|t| DateTime::<Utc>::from(t).to_rfc3339()),
+                total_size: sidecar.as_ref().and_then(|s| s.total_size),
+                url: sidecar.map(|s| s.url),
+            });
+        }
+    }
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    orphans
+}
+
+/// Look for orphans in the download folder, the temp folder, the named
+/// locations and the category folders.
+pub async fn find_orphans(db: &Database, adapter: &EngineAdapter) -> Result<Vec<OrphanedPartial>> {
+    let settings = db.get_settings_async().await?;
+    let mut dirs = vec![PathBuf::from(&settings.download_path)];
+    if !settings.temp_download_path.is_empty() {
+        dirs.push(PathBuf::from(&settings.temp_download_path));
+    }
+    for location in db.get_download_locations_async().await? {
+        dirs.push(PathBuf::from(expand_tilde(&location.path)));
+    }
+    for rule in db.get_category_rules_async().await? {
+        if !rule.directory.is_empty() {
+            dirs.push(crate::categories::resolve_directory(
+                &settings.download_path,
+                &rule.directory,
+            ));
+        }
+    }
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| seen.insert(dir.clone()));
+
+    let mut downloads = db.get_incomplete_downloads_async().await?;
+    downloads.extend(adapter.get_all());
+    let known: HashSet<PathBuf> = downloads
+        .iter()
+        .map(|d| part_path(Path::new(&d.save_path), &d.name))
+        .collect();
+    tokio::task::spawn_blocking(move || scan(&dirs, &known))
+        .await
+        .map_err(|e| crate::Error::Database(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sidecar(url: &str, etag: Option<&str>, total_size: Option<u64>) -> Sidecar {
+        Sidecar {
+            url: url.into(),
+            etag: etag.map(str::to_string),
+            total_size,
+            checksum: None,
+            added_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_merge_previous() {
+        let mut previous = sidecar("https://example.com/a.iso", Some("\"v1\""), Some(100));
+        previous.checksum = Some("sha256:ab".into());
+
+        let mut same = sidecar("https://example.com/a.iso", Some("\"v1\""), None);
+        assert_eq!(same.merge_previous(&previous), None);
+        assert_eq!(same.checksum.as_deref(), Some("sha256:ab"));
+
+        let mut changed = sidecar("https://example.com/a.iso", Some("\"v2\""), Some(100));
+        assert!(changed.merge_previous(&previous).is_some());
+        let mut resized = sidecar("https://example.com/a.iso", None, Some(120));
+        assert!(resized.merge_previous(&previous).is_some());
+
+        let mut other = sidecar("https://example.com/b.iso", Some("\"v2\""), None);
+        assert_eq!(other.merge_previous(&previous), None);
+        assert_eq!(other.checksum, None);
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-orphans-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("known.iso.part"), b"xx").unwrap();
+        std::fs::write(dir.join("lost.iso.part"), b"xxx").unwrap();
+        std::fs::write(dir.join("bare.bin.part"), b"x").unwrap();
+        std::fs::write(dir.join("done.iso"), b"x").unwrap();
+        write_sidecar(
+            &dir.join("lost.iso.part"),
+            &sidecar("https://example.com/lost.iso", None, Some(9)),
+        );
+        // The download finished, leaving its sidecar behind
+        write_sidecar(
+            &dir.join("done.iso.part"),
+            &sidecar("https://example.com/done.iso", None, None),
+        );
+
+        let known = HashSet::from([part_path(&dir, "known.iso")]);
+        let orphans = scan(std::slice::from_ref(&dir), &known);
+        let found: Vec<_> = orphans
+            .iter()
+            .map(|o| (o.name.as_str(), o.size, o.total_size, o.url.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("bare.bin", 1, None, None),
+                ("lost.iso", 3, Some(9), Some("https://example.com/lost.iso"))
+            ]
+        );
+        assert_eq!(
+            sidecar_path(&dir.join("done.iso.part")),
+            dir.join("done.iso.goshmeta")
+        );
+        assert!(!dir.join("done.iso.goshmeta").exists());
+        assert!(dir.join("lost.iso.goshmeta").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+//! Where the app keeps its state. Normally that is the platform data
+//! directory; in portable mode (`--portable`, or a `portable` file next to
+//! the executable) the database, settings, session marker and logs all go
+//! to a `data` folder beside the binary, so the app can run from a USB
+//! stick. Some state still lands on the host: the webview keeps its cache
+//! and local storage (the queue order) in the platform directories, the
+//! window state plugin saves the window position there, and the default
+//! download folder is the host's. Relative paths in settings are taken
+//! from beside the binary, though (see `data_root`).
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+
+pub const PORTABLE_FLAG: &str = "--portable";
+
+/// An empty file with this name beside the executable turns portable mode on.
+pub const PORTABLE_MARKER: &str = "portable";
+
+const PORTABLE_DATA_DIR: &str = "data";
+const APP_DB_FILE: &str = "gosh-fetch.db";
+const ENGINE_DB_FILE: &str = "engine.db";
+const SESSION_MARKER_FILE: &str = "session.lock";
+const LOG_DIR: &str = "logs";
+
+/// The portable data directory, once `init` has run; None in normal mode.
+static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Decide between portable and normal mode from the command line and the
+/// executable's location. Runs once at startup; later calls keep the
+/// first answer.
+pub fn init<I: IntoIterator<Item = String>>(args: I) {
+    PORTABLE_DIR.get_or_init(|| {
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        let dir = portable_dir(args, exe_dir.as_deref());
+        if let Some(dir) = &dir {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                eprintln!(
+                    "Failed to create portable data dir {}: {}",
+                    dir.display(),
+                    e
+                );
+            }
+        }
+        dir
+    });
+}
+
+/// The `data` folder beside the executable if portable mode was asked for.
+fn portable_dir<I: IntoIterator<Item = String>>(
+    args: I,
+    exe_dir: Option<&Path>,
+) -> Option<PathBuf> {
+    let exe_dir = exe_dir?;
+    let flagged = args.into_iter().any(|arg| arg == PORTABLE_FLAG);
+    (flagged || exe_dir.join(PORTABLE_MARKER).is_file()).then(|| exe_dir.join(PORTABLE_DATA_DIR))
+}
+
+pub fn is_portable() -> bool {
+    PORTABLE_DIR.get().is_some_and(Option::is_some)
+}
+
+/// What relative paths in settings and downloads are resolved against in
+/// portable mode: the folder holding the executable, so a download folder
+/// such as `Downloads` stays on the same stick. None in normal mode, where
+/// the home directory is used.
+pub fn data_root() -> Option<PathBuf> {
+    PORTABLE_DIR
+        .get()?
+        .as_ref()?
+        .parent()
+        .map(Path::to_path_buf)
+}
+
+/// Directory holding the databases and session marker.
+pub fn data_dir(app: &AppHandle) -> PathBuf {
+    if let Some(Some(dir)) = PORTABLE_DIR.get() {
+        return dir.clone();
+    }
+    app.path().app_data_dir().unwrap_or_else(|_| {
+        // Fallback reproducing the legacy sidecar's resolution
+        dirs::data_dir()
+            .expect("Could not determine platform data directory")
+            .join("com.gosh.fetch")
+    })
+}
+
+/// Log directory in portable mode. In normal mode the log plugin uses the
+/// platform log directory.
+pub fn portable_log_dir() -> Option<PathBuf> {
+    PORTABLE_DIR.get()?.as_ref().map(|dir| dir.join(LOG_DIR))
+}
+
+/// App database (history, settings, trackers).
+pub fn app_db(data_dir: &Path) -> PathBuf {
+    data_dir.join(APP_DB_FILE)
+}
+
+/// The download engine's own state database.
+pub fn engine_db(data_dir: &Path) -> PathBuf {
+    data_dir.join(ENGINE_DB_FILE)
+}
+
+/// Marker written while a session runs; see `recovery`.
+pub fn session_marker(data_dir: &Path) -> PathBuf {
+    data_dir.join(SESSION_MARKER_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portable_detection() {
+        let exe_dir =
+            std::env::temp_dir().join(format!("gosh-fetch-portable-{}", std::process::id()));
+        std::fs::create_dir_all(&exe_dir).unwrap();
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(portable_dir(args(&["gosh-fetch"]), Some(&exe_dir)), None);
+        assert_eq!(
+            portable_dir(args(&["gosh-fetch", "--portable"]), Some(&exe_dir)),
+            Some(exe_dir.join("data"))
+        );
+        assert_eq!(portable_dir(args(&["--portable"]), None), None);
+
+        std::fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(
+            portable_dir(args(&["gosh-fetch"]), Some(&exe_dir)),
+            Some(exe_dir.join("data"))
+        );
+
+        std::fs::remove_dir_all(&exe_dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+//! Reading the start of a download's file and sniffing its type from magic
+//! bytes, for previews and content checks.
+
+use crate::engine_adapter::TorrentFileInfo;
+use crate::types::{Download, DownloadType};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Largest preview a caller may ask for.
+pub const MAX_PREVIEW_BYTES: u64 = 1024 * 1024;
+
+/// Login and error pages are small; a bigger HTML body is more likely a
+/// real page the user meant to save.
+const ERROR_PAGE_MAX_SIZE: u64 = 512 * 1024;
+
+/// Extensions under which an HTML body is expected.
+const HTML_EXTENSIONS: &[&str] = &[
+    "htm", "html", "xhtml", "shtml", "php", "asp", "aspx", "jsp", "cgi", "txt", "xml", "svg",
+];
+
+/// File on disk that holds the start of `download`. For a torrent that is
+/// the first selected file, or without the file list (a torrent only left
+/// in history) the first file in its folder by name; an in-progress HTTP
+/// download may still carry a `.part` suffix.
+pub fn locate_file(download: &Download, torrent_files: &[TorrentFileInfo]) -> Option<PathBuf> {
+    let dir = Path::new(&download.save_path);
+    let mut candidates = Vec::new();
+    match download.download_type {
+        DownloadType::Http => {
+            candidates.push(dir.join(&download.name));
+            candidates.push(crate::orphans::part_path(dir, &download.name));
+        }
+        DownloadType::Torrent | DownloadType::Magnet => {
+            if let Some(file) = torrent_files.iter().find(|f| f.selected) {
+                candidates.push(dir.join(&file.path));
+                candidates.push(dir.join(&download.name).join(&file.path));
+            }
+            let root = dir.join(&download.name);
+            if root.is_dir() {
+                candidates.extend(first_file(&root));
+            }
+            candidates.push(root);
+        }
+    }
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+/// First regular file under `dir`, walking entries in name order.
+fn first_file(dir: &Path) -> Option<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+    entries.into_iter().find_map(|path| match path.is_dir() {
+        true => first_file(&path),
+        false => path.is_file().then_some(path),
+    })
+}
+
+/// Read up to `max` bytes from the start of `path`.
+pub fn read_prefix(path: &Path, max: u64) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    std::fs::File::open(path)?
+        .take(max)
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// MIME type from the leading bytes of a file, for the formats people
+/// usually download. None when nothing matched.
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x28\xb5\x2f\xfd", "application/zstd"),
+        (b"BZh", "application/x-bzip2"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"Rar!\x1a\x07", "application/vnd.rar"),
+        (b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"\x7fELF", "application/x-elf"),
+        (b"MZ", "application/vnd.microsoft.portable-executable"),
+        (b"d8:announce", "application/x-bittorrent"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return Some(mime);
+    }
+    if data.len() >= 12 && &data[0..4] == b"RIFF" {
+        match &data[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            b"AVI " => return Some("video/x-msvideo"),
+            _ => {}
+        }
+    }
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return Some("video/mp4");
+    }
+    if looks_like_html(data) {
+        return Some("text/html");
+    }
+    None
+}
+
+/// Why a finished download looks like an HTML error or login page rather
+/// than the file its name promises, or None if it looks fine. `head` is the
+/// start of the file and `size` its full length.
+pub fn suspect_error_page(name: &str, head: &[u8], size: u64) -> Option<String> {
+    if size > ERROR_PAGE_MAX_SIZE || sniff_mime(head) != Some("text/html") {
+        return None;
+    }
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    let expected = match extension.as_deref() {
+        Some(ext) if HTML_EXTENSIONS.contains(&ext) => return None,
+        Some(ext) => format!(".{} file", ext),
+        // No extension: only suspicious if it's tiny
+        None if size > 64 * 1024 => return None,
+        None => "file".to_string(),
+    };
+    Some(format!(
+        "Received an HTML page ({} bytes) instead of the {}; the link may need a login or have expired",
+        size, expected
+    ))
+}
+
+/// True if the text starts (after whitespace and a BOM) with an HTML
+/// doctype or tag.
+fn looks_like_html(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let head: Vec<u8> = data[start..]
+        .iter()
+        .take(15)
+        .map(u8::to_ascii_lowercase)
+        .collect();
+    head.starts_with(b"<!doctype html") || head.starts_with(b"<html") || head.starts_with(b"<head")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DownloadState;
+
+    #[test]
+    fn test_sniff_mime() {
+        assert_eq!(
+            sniff_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some("image/png")
+        );
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(
+            sniff_mime(b"\0\0\0\x20ftypisom\0\0\x02\0"),
+            Some("video/mp4")
+        );
+        assert_eq!(
+            sniff_mime(b"\xef\xbb\xbf\n  <!DOCTYPE html><html>"),
+            Some("text/html")
+        );
+        assert_eq!(sniff_mime(b"<HTML><body>Login</body>"), Some("text/html"));
+        assert_eq!(sniff_mime(b"plain text"), None);
+        assert_eq!(sniff_mime(b""), None);
+    }
+
+    #[test]
+    fn test_suspect_error_page() {
+        let html = b"<!DOCTYPE html><html><title>Sign in</title>";
+        let reason = suspect_error_page("ubuntu.iso", html, 4096).unwrap();
+        assert!(reason.contains(".iso file"), "{}", reason);
+
+        assert_eq!(suspect_error_page("index.html", html, 4096), None);
+        assert_eq!(suspect_error_page("ubuntu.iso", b"CD001 data", 4096), None);
+        // A large HTML body is probably what the user asked for
+        assert_eq!(
+            suspect_error_page("ubuntu.iso", html, 10 * 1024 * 1024),
+            None
+        );
+        assert!(suspect_error_page("download", html, 2048).is_some());
+        assert_eq!(suspect_error_page("download", html, 200 * 1024), None);
+    }
+
+    #[test]
+    fn test_locate_and_read_prefix() {
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-preview-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("file.bin.part"), b"partial data").unwrap();
+
+        let download = Download {
+            gid: "gid".into(),
+            name: "file.bin".into(),
+            url: Some("https://example.com/file.bin".into()),
+            status: DownloadState::Active,
+            total_size: 100,
+            completed_size: 12,
+            save_path: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let path = locate_file(&download, &[]).unwrap();
+        assert_eq!(path, dir.join("file.bin.part"));
+        assert_eq!(read_prefix(&path, 7).unwrap(), b"partial");
+
+        // The finished file wins over a leftover .part
+        std::fs::write(dir.join("file.bin"), b"complete").unwrap();
+        assert_eq!(locate_file(&download, &[]).unwrap(), dir.join("file.bin"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_locate_multi_file_torrent_from_history() {
+        let dir =
+            std::env::temp_dir().join(format!("gosh-fetch-preview-multi-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("Album/CD2")).unwrap();
+        std::fs::create_dir_all(dir.join("Album/CD1")).unwrap();
+        std::fs::write(dir.join("Album/CD2/01.flac"), b"fLaC two").unwrap();
+        std::fs::write(dir.join("Album/CD1/01.flac"), b"fLaC one").unwrap();
+
+        let download = Download {
+            gid: "gid".into(),
+            name: "Album".into(),
+            download_type: DownloadType::Torrent,
+            status: DownloadState::Complete,
+            save_path: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            locate_file(&download, &[]).unwrap(),
+            dir.join("Album/CD1/01.flac")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
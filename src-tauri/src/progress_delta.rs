@@ -0,0 +1,125 @@
+//! Delta progress events. The engine reports a download's whole progress
+//! every time; with hundreds of active torrents most of it is the same as
+//! last time. With `delta_progress_events` on, `download:progress` carries
+//! only the fields that changed, numbered per download so a client can tell
+//! it missed one and fetch the full progress with `get_progress_snapshots`.
+
+use crate::types::ProgressSnapshot;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct ProgressDeltas {
+    /// Sequence number and fields of each download's last report
+    last: HashMap<String, (u64, Map<String, Value>)>,
+}
+
+impl ProgressDeltas {
+    /// Record a progress report of `gid` and return the event payload that
+    /// carries it: the whole progress the first time, the changed fields
+    /// after that (removed ones as null). None if nothing changed.
+    pub fn record(&mut self, gid: &str, progress: Value) -> Option<Value> {
+        let Value::Object(fields) = progress else {
+            return None;
+        };
+        let Some((seq, previous)) = self.last.get_mut(gid) else {
+            let payload =
+                serde_json::json!({ "gid": gid, "seq": 1, "full": true, "progress": fields });
+            self.last.insert(gid.to_string(), (1, fields));
+            return Some(payload);
+        };
+
+        let mut changed: Map<String, Value> = fields
+            .iter()
+            .filter(|(key, value)| previous.get(*key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        for key in previous.keys().filter(|key| !fields.contains_key(*key)) {
+            changed.insert(key.clone(), Value::Null);
+        }
+        if changed.is_empty() {
+            return None;
+        }
+        *seq += 1;
+        *previous = fields;
+        Some(serde_json::json!({ "gid": gid, "seq": *seq, "full": false, "changed": changed }))
+    }
+
+    /// The last full progress of the downloads in `gids`, or of all of
+    /// them.
+    pub fn snapshots(&self, gids: Option<&[String]>) -> Vec<ProgressSnapshot> {
+        let snapshot =
+            |(gid, (seq, fields)): (&String, &(u64, Map<String, Value>))| ProgressSnapshot {
+                gid: gid.clone(),
+                seq: *seq,
+                progress: Value::Object(fields.clone()),
+            };
+        let mut snapshots: Vec<ProgressSnapshot> = match gids {
+            Some(gids) => gids
+                .iter()
+                .filter_map(|gid| self.last.get_key_value(gid))
+                .map(snapshot)
+                .collect(),
+            None => self.last.iter().map(snapshot).collect(),
+        };
+        snapshots.sort_by(|a, b| a.gid.cmp(&b.gid));
+        snapshots
+    }
+
+    pub fn forget(&mut self, gid: &str) {
+        self.last.remove(gid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record() {
+        let mut deltas = ProgressDeltas::default();
+        let first = deltas
+            .record(
+                "a",
+                json!({ "completedSize": 10, "downloadSpeed": 5, "seeders": 2 }),
+            )
+            .unwrap();
+        assert_eq!(first["full"], true);
+        assert_eq!(first["progress"]["completedSize"], 10);
+
+        assert_eq!(
+            deltas.record(
+                "a",
+                json!({ "completedSize": 10, "downloadSpeed": 5, "seeders": 2 })
+            ),
+            None
+        );
+        let second = deltas
+            .record("a", json!({ "completedSize": 20, "downloadSpeed": 5 }))
+            .unwrap();
+        assert_eq!(
+            second,
+            json!({
+                "gid": "a",
+                "seq": 2,
+                "full": false,
+                "changed": { "completedSize": 20, "seeders": null }
+            })
+        );
+
+        let snapshots = deltas.snapshots(Some(&["a".to_string(), "b".to_string()]));
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(
+            (snapshots[0].seq, &snapshots[0].progress),
+            (2, &json!({ "completedSize": 20, "downloadSpeed": 5 }))
+        );
+
+        deltas.forget("a");
+        assert!(deltas.snapshots(None).is_empty());
+        assert_eq!(
+            deltas.record("a", json!({ "completedSize": 30 })).unwrap()["seq"],
+            1
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! What an HTTP server tells us about a file before the engine fetches it.
+//! A server that ignores `Range` can only be downloaded over one
+//! connection, and a paused download starts over from the first byte when
+//! resumed; the engine would otherwise restart it without a word, so the
+//! app probes before adding and reports such downloads as not resumable.
+//! The same probe finds the size of files served without `Content-Length`
+//! (chunked responses), from the total in `Content-Range`, and the final
+//! URL and `Content-Disposition` that `naming` picks a filename from.
+//! Redirects it follows are recorded and held to the redirect rules, so a
+//! download that would be bounced too often or from https to http is
+//! refused before the engine gets it. The rules hold for the probe only:
+//! the engine follows redirects on its own requests by itself.
+//!
+//! The probe is also the app's only look at the server's latency (the
+//! engine doesn't report its own): the time to the response headers and
+//! the part of it spent on redirects are kept for the details panel and
+//! the history statistics.
+//!
+//! Some servers honour a range from the first byte but send the whole file
+//! for any range further in, which breaks every segment but the first. A
+//! second one-byte request for the last byte catches them; their host is
+//! remembered, so later downloads from it go straight to one connection.
+//!
+//! Probes carry the download's own user agent, referer and headers (with
+//! any credentials in the URL), since a server that wants a cookie or
+//! token may answer a bare request with a login page. Results live only in
+//! memory: after a restart a download is probed again on its first
+//! progress report, and until then its `resumable` is unknown.
+
+use crate::db::Settings;
+use crate::types::DownloadOptions;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// How long a host found to break ranges keeps being treated that way
+/// before it is checked again.
+pub const BROKEN_RANGES_MEMORY_DAYS: i64 = 30;
+
+/// Answer to a one-byte ranged request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerProbe {
+    /// Whether byte ranges are honoured; None when inconclusive
+    pub ranges: Option<bool>,
+    /// Full size of the file, if the server named one
+    pub total_size: Option<u64>,
+    /// Where redirects led, if anywhere other than the URL asked for
+    pub final_url: Option<String>,
+    /// Raw `Content-Disposition` header
+    pub content_disposition: Option<String>,
+    /// URLs redirected to, in order, including a blocked one
+    pub redirects: Vec<String>,
+    /// Why a redirect was refused
+    pub redirect_error: Option<String>,
+    /// Ranges were honoured from the first byte but not further in
+    pub broken_ranges: bool,
+    /// Time to the final response's headers, DNS, connecting, TLS and
+    /// redirects included
+    pub ttfb_ms: Option<u64>,
+    /// Time until the last redirect was answered; None without redirects
+    pub redirect_ms: Option<u64>,
+    /// The file's `ETag`, to tell whether it changed between attempts
+    pub etag: Option<String>,
+}
+
+/// What a probe sends besides the range: the user agent, referer and
+/// headers (authorization and cookies among them) the engine will send for
+/// the download, so the server answers the probe as it will the download.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeRequest {
+    pub user_agent: String,
+    pub referer: Option<String>,
+    pub headers: Vec<(String, String)>,
+    /// Skip certificate checks, as the engine does with the
+    /// `accept_invalid_certs` setting
+    pub accept_invalid_certs: bool,
+}
+
+impl ProbeRequest {
+    /// The request context of a download added with `options`.
+    pub fn new(settings: &Settings, options: &DownloadOptions) -> Self {
+        let headers = options
+            .header
+            .iter()
+            .flatten()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Self {
+            user_agent: options
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| settings.user_agent.clone()),
+            referer: options.referer.clone(),
+            headers,
+            accept_invalid_certs: settings.accept_invalid_certs,
+        }
+    }
+}
+
+/// Limits on the redirects a download may follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedirectRules {
+    pub max_redirects: usize,
+    /// Follow redirects from https to plain http
+    pub allow_downgrade: bool,
+}
+
+impl RedirectRules {
+    /// The configured rules, with a download's own `max_redirects` if given.
+    pub fn from_settings(settings: &Settings, max_redirects: Option<u32>) -> Self {
+        Self {
+            max_redirects: max_redirects.unwrap_or(settings.max_redirects) as usize,
+            allow_downgrade: settings.allow_insecure_redirects,
+        }
+    }
+
+    /// Whether to follow a redirect to `next` after `followed` hops from
+    /// `previous`; Err with the reason if not.
+    fn check(&self, followed: usize, previous: &str, next: &str) -> Result<(), String> {
+        if followed >= self.max_redirects {
+            return Err(format!("Too many redirects (limit {})", self.max_redirects));
+        }
+        let scheme = |url: &str| url.split_once("://").map(|(s, _)| s.to_ascii_lowercase());
+        if !self.allow_downgrade
+            && scheme(previous).as_deref() == Some("https")
+            && scheme(next).as_deref() == Some("http")
+        {
+            return Err(format!("Refused redirect from https to http: {}", next));
+        }
+        Ok(())
+    }
+}
+
+/// Probe the server behind `url` with a one-byte ranged GET and, when
+/// `check_ranges` and it answered with a range, another for the last byte.
+/// Both carry `request`. A failed probe learns nothing beyond the redirects
+/// it saw.
+pub async fn probe_server(
+    url: &str,
+    proxy_url: &str,
+    request: &ProbeRequest,
+    rules: RedirectRules,
+    check_ranges: bool,
+) -> ServerProbe {
+    let chain = Arc::new(Mutex::new((Vec::new(), None, None)));
+    let seen = chain.clone();
+    let started = Instant::now();
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        let next = attempt.url().to_string();
+        let previous = attempt
+            .previous()
+            .last()
+            .map(|u| u.to_string())
+            .unwrap_or_default();
+        let followed = attempt.previous().len() - 1;
+        let mut seen = seen.lock().unwrap();
+        seen.0.push(next.clone());
+        seen.2 = Some(started.elapsed().as_millis() as u64);
+        match rules.check(followed, &previous, &next) {
+            Ok(()) => attempt.follow(),
+            Err(reason) => {
+                seen.1 = Some(reason.clone());
+                attempt.error(reason)
+            }
+        }
+    });
+    let probe = send_probe(url, proxy_url, request, policy, "bytes=0-0").await;
+    let (redirects, redirect_error, redirect_ms) = std::mem::take(&mut *chain.lock().unwrap());
+    let mut probe = ServerProbe {
+        redirects,
+        redirect_error,
+        redirect_ms,
+        ..probe
+    };
+
+    let last_byte = probe
+        .total_size
+        .filter(|total| *total > 1)
+        .map(|total| total - 1);
+    if let (true, Some(true), Some(last)) = (check_ranges, probe.ranges, last_byte) {
+        // Redirects were already checked; the final URL is asked directly
+        let target = probe.final_url.clone().unwrap_or_else(|| url.to_string());
+        let range = format!("bytes={}-{}", last, last);
+        let check = send_probe(
+            &target,
+            proxy_url,
+            request,
+            reqwest::redirect::Policy::none(),
+            &range,
+        )
+        .await;
+        if check.ranges == Some(false) {
+            log::info!(
+                "{} honours byte ranges only from the start of the file",
+                url
+            );
+            probe.ranges = Some(false);
+            probe.broken_ranges = true;
+        }
+    }
+    probe
+}
+
+async fn send_probe(
+    url: &str,
+    proxy_url: &str,
+    request: &ProbeRequest,
+    policy: reqwest::redirect::Policy,
+    range: &str,
+) -> ServerProbe {
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .user_agent(request.user_agent.as_str())
+        .redirect(policy)
+        .danger_accept_invalid_certs(request.accept_invalid_certs);
+    if !proxy_url.is_empty() {
+        match reqwest::Proxy::all(proxy_url) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(_) => return ServerProbe::default(),
+        }
+    }
+    let Ok(client) = builder.build() else {
+        return ServerProbe::default();
+    };
+    let mut get = client.get(url);
+    if let Some(referer) = &request.referer {
+        get = get.header(reqwest::header::REFERER, referer);
+    }
+    for (name, value) in &request.headers {
+        get = get.header(name.as_str(), value.as_str());
+    }
+    let started = Instant::now();
+    let response = match get.header(reqwest::header::RANGE, range).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            log::debug!("Range probe for {} failed: {}", url, e);
+            return ServerProbe::default();
+        }
+    };
+    // `send` returns once the headers are in
+    let ttfb_ms = started.elapsed().as_millis() as u64;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let accept_ranges = header(reqwest::header::ACCEPT_RANGES);
+    let content_range = header(reqwest::header::CONTENT_RANGE);
+    let status = response.status().as_u16();
+    // An error page's name and location say nothing about the file
+    let success = response.status().is_success();
+    let content_disposition = header(reqwest::header::CONTENT_DISPOSITION).filter(|_| success);
+    let final_url =
+        Some(response.url().to_string()).filter(|final_url| success && final_url != url);
+    // Dropping the response closes the body without reading it
+    ServerProbe {
+        ranges: range_support(status, accept_ranges.as_deref()),
+        total_size: match status {
+            206 => content_range.as_deref().and_then(content_range_total),
+            200 => response.content_length().filter(|len| *len > 0),
+            _ => None,
+        },
+        final_url,
+        content_disposition,
+        ttfb_ms: Some(ttfb_ms),
+        etag: header(reqwest::header::ETAG).filter(|_| success),
+        ..Default::default()
+    }
+}
+
+/// Interpret the status of a ranged request.
+fn range_support(status: u16, accept_ranges: Option<&str>) -> Option<bool> {
+    match status {
+        206 => Some(true),
+        // The whole body instead of the one byte asked for
+        200 => Some(false),
+        _ if accept_ranges.is_some_and(|v| v.trim().eq_ignore_ascii_case("none")) => Some(false),
+        _ => None,
+    }
+}
+
+/// Total from a `Content-Range: bytes 0-0/1234` header; None for `/*`.
+fn content_range_total(value: &str) -> Option<u64> {
+    let (unit, range) = value.trim().split_once(' ')?;
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    range.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_support() {
+        assert_eq!(range_support(206, Some("bytes")), Some(true));
+        assert_eq!(range_support(206, None), Some(true));
+        assert_eq!(range_support(200, Some("bytes")), Some(false));
+        assert_eq!(range_support(200, None), Some(false));
+        assert_eq!(range_support(416, Some("None")), Some(false));
+        assert_eq!(range_support(403, None), None);
+        assert_eq!(range_support(416, Some("bytes")), None);
+    }
+
+    #[test]
+    fn test_redirect_rules() {
+        let rules = RedirectRules {
+            max_redirects: 2,
+            allow_downgrade: false,
+        };
+        assert!(rules
+            .check(0, "https://a.example/f", "https://b.example/f")
+            .is_ok());
+        assert!(rules
+            .check(1, "http://a.example/f", "http://b.example/f")
+            .is_ok());
+        assert!(rules
+            .check(2, "https://a.example/f", "https://b.example/f")
+            .is_err());
+        assert!(rules
+            .check(0, "HTTPS://a.example/f", "http://b.example/f")
+            .is_err());
+
+        let lenient = RedirectRules {
+            allow_downgrade: true,
+            ..rules
+        };
+        assert!(lenient
+            .check(0, "https://a.example/f", "http://b.example/f")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_probe_request() {
+        let settings = Settings {
+            user_agent: "gosh-dl/0.5".into(),
+            ..Default::default()
+        };
+        let options = DownloadOptions {
+            referer: Some("https://example.com/page".into()),
+            header: Some(vec![
+                "Authorization: Bearer abc".into(),
+                "Cookie:a=b".into(),
+                "junk".into(),
+            ]),
+            ..Default::default()
+        };
+        let request = ProbeRequest::new(&settings, &options);
+        assert_eq!(request.user_agent, "gosh-dl/0.5");
+        assert_eq!(request.referer.as_deref(), Some("https://example.com/page"));
+        assert_eq!(
+            request.headers,
+            vec![
+                ("Authorization".to_string(), "Bearer abc".to_string()),
+                ("Cookie".to_string(), "a=b".to_string())
+            ]
+        );
+
+        let options = DownloadOptions {
+            user_agent: Some("curl/8".into()),
+            ..Default::default()
+        };
+        assert_eq!(ProbeRequest::new(&settings, &options).user_agent, "curl/8");
+    }
+
+    #[test]
+    fn test_content_range_total() {
+        assert_eq!(content_range_total("bytes 0-0/1234"), Some(1234));
+        assert_eq!(content_range_total("Bytes 0-0/ 77"), Some(77));
+        assert_eq!(content_range_total("bytes 0-0/*"), None);
+        assert_eq!(content_range_total("items 0-0/10"), None);
+        assert_eq!(content_range_total("garbage"), None);
+    }
+}
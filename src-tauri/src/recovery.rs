@@ -0,0 +1,131 @@
+//! Crash recovery. A marker file is written to the data directory when a
+//! session starts and removed on clean shutdown, so finding one at startup
+//! means the previous session was killed or crashed. Downloads it had
+//! running are held paused until the user picks which to restore.
+
+use crate::db::Database;
+use crate::engine_adapter::EngineAdapter;
+use crate::types::{Download, DownloadState, InterruptedDownload};
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionMarker {
+    started_at: DateTime<Utc>,
+    pid: u32,
+}
+
+/// Settings-table key holding when the app last shut down cleanly
+/// (RFC 3339).
+pub const CLEAN_SHUTDOWN_KEY: &str = "last_clean_shutdown";
+
+/// The previous session left its marker behind.
+#[derive(Debug)]
+pub struct UncleanShutdown {
+    /// None if the marker couldn't be parsed (e.g. cut short by the crash)
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// Write this session's marker, reporting whether the last session's was
+/// still there.
+pub fn begin_session(data_dir: &Path) -> Result<Option<UncleanShutdown>> {
+    let path = crate::paths::session_marker(data_dir);
+    let previous = match std::fs::read_to_string(&path) {
+        Ok(contents) => Some(UncleanShutdown {
+            started_at: serde_json::from_str::<SessionMarker>(&contents)
+                .ok()
+                .map(|m| m.started_at),
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let marker = SessionMarker {
+        started_at: Utc::now(),
+        pid: std::process::id(),
+    };
+    std::fs::write(&path, serde_json::to_string(&marker)?)?;
+    Ok(previous)
+}
+
+/// Remove the marker; the next start won't offer recovery.
+pub fn end_session(data_dir: &Path) {
+    match std::fs::remove_file(crate::paths::session_marker(data_dir)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Failed to remove session marker: {}", e),
+    }
+}
+
+/// Whether a stored download was transferring or queued when last saved.
+/// Paused downloads stay paused anyway, so there is nothing to ask about.
+pub fn was_running(download: &Download) -> bool {
+    matches!(
+        download.status,
+        DownloadState::Active | DownloadState::Waiting | DownloadState::WaitingForNetwork
+    )
+}
+
+/// Find the downloads the crashed session had running, from the app
+/// database, and pause the ones the engine brought back so nothing starts
+/// before the user has chosen.
+pub async fn hold_interrupted(
+    db: &Database,
+    adapter: &EngineAdapter,
+) -> Result<Vec<InterruptedDownload>> {
+    let mut interrupted = Vec::new();
+    for download in db.get_incomplete_downloads_async().await? {
+        if !was_running(&download) {
+            continue;
+        }
+        let engine_status = adapter.get_status(&download.gid).map(|d| d.status);
+        if matches!(
+            engine_status,
+            Some(DownloadState::Active | DownloadState::Waiting)
+        ) {
+            if let Err(e) = adapter.pause(&download.gid).await {
+                log::warn!(
+                    "Failed to hold interrupted download {}: {}",
+                    download.gid,
+                    e
+                );
+            }
+        }
+        interrupted.push(InterruptedDownload {
+            gid: download.gid,
+            name: download.name,
+            download_type: download.download_type,
+            total_size: download.total_size,
+            completed_size: download.completed_size,
+            save_path: download.save_path,
+            in_engine: engine_status.is_some(),
+        });
+    }
+    Ok(interrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_marker() {
+        let dir = std::env::temp_dir().join(format!("gosh-fetch-recovery-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        end_session(&dir);
+
+        assert!(begin_session(&dir).unwrap().is_none());
+        // Starting again without ending the session looks like a crash
+        let unclean = begin_session(&dir).unwrap().unwrap();
+        assert!(unclean.started_at.is_some());
+
+        end_session(&dir);
+        assert!(begin_session(&dir).unwrap().is_none());
+
+        std::fs::write(crate::paths::session_marker(&dir), "{\"startedAt\":").unwrap();
+        assert!(begin_session(&dir).unwrap().unwrap().started_at.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+//! Rename rules: a finished HTTP download's file is renamed by the first
+//! rule whose category and host match it and whose pattern is found in
+//! its name, so "Show.Name.S01E02.720p.WEB.mkv" can land as
+//! "Show.Name - S01E02.mkv". Each match of the pattern is replaced with
+//! the rule's replacement, which takes `$1`-style groups and these tokens:
+//!
+//! - `{name}`: the file name without its extension
+//! - `{ext}`: the extension, without the dot
+//! - `{title}`: `{name}` with dots and underscores as spaces
+//! - `{date}`: the day it finished, `YYYY-MM-DD`
+//! - `{host}`: the host it was downloaded from
+//! - `{category}`: the category it was saved under
+//!
+//! Torrents are never renamed, since they seed from their files. The new
+//! path is kept in the database so the download is still found there
+//! after a restart.
+
+use crate::types::{DownloadType, RenamePreview, RenameRule};
+use crate::{AppState, Error, Result};
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Settings-table key holding the rename rules (JSON).
+pub const RENAME_RULES_KEY: &str = "rename_rules";
+
+/// What a rule is matched against.
+#[derive(Debug, Clone, Copy)]
+pub struct RenameTarget<'a> {
+    pub name: &'a str,
+    pub url: Option<&'a str>,
+    pub category: &'a str,
+    pub date: NaiveDate,
+}
+
+fn compile(find: &str) -> Result<Regex> {
+    let source = if find.is_empty() { "^.*$" } else { find };
+    RegexBuilder::new(source)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Error::InvalidInput(format!("Invalid rename pattern '{}': {}", find, e)))
+}
+
+/// Check rules from the frontend: trimmed, uniquely named, with a pattern
+/// that compiles and a replacement.
+pub fn normalize_rules(rules: Vec<RenameRule>) -> Result<Vec<RenameRule>> {
+    let mut names = HashSet::new();
+    rules
+        .into_iter()
+        .map(|rule| {
+            let name = rule.name.trim().to_string();
+            if name.is_empty() {
+                return Err(Error::InvalidInput(
+                    "Rename rule name cannot be empty".into(),
+                ));
+            }
+            if !names.insert(name.to_lowercase()) {
+                return Err(Error::InvalidInput(format!(
+                    "Duplicate rename rule: {}",
+                    name
+                )));
+            }
+            compile(&rule.find)?;
+            if rule.replace.trim().is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "Rename rule '{}' needs a replacement",
+                    name
+                )));
+            }
+            Ok(RenameRule {
+                name,
+                category: rule.category.trim().to_string(),
+                host: rule
+                    .host
+                    .trim()
+                    .trim_start_matches('.')
+                    .to_ascii_lowercase(),
+                find: rule.find,
+                replace: rule.replace,
+            })
+        })
+        .collect()
+}
+
+fn applies(rule: &RenameRule, target: &RenameTarget, host: &str) -> bool {
+    (rule.category.is_empty() || rule.category.eq_ignore_ascii_case(target.category))
+        && (rule.host.is_empty() || host == rule.host || host.ends_with(&format!(".{}", rule.host)))
+}
+
+/// The replacement with its tokens filled in, `$` in their values escaped.
+fn expand(replace: &str, target: &RenameTarget, host: &str) -> String {
+    let (stem, ext) = match target.name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, ext),
+        _ => (target.name, ""),
+    };
+    let title = stem
+        .replace(['.', '_'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let tokens = [
+        ("{name}", stem.to_string()),
+        ("{ext}", ext.to_string()),
+        ("{title}", title),
+        ("{date}", target.date.format("%Y-%m-%d").to_string()),
+        ("{host}", host.to_string()),
+        ("{category}", target.category.to_string()),
+    ];
+    tokens
+        .iter()
+        .fold(replace.to_string(), |out, (token, value)| {
+            out.replace(token, &value.replace('$', "$$"))
+        })
+}
+
+/// The name the first matching rule gives `target`, with the rule's name.
+/// None if no rule matches or the name would stay the same.
+pub fn rename(rules: &[RenameRule], target: &RenameTarget) -> Option<RenamePreview> {
+    let host = target
+        .url
+        .and_then(crate::host_profiles::url_host)
+        .unwrap_or_default();
+    let (rule, regex) = rules
+        .iter()
+        .filter(|rule| applies(rule, target, &host))
+        .filter_map(|rule| compile(&rule.find).ok().map(|regex| (rule, regex)))
+        .find(|(_, regex)| regex.is_match(target.name))?;
+    let renamed = regex.replace_all(target.name, expand(&rule.replace, target, &host).as_str());
+    let renamed = renamed.replace(['/', '\\'], "_").trim().to_string();
+    (!renamed.is_empty() && renamed != target.name && renamed != "." && renamed != "..").then(
+        || RenamePreview {
+            rule: rule.name.clone(),
+            name: renamed,
+        },
+    )
+}
+
+pub async fn load_rules(state: &AppState) -> Result<Vec<RenameRule>> {
+    match state
+        .get_db()
+        .await?
+        .get_meta_async(RENAME_RULES_KEY)
+        .await?
+    {
+        Some(rules) => Ok(serde_json::from_str(&rules)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Rename a finished HTTP download's file by the rules, in the directory
+/// it ended up in. A name already taken gets a ` (n)` suffix; a failed
+/// rename leaves the file as it is and flags the download.
+pub async fn finish_download(state: &AppState, gid: &str) -> Result<()> {
+    let rules = load_rules(state).await?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let adapter = state.get_adapter().await?;
+    let Some(download) = adapter
+        .get_status(gid)
+        .filter(|d| d.download_type == DownloadType::Http)
+    else {
+        return Ok(());
+    };
+    let Some(source) = crate::preview::locate_file(&download, &[]) else {
+        return Ok(());
+    };
+    let target = RenameTarget {
+        name: &download.name,
+        url: download.url.as_deref(),
+        category: &download.category,
+        date: chrono::Local::now().date_naive(),
+    };
+    let Some(renamed) = rename(&rules, &target) else {
+        return Ok(());
+    };
+    let dir = source.parent().map(PathBuf::from).unwrap_or_default();
+    let dest = crate::staging::unique_destination(&dir, &renamed.name);
+    if let Err(e) = std::fs::rename(&source, &dest) {
+        log::warn!(
+            "Failed to rename {} by rule '{}': {}",
+            download.name,
+            renamed.rule,
+            e
+        );
+        adapter.flag_warning(gid, format!("Could not rename to {}: {}", renamed.name, e));
+        return Ok(());
+    }
+    log::info!(
+        "Renamed {} to {} by rule '{}'",
+        download.name,
+        dest.display(),
+        renamed.rule
+    );
+    adapter.set_moved(gid, dest.clone());
+    state
+        .get_db()
+        .await?
+        .save_rename_async(gid.to_string(), dest.to_string_lossy().to_string())
+        .await
+}
+
+/// Report renamed downloads at their new paths again after a restart, and
+/// drop the records of downloads the engine no longer has. Runs after the
+/// temp directory moves are re-applied, which renames override.
+pub async fn restore(state: &AppState) -> Result<()> {
+    let db = state.get_db().await?;
+    let adapter = state.get_adapter().await?;
+    for (gid, path) in db.get_renames_async().await? {
+        match adapter.get_status(&gid) {
+            Some(_) => adapter.set_moved(&gid, PathBuf::from(path)),
+            None => db.delete_rename_async(gid).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Path a download was renamed to.
+pub async fn renamed_path(state: &AppState, gid: &str) -> Result<Option<PathBuf>> {
+    let renames = state.get_db().await?.get_renames_async().await?;
+    Ok(renames
+        .into_iter()
+        .find(|(g, _)| g == gid)
+        .map(|(_, path)| PathBuf::from(path)))
+}
+
+pub async fn forget(state: &AppState, gid: &str) -> Result<()> {
+    state
+        .get_db()
+        .await?
+        .delete_rename_async(gid.to_string())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, category: &str, host: &str, find: &str, replace: &str) -> RenameRule {
+        RenameRule {
+            name: name.into(),
+            category: category.into(),
+            host: host.into(),
+            find: find.into(),
+            replace: replace.into(),
+        }
+    }
+
+    fn target<'a>(name: &'a str, url: Option<&'a str>, category: &'a str) -> RenameTarget<'a> {
+        RenameTarget {
+            name,
+            url,
+            category,
+            date: NaiveDate::from_ymd_opt(2026, 3, 14).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_rename() {
+        let rules = vec![
+            rule(
+                "Episodes",
+                "TV",
+                "",
+                r"\.(S\d+E\d+)\..*\.(\w+)$",
+                " - $1.$2",
+            ),
+            rule("Movies", "Movies", "", "", "{title}.{ext}"),
+            rule(
+                "Reports",
+                "",
+                "reports.example.com",
+                "",
+                "{date} {name}.{ext}",
+            ),
+        ];
+        let episode = rename(&rules, &target("Show.Name.S01E02.720p.WEB.mkv", None, "tv")).unwrap();
+        assert_eq!(
+            (episode.rule.as_str(), episode.name.as_str()),
+            ("Episodes", "Show.Name - S01E02.mkv")
+        );
+        assert_eq!(
+            rename(&rules, &target("Some_Movie.2024.mkv", None, "Movies"))
+                .unwrap()
+                .name,
+            "Some Movie 2024.mkv"
+        );
+        assert_eq!(
+            rename(&rules, &target("Some Movie 2024.mkv", None, "Movies")),
+            None
+        );
+        assert_eq!(
+            rename(&rules, &target("Show.Name.S01E02.720p.WEB.mkv", None, "")),
+            None
+        );
+
+        let url = Some("https://eu.reports.example.com/q1.pdf?id=4");
+        assert_eq!(
+            rename(&rules, &target("q1.pdf", url, "")).unwrap().name,
+            "2026-03-14 q1.pdf"
+        );
+        assert_eq!(
+            rename(
+                &rules,
+                &target("q1.pdf", Some("https://example.com/q1.pdf"), "")
+            ),
+            None
+        );
+
+        // Token values can't inject groups or paths
+        let rules = vec![rule("Host", "", "", "", "{host}/{name}")];
+        let renamed = rename(
+            &rules,
+            &target("a$1.txt", Some("https://cdn.example.org/a"), ""),
+        )
+        .unwrap();
+        assert_eq!(renamed.name, "cdn.example.org_a$1");
+    }
+
+    #[test]
+    fn test_normalize_rules() {
+        let rules = normalize_rules(vec![rule(
+            " Tidy ",
+            " TV ",
+            ".Example.COM",
+            "",
+            "{title}.{ext}",
+        )])
+        .unwrap();
+        assert_eq!(
+            rules,
+            vec![rule("Tidy", "TV", "example.com", "", "{title}.{ext}")]
+        );
+        assert!(normalize_rules(vec![rule("Bad", "", "", "(", "x")]).is_err());
+        assert!(normalize_rules(vec![rule("Empty", "", "", "x", " ")]).is_err());
+        assert!(
+            normalize_rules(vec![rule("A", "", "", "", "x"), rule("a", "", "", "", "y")]).is_err()
+        );
+    }
+}
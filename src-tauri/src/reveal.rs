@@ -0,0 +1,181 @@
+//! Opening downloads from the app: the file itself with its default app,
+//! its folder, or the file selected in the file manager. Paths arrive from
+//! the webview, so before anything is handed to the OS they are checked
+//! against the configured places downloads are saved: the download
+//! directory, the temp directory, named locations and category folders.
+//! A download saved anywhere else can still be opened by GID, where the
+//! path comes from its own record rather than the webview. A target that
+//! is gone is reported as `Error::FileMissing` rather than a generic
+//! failure.
+
+use crate::categories::resolve_directory;
+use crate::db::{expand_tilde, Settings};
+use crate::types::{CategoryRule, Download, DownloadLocation};
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directories downloads may be opened from, canonicalized; ones that
+/// don't exist (a detached drive) are left out.
+pub fn download_roots(
+    settings: &Settings,
+    locations: &[DownloadLocation],
+    rules: &[CategoryRule],
+) -> Vec<PathBuf> {
+    let mut roots = vec![PathBuf::from(&settings.download_path)];
+    if !settings.temp_download_path.is_empty() {
+        roots.push(PathBuf::from(&settings.temp_download_path));
+    }
+    roots.extend(
+        locations
+            .iter()
+            .map(|l| PathBuf::from(expand_tilde(&l.path))),
+    );
+    roots.extend(
+        rules
+            .iter()
+            .map(|r| resolve_directory(&settings.download_path, &r.directory)),
+    );
+
+    let mut canonical: Vec<PathBuf> = roots
+        .into_iter()
+        .filter(|root| !root.as_os_str().is_empty())
+        .filter_map(|root| root.canonicalize().ok())
+        .collect();
+    canonical.sort();
+    canonical.dedup();
+    canonical
+}
+
+/// Canonical form of `path` if it is inside one of `roots`.
+pub fn check_target(path: &str, roots: &[PathBuf]) -> Result<PathBuf> {
+    if path.is_empty() {
+        return Err(Error::InvalidInput("Path cannot be empty".into()));
+    }
+    if path.contains("://") {
+        return Err(Error::InvalidInput(
+            "URL schemes are not allowed in file paths".into(),
+        ));
+    }
+    let canonical = Path::new(path).canonicalize().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Error::FileMissing(path.to_string()),
+        _ => Error::InvalidInput(format!("Path is inaccessible: {}", path)),
+    })?;
+    if !roots.iter().any(|root| canonical.starts_with(root)) {
+        return Err(Error::InvalidInput(format!(
+            "Not inside a download directory: {}",
+            path
+        )));
+    }
+    Ok(canonical)
+}
+
+/// What a download put on disk: its file, or the root folder of a
+/// multi-file torrent.
+pub fn download_target(download: &Download) -> PathBuf {
+    Path::new(&download.save_path).join(&download.name)
+}
+
+/// Open a file with the app the OS associates with it, or a folder in the
+/// file manager. The path goes to the launcher as a single argument, never
+/// through a shell.
+pub fn open(path: &Path) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    Command::new("xdg-open").arg(path).spawn()?;
+
+    #[cfg(target_os = "macos")]
+    Command::new("open").arg(path).spawn()?;
+
+    #[cfg(target_os = "windows")]
+    Command::new("explorer").arg(path).spawn()?;
+
+    Ok(())
+}
+
+/// Show `path` in the file manager, selected where the platform can do
+/// that. Linux file managers have no common way, so there the folder
+/// holding it is opened.
+pub fn reveal(path: &Path) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let folder = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        };
+        Command::new("xdg-open").arg(folder).spawn()?;
+    }
+
+    #[cfg(target_os = "macos")]
+    Command::new("open").arg("-R").arg(path).spawn()?;
+
+    #[cfg(target_os = "windows")]
+    Command::new("explorer").arg("/select,").arg(path).spawn()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gosh-fetch-reveal-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rule(name: &str, directory: &str) -> CategoryRule {
+        CategoryRule {
+            name: name.into(),
+            directory: directory.into(),
+            extensions: Vec::new(),
+            mime_types: Vec::new(),
+            archive_after_days: None,
+            archive_directory: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_target() {
+        let dir = temp_dir("target");
+        let root = dir.join("downloads");
+        std::fs::create_dir_all(root.join("show")).unwrap();
+        std::fs::write(root.join("show/e01.mkv"), b"x").unwrap();
+        std::fs::write(dir.join("secret.txt"), b"x").unwrap();
+        let roots = vec![root.canonicalize().unwrap()];
+
+        let inside = root.join("show/e01.mkv");
+        assert!(check_target(inside.to_str().unwrap(), &roots).is_ok());
+        let escaping = root.join("../secret.txt");
+        assert!(matches!(
+            check_target(escaping.to_str().unwrap(), &roots),
+            Err(Error::InvalidInput(_))
+        ));
+        let gone = root.join("show/e02.mkv");
+        assert!(matches!(
+            check_target(gone.to_str().unwrap(), &roots),
+            Err(Error::FileMissing(_))
+        ));
+        assert!(check_target("file:///etc/passwd", &roots).is_err());
+        assert!(check_target("", &roots).is_err());
+    }
+
+    #[test]
+    fn test_download_roots() {
+        let dir = temp_dir("roots");
+        std::fs::create_dir_all(dir.join("Videos")).unwrap();
+        let settings = Settings {
+            download_path: dir.to_string_lossy().to_string(),
+            temp_download_path: String::new(),
+            ..Default::default()
+        };
+        let rules = vec![rule("Videos", "Videos"), rule("Music", "Music")];
+        let roots = download_roots(&settings, &[], &rules);
+        let base = dir.canonicalize().unwrap();
+        // Music doesn't exist yet
+        assert_eq!(roots, vec![base.clone(), base.join("Videos")]);
+    }
+}
@@ -0,0 +1,388 @@
+//! Version-keeping for repeatedly fetched files.
+//!
+//! gosh-dl finalizes HTTP downloads under the name it resolves and has no
+//! notion of earlier copies, so the existing file is moved aside right
+//! before the download is handed to the engine. The policy is only settled
+//! once the download finishes: the moved copy is then deleted (overwrite)
+//! or old copies pruned (keep last). If the download fails or is removed
+//! first, the copy is moved back, so a bad fetch never costs the last good
+//! one. Pending rotations are kept in memory; after a restart a moved copy
+//! simply stays as a timestamped version.
+
+use crate::types::FileRotation;
+use crate::AppState;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A previous copy moved aside for a download that hasn't finished yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRotation {
+    pub target: PathBuf,
+    pub moved_to: PathBuf,
+    pub policy: FileRotation,
+    /// Moved back to `target` after the download failed
+    pub restored: bool,
+}
+
+impl PendingRotation {
+    /// Apply the policy now that the new copy is in place.
+    pub fn complete(&self) -> std::io::Result<()> {
+        if self.restored {
+            return Ok(());
+        }
+        match &self.policy {
+            FileRotation::Overwrite => {
+                std::fs::remove_file(&self.moved_to)?;
+                log::info!("Removed previous copy of {}", self.target.display());
+            }
+            FileRotation::TimestampSuffix => {}
+            FileRotation::KeepLast { count } => {
+                if let (Some(dir), Some(name)) = (self.target.parent(), self.target.file_name()) {
+                    prune_versions(dir, &name.to_string_lossy(), *count as usize)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the previous copy back, unless something else took its name.
+    pub fn restore(&mut self) -> std::io::Result<()> {
+        if self.restored || self.target.exists() || !self.moved_to.exists() {
+            return Ok(());
+        }
+        std::fs::rename(&self.moved_to, &self.target)?;
+        self.restored = true;
+        log::info!("Moved previous copy of {} back", self.target.display());
+        Ok(())
+    }
+
+    /// Move a restored copy aside again, for a failed download that is
+    /// being resumed.
+    pub fn redo(&mut self) -> std::io::Result<()> {
+        if !self.restored || !self.target.is_file() {
+            return Ok(());
+        }
+        let (Some(dir), Some(name)) = (self.target.parent(), self.target.file_name()) else {
+            return Ok(());
+        };
+        self.moved_to = rotate_aside(dir, &name.to_string_lossy())?;
+        self.restored = false;
+        Ok(())
+    }
+}
+
+/// Move `dir/filename` aside if that file already exists, to be settled
+/// by `policy` once the download finishes.
+pub fn rotate_existing(
+    dir: &Path,
+    filename: &str,
+    policy: &FileRotation,
+) -> std::io::Result<Option<PendingRotation>> {
+    let target = dir.join(filename);
+    if !target.is_file() {
+        return Ok(None);
+    }
+    let moved_to = rotate_aside(dir, filename)?;
+    Ok(Some(PendingRotation {
+        target,
+        moved_to,
+        policy: policy.clone(),
+        restored: false,
+    }))
+}
+
+/// Settle the rotation of a download that finished.
+pub fn finish_download(state: &AppState, gid: &str) {
+    let Some(rotation) = state.rotations().lock().unwrap().remove(gid) else {
+        return;
+    };
+    if let Err(e) = rotation.complete() {
+        log::warn!(
+            "Failed to settle previous copies of {}: {}",
+            rotation.target.display(),
+            e
+        );
+    }
+}
+
+/// Put the previous copy back for a download that failed. The rotation
+/// stays pending in case the download is resumed.
+pub fn restore(state: &AppState, gid: &str) {
+    if let Some(rotation) = state.rotations().lock().unwrap().get_mut(gid) {
+        if let Err(e) = rotation.restore() {
+            log::warn!(
+                "Failed to restore previous copy of {}: {}",
+                rotation.target.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Move the previous copy aside again for a failed download being resumed.
+pub fn resume(state: &AppState, gid: &str) -> std::io::Result<()> {
+    match state.rotations().lock().unwrap().get_mut(gid) {
+        Some(rotation) => rotation.redo(),
+        None => Ok(()),
+    }
+}
+
+/// Put the previous copy back for a download that was removed.
+pub fn forget(state: &AppState, gid: &str) {
+    let Some(mut rotation) = state.rotations().lock().unwrap().remove(gid) else {
+        return;
+    };
+    if let Err(e) = rotation.restore() {
+        log::warn!(
+            "Failed to restore previous copy of {}: {}",
+            rotation.target.display(),
+            e
+        );
+    }
+}
+
+/// Move `dir/filename` to a timestamped sibling, e.g. `report.20261016-101500.pdf`.
+fn rotate_aside(dir: &Path, filename: &str) -> std::io::Result<PathBuf> {
+    let (stem, ext) = split_name(filename);
+    let timestamp = Local::now().format(TIMESTAMP_FORMAT).to_string();
+
+    let mut rotated = dir.join(format!("{}.{}{}", stem, timestamp, ext));
+    let mut n = 2;
+    while rotated.exists() {
+        rotated = dir.join(format!("{}.{}-{}{}", stem, timestamp, n, ext));
+        n += 1;
+    }
+
+    std::fs::rename(dir.join(filename), &rotated)?;
+    log::info!(
+        "Rotated previous copy of {} to {}",
+        filename,
+        rotated.display()
+    );
+    Ok(rotated)
+}
+
+/// Delete the oldest rotated copies of `filename` so that at most `keep` remain.
+fn prune_versions(dir: &Path, filename: &str, keep: usize) -> std::io::Result<()> {
+    let mut versions = list_versions(dir, filename)?;
+    if versions.len() <= keep {
+        return Ok(());
+    }
+    versions.sort();
+    let excess = versions.len() - keep;
+    for version in versions.into_iter().take(excess) {
+        std::fs::remove_file(&version.path)?;
+        log::info!("Pruned old version {}", version.path.display());
+    }
+    Ok(())
+}
+
+/// A rotated copy, ordered oldest first. Copies moved aside within the
+/// same second get a `-2`, `-3`, ... suffix after the timestamp, which
+/// doesn't sort by name (`-2` before `.`, `-10` before `-2`), so the
+/// suffix is compared as a number.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    timestamp: String,
+    /// 1 for the first copy of its second
+    n: u32,
+    path: PathBuf,
+}
+
+/// Rotated copies of `filename` in `dir` (not including the file itself).
+fn list_versions(dir: &Path, filename: &str) -> std::io::Result<Vec<Version>> {
+    let (stem, ext) = split_name(filename);
+    let prefix = format!("{}.", stem);
+
+    let mut versions = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(middle) = name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(ext))
+        else {
+            continue;
+        };
+        let Some((timestamp, n)) = parse_version(middle) else {
+            continue;
+        };
+        if entry.path().is_file() {
+            versions.push(Version {
+                timestamp: timestamp.to_string(),
+                n,
+                path: entry.path(),
+            });
+        }
+    }
+    Ok(versions)
+}
+
+/// Split the part of a rotated name between stem and extension into its
+/// timestamp and collision number, e.g. `20261016-101500-2`.
+fn parse_version(middle: &str) -> Option<(&str, u32)> {
+    let timestamp = middle.get(..15)?;
+    chrono::NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    let n = match &middle[15..] {
+        "" => 1,
+        rest => rest.strip_prefix('-')?.parse().ok()?,
+    };
+    Some((timestamp, n))
+}
+
+/// Split a filename into stem and extension (with its leading dot).
+fn split_name(filename: &str) -> (&str, &str) {
+    match filename.rfind('.') {
+        Some(i) if i > 0 => (&filename[..i], &filename[i..]),
+        _ => (filename, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gosh-fetch-rotation-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_split_name() {
+        assert_eq!(split_name("file.zip"), ("file", ".zip"));
+        assert_eq!(split_name("archive.tar.gz"), ("archive.tar", ".gz"));
+        assert_eq!(split_name("README"), ("README", ""));
+        assert_eq!(split_name(".bashrc"), (".bashrc", ""));
+    }
+
+    #[test]
+    fn test_missing_file_is_noop() {
+        let dir = temp_dir("missing");
+        let result = rotate_existing(&dir, "none.txt", &FileRotation::Overwrite).unwrap();
+        assert!(result.is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_overwrite_removes_existing_once_done() {
+        let dir = temp_dir("overwrite");
+        std::fs::write(dir.join("data.csv"), "old").unwrap();
+        let rotation = rotate_existing(&dir, "data.csv", &FileRotation::Overwrite)
+            .unwrap()
+            .unwrap();
+        assert!(!dir.join("data.csv").exists());
+        assert_eq!(std::fs::read_to_string(&rotation.moved_to).unwrap(), "old");
+
+        rotation.complete().unwrap();
+        assert!(list_versions(&dir, "data.csv").unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_timestamp_suffix_moves_existing() {
+        let dir = temp_dir("timestamp");
+        std::fs::write(dir.join("data.csv"), "old").unwrap();
+        let rotation = rotate_existing(&dir, "data.csv", &FileRotation::TimestampSuffix)
+            .unwrap()
+            .unwrap();
+        assert!(!dir.join("data.csv").exists());
+        assert_eq!(std::fs::read_to_string(&rotation.moved_to).unwrap(), "old");
+        rotation.complete().unwrap();
+        assert_eq!(list_versions(&dir, "data.csv").unwrap().len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_keep_last_prunes_oldest() {
+        let dir = temp_dir("keep_last");
+        std::fs::write(dir.join("data.20200101-000000.csv"), "oldest").unwrap();
+        std::fs::write(dir.join("data.20210101-000000.csv"), "older").unwrap();
+        std::fs::write(dir.join("data.csv"), "current").unwrap();
+        std::fs::write(dir.join("data.backup.csv"), "unrelated").unwrap();
+
+        let rotation = rotate_existing(&dir, "data.csv", &FileRotation::KeepLast { count: 2 })
+            .unwrap()
+            .unwrap();
+        // Nothing is pruned until the new copy is in
+        assert_eq!(list_versions(&dir, "data.csv").unwrap().len(), 3);
+        std::fs::write(dir.join("data.csv"), "new").unwrap();
+        rotation.complete().unwrap();
+
+        let versions = list_versions(&dir, "data.csv").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(!dir.join("data.20200101-000000.csv").exists());
+        assert!(dir.join("data.20210101-000000.csv").exists());
+        assert!(dir.join("data.backup.csv").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_keep_last_orders_same_second_copies() {
+        let dir = temp_dir("same_second");
+        std::fs::write(dir.join("data.20200101-120000.csv"), "first").unwrap();
+        std::fs::write(dir.join("data.20200101-120000-2.csv"), "second").unwrap();
+        std::fs::write(dir.join("data.20200101-120000-10.csv"), "tenth").unwrap();
+        std::fs::write(dir.join("data.csv"), "current").unwrap();
+
+        let rotation = rotate_existing(&dir, "data.csv", &FileRotation::KeepLast { count: 2 })
+            .unwrap()
+            .unwrap();
+        std::fs::write(dir.join("data.csv"), "new").unwrap();
+        rotation.complete().unwrap();
+
+        assert_eq!(list_versions(&dir, "data.csv").unwrap().len(), 2);
+        assert!(!dir.join("data.20200101-120000.csv").exists());
+        assert!(!dir.join("data.20200101-120000-2.csv").exists());
+        assert!(dir.join("data.20200101-120000-10.csv").exists());
+        assert!(rotation.moved_to.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("20261016-101500"),
+            Some(("20261016-101500", 1))
+        );
+        assert_eq!(
+            parse_version("20261016-101500-12"),
+            Some(("20261016-101500", 12))
+        );
+        assert_eq!(parse_version("20261016-1015"), None);
+        assert_eq!(parse_version("20261016-101500-"), None);
+        assert_eq!(parse_version("2026-10-16-1015"), None);
+    }
+
+    #[test]
+    fn test_failed_download_restores_copy() {
+        let dir = temp_dir("restore");
+        std::fs::write(dir.join("data.csv"), "good").unwrap();
+        let mut rotation = rotate_existing(&dir, "data.csv", &FileRotation::Overwrite)
+            .unwrap()
+            .unwrap();
+
+        rotation.restore().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("data.csv")).unwrap(),
+            "good"
+        );
+        assert!(list_versions(&dir, "data.csv").unwrap().is_empty());
+        // A restored copy survives completing the rotation
+        rotation.complete().unwrap();
+        assert!(dir.join("data.csv").exists());
+
+        // Resumed: aside again, then settled
+        rotation.redo().unwrap();
+        assert!(!dir.join("data.csv").exists());
+        rotation.complete().unwrap();
+        assert!(list_versions(&dir, "data.csv").unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+//! Upload totals per torrent, kept across restarts. The engine reports
+//! only current upload speeds, so the app samples them every few seconds
+//! and adds up what was sent and how long each torrent has been seeding.
+//! The totals drive the ratio and seed-time limits of the auto-stop check.
+
+use crate::types::{Download, DownloadState, DownloadType, SeedingStats};
+use std::collections::{HashMap, HashSet};
+
+/// Totals of one torrent as stored in the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeedTotals {
+    pub uploaded: u64,
+    pub seed_secs: u64,
+    /// Bytes of the torrent downloaded, the ratio's denominator
+    pub size: u64,
+}
+
+impl SeedTotals {
+    pub fn stats(&self) -> SeedingStats {
+        SeedingStats {
+            uploaded: self.uploaded,
+            ratio: ratio(self.uploaded, self.size),
+            seed_time_secs: self.seed_secs,
+        }
+    }
+
+    /// How much these totals grew since `previous`. A torrent re-added
+    /// from scratch counts from zero rather than going negative.
+    pub fn since(&self, previous: &SeedTotals) -> SeedTotals {
+        SeedTotals {
+            uploaded: self.uploaded.saturating_sub(previous.uploaded),
+            seed_secs: self.seed_secs.saturating_sub(previous.seed_secs),
+            size: self.size.saturating_sub(previous.size),
+        }
+    }
+}
+
+fn ratio(uploaded: u64, size: u64) -> f64 {
+    if size == 0 {
+        0.0
+    } else {
+        uploaded as f64 / size as f64
+    }
+}
+
+/// True for a finished torrent that is still uploading to its swarm.
+pub fn is_seeding(download: &Download) -> bool {
+    download.download_type != DownloadType::Http
+        && download.status == DownloadState::Active
+        && download.total_size > 0
+        && download.completed_size >= download.total_size
+}
+
+#[derive(Debug, Default)]
+pub struct SeedLedger {
+    totals: HashMap<String, SeedTotals>,
+    /// GIDs changed since the last `take_dirty`
+    dirty: HashSet<String>,
+}
+
+impl SeedLedger {
+    /// Start from the totals saved by an earlier session.
+    pub fn load(&mut self, totals: HashMap<String, SeedTotals>) {
+        self.totals = totals;
+        self.dirty.clear();
+    }
+
+    /// Add `elapsed_secs` at the torrents' current upload speeds.
+    pub fn sample(&mut self, downloads: &[Download], elapsed_secs: u64) {
+        for download in downloads
+            .iter()
+            .filter(|d| d.download_type != DownloadType::Http)
+        {
+            let seeding = is_seeding(download);
+            let totals = self.totals.entry(download.gid.clone()).or_default();
+            let before = *totals;
+            totals.uploaded += download.upload_speed * elapsed_secs;
+            if seeding {
+                totals.seed_secs += elapsed_secs;
+            }
+            totals.size = download.completed_size;
+            if *totals != before {
+                self.dirty.insert(download.gid.clone());
+            }
+        }
+    }
+
+    pub fn get(&self, gid: &str) -> Option<SeedTotals> {
+        self.totals.get(gid).copied()
+    }
+
+    /// Uploaded bytes and share ratio over every torrent seen, removed
+    /// ones included.
+    pub fn overall(&self) -> (u64, f64) {
+        let uploaded = self.totals.values().map(|t| t.uploaded).sum();
+        let size = self.totals.values().map(|t| t.size).sum();
+        (uploaded, ratio(uploaded, size))
+    }
+
+    /// Totals of every torrent seen, removed ones included.
+    pub fn all(&self) -> Vec<(String, SeedTotals)> {
+        self.totals
+            .iter()
+            .map(|(gid, totals)| (gid.clone(), *totals))
+            .collect()
+    }
+
+    /// Totals changed since the last call, for saving.
+    pub fn take_dirty(&mut self) -> Vec<(String, SeedTotals)> {
+        self.dirty
+            .drain()
+            .filter_map(|gid| self.totals.get(&gid).map(|totals| (gid, *totals)))
+            .collect()
+    }
+}
+
+/// Why a seeding torrent should stop: its ratio reached `ratio_limit` or
+/// it has seeded for `time_limit_secs`. Zero turns a limit off.
+pub fn limit_reached(
+    download: &Download,
+    ratio_limit: f64,
+    time_limit_secs: u64,
+) -> Option<String> {
+    let stats = download.seeding.filter(|_| is_seeding(download))?;
+    if ratio_limit > 0.0 && stats.ratio >= ratio_limit {
+        return Some(format!(
+            "Share ratio {:.2} reached the limit of {:.2}",
+            stats.ratio, ratio_limit
+        ));
+    }
+    if time_limit_secs > 0 && stats.seed_time_secs >= time_limit_secs {
+        return Some(format!(
+            "Seeded for {} minutes (limit {})",
+            stats.seed_time_secs / 60,
+            time_limit_secs / 60
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent(gid: &str, status: DownloadState, completed: u64, upload_speed: u64) -> Download {
+        Download {
+            gid: gid.into(),
+            name: gid.into(),
+            download_type: DownloadType::Torrent,
+            status,
+            total_size: 1000,
+            completed_size: completed,
+            upload_speed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut ledger = SeedLedger::default();
+        ledger.load(HashMap::from([(
+            "a".to_string(),
+            SeedTotals {
+                uploaded: 500,
+                seed_secs: 60,
+                size: 1000,
+            },
+        )]));
+        let mut http = torrent("h", DownloadState::Active, 1000, 100);
+        http.download_type = DownloadType::Http;
+        let downloads = [
+            torrent("a", DownloadState::Active, 1000, 100),
+            torrent("b", DownloadState::Active, 400, 10),
+            torrent("idle", DownloadState::Paused, 1000, 0),
+            http,
+        ];
+        ledger.sample(&downloads, 5);
+
+        assert_eq!(
+            ledger.get("a"),
+            Some(SeedTotals {
+                uploaded: 1000,
+                seed_secs: 65,
+                size: 1000
+            })
+        );
+        // Still downloading: uploads count, seed time doesn't
+        assert_eq!(
+            ledger.get("b"),
+            Some(SeedTotals {
+                uploaded: 50,
+                seed_secs: 0,
+                size: 400
+            })
+        );
+        assert_eq!(ledger.get("h"), None);
+        assert_eq!(ledger.overall(), (1050, 1050.0 / 2400.0));
+        assert_eq!(ledger.all().len(), 3);
+
+        let mut dirty: Vec<String> = ledger
+            .take_dirty()
+            .into_iter()
+            .map(|(gid, _)| gid)
+            .collect();
+        dirty.sort();
+        assert_eq!(dirty, vec!["a", "b", "idle"]);
+        ledger.sample(&downloads[2..3], 5);
+        assert!(ledger.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn test_since() {
+        let before = SeedTotals {
+            uploaded: 500,
+            seed_secs: 60,
+            size: 1000,
+        };
+        let after = SeedTotals {
+            uploaded: 800,
+            seed_secs: 90,
+            size: 1000,
+        };
+        assert_eq!(
+            after.since(&before),
+            SeedTotals {
+                uploaded: 300,
+                seed_secs: 30,
+                size: 0
+            }
+        );
+        assert_eq!(
+            before.since(&after),
+            SeedTotals {
+                uploaded: 0,
+                seed_secs: 0,
+                size: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_limit_reached() {
+        let mut seeding = torrent("a", DownloadState::Active, 1000, 0);
+        seeding.seeding = Some(SeedingStats {
+            uploaded: 1500,
+            ratio: 1.5,
+            seed_time_secs: 600,
+        });
+        assert!(limit_reached(&seeding, 1.5, 0)
+            .unwrap()
+            .contains("ratio 1.50"));
+        assert_eq!(limit_reached(&seeding, 2.0, 0), None);
+        assert!(limit_reached(&seeding, 0.0, 600)
+            .unwrap()
+            .contains("10 minutes"));
+        assert_eq!(limit_reached(&seeding, 0.0, 0), None);
+
+        let mut downloading = seeding.clone();
+        downloading.completed_size = 500;
+        assert_eq!(limit_reached(&downloading, 1.0, 60), None);
+    }
+}
@@ -0,0 +1,363 @@
+//! Checking a proposed `Settings` before it is saved, so the settings page
+//! can point at the field that is wrong instead of failing the whole save.
+//! Errors are what `db_save_settings` would refuse or what can't work at
+//! all (an unwritable download folder, a proxy URL that doesn't parse);
+//! warnings are what will probably misbehave but can be saved (a folder
+//! that doesn't exist yet, a proxy nobody answers on).
+
+use crate::db::{expand_tilde, Settings};
+use crate::types::{IssueLevel, SettingIssue};
+use crate::validation::validate_time_of_day;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long the proxy gets to accept a connection.
+const PROXY_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Port assumed for a SOCKS proxy URL without one.
+const DEFAULT_SOCKS_PORT: u16 = 1080;
+
+fn issue(issues: &mut Vec<SettingIssue>, field: &str, level: IssueLevel, message: String) {
+    issues.push(SettingIssue {
+        field: field.to_string(),
+        level,
+        message,
+    });
+}
+
+/// Everything wrong with `settings` that can be told without the network.
+pub fn check_settings(settings: &Settings) -> Vec<SettingIssue> {
+    let mut issues = Vec::new();
+
+    check_dir(&mut issues, "download_path", &settings.download_path, true);
+    if !settings.temp_download_path.trim().is_empty() {
+        check_dir(
+            &mut issues,
+            "temp_download_path",
+            &settings.temp_download_path,
+            false,
+        );
+    }
+    if settings.stats_report_enabled {
+        check_dir(
+            &mut issues,
+            "stats_report_path",
+            &settings.stats_report_path,
+            false,
+        );
+    }
+    if settings.archive_enabled {
+        check_dir(&mut issues, "archive_path", &settings.archive_path, true);
+        if settings.archive_after_days == 0 {
+            issue(
+                &mut issues,
+                "archive_after_days",
+                IssueLevel::Error,
+                "Must be at least 1".into(),
+            );
+        }
+    }
+    // Zero would keep everything, which is what "forever" is for
+    let retention = match settings.history_retention.as_str() {
+        "days" => Some(("history_retention_days", settings.history_retention_days)),
+        "count" => Some(("history_retention_count", settings.history_retention_count)),
+        _ => None,
+    };
+    if let Some((field, 0)) = retention {
+        issue(
+            &mut issues,
+            field,
+            IssueLevel::Error,
+            "Must be at least 1".into(),
+        );
+    }
+    let geoip_db = settings.geoip_db_path.trim();
+    if settings.peer_geoip && !geoip_db.is_empty() && !Path::new(&expand_tilde(geoip_db)).is_file()
+    {
+        issue(
+            &mut issues,
+            "geoip_db_path",
+            IssueLevel::Warning,
+            "GeoIP database file not found".into(),
+        );
+    }
+
+    for (field, value) in [
+        (
+            "max_concurrent_downloads",
+            settings.max_concurrent_downloads,
+        ),
+        (
+            "max_connections_per_server",
+            settings.max_connections_per_server,
+        ),
+        ("split_count", settings.split_count),
+        (
+            "stats_report_interval_hours",
+            settings.stats_report_interval_hours,
+        ),
+        (
+            "bt_webseed_max_connections",
+            settings.bt_webseed_max_connections,
+        ),
+    ] {
+        if value == 0 {
+            issue(
+                &mut issues,
+                field,
+                IssueLevel::Error,
+                "Must be at least 1".into(),
+            );
+        }
+    }
+    for (field, value) in [
+        ("battery_threshold", settings.battery_threshold),
+        ("load_guard_cpu_percent", settings.load_guard_cpu_percent),
+    ] {
+        if !(1..=100).contains(&value) {
+            issue(
+                &mut issues,
+                field,
+                IssueLevel::Error,
+                "Must be a percentage from 1 to 100".into(),
+            );
+        }
+    }
+    for (field, value) in [
+        ("connect_timeout", settings.connect_timeout),
+        ("read_timeout", settings.read_timeout),
+    ] {
+        if value == 0 {
+            issue(
+                &mut issues,
+                field,
+                IssueLevel::Error,
+                "Timeout must be at least a second".into(),
+            );
+        }
+    }
+    if settings.battery_mode_enabled
+        && settings.download_speed_limit > 0
+        && settings.battery_download_limit > settings.download_speed_limit
+    {
+        issue(
+            &mut issues,
+            "battery_download_limit",
+            IssueLevel::Warning,
+            "Higher than the global download limit, so it never applies".into(),
+        );
+    }
+
+    for (field, value) in [
+        ("quiet_hours_start", &settings.quiet_hours_start),
+        ("quiet_hours_end", &settings.quiet_hours_end),
+    ] {
+        if let Err(e) = validate_time_of_day(value) {
+            issue(&mut issues, field, IssueLevel::Error, e.to_string());
+        }
+    }
+    if let Err(e) = crate::commands::validate_export_format(&settings.stats_report_format) {
+        issue(
+            &mut issues,
+            "stats_report_format",
+            IssueLevel::Error,
+            e.to_string(),
+        );
+    }
+
+    if let Err(message) = proxy_address(&settings.proxy_url) {
+        issue(&mut issues, "proxy_url", IssueLevel::Error, message);
+    }
+    issues
+}
+
+/// `check_settings`, plus whether the proxy accepts connections.
+pub async fn check_settings_online(settings: &Settings) -> Vec<SettingIssue> {
+    let mut issues = check_settings(settings);
+    if let Ok(Some((host, port))) = proxy_address(&settings.proxy_url) {
+        let connect = tokio::net::TcpStream::connect((host.as_str(), port));
+        let message = match tokio::time::timeout(PROXY_CONNECT_TIMEOUT, connect).await {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(format!(
+                "Couldn't connect to the proxy at {}:{}: {}",
+                host, port, e
+            )),
+            Err(_) => Some(format!("The proxy at {}:{} didn't answer", host, port)),
+        };
+        if let Some(message) = message {
+            issue(&mut issues, "proxy_url", IssueLevel::Warning, message);
+        }
+    }
+    issues
+}
+
+/// Check a folder setting: absolute, a directory, and writable. A folder
+/// that doesn't exist yet is only worth a warning; `required` makes an
+/// empty value an error rather than nothing to check.
+fn check_dir(issues: &mut Vec<SettingIssue>, field: &str, value: &str, required: bool) {
+    let value = value.trim();
+    if value.is_empty() {
+        if required {
+            issue(
+                issues,
+                field,
+                IssueLevel::Error,
+                "A folder is required".into(),
+            );
+        }
+        return;
+    }
+    let path = expand_tilde(value);
+    let path = Path::new(&path);
+    if !path.is_absolute() {
+        issue(
+            issues,
+            field,
+            IssueLevel::Error,
+            "Must be an absolute path".into(),
+        );
+    } else if !path.exists() {
+        issue(
+            issues,
+            field,
+            IssueLevel::Warning,
+            "Folder doesn't exist yet".into(),
+        );
+    } else if !path.is_dir() {
+        issue(issues, field, IssueLevel::Error, "Not a folder".into());
+    } else if !is_writable(path) {
+        issue(
+            issues,
+            field,
+            IssueLevel::Error,
+            "Folder isn't writable".into(),
+        );
+    }
+}
+
+/// Whether a file can be created in `dir`, by creating and removing one.
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".gosh-fetch-write-check-{}", std::process::id()));
+    let created = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .is_ok();
+    if created {
+        let _ = std::fs::remove_file(&probe);
+    }
+    created
+}
+
+/// The host and port a proxy URL points at, `None` for no proxy, or why
+/// the URL won't do.
+fn proxy_address(proxy_url: &str) -> Result<Option<(String, u16)>, String> {
+    let proxy_url = proxy_url.trim();
+    if proxy_url.is_empty() {
+        return Ok(None);
+    }
+    let url = url::Url::parse(proxy_url).map_err(|e| format!("Not a valid proxy URL: {}", e))?;
+    if !matches!(
+        url.scheme(),
+        "http" | "https" | "socks4" | "socks5" | "socks5h"
+    ) {
+        return Err(format!("Unsupported proxy type: {}", url.scheme()));
+    }
+    let host = url
+        .host_str()
+        .filter(|h| !h.is_empty())
+        .ok_or("Proxy URL has no host")?;
+    let port = url.port_or_known_default().unwrap_or(DEFAULT_SOCKS_PORT);
+    if port == 0 {
+        return Err("Proxy port must be from 1 to 65535".into());
+    }
+    Ok(Some((host.trim_matches(['[', ']']).to_string(), port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(issues: &[SettingIssue], level: IssueLevel) -> Vec<&str> {
+        issues
+            .iter()
+            .filter(|i| i.level == level)
+            .map(|i| i.field.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_check_settings() {
+        let dir =
+            std::env::temp_dir().join(format!("gosh-fetch-settings-check-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut settings = Settings {
+            download_path: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        assert!(check_settings(&settings).is_empty());
+
+        settings.temp_download_path = dir.join("missing").to_string_lossy().into_owned();
+        settings.split_count = 0;
+        settings.battery_threshold = 101;
+        settings.quiet_hours_end = "25:00".into();
+        settings.proxy_url = "ftp://proxy.example".into();
+        settings.history_retention = "count".into();
+        settings.history_retention_count = 0;
+        settings.history_retention_days = 0;
+        let issues = check_settings(&settings);
+        assert_eq!(
+            fields(&issues, IssueLevel::Warning),
+            vec!["temp_download_path"]
+        );
+        assert_eq!(
+            fields(&issues, IssueLevel::Error),
+            vec![
+                "history_retention_count",
+                "split_count",
+                "battery_threshold",
+                "quiet_hours_end",
+                "proxy_url"
+            ]
+        );
+
+        settings.download_path = "relative/dir".into();
+        assert_eq!(check_settings(&settings)[0].field, "download_path");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_proxy_address() {
+        assert_eq!(proxy_address(""), Ok(None));
+        assert_eq!(
+            proxy_address("http://proxy.example"),
+            Ok(Some(("proxy.example".into(), 80)))
+        );
+        assert_eq!(
+            proxy_address("socks5://u:p@10.0.0.1"),
+            Ok(Some(("10.0.0.1".into(), 1080)))
+        );
+        assert_eq!(
+            proxy_address("socks5://[::1]:9050"),
+            Ok(Some(("::1".into(), 9050)))
+        );
+        assert!(proxy_address("proxy.example:8080").is_err());
+        assert!(proxy_address("http://proxy.example:0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_proxy() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let settings = Settings {
+            download_path: std::env::temp_dir().to_string_lossy().into_owned(),
+            proxy_url: format!("http://127.0.0.1:{}", port),
+            ..Default::default()
+        };
+        assert!(check_settings_online(&settings).await.is_empty());
+
+        drop(listener);
+        let issues = check_settings_online(&settings).await;
+        assert_eq!(fields(&issues, IssueLevel::Warning), vec!["proxy_url"]);
+    }
+}
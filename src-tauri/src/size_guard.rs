@@ -0,0 +1,131 @@
+//! Catches HTTP downloads whose size changes under them: a server that
+//! lies about Content-Length, or CDN nodes handing different versions of a
+//! file to different segments. The first total reported is the expected
+//! size; a different total later, more bytes than that, or a finished file
+//! of another size is a mismatch.
+
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    /// Not an HTTP download, or already reported
+    Ignored,
+    /// Expected size, once the server has named one
+    Watching(Option<u64>),
+}
+
+#[derive(Debug, Default)]
+pub struct SizeGuard {
+    entries: HashMap<String, Entry>,
+    /// URLs already restarted once after a mismatch
+    retried: HashSet<String>,
+}
+
+impl SizeGuard {
+    pub fn is_tracked(&self, gid: &str) -> bool {
+        self.entries.contains_key(gid)
+    }
+
+    /// Start (or decline) watching a download seen for the first time.
+    pub fn track(&mut self, gid: &str, http: bool) {
+        let entry = if http {
+            Entry::Watching(None)
+        } else {
+            Entry::Ignored
+        };
+        self.entries.insert(gid.to_string(), entry);
+    }
+
+    /// Record a progress report. Returns `(expected, observed)` the first
+    /// time the download disagrees with its expected size.
+    pub fn observe(&mut self, gid: &str, total: Option<u64>, completed: u64) -> Option<(u64, u64)> {
+        let entry = self.entries.get_mut(gid)?;
+        let Entry::Watching(expected) = entry else {
+            return None;
+        };
+        let total = total.filter(|t| *t > 0);
+        let expected = match (*expected, total) {
+            (Some(expected), _) => expected,
+            (None, Some(total)) => *expected.insert(total),
+            (None, None) => return None,
+        };
+        let mismatch = match total {
+            Some(total) if total != expected => Some((expected, total)),
+            _ if completed > expected => Some((expected, completed)),
+            _ => None,
+        };
+        if mismatch.is_some() {
+            *entry = Entry::Ignored;
+        }
+        mismatch
+    }
+
+    /// Compare the finished file's size with the expected one.
+    pub fn check_finished(&mut self, gid: &str, size: u64) -> Option<(u64, u64)> {
+        match self.entries.remove(gid)? {
+            Entry::Watching(Some(expected)) if expected != size => Some((expected, size)),
+            _ => None,
+        }
+    }
+
+    /// Stop watching; a resumed download starts over with the size the
+    /// server reports then.
+    pub fn forget(&mut self, gid: &str) {
+        self.entries.remove(gid);
+    }
+
+    /// Forget every download, as after resuming them all.
+    pub fn forget_all(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Whether `url` may be restarted after a mismatch; true only once.
+    pub fn allow_retry(&mut self, url: &str) -> bool {
+        self.retried.insert(url.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_changes() {
+        let mut guard = SizeGuard::default();
+        assert_eq!(guard.observe("a", Some(100), 0), None);
+        guard.track("a", true);
+        guard.track("t", false);
+        assert_eq!(guard.observe("t", Some(100), 500), None);
+
+        // Unknown length first, then the server names a size
+        assert_eq!(guard.observe("a", None, 10), None);
+        assert_eq!(guard.observe("a", Some(100), 20), None);
+        assert_eq!(guard.observe("a", None, 50), None);
+        assert_eq!(guard.observe("a", Some(120), 60), Some((100, 120)));
+        // Reported once
+        assert_eq!(guard.observe("a", Some(130), 70), None);
+
+        guard.forget("a");
+        guard.track("a", true);
+        assert_eq!(guard.observe("a", Some(120), 0), None);
+        assert_eq!(guard.observe("a", None, 121), Some((120, 121)));
+    }
+
+    #[test]
+    fn test_finished_size_and_retry() {
+        let mut guard = SizeGuard::default();
+        guard.track("a", true);
+        guard.observe("a", Some(100), 100);
+        assert_eq!(guard.check_finished("a", 90), Some((100, 90)));
+        assert!(!guard.is_tracked("a"));
+
+        guard.track("b", true);
+        guard.observe("b", Some(100), 100);
+        assert_eq!(guard.check_finished("b", 100), None);
+        guard.track("c", true);
+        assert_eq!(guard.check_finished("c", 5), None);
+
+        assert!(guard.allow_retry("https://example.com/f"));
+        assert!(!guard.allow_retry("https://example.com/f"));
+    }
+}
@@ -0,0 +1,375 @@
+//! Temp directory for in-progress HTTP downloads. With `temp_download_path`
+//! set, each download is written to a folder of its own there (an SSD, say)
+//! and moved to its real destination (an HDD or NAS) when it finishes:
+//! renamed when both are on the same drive, otherwise copied in chunks with
+//! progress reported as a `move-download` task. Torrents keep writing in
+//! place, since they seed from their files.
+
+use crate::tasks::TaskHandle;
+use crate::types::{DownloadOptions, DownloadState, DownloadType};
+use crate::{AppState, Error, Result};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+const COPY_CHUNK: usize = 8 * 1024 * 1024;
+
+/// An HTTP download written to the temp directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedDownload {
+    /// Folder under the temp directory holding only this download
+    pub temp_dir: String,
+    /// None between creating the folder and the engine accepting the URL
+    pub gid: Option<String>,
+    /// Directory the download would have been saved to without staging
+    pub final_dir: String,
+    /// Where the file ended up, once moved
+    pub final_path: Option<String>,
+}
+
+/// Point `options` at a new folder in the temp directory, if one is
+/// configured, and record where the file should go afterwards. Call this
+/// once the download's real directory has been decided.
+pub async fn stage(
+    state: &AppState,
+    options: &mut DownloadOptions,
+) -> Result<Option<StagedDownload>> {
+    let db = state.get_db().await?;
+    let settings = db.get_settings_async().await?;
+    let temp_root = settings.temp_download_path.trim();
+    if temp_root.is_empty() {
+        return Ok(None);
+    }
+    crate::locations::prepare_directory(Path::new(temp_root))?;
+    let temp_dir = create_staging_dir(Path::new(temp_root))?;
+    let staged = StagedDownload {
+        temp_dir: temp_dir.to_string_lossy().to_string(),
+        gid: None,
+        final_dir: options.dir.clone().unwrap_or(settings.download_path),
+        final_path: None,
+    };
+    db.save_staged_async(staged.clone()).await?;
+    options.dir = Some(staged.temp_dir.clone());
+    Ok(Some(staged))
+}
+
+/// Tie a staged download to the GID the engine gave it, or clean up if the
+/// engine refused it.
+pub async fn attach(
+    state: &AppState,
+    staged: Option<StagedDownload>,
+    gid: Option<&str>,
+) -> Result<()> {
+    let Some(mut staged) = staged else {
+        return Ok(());
+    };
+    let db = state.get_db().await?;
+    match gid {
+        Some(gid) => {
+            staged.gid = Some(gid.to_string());
+            db.save_staged_async(staged).await
+        }
+        None => {
+            let _ = std::fs::remove_dir(&staged.temp_dir);
+            db.delete_staged_async(staged.temp_dir).await
+        }
+    }
+}
+
+/// Move a finished download out of the temp directory. Downloads that
+/// weren't staged are left alone. A failed move keeps the file where it is
+/// and flags the download with a warning.
+pub async fn finish_download(state: &AppState, app: &AppHandle, gid: &str) -> Result<()> {
+    let db = state.get_db().await?;
+    let adapter = state.get_adapter().await?;
+    let Some(download) = adapter.get_status(gid) else {
+        return Ok(());
+    };
+    if download.download_type != DownloadType::Http {
+        return Ok(());
+    }
+    let Some(mut staged) = db
+        .get_staged_downloads_async()
+        .await?
+        .into_iter()
+        .find(|s| {
+            s.final_path.is_none()
+                && (s.gid.as_deref() == Some(gid)
+                    || Path::new(&s.temp_dir) == Path::new(&download.save_path))
+        })
+    else {
+        return Ok(());
+    };
+    // Gone if it was deleted in the meantime
+    let Some(source) = crate::preview::locate_file(&download, &[]) else {
+        return Ok(());
+    };
+
+    let mut task = state.tasks().start(
+        app,
+        "move-download",
+        format!("Move {}", download.name),
+        true,
+    );
+    let final_dir = PathBuf::from(&staged.final_dir);
+    let result = tokio::task::spawn_blocking(move || {
+        let result = crate::locations::prepare_directory(&final_dir).and_then(|()| {
+            move_file(&source, &final_dir, |done, total| {
+                report(&mut task, done, total)
+            })
+        });
+        task.finish(&result);
+        result
+    })
+    .await
+    .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+
+    match result {
+        Ok(Some(path)) => {
+            log::info!("Moved {} to {}", download.name, path.display());
+            let _ = std::fs::remove_dir(&staged.temp_dir);
+            staged.gid = Some(gid.to_string());
+            staged.final_path = Some(path.to_string_lossy().to_string());
+            db.save_staged_async(staged).await?;
+            adapter.set_moved(gid, path);
+        }
+        Ok(None) => {
+            adapter.flag_warning(gid, format!("Move to {} was cancelled", staged.final_dir));
+        }
+        Err(e) => {
+            log::warn!(
+                "Failed to move {} to {}: {}",
+                download.name,
+                staged.final_dir,
+                e
+            );
+            adapter.flag_warning(
+                gid,
+                format!("Could not move to {}: {}", staged.final_dir, e),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn report(task: &mut TaskHandle, done: u64, total: u64) -> bool {
+    task.progress(done, total, None);
+    !task.is_cancelled()
+}
+
+/// Pick up where the last session left off: re-apply finished moves and
+/// move downloads that completed without being moved.
+pub async fn resume(state: &AppState, app: &AppHandle) -> Result<()> {
+    let db = state.get_db().await?;
+    let adapter = state.get_adapter().await?;
+    for staged in db.get_staged_downloads_async().await? {
+        let status = staged
+            .gid
+            .as_deref()
+            .and_then(|gid| adapter.get_status(gid));
+        match (&staged.gid, &staged.final_path, status) {
+            (Some(gid), Some(path), _) => adapter.set_moved(gid, PathBuf::from(path)),
+            (Some(gid), None, Some(download)) => {
+                if download.status == DownloadState::Complete {
+                    finish_download(state, app, gid).await?;
+                }
+            }
+            _ => {
+                let _ = std::fs::remove_dir(&staged.temp_dir);
+                db.delete_staged_async(staged.temp_dir).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Directory a download is meant to end up in: its final directory if it
+/// is staged, else `save_path`.
+pub async fn destination(state: &AppState, gid: &str, save_path: &str) -> Result<String> {
+    let staged = state.get_db().await?.get_staged_downloads_async().await?;
+    Ok(staged
+        .into_iter()
+        .find(|s| s.gid.as_deref() == Some(gid))
+        .map_or_else(|| save_path.to_string(), |s| s.final_dir))
+}
+
+/// Final path of a download moved out of the temp directory.
+pub async fn moved_path(state: &AppState, gid: &str) -> Result<Option<PathBuf>> {
+    let staged = state.get_db().await?.get_staged_downloads_async().await?;
+    Ok(staged
+        .into_iter()
+        .find(|s| s.gid.as_deref() == Some(gid))
+        .and_then(|s| s.final_path)
+        .map(PathBuf::from))
+}
+
+/// Drop the record of a removed download, or of one whose file was
+/// deleted, and its staging folder.
+pub async fn forget(state: &AppState, gid: &str) -> Result<()> {
+    let db = state.get_db().await?;
+    for staged in db.get_staged_downloads_async().await? {
+        if staged.gid.as_deref() == Some(gid) {
+            let _ = std::fs::remove_dir(&staged.temp_dir);
+            db.delete_staged_async(staged.temp_dir).await?;
+        }
+    }
+    Ok(())
+}
+
+/// New empty folder under `root`, named after the current time.
+fn create_staging_dir(root: &Path) -> Result<PathBuf> {
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S%.3f");
+    for n in 0.. {
+        let dir = root.join(format!("{}-{}", stamp, n));
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+/// `dir/name`, or `dir/name (n).ext` with the first free `n` if taken.
+pub(crate) fn unique_destination(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name, None),
+    };
+    (1..)
+        .map(|n| match extension {
+            Some(extension) => dir.join(format!("{} ({}).{}", stem, n, extension)),
+            None => dir.join(format!("{} ({})", stem, n)),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Move `source` into `dest_dir`. On the same drive this is a rename;
+/// across drives the file is copied in chunks to a hidden name beside the
+/// destination and renamed into place, so a partial copy is never visible
+/// under the real name. `progress(done, total)` returning false cancels
+/// the copy, leaving the source untouched. Returns None if cancelled.
+pub fn move_file(
+    source: &Path,
+    dest_dir: &Path,
+    progress: impl FnMut(u64, u64) -> bool,
+) -> Result<Option<PathBuf>> {
+    let name = source
+        .file_name()
+        .ok_or_else(|| Error::InvalidInput(format!("Not a file: {}", source.display())))?
+        .to_string_lossy()
+        .to_string();
+    let dest = unique_destination(dest_dir, &name);
+    if std::fs::rename(source, &dest).is_ok() {
+        return Ok(Some(dest));
+    }
+    let partial = dest_dir.join(format!(".{}.moving", name));
+    match copy_chunked(source, &partial, progress) {
+        Ok(true) => {
+            std::fs::rename(&partial, &dest)?;
+            std::fs::remove_file(source)?;
+            Ok(Some(dest))
+        }
+        result => {
+            let _ = std::fs::remove_file(&partial);
+            result.map(|_| None)
+        }
+    }
+}
+
+fn copy_chunked(
+    source: &Path,
+    dest: &Path,
+    mut progress: impl FnMut(u64, u64) -> bool,
+) -> Result<bool> {
+    let total = std::fs::metadata(source)?.len();
+    let mut reader = std::fs::File::open(source)?;
+    let mut writer = std::fs::File::create(dest)?;
+    let mut buffer = vec![0; COPY_CHUNK];
+    let mut done = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+        done += read as u64;
+        if !progress(done, total) {
+            return Ok(false);
+        }
+    }
+    writer.sync_all()?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gosh-fetch-staging-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_move_file() {
+        let root = temp_dir("move");
+        let staging = create_staging_dir(&root).unwrap();
+        assert_ne!(create_staging_dir(&root).unwrap(), staging);
+        let dest = root.join("dest");
+        std::fs::create_dir(&dest).unwrap();
+
+        std::fs::write(dest.join("file.iso"), "old").unwrap();
+        std::fs::write(staging.join("file.iso"), "new").unwrap();
+        let moved = move_file(&staging.join("file.iso"), &dest, |_, _| true).unwrap();
+        assert_eq!(moved, Some(dest.join("file (1).iso")));
+        assert_eq!(
+            std::fs::read_to_string(dest.join("file (1).iso")).unwrap(),
+            "new"
+        );
+        assert!(!staging.join("file.iso").exists());
+        assert_eq!(
+            unique_destination(&dest, "file.iso"),
+            dest.join("file (2).iso")
+        );
+        assert_eq!(unique_destination(&dest, ".hidden"), dest.join(".hidden"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_copy_chunked() {
+        let root = temp_dir("copy");
+        let source = root.join("big.bin");
+        std::fs::write(&source, vec![7u8; COPY_CHUNK + 10]).unwrap();
+
+        let mut reports = Vec::new();
+        let copied = copy_chunked(&source, &root.join("copy.bin"), |done, total| {
+            reports.push((done, total));
+            true
+        })
+        .unwrap();
+        assert!(copied);
+        assert_eq!(
+            std::fs::read(root.join("copy.bin")).unwrap().len(),
+            COPY_CHUNK + 10
+        );
+        assert_eq!(
+            reports.last(),
+            Some(&(COPY_CHUNK as u64 + 10, COPY_CHUNK as u64 + 10))
+        );
+
+        assert!(!copy_chunked(&source, &root.join("cancelled.bin"), |_, _| false).unwrap());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
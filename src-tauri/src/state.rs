@@ -1,12 +1,30 @@
-use crate::db::Database;
-use crate::engine_adapter::EngineAdapter;
-use crate::types::DownloadState;
-use crate::utils::TrackerUpdater;
-use crate::Result;
+use crate::changes::{ChangeLog, Changed};
+use crate::db::{Database, Settings};
+use crate::engine_adapter::{EngineAdapter, Hold};
+use crate::events::{FailedDownload, FailureBatch};
+use crate::geoip::GeoIpDb;
+use crate::host_profiles::ThroughputTracker;
+use crate::host_stats::{HostOutcomes, Limits};
+use crate::orphans::Sidecar;
+use crate::progress_delta::ProgressDeltas;
+use crate::ranges::{ProbeRequest, RedirectRules, ServerProbe};
+use crate::rotation::PendingRotation;
+use crate::size_guard::SizeGuard;
+use crate::subscriptions::Subscriptions;
+use crate::tasks::TaskRegistry;
+use crate::types::{
+    CrashRecoveryInfo, Download, DownloadOptions, DownloadState, DownloadType, FdBudget,
+    HostActivity, HostProfile, LoadGuardStatus, OrphanedPartial, ProgressSnapshot, UiFilters,
+};
+use crate::utils::{NumberFormat, PowerState, TrackerUpdater, UnitSystem};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use gosh_dl::config::{EncryptionConfig, EncryptionPolicy};
 use gosh_dl::{DownloadEngine, DownloadEvent, EngineConfig, RecursiveJobEvent};
 use serde::Serialize;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 use tauri::{AppHandle, Emitter};
@@ -23,6 +41,9 @@ pub enum OpenRequest {
     TorrentFile { path: String },
 }
 
+/// A loaded GeoIP database and the path it was read from.
+type CachedGeoIp = (String, Arc<GeoIpDb>);
+
 #[derive(Clone)]
 pub struct AppState {
     engine: Arc<RwLock<Option<Arc<DownloadEngine>>>>,
@@ -36,6 +57,44 @@ pub struct AppState {
     tracker_updater: Arc<RwLock<TrackerUpdater>>,
     frontend_ready: Arc<AtomicBool>,
     pending_opens: Arc<Mutex<Vec<OpenRequest>>>,
+    engine_started_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    engine_restarts: Arc<AtomicU32>,
+    engine_last_error: Arc<Mutex<Option<String>>>,
+    number_format: Arc<Mutex<NumberFormat>>,
+    ui_filters: Arc<Mutex<UiFilters>>,
+    /// (version, hash of the last serialized UI snapshot)
+    ui_snapshot_version: Arc<Mutex<(u64, u64)>>,
+    /// Per-download versions handed out by `get_changes`
+    download_changes: Arc<Mutex<ChangeLog>>,
+    failure_batch: Arc<Mutex<FailureBatch>>,
+    /// Set while the VPN kill switch has torrent traffic blocked.
+    vpn_down: Arc<AtomicBool>,
+    /// Set while the battery limits are applied to the engine.
+    battery_saver: Arc<AtomicBool>,
+    tasks: TaskRegistry,
+    /// Downloads interrupted by a crash of the previous session, until the
+    /// user answers the restore prompt.
+    crash_recovery: Arc<Mutex<Option<CrashRecoveryInfo>>>,
+    /// Partial files no download claims, once the startup scan has run
+    orphaned_partials: Arc<Mutex<Option<Vec<OrphanedPartial>>>>,
+    size_guard: Arc<Mutex<SizeGuard>>,
+    /// Previous copies moved aside for downloads still running
+    rotations: Arc<Mutex<HashMap<String, PendingRotation>>>,
+    throughput: Arc<Mutex<ThroughputTracker>>,
+    /// Recently finished HTTP downloads per host, for `get_host_stats`
+    host_outcomes: Arc<Mutex<HostOutcomes>>,
+    /// GeoIP database for peer countries, once loaded
+    geoip: Arc<Mutex<Option<CachedGeoIp>>>,
+    /// Open file budget the engine's caps were last fitted to
+    fd_budget: Arc<Mutex<FdBudget>>,
+    /// Filtered event streams opened with `subscribe_download_events`
+    subscriptions: Arc<Mutex<Subscriptions>>,
+    /// Set while the system load guard has the engine eased off
+    load_guard: Arc<Mutex<LoadGuardStatus>>,
+    /// Last progress sent per download, for delta progress events
+    progress_deltas: Arc<Mutex<ProgressDeltas>>,
+    /// Mirrors the `delta_progress_events` setting
+    delta_progress: Arc<AtomicBool>,
 }
 
 impl AppState {
@@ -52,6 +111,29 @@ impl AppState {
             tracker_updater: Arc::new(RwLock::new(TrackerUpdater::new())),
             frontend_ready: Arc::new(AtomicBool::new(false)),
             pending_opens: Arc::new(Mutex::new(Vec::new())),
+            engine_started_at: Arc::new(Mutex::new(None)),
+            engine_restarts: Arc::new(AtomicU32::new(0)),
+            engine_last_error: Arc::new(Mutex::new(None)),
+            number_format: Arc::new(Mutex::new(NumberFormat::default())),
+            ui_filters: Arc::new(Mutex::new(UiFilters::default())),
+            ui_snapshot_version: Arc::new(Mutex::new((0, 0))),
+            download_changes: Arc::new(Mutex::new(ChangeLog::default())),
+            failure_batch: Arc::new(Mutex::new(FailureBatch::default())),
+            vpn_down: Arc::new(AtomicBool::new(false)),
+            battery_saver: Arc::new(AtomicBool::new(false)),
+            tasks: TaskRegistry::default(),
+            crash_recovery: Arc::new(Mutex::new(None)),
+            orphaned_partials: Arc::new(Mutex::new(None)),
+            size_guard: Arc::new(Mutex::new(SizeGuard::default())),
+            rotations: Arc::new(Mutex::new(HashMap::new())),
+            throughput: Arc::new(Mutex::new(ThroughputTracker::default())),
+            host_outcomes: Arc::new(Mutex::new(HostOutcomes::default())),
+            geoip: Arc::new(Mutex::new(None)),
+            fd_budget: Arc::new(Mutex::new(FdBudget::default())),
+            subscriptions: Arc::new(Mutex::new(Subscriptions::default())),
+            load_guard: Arc::new(Mutex::new(LoadGuardStatus::default())),
+            progress_deltas: Arc::new(Mutex::new(ProgressDeltas::default())),
+            delta_progress: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -73,6 +155,170 @@ impl AppState {
         self.quitting.store(value, Ordering::Relaxed);
     }
 
+    /// Formatting used for backend-rendered sizes and speeds (tray tooltip).
+    pub fn number_format(&self) -> NumberFormat {
+        *self.number_format.lock().unwrap()
+    }
+
+    pub fn set_unit_system(&self, setting: &str) {
+        *self.number_format.lock().unwrap() =
+            NumberFormat::with_system_locale(UnitSystem::from_setting(setting));
+    }
+
+    pub fn set_delta_progress(&self, value: bool) {
+        self.delta_progress.store(value, Ordering::Relaxed);
+    }
+
+    /// Full progress last reported for `gids`, or for every download.
+    pub fn progress_snapshots(&self, gids: Option<&[String]>) -> Vec<ProgressSnapshot> {
+        self.progress_deltas.lock().unwrap().snapshots(gids)
+    }
+
+    pub fn ui_filters(&self) -> UiFilters {
+        self.ui_filters.lock().unwrap().clone()
+    }
+
+    pub fn set_ui_filters(&self, filters: UiFilters) {
+        *self.ui_filters.lock().unwrap() = filters;
+    }
+
+    /// Version for a UI snapshot with the given content hash: unchanged if
+    /// the content is the same as last time, bumped otherwise.
+    pub fn ui_snapshot_version(&self, hash: u64) -> u64 {
+        let mut current = self.ui_snapshot_version.lock().unwrap();
+        if current.1 != hash || current.0 == 0 {
+            *current = (current.0 + 1, hash);
+        }
+        current.0
+    }
+
+    /// Record `downloads` in the change log and return what changed after
+    /// `since`.
+    pub fn download_changes(&self, downloads: &[Download], since: Option<u64>) -> Changed {
+        self.download_changes
+            .lock()
+            .unwrap()
+            .update(downloads, since)
+    }
+
+    /// Failures waiting for the next `download:failure-digest`.
+    pub fn failure_batch(&self) -> Arc<Mutex<FailureBatch>> {
+        self.failure_batch.clone()
+    }
+
+    pub fn is_vpn_down(&self) -> bool {
+        self.vpn_down.load(Ordering::Relaxed)
+    }
+
+    pub fn set_vpn_down(&self, value: bool) {
+        self.vpn_down.store(value, Ordering::Relaxed);
+    }
+
+    pub fn is_battery_saver(&self) -> bool {
+        self.battery_saver.load(Ordering::Relaxed)
+    }
+
+    pub fn set_battery_saver(&self, value: bool) {
+        self.battery_saver.store(value, Ordering::Relaxed);
+    }
+
+    pub fn load_guard_status(&self) -> LoadGuardStatus {
+        self.load_guard.lock().unwrap().clone()
+    }
+
+    pub fn is_load_guarded(&self) -> bool {
+        self.load_guard.lock().unwrap().active
+    }
+
+    pub fn update_load_guard(&self, f: impl FnOnce(&mut LoadGuardStatus)) {
+        f(&mut self.load_guard.lock().unwrap());
+    }
+
+    /// Sizes seen per download, and the URLs already restarted once over
+    /// a size mismatch.
+    pub fn size_guard(&self) -> &Mutex<SizeGuard> {
+        &self.size_guard
+    }
+
+    pub fn rotations(&self) -> &Mutex<HashMap<String, PendingRotation>> {
+        &self.rotations
+    }
+
+    /// Long-running background jobs reported to the frontend as tasks.
+    pub fn tasks(&self) -> &TaskRegistry {
+        &self.tasks
+    }
+
+    pub fn subscriptions(&self) -> &Mutex<Subscriptions> {
+        &self.subscriptions
+    }
+
+    /// What the engine is held to right now.
+    pub fn limits(&self, adapter: &EngineAdapter) -> Limits {
+        let config = adapter.engine().get_config();
+        Limits {
+            connections_per_download: config.max_connections_per_download as u32,
+            global_limit: config.global_download_limit.is_some_and(|limit| limit > 0),
+            battery_saver: self.is_battery_saver(),
+            load_guard: self.is_load_guarded(),
+        }
+    }
+
+    /// Per-host figures for `downloads` and the recent outcomes of each
+    /// host's downloads.
+    pub fn host_stats(
+        &self,
+        downloads: &[Download],
+        limits: Limits,
+        profiles: &[HostProfile],
+    ) -> Vec<HostActivity> {
+        let outcomes = self.host_outcomes.lock().unwrap();
+        crate::host_stats::host_stats(
+            downloads,
+            &outcomes,
+            limits,
+            profiles,
+            std::time::Instant::now(),
+        )
+    }
+
+    /// The GeoIP database if peer lookups are on, read on first use and
+    /// again whenever `geoip_db_path` changes.
+    pub fn geoip(&self, settings: &Settings) -> Result<Option<Arc<GeoIpDb>>> {
+        if !settings.peer_geoip || settings.geoip_db_path.is_empty() {
+            return Ok(None);
+        }
+        let mut cached = self.geoip.lock().unwrap();
+        if let Some((path, db)) = cached.as_ref() {
+            if *path == settings.geoip_db_path {
+                return Ok(Some(db.clone()));
+            }
+        }
+        let db = Arc::new(GeoIpDb::open(std::path::Path::new(
+            &settings.geoip_db_path,
+        ))?);
+        log::info!("Loaded GeoIP database {}", settings.geoip_db_path);
+        *cached = Some((settings.geoip_db_path.clone(), db.clone()));
+        Ok(Some(db))
+    }
+
+    /// Fail with a network error if the VPN kill switch is blocking torrents.
+    pub async fn ensure_torrents_allowed(&self) -> Result<()> {
+        if !self.is_vpn_down() {
+            return Ok(());
+        }
+        let interface = self
+            .get_db()
+            .await?
+            .get_settings_async()
+            .await?
+            .bt_vpn_interface;
+        Err(Error::Network(format!(
+            "VPN interface {} is down; torrents are blocked until it reconnects",
+            interface
+        )))
+    }
+
     /// Deliver an OS open request (magnet / .torrent). Emits to the frontend
     /// when it is ready, otherwise queues it for `get_pending_open_requests`.
     pub fn deliver_open_request(&self, app: &AppHandle, request: OpenRequest) {
@@ -89,12 +335,96 @@ impl AppState {
         }
     }
 
+    /// True once the webview has asked for queued open requests, so events
+    /// sent now reach its listeners.
+    pub fn is_frontend_ready(&self) -> bool {
+        self.frontend_ready.load(Ordering::Relaxed)
+    }
+
     /// Mark the frontend ready and drain any queued open requests.
     pub fn take_pending_open_requests(&self) -> Vec<OpenRequest> {
         self.frontend_ready.store(true, Ordering::Relaxed);
         std::mem::take(&mut *self.pending_opens.lock().unwrap())
     }
 
+    /// When the running engine was started, if it is running.
+    pub fn engine_started_at(&self) -> Option<DateTime<Utc>> {
+        *self.engine_started_at.lock().unwrap()
+    }
+
+    pub fn engine_restart_count(&self) -> u32 {
+        self.engine_restarts.load(Ordering::Relaxed)
+    }
+
+    pub fn engine_last_error(&self) -> Option<String> {
+        self.engine_last_error.lock().unwrap().clone()
+    }
+
+    pub fn crash_recovery(&self) -> Option<CrashRecoveryInfo> {
+        self.crash_recovery.lock().unwrap().clone()
+    }
+
+    /// Hand over the pending recovery prompt; it is answered only once.
+    pub fn take_crash_recovery(&self) -> Option<CrashRecoveryInfo> {
+        self.crash_recovery.lock().unwrap().take()
+    }
+
+    /// Partial files found by the last scan, scanning first if none has run.
+    pub async fn orphaned_partials(&self) -> Result<Vec<OrphanedPartial>> {
+        if let Some(orphans) = self.orphaned_partials.lock().unwrap().clone() {
+            return Ok(orphans);
+        }
+        self.scan_orphaned_partials().await
+    }
+
+    pub async fn scan_orphaned_partials(&self) -> Result<Vec<OrphanedPartial>> {
+        let db = self.get_db().await?;
+        let adapter = self.get_adapter().await?;
+        let orphans = crate::orphans::find_orphans(&db, &adapter).await?;
+        *self.orphaned_partials.lock().unwrap() = Some(orphans.clone());
+        Ok(orphans)
+    }
+
+    /// Drop `paths` from the orphan list once they are resumed or deleted.
+    pub fn forget_orphaned_partials(&self, paths: &[String]) {
+        if let Some(orphans) = self.orphaned_partials.lock().unwrap().as_mut() {
+            orphans.retain(|o| !paths.contains(&o.path));
+        }
+    }
+
+    /// Lower `config`'s caps to fit the open file limit, and remember the
+    /// budget for `get_engine_health`.
+    pub fn apply_fd_budget(&self, config: &mut EngineConfig) {
+        *self.fd_budget.lock().unwrap() = crate::fd_budget::apply(config);
+    }
+
+    /// The open file budget, with the descriptors open right now.
+    pub fn fd_budget(&self) -> FdBudget {
+        FdBudget {
+            open: crate::fd_budget::open_fds(),
+            ..self.fd_budget.lock().unwrap().clone()
+        }
+    }
+
+    /// Remember why the engine failed to start, for `get_engine_health`.
+    pub fn record_engine_error(&self, message: String) {
+        *self.engine_last_error.lock().unwrap() = Some(message);
+    }
+
+    /// Re-apply DHT/PEX/LPD to the running engine from the saved settings,
+    /// honoring the VPN kill switch.
+    pub async fn sync_peer_discovery(&self) -> Result<()> {
+        let settings = self.get_db().await?.get_settings_async().await?;
+        let engine = self.get_engine().await?;
+        let mut config = engine.get_config();
+        apply_peer_discovery(&mut config, &settings, self.is_vpn_down());
+        if self.is_battery_saver() {
+            apply_battery_limits(&mut config, &settings);
+        }
+        engine.set_config(config)?;
+        Ok(())
+    }
+
     pub async fn initialize(&self, data_dir: PathBuf, app: AppHandle) -> Result<()> {
         *self.data_dir.write().await = Some(data_dir.clone());
 
@@ -102,8 +432,15 @@ impl AppState {
         let db = Database::new(&data_dir)?;
         *self.db.write().await = Some(db.clone());
 
+        let unclean = crate::recovery::begin_session(&data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to write session marker: {}", e);
+            None
+        });
+
         // Load saved settings from DB, falling back to defaults for a fresh install
         let settings = db.get_settings().unwrap_or_default();
+        self.set_unit_system(&settings.unit_system);
+        self.set_delta_progress(settings.delta_progress_events);
 
         let mut config = EngineConfig::default();
         config.download_dir = PathBuf::from(&settings.download_path);
@@ -112,12 +449,18 @@ impl AppState {
             .max_connections_per_server
             .max(settings.split_count) as usize;
         config.user_agent = settings.user_agent.clone();
-        config.enable_dht = settings.bt_enable_dht;
-        config.enable_pex = settings.bt_enable_pex;
-        config.enable_lpd = settings.bt_enable_lpd;
+        // Check the VPN before the engine starts, so it comes up with peer
+        // discovery off and restored torrents are held below before any
+        // command can start them
+        let vpn_down = !settings.bt_vpn_interface.is_empty()
+            && !crate::vpn::routes_through_interface(&settings.bt_vpn_interface);
+        self.set_vpn_down(vpn_down);
+        apply_peer_discovery(&mut config, &settings, vpn_down);
         config.max_peers = settings.bt_max_peers as usize;
+        apply_web_seeds(&mut config, &settings);
+        apply_encryption(&mut config, &settings);
         config.seed_ratio = settings.bt_seed_ratio;
-        config.database_path = Some(data_dir.join("engine.db"));
+        config.database_path = Some(crate::paths::engine_db(&data_dir));
 
         if settings.download_speed_limit > 0 {
             config.global_download_limit = Some(settings.download_speed_limit);
@@ -135,6 +478,8 @@ impl AppState {
         config.http.connect_timeout = settings.connect_timeout;
         config.http.read_timeout = settings.read_timeout;
         config.http.max_retries = settings.max_retries as usize;
+        config.http.max_redirects = settings.max_redirects as usize;
+        config.http.accept_invalid_certs = settings.accept_invalid_certs;
 
         // File allocation mode
         config.torrent.allocation_mode = match settings.allocation_mode.as_str() {
@@ -143,17 +488,203 @@ impl AppState {
             _ => gosh_dl::AllocationMode::None,
         };
 
+        let battery_saver = battery_saver_applies(&settings, crate::utils::power_state());
+        self.set_battery_saver(battery_saver);
+        if battery_saver {
+            apply_battery_limits(&mut config, &settings);
+        }
+        self.apply_fd_budget(&mut config);
+
         let engine = DownloadEngine::new(config).await?;
+        match db.get_meta_async(crate::commands::SCHEDULE_RULES_KEY).await {
+            Ok(Some(rules)) => match serde_json::from_str(&rules) {
+                Ok(rules) => engine.set_schedule_rules(rules),
+                Err(e) => log::warn!("Ignoring unreadable speed schedule: {}", e),
+            },
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to load speed schedule: {}", e),
+        }
         let adapter = EngineAdapter::new(engine.clone());
+        if vpn_down {
+            let gids = adapter.hold(Hold::Vpn).await;
+            log::warn!(
+                "VPN interface {} is down at startup: {} torrent(s) held",
+                settings.bt_vpn_interface,
+                gids.len()
+            );
+        }
+        match db.get_seed_totals_async().await {
+            Ok(totals) => adapter.load_seed_totals(totals),
+            Err(e) => log::warn!("Failed to load seeding totals: {}", e),
+        }
+        match db.get_traffic_totals_async().await {
+            Ok(totals) => adapter.load_traffic_totals(totals),
+            Err(e) => log::warn!("Failed to load traffic totals: {}", e),
+        }
+        match db.get_download_queue_async().await {
+            Ok(entries) => adapter.load_queue(entries),
+            Err(e) => log::warn!("Failed to load the download queue: {}", e),
+        }
+        // The engine restores S3 downloads with their presigned URLs
+        match db.get_download_urls_async().await {
+            Ok(urls) => {
+                for (gid, url) in urls
+                    .into_iter()
+                    .filter(|(_, url)| crate::s3::is_s3_url(url))
+                {
+                    adapter.set_source(&gid, url);
+                }
+            }
+            Err(e) => log::warn!("Failed to load download URLs: {}", e),
+        }
+        // The engine restores these as plain completed downloads
+        match db.get_completed_downloads_async().await {
+            Ok(history) => {
+                for download in history {
+                    if let (DownloadState::Warning, Some(message)) =
+                        (download.status, download.error_message)
+                    {
+                        adapter.flag_warning(&download.gid, message);
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to load download warnings: {}", e),
+        }
+
+        // Settle crash recovery before commands can reach the engine, so
+        // nothing re-adds or resumes the interrupted downloads first
+        if let Some(unclean) = unclean {
+            let downloads = crate::recovery::hold_interrupted(&db, &adapter)
+                .await
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to gather interrupted downloads: {}", e);
+                    Vec::new()
+                });
+            log::warn!(
+                "Previous session did not shut down cleanly; {} download(s) interrupted",
+                downloads.len()
+            );
+            if !downloads.is_empty() {
+                *self.crash_recovery.lock().unwrap() = Some(CrashRecoveryInfo {
+                    session_started_at: unclean.started_at.map(|t| t.to_rfc3339()),
+                    downloads,
+                });
+            }
+        }
 
         *self.engine.write().await = Some(engine.clone());
-        *self.adapter.write().await = Some(adapter);
+        *self.adapter.write().await = Some(adapter.clone());
 
         // Forward engine events to the webview
         let mut events = engine.subscribe();
         let event_app = app.clone();
+        let event_state = self.clone();
         let handle = tokio::spawn(async move {
             while let Ok(event) = events.recv().await {
+                if let DownloadEvent::Removed { id } = &event {
+                    let gid = id.as_uuid().to_string();
+                    if let Err(e) = crate::staging::forget(&event_state, &gid).await {
+                        log::warn!("Failed to clean up staging for {}: {}", gid, e);
+                    }
+                    if let Err(e) = crate::rename_rules::forget(&event_state, &gid).await {
+                        log::warn!("Failed to forget the rename of {}: {}", gid, e);
+                    }
+                    crate::rotation::forget(&event_state, &gid);
+                    if let Ok(db) = event_state.get_db().await {
+                        if let Err(e) = db.delete_download_options_async(gid.clone()).await {
+                            log::warn!("Failed to forget the options of {}: {}", gid, e);
+                        }
+                    }
+                    event_state.throughput.lock().unwrap().forget(&gid);
+                    event_state.progress_deltas.lock().unwrap().forget(&gid);
+                }
+                if let DownloadEvent::Progress { id, progress } = &event {
+                    let gid = id.as_uuid().to_string();
+                    event_state.spawn_server_probe(&gid).await;
+                    if let Some((expected, observed)) = event_state
+                        .observe_size(&gid, progress.total_size, progress.completed_size)
+                        .await
+                    {
+                        event_state.spawn_size_mismatch(
+                            &event_app,
+                            gid.clone(),
+                            expected,
+                            observed,
+                        );
+                    }
+                    event_state
+                        .observe_throughput(&gid, progress.download_speed, progress.connections)
+                        .await;
+                }
+                if let DownloadEvent::Completed { id, .. } = &event {
+                    let gid = id.as_uuid().to_string();
+                    let sidecar = event_state.take_sidecar(&gid).await;
+                    event_state.spawn_record_throughput(&gid);
+                    event_state.record_host_outcome(&gid, false).await;
+                    if let Some((expected, observed)) = event_state.check_completed_size(&gid).await
+                    {
+                        event_state.spawn_size_mismatch(&event_app, gid, expected, observed);
+                    } else if event_state
+                        .check_completed_content(&gid)
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::warn!("Failed to check content of {}: {}", gid, e);
+                            false
+                        })
+                    {
+                        // The error page was deleted: nothing to verify or
+                        // move, and the copy it replaced is put back
+                        crate::rotation::restore(&event_state, &gid);
+                        if let Err(e) = crate::staging::forget(&event_state, &gid).await {
+                            log::warn!("Failed to clean up staging for {}: {}", gid, e);
+                        }
+                    } else {
+                        let (state, app) = (event_state.clone(), event_app.clone());
+                        let expected = sidecar.and_then(|sidecar| sidecar.checksum);
+                        tokio::spawn(async move {
+                            match crate::verify::verify_completed(&state, &app, &gid, expected)
+                                .await
+                            {
+                                Ok(true) => crate::rotation::finish_download(&state, &gid),
+                                Ok(false) => return,
+                                Err(e) => log::warn!("Failed to verify {}: {}", gid, e),
+                            }
+                            if let Err(e) =
+                                crate::staging::finish_download(&state, &app, &gid).await
+                            {
+                                log::warn!(
+                                    "Failed to move {} out of the temp directory: {}",
+                                    gid,
+                                    e
+                                );
+                            }
+                            if let Err(e) = crate::rename_rules::finish_download(&state, &gid).await
+                            {
+                                log::warn!("Failed to apply rename rules to {}: {}", gid, e);
+                            }
+                        });
+                    }
+                }
+                if let DownloadEvent::Failed { id, error, .. } = &event {
+                    let gid = id.as_uuid().to_string();
+                    event_state.throughput.lock().unwrap().forget(&gid);
+                    crate::rotation::restore(&event_state, &gid);
+                    event_state.record_host_outcome(&gid, true).await;
+                    let name = match event_state.get_adapter().await {
+                        Ok(adapter) => adapter.get_status(&gid).map(|d| d.name),
+                        Err(_) => None,
+                    }
+                    .unwrap_or_else(|| gid.clone());
+                    crate::events::queue_failure(
+                        &event_app,
+                        &event_state,
+                        FailedDownload {
+                            gid,
+                            name,
+                            error: crate::fd_budget::explain_error(error),
+                        },
+                    );
+                }
                 let event_name = match &event {
                     DownloadEvent::Added { .. } => "download:added",
                     DownloadEvent::Started { .. } => "download:started",
@@ -165,7 +696,34 @@ impl AppState {
                     DownloadEvent::Paused { .. } => "download:paused",
                     DownloadEvent::Resumed { .. } => "download:resumed",
                 };
-                let payload = serde_json::to_value(&event).unwrap_or(serde_json::Value::Null);
+                let mut payload = serde_json::to_value(&event).unwrap_or(serde_json::Value::Null);
+                if let DownloadEvent::Progress { id, progress } = &event {
+                    let progress =
+                        serde_json::to_value(progress).unwrap_or(serde_json::Value::Null);
+                    let delta = event_state
+                        .progress_deltas
+                        .lock()
+                        .unwrap()
+                        .record(&id.as_uuid().to_string(), progress);
+                    if event_state.delta_progress.load(Ordering::Relaxed) {
+                        match delta {
+                            Some(delta) => payload = delta,
+                            None => continue,
+                        }
+                    }
+                }
+                let gid = crate::subscriptions::event_gid(&event);
+                let subscribers = event_state
+                    .subscriptions
+                    .lock()
+                    .unwrap()
+                    .matching(&gid, event_name);
+                for id in subscribers {
+                    let _ = event_app.emit(
+                        &crate::subscriptions::channel(&id),
+                        serde_json::json!({ "event": event_name, "gid": gid, "payload": payload }),
+                    );
+                }
                 let _ = event_app.emit(event_name, payload);
             }
         });
@@ -185,20 +743,347 @@ impl AppState {
                         "recursive:updated",
                         serde_json::json!({ "job": job, "status": status }),
                     ),
-                    RecursiveJobEvent::Removed { id } => (
-                        "recursive:removed",
-                        serde_json::json!({ "id": id }),
-                    ),
+                    RecursiveJobEvent::Removed { id } => {
+                        ("recursive:removed", serde_json::json!({ "id": id }))
+                    }
                 };
                 let _ = recursive_app.emit(event_name, payload);
             }
         });
         *self.recursive_event_handle.write().await = Some(recursive_handle);
 
+        let orphan_state = self.clone();
+        tokio::spawn(async move {
+            match orphan_state.scan_orphaned_partials().await {
+                Ok(orphans) if !orphans.is_empty() => {
+                    log::info!("Found {} orphaned partial download(s)", orphans.len())
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to look for orphaned partial downloads: {}", e),
+            }
+        });
+
+        let (staging_state, staging_app) = (self.clone(), app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = crate::staging::resume(&staging_state, &staging_app).await {
+                log::warn!("Failed to resume moves out of the temp directory: {}", e);
+            }
+            if let Err(e) = crate::rename_rules::restore(&staging_state).await {
+                log::warn!("Failed to restore renamed downloads: {}", e);
+            }
+        });
+
+        *self.engine_started_at.lock().unwrap() = Some(Utc::now());
         log::info!("App state initialized with gosh-dl engine");
         Ok(())
     }
 
+    /// Remove the URL sidecar of a finished HTTP download, returning what
+    /// it recorded (the checksum to verify the file against).
+    async fn take_sidecar(&self, gid: &str) -> Option<Sidecar> {
+        let adapter = self.get_adapter().await.ok()?;
+        let download = adapter
+            .get_status(gid)
+            .filter(|d| d.download_type == DownloadType::Http)?;
+        let part = crate::orphans::part_path(Path::new(&download.save_path), &download.name);
+        let sidecar = crate::orphans::read_sidecar(&part);
+        crate::orphans::remove_sidecar(&part);
+        sidecar
+    }
+
+    /// Feed a progress report to the size guard, which starts watching
+    /// HTTP downloads the first time they report.
+    async fn observe_size(
+        &self,
+        gid: &str,
+        total: Option<u64>,
+        completed: u64,
+    ) -> Option<(u64, u64)> {
+        if !self.size_guard.lock().unwrap().is_tracked(gid) {
+            let http = self
+                .get_adapter()
+                .await
+                .ok()?
+                .get_status(gid)
+                .is_some_and(|d| d.download_type == DownloadType::Http);
+            self.size_guard.lock().unwrap().track(gid, http);
+        }
+        self.size_guard
+            .lock()
+            .unwrap()
+            .observe(gid, total, completed)
+    }
+
+    /// Feed a progress report to the throughput tracker, which starts
+    /// measuring HTTP downloads the first time they report.
+    async fn observe_throughput(&self, gid: &str, speed: u64, connections: u32) {
+        let Ok(adapter) = self.get_adapter().await else {
+            return;
+        };
+        if !self.throughput.lock().unwrap().is_tracked(gid) {
+            let host = adapter
+                .get_status(gid)
+                .filter(|d| d.download_type == DownloadType::Http)
+                .and_then(|d| d.url)
+                .and_then(|url| crate::host_profiles::url_host(&url));
+            self.throughput.lock().unwrap().track(gid, host);
+        }
+        let throttled = self.limits(&adapter).slows_downloads();
+        self.throughput
+            .lock()
+            .unwrap()
+            .observe(gid, speed, connections, throttled);
+    }
+
+    /// Add a finished download's measured speed to its host's profile.
+    fn spawn_record_throughput(&self, gid: &str) {
+        let Some((host, connections, speed)) = self.throughput.lock().unwrap().finish(gid) else {
+            return;
+        };
+        let state = self.clone();
+        tokio::spawn(async move {
+            let result = match state.get_db().await {
+                Ok(db) => {
+                    db.record_host_speed_async(host.clone(), connections, speed)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to update the connection profile of {}: {}", host, e);
+            }
+        });
+    }
+
+    /// Count a finished or failed HTTP download toward its host's recent
+    /// error rate.
+    async fn record_host_outcome(&self, gid: &str, failed: bool) {
+        let Ok(adapter) = self.get_adapter().await else {
+            return;
+        };
+        let host = adapter
+            .get_status(gid)
+            .filter(|d| d.download_type == DownloadType::Http)
+            .and_then(|d| d.url)
+            .and_then(|url| crate::host_profiles::url_host(&url));
+        if let Some(host) = host {
+            self.host_outcomes
+                .lock()
+                .unwrap()
+                .record(host, failed, std::time::Instant::now());
+        }
+    }
+
+    /// Compare a finished HTTP download's file with the size it started with.
+    async fn check_completed_size(&self, gid: &str) -> Option<(u64, u64)> {
+        let download = self.get_adapter().await.ok()?.get_status(gid)?;
+        let size = crate::preview::locate_file(&download, &[])
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len());
+        let mut guard = self.size_guard.lock().unwrap();
+        match size {
+            Some(size) => guard.check_finished(gid, size),
+            None => {
+                guard.forget(gid);
+                None
+            }
+        }
+    }
+
+    /// Probe the server of an HTTP download the app hasn't probed this
+    /// session (one restored from an earlier session, or added before the
+    /// probe could run), once per download, with the request context it was
+    /// added with. Probe results aren't persisted, so until this runs after a
+    /// restart the download's size may be unknown and `resumable` is null.
+    async fn spawn_server_probe(&self, gid: &str) {
+        let Ok(adapter) = self.get_adapter().await else {
+            return;
+        };
+        if adapter.is_probed(gid) {
+            return;
+        }
+        let Some(url) = adapter
+            .get_status(gid)
+            .filter(|d| d.download_type == DownloadType::Http)
+            .and_then(|d| d.url)
+        else {
+            return;
+        };
+        // Marked up front so later progress reports don't probe again
+        adapter.set_server_probe(gid, ServerProbe::default());
+        let (state, gid) = (self.clone(), gid.to_string());
+        tokio::spawn(async move {
+            let (settings, options) = match state.get_db().await {
+                Ok(db) => (
+                    db.get_settings_async().await.unwrap_or_default(),
+                    db.get_download_options_async(gid.clone())
+                        .await
+                        .ok()
+                        .flatten()
+                        .map(|(_, o)| o),
+                ),
+                Err(_) => (Settings::default(), None),
+            };
+            let options = options.unwrap_or_default();
+            // The URL it was added with, which for S3 is signed afresh
+            let target = if crate::s3::is_s3_url(&url) {
+                let config = crate::s3::S3Config::from_settings(&settings);
+                match crate::s3::resolve_url(&config, &url, chrono::Utc::now()) {
+                    Ok(target) => target,
+                    Err(e) => {
+                        log::warn!("Failed to probe {}: {}", gid, e);
+                        return;
+                    }
+                }
+            } else {
+                url.clone()
+            };
+            let request = ProbeRequest::new(&settings, &options);
+            let rules = RedirectRules::from_settings(&settings, options.max_redirects);
+            let probe =
+                crate::ranges::probe_server(&target, &settings.proxy_url, &request, rules, true)
+                    .await;
+            if let Some(total) = probe.total_size {
+                log::debug!("Discovered size of {}: {} bytes", gid, total);
+            }
+            if let Some(reason) = &probe.redirect_error {
+                log::warn!(
+                    "Restored download {} breaks the redirect rules: {}",
+                    gid,
+                    reason
+                );
+            }
+            crate::commands::record_latency(&state, &gid, &url, &probe).await;
+            if let (true, Some(host), Ok(db)) = (
+                probe.broken_ranges,
+                crate::host_profiles::url_host(&url),
+                state.get_db().await,
+            ) {
+                if let Err(e) = db.record_broken_ranges_async(host).await {
+                    log::warn!("Failed to remember broken ranges for {}: {}", url, e);
+                }
+            }
+            adapter.set_server_probe(&gid, probe);
+        });
+    }
+
+    fn spawn_size_mismatch(&self, app: &AppHandle, gid: String, expected: u64, observed: u64) {
+        let (state, app) = (self.clone(), app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = state
+                .handle_size_mismatch(&app, &gid, expected, observed)
+                .await
+            {
+                log::warn!("Failed to stop {} after a size mismatch: {}", gid, e);
+            }
+        });
+    }
+
+    /// Stop a download whose size changed before more of the file is
+    /// overwritten with mismatched data. It is restarted from scratch once
+    /// per URL if `retry_size_mismatch` is on; otherwise it fails with
+    /// `SizeMismatch`.
+    async fn handle_size_mismatch(
+        &self,
+        app: &AppHandle,
+        gid: &str,
+        expected: u64,
+        observed: u64,
+    ) -> Result<()> {
+        let error = Error::SizeMismatch { expected, observed };
+        let adapter = self.get_adapter().await?;
+        let Some(download) = adapter.get_status(gid) else {
+            return Ok(());
+        };
+        log::warn!("{}: {}", download.name, error);
+
+        let retry = self
+            .get_db()
+            .await?
+            .get_settings_async()
+            .await?
+            .retry_size_mismatch;
+        let retry_url = download
+            .url
+            .clone()
+            .filter(|url| retry && self.size_guard.lock().unwrap().allow_retry(url));
+        if let Some(url) = retry_url {
+            let dir = crate::staging::destination(self, gid, &download.save_path).await?;
+            // Added again as it was first added, headers, checksum and
+            // limits included, into the folder and under the name it got
+            let (url, options) = match self
+                .get_db()
+                .await?
+                .get_download_options_async(gid.to_string())
+                .await?
+            {
+                Some((given, options)) => (given.unwrap_or(url), options),
+                None => (url, DownloadOptions::default()),
+            };
+            adapter.remove(gid, true).await?;
+            let options = DownloadOptions {
+                dir: Some(dir),
+                out: Some(download.name.clone()),
+                location: None,
+                rotation: None,
+                ..options
+            };
+            let new_gid = crate::commands::add_download(self, url, Some(options)).await?;
+            log::info!("Restarted {} from scratch as {}", download.name, new_gid);
+            return Ok(());
+        }
+
+        if download.status != DownloadState::Complete {
+            adapter.pause(gid).await?;
+        }
+        adapter.fail(gid, error.to_string());
+        crate::events::queue_failure(
+            app,
+            self,
+            FailedDownload {
+                gid: gid.to_string(),
+                name: download.name,
+                error: error.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Flag a finished HTTP download whose file turned out to be an HTML
+    /// error or login page, deleting the file if the user asked for that.
+    /// Returns true if the file was deleted.
+    async fn check_completed_content(&self, gid: &str) -> Result<bool> {
+        let adapter = self.get_adapter().await?;
+        let Some(download) = adapter.get_status(gid) else {
+            return Ok(false);
+        };
+        if download.download_type != DownloadType::Http {
+            return Ok(false);
+        }
+        let Some(path) = crate::preview::locate_file(&download, &[]) else {
+            return Ok(false);
+        };
+        let size = std::fs::metadata(&path)?.len();
+        let head = crate::preview::read_prefix(&path, 4096)?;
+        let Some(mut message) = crate::preview::suspect_error_page(&download.name, &head, size)
+        else {
+            return Ok(false);
+        };
+        let delete = self
+            .get_db()
+            .await?
+            .get_settings_async()
+            .await?
+            .delete_error_pages;
+        if delete {
+            std::fs::remove_file(&path)?;
+            message.push_str(" (file deleted)");
+        }
+        log::warn!("{}: {}", download.name, message);
+        adapter.flag_warning(gid, message);
+        Ok(delete)
+    }
+
     pub async fn get_adapter(&self) -> Result<EngineAdapter> {
         self.adapter
             .read()
@@ -227,23 +1112,45 @@ impl AppState {
         self.tracker_updater.clone()
     }
 
+    /// How long the app may take to shut down before it exits anyway.
+    pub async fn shutdown_timeout(&self) -> std::time::Duration {
+        let secs = match self.db.read().await.clone() {
+            Some(db) => db
+                .get_settings_async()
+                .await
+                .map_or(10, |s| s.shutdown_timeout_secs),
+            None => 10,
+        };
+        std::time::Duration::from_secs(u64::from(secs.max(1)))
+    }
+
+    /// Stop the engine, saving what the next start needs first: every
+    /// download's progress and the seeding totals. The session marker is
+    /// removed and the time recorded last, so a shutdown cut short by
+    /// `shutdown_timeout` is offered for crash recovery like a crash.
     pub async fn shutdown(&self) -> Result<()> {
-        // Persist a final history snapshot so completed items survive app restarts.
-        // We intentionally avoid writing incomplete states here because incomplete
-        // restoration is handled by the engine's own storage layer.
-        if let (Some(adapter), Some(db)) = (
-            self.adapter.read().await.clone(),
-            self.db.read().await.clone(),
-        ) {
+        let db = self.db.read().await.clone();
+        if let (Some(adapter), Some(db)) = (self.adapter.read().await.clone(), db.as_ref()) {
+            // Incomplete downloads too, so the database is no further behind
+            // than the engine's own storage if it is restored from
             let downloads = adapter.get_all();
+            let count = downloads.len();
             for download in downloads {
-                if download.status != DownloadState::Complete {
-                    continue;
-                }
-                if let Err(e) = db.save_download_async(download).await {
-                    log::warn!("Failed to persist download snapshot during shutdown: {}", e);
-                }
+                db.queue_download(download);
+            }
+            if let Err(e) = db.flush_downloads_async().await {
+                log::warn!(
+                    "Failed to persist download snapshots during shutdown: {}",
+                    e
+                );
+            }
+            if let Err(e) = db.save_seed_totals_async(adapter.seed_totals()).await {
+                log::warn!("Failed to save seeding totals during shutdown: {}", e);
             }
+            if let Err(e) = db.save_traffic_totals_async(adapter.traffic_totals()).await {
+                log::warn!("Failed to save traffic totals during shutdown: {}", e);
+            }
+            log::info!("Checkpointed {} downloads", count);
         }
 
         if let Some(handle) = self.event_handle.write().await.take() {
@@ -252,9 +1159,23 @@ impl AppState {
         if let Some(handle) = self.recursive_event_handle.write().await.take() {
             handle.abort();
         }
+        // The engine stops its torrents, sending trackers their `stopped`
+        // announce, and saves its own resume data
         if let Some(ref engine) = *self.engine.read().await {
             engine.shutdown().await?;
         }
+        *self.engine_started_at.lock().unwrap() = None;
+        if let Some(db) = &db {
+            if let Err(e) = db
+                .set_meta_async(crate::recovery::CLEAN_SHUTDOWN_KEY, Utc::now().to_rfc3339())
+                .await
+            {
+                log::warn!("Failed to record clean shutdown: {}", e);
+            }
+        }
+        if let Some(data_dir) = self.data_dir.read().await.as_deref() {
+            crate::recovery::end_session(data_dir);
+        }
         log::info!("Download engine shut down");
         Ok(())
     }
@@ -279,14 +1200,173 @@ impl AppState {
     }
 
     pub async fn reinitialize(&self, app: AppHandle) -> Result<()> {
+        self.engine_restarts.fetch_add(1, Ordering::Relaxed);
         self.shutdown().await?;
         let data_dir = self.get_data_dir().await?;
         self.initialize(data_dir, app).await
     }
 }
 
+/// Set DHT/PEX/LPD from the user's settings. The VPN kill switch turns them
+/// off engine-wide while the VPN is down. Private torrents need nothing
+/// here: the engine keeps each one off these sources itself (BEP 27).
+pub fn apply_peer_discovery(config: &mut EngineConfig, settings: &Settings, vpn_down: bool) {
+    if vpn_down {
+        config.enable_dht = false;
+        config.enable_pex = false;
+        config.enable_lpd = false;
+    } else {
+        config.enable_dht = settings.bt_enable_dht;
+        config.enable_pex = settings.bt_enable_pex;
+        config.enable_lpd = settings.bt_enable_lpd;
+    }
+}
+
+/// Set web seeding (BEP 19) from the user's settings. The engine reads it
+/// when a torrent starts, so running torrents keep what they started with.
+pub fn apply_web_seeds(config: &mut EngineConfig, settings: &Settings) {
+    config.torrent.webseed.enabled = settings.bt_webseed_enabled;
+    config.torrent.webseed.max_connections = settings.bt_webseed_max_connections as usize;
+}
+
+/// Set peer connection encryption (MSE/PE) from the `bt_encryption`
+/// setting; unknown values mean "prefer". The engine reads it when a
+/// torrent starts. It also takes a config equal to its own default
+/// (`Preferred`, no minimum padding) to mean plaintext, so the handshake
+/// padding starts at one byte to make "prefer" encrypt.
+pub fn apply_encryption(config: &mut EngineConfig, settings: &Settings) {
+    let policy = match settings.bt_encryption.as_str() {
+        "disabled" => EncryptionPolicy::Disabled,
+        "require" => EncryptionPolicy::Required,
+        _ => EncryptionPolicy::Preferred,
+    };
+    config.torrent.encryption = EncryptionConfig {
+        policy,
+        allow_plaintext: policy != EncryptionPolicy::Required,
+        min_padding: 1,
+        ..Default::default()
+    };
+}
+
+/// Whether the battery limits should be in force for this power state.
+pub fn battery_saver_applies(settings: &Settings, power: Option<PowerState>) -> bool {
+    settings.battery_mode_enabled
+        && power.is_some_and(|p| p.on_battery && u32::from(p.percent) < settings.battery_threshold)
+}
+
+/// Cut the engine down to the battery limits: fewer connections per
+/// download, DHT off (no routing-table refresh traffic), and speed caps no
+/// higher than the battery limits. Applied on top of the normal settings.
+pub fn apply_battery_limits(config: &mut EngineConfig, settings: &Settings) {
+    config.max_connections_per_download = config
+        .max_connections_per_download
+        .min(settings.battery_max_connections.max(1) as usize);
+    config.global_download_limit = lower_limit(
+        config.global_download_limit,
+        settings.battery_download_limit,
+    );
+    config.global_upload_limit =
+        lower_limit(config.global_upload_limit, settings.battery_upload_limit);
+    config.enable_dht = false;
+}
+
+/// The stricter of a speed limit (None = unlimited) and a battery limit
+/// (0 = unlimited).
+pub fn lower_limit(current: Option<u64>, battery: u64) -> Option<u64> {
+    match (current, battery) {
+        (current, 0) => current,
+        (None, battery) => Some(battery),
+        (Some(current), battery) => Some(current.min(battery)),
+    }
+}
+
 impl Default for AppState {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_battery_limits() {
+        let mut settings = Settings {
+            battery_mode_enabled: true,
+            ..Default::default()
+        };
+        let battery = |percent| {
+            Some(PowerState {
+                on_battery: true,
+                percent,
+            })
+        };
+        assert!(battery_saver_applies(&settings, battery(30)));
+        assert!(!battery_saver_applies(&settings, battery(80)));
+        assert!(!battery_saver_applies(
+            &settings,
+            Some(PowerState {
+                on_battery: false,
+                percent: 30
+            })
+        ));
+        assert!(!battery_saver_applies(&settings, None));
+
+        let mut config = EngineConfig {
+            max_connections_per_download: 8,
+            global_download_limit: Some(1024 * 1024),
+            global_upload_limit: None,
+            ..Default::default()
+        };
+        apply_battery_limits(&mut config, &settings);
+        assert_eq!(config.max_connections_per_download, 2);
+        // A user limit already below the battery cap is kept
+        assert_eq!(config.global_download_limit, Some(1024 * 1024));
+        assert_eq!(config.global_upload_limit, Some(256 * 1024));
+        assert!(!config.enable_dht);
+
+        settings.battery_mode_enabled = false;
+        assert!(!battery_saver_applies(&settings, battery(10)));
+        assert_eq!(lower_limit(Some(500), 0), Some(500));
+    }
+
+    #[test]
+    fn test_apply_encryption() {
+        let mut settings = Settings::default();
+        let mut config = EngineConfig::default();
+        let enabled = |config: &EngineConfig| {
+            crate::capabilities::encryption_enabled(&config.torrent.encryption)
+        };
+        assert!(!enabled(&config));
+
+        apply_encryption(&mut config, &settings);
+        assert_eq!(
+            config.torrent.encryption.policy,
+            EncryptionPolicy::Preferred
+        );
+        assert!(config.torrent.encryption.allow_plaintext);
+        assert!(enabled(&config));
+
+        settings.bt_encryption = "require".into();
+        apply_encryption(&mut config, &settings);
+        assert_eq!(config.torrent.encryption.policy, EncryptionPolicy::Required);
+        assert!(!config.torrent.encryption.allow_plaintext);
+
+        settings.bt_encryption = "disabled".into();
+        apply_encryption(&mut config, &settings);
+        assert!(!enabled(&config));
+    }
+
+    #[test]
+    fn test_apply_peer_discovery_vpn_down() {
+        let settings = Settings::default();
+        let mut config = EngineConfig::default();
+
+        apply_peer_discovery(&mut config, &settings, true);
+        assert!(!config.enable_dht && !config.enable_pex && !config.enable_lpd);
+
+        apply_peer_discovery(&mut config, &settings, false);
+        assert!(config.enable_dht && config.enable_pex && config.enable_lpd);
+    }
+}
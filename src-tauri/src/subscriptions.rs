@@ -0,0 +1,133 @@
+//! Filtered download event subscriptions for lightweight clients (scripts,
+//! CLI progress bars, automation frontends) that only care about a few
+//! downloads or event types. Every engine event is still broadcast under
+//! its own name; in addition, each subscription whose filter matches gets
+//! it on `download-events:<id>`, so a client listens to one event instead
+//! of the whole stream.
+
+use crate::types::EventFilter;
+use crate::{Error, Result};
+use gosh_dl::DownloadEvent;
+use std::collections::HashMap;
+
+/// Subscriptions a client may hold open at once.
+const MAX_SUBSCRIPTIONS: usize = 64;
+
+/// Event name a subscription's events are emitted under.
+pub fn channel(id: &str) -> String {
+    format!("download-events:{}", id)
+}
+
+/// GID of the download an engine event is about.
+pub fn event_gid(event: &DownloadEvent) -> String {
+    let id = match event {
+        DownloadEvent::Added { id }
+        | DownloadEvent::Started { id }
+        | DownloadEvent::Progress { id, .. }
+        | DownloadEvent::StateChanged { id, .. }
+        | DownloadEvent::Completed { id }
+        | DownloadEvent::Failed { id, .. }
+        | DownloadEvent::Removed { id }
+        | DownloadEvent::Paused { id }
+        | DownloadEvent::Resumed { id } => id,
+    };
+    id.as_uuid().to_string()
+}
+
+impl EventFilter {
+    /// Whether an event named `event` (`download:progress`, or just
+    /// `progress`) for `gid` passes; empty lists match everything.
+    pub fn matches(&self, gid: &str, event: &str) -> bool {
+        let short = event.strip_prefix("download:").unwrap_or(event);
+        (self.gids.is_empty() || self.gids.iter().any(|g| g == gid))
+            && (self.events.is_empty() || self.events.iter().any(|e| e == event || e == short))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    filters: HashMap<String, EventFilter>,
+    next_id: u64,
+}
+
+impl Subscriptions {
+    pub fn subscribe(&mut self, filter: EventFilter) -> Result<String> {
+        if self.filters.len() >= MAX_SUBSCRIPTIONS {
+            return Err(Error::InvalidInput(format!(
+                "Too many event subscriptions (limit {})",
+                MAX_SUBSCRIPTIONS
+            )));
+        }
+        self.next_id += 1;
+        let id = format!("sub-{}", self.next_id);
+        self.filters.insert(id.clone(), filter);
+        Ok(id)
+    }
+
+    pub fn unsubscribe(&mut self, id: &str) -> Result<()> {
+        self.filters
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| Error::NotFound(format!("Event subscription not found: {}", id)))
+    }
+
+    /// IDs of the subscriptions an event passes.
+    pub fn matching(&self, gid: &str, event: &str) -> Vec<String> {
+        self.filters
+            .iter()
+            .filter(|(_, filter)| filter.matches(gid, event))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(gids: &[&str], events: &[&str]) -> EventFilter {
+        EventFilter {
+            gids: gids.iter().map(|s| s.to_string()).collect(),
+            events: events.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        assert!(filter(&[], &[]).matches("a", "download:progress"));
+        let progress_of_a = filter(&["a"], &["progress", "download:completed"]);
+        assert!(progress_of_a.matches("a", "download:progress"));
+        assert!(progress_of_a.matches("a", "download:completed"));
+        assert!(!progress_of_a.matches("b", "download:progress"));
+        assert!(!progress_of_a.matches("a", "download:paused"));
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let mut subscriptions = Subscriptions::default();
+        let all = subscriptions.subscribe(filter(&[], &[])).unwrap();
+        let only_b = subscriptions.subscribe(filter(&["b"], &[])).unwrap();
+        assert_ne!(all, only_b);
+
+        let mut matching = subscriptions.matching("b", "download:added");
+        matching.sort();
+        assert_eq!(matching, vec![all.clone(), only_b.clone()]);
+        assert_eq!(
+            subscriptions.matching("a", "download:added"),
+            vec![all.clone()]
+        );
+
+        subscriptions.unsubscribe(&all).unwrap();
+        assert!(subscriptions.unsubscribe(&all).is_err());
+        assert!(subscriptions.matching("a", "download:added").is_empty());
+    }
+
+    #[test]
+    fn test_subscription_limit() {
+        let mut subscriptions = Subscriptions::default();
+        for _ in 0..MAX_SUBSCRIPTIONS {
+            subscriptions.subscribe(EventFilter::default()).unwrap();
+        }
+        assert!(subscriptions.subscribe(EventFilter::default()).is_err());
+    }
+}
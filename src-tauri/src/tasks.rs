@@ -0,0 +1,263 @@
+//! Long-running background operations (history export, maintenance) that
+//! report progress over `task:progress` and can be listed and cancelled
+//! while they run.
+
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+
+pub const TASK_PROGRESS_EVENT: &str = "task:progress";
+
+/// Finished tasks kept around so the UI can show how they ended.
+const MAX_FINISHED_TASKS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskProgress {
+    pub id: String,
+    /// Machine-readable operation, e.g. `history-export`
+    pub kind: String,
+    pub label: String,
+    pub status: TaskStatus,
+    /// 0-100; None while the amount of work is unknown
+    pub percent: Option<f64>,
+    /// Current step while running, the error after a failure
+    pub message: Option<String>,
+    pub cancellable: bool,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+struct Entry {
+    progress: TaskProgress,
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Default)]
+pub struct TaskRegistry {
+    entries: Arc<Mutex<Vec<Entry>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl TaskRegistry {
+    /// Register a running task and announce it.
+    pub fn start(
+        &self,
+        app: &AppHandle,
+        kind: &str,
+        label: String,
+        cancellable: bool,
+    ) -> TaskHandle {
+        let (progress, cancel) = self.insert(kind, label, cancellable);
+        let _ = app.emit(TASK_PROGRESS_EVENT, &progress);
+        TaskHandle {
+            id: progress.id,
+            registry: self.clone(),
+            app: app.clone(),
+            cancel,
+            last_percent: None,
+        }
+    }
+
+    /// Running tasks first, then recently finished ones, newest first.
+    pub fn list(&self) -> Vec<TaskProgress> {
+        let entries = self.entries.lock().unwrap();
+        let mut tasks: Vec<TaskProgress> =
+            entries.iter().rev().map(|e| e.progress.clone()).collect();
+        tasks.sort_by_key(|t| t.status != TaskStatus::Running);
+        tasks
+    }
+
+    /// Ask a running task to stop. It reports `cancelled` once it has.
+    pub fn cancel(&self, id: &str) -> Result<()> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries
+            .iter()
+            .find(|e| e.progress.id == id)
+            .ok_or_else(|| Error::NotFound(format!("Task not found: {}", id)))?;
+        if !entry.progress.cancellable {
+            return Err(Error::InvalidInput(format!(
+                "{} cannot be cancelled",
+                entry.progress.label
+            )));
+        }
+        if entry.progress.status != TaskStatus::Running {
+            return Err(Error::InvalidInput(format!(
+                "{} has already finished",
+                entry.progress.label
+            )));
+        }
+        entry.cancel.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn insert(
+        &self,
+        kind: &str,
+        label: String,
+        cancellable: bool,
+    ) -> (TaskProgress, Arc<AtomicBool>) {
+        let progress = TaskProgress {
+            id: format!("task-{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1),
+            kind: kind.to_string(),
+            label,
+            status: TaskStatus::Running,
+            percent: None,
+            message: None,
+            cancellable,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.entries.lock().unwrap().push(Entry {
+            progress: progress.clone(),
+            cancel: cancel.clone(),
+        });
+        (progress, cancel)
+    }
+
+    /// Apply `f` to a task and return the updated snapshot.
+    fn update(&self, id: &str, f: impl FnOnce(&mut TaskProgress)) -> Option<TaskProgress> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.iter_mut().find(|e| e.progress.id == id)?;
+        f(&mut entry.progress);
+        let snapshot = entry.progress.clone();
+        let finished = entries
+            .iter()
+            .filter(|e| e.progress.status != TaskStatus::Running)
+            .count();
+        if finished > MAX_FINISHED_TASKS {
+            // Oldest finished entries go first
+            let mut excess = finished - MAX_FINISHED_TASKS;
+            entries.retain(|e| {
+                if excess > 0 && e.progress.status != TaskStatus::Running {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        Some(snapshot)
+    }
+}
+
+/// Held by the code doing the work; reports progress and the outcome.
+pub struct TaskHandle {
+    id: String,
+    registry: TaskRegistry,
+    app: AppHandle,
+    cancel: Arc<AtomicBool>,
+    last_percent: Option<u32>,
+}
+
+impl TaskHandle {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// True once the user asked to cancel; the worker should stop and
+    /// clean up, then call `finish`.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Record `done` of `total` units of work. Events go out when the
+    /// whole-number percentage changes, so callers can report every item.
+    pub fn progress(&mut self, done: u64, total: u64, message: Option<String>) {
+        let percent = percent_of(done, total);
+        let whole = percent.map(|p| p as u32);
+        if whole == self.last_percent && message.is_none() {
+            return;
+        }
+        self.last_percent = whole;
+        if let Some(snapshot) = self.registry.update(&self.id, |t| {
+            t.percent = percent;
+            if message.is_some() {
+                t.message = message;
+            }
+        }) {
+            let _ = self.app.emit(TASK_PROGRESS_EVENT, &snapshot);
+        }
+    }
+
+    /// Record how the task ended. A cancel request wins over the result,
+    /// since the worker stopped early because of it.
+    pub fn finish<T>(self, result: &Result<T>) {
+        let cancelled = self.is_cancelled();
+        if let Some(snapshot) = self.registry.update(&self.id, |t| {
+            t.finished_at = Some(Utc::now());
+            t.status = match result {
+                _ if cancelled => TaskStatus::Cancelled,
+                Ok(_) => {
+                    t.percent = Some(100.0);
+                    t.message = None;
+                    TaskStatus::Completed
+                }
+                Err(e) => {
+                    t.message = Some(e.to_string());
+                    TaskStatus::Failed
+                }
+            };
+        }) {
+            let _ = self.app.emit(TASK_PROGRESS_EVENT, &snapshot);
+        }
+    }
+}
+
+fn percent_of(done: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        return None;
+    }
+    Some((done.min(total) as f64 / total as f64 * 100.0 * 10.0).round() / 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_of() {
+        assert_eq!(percent_of(0, 0), None);
+        assert_eq!(percent_of(1, 3), Some(33.3));
+        assert_eq!(percent_of(5, 4), Some(100.0));
+    }
+
+    #[test]
+    fn test_cancel_and_prune() {
+        let registry = TaskRegistry::default();
+        let (task, cancel) = registry.insert("history-export", "Export history".into(), true);
+        let (fixed, _) = registry.insert("vacuum", "Compact database".into(), false);
+
+        assert!(registry.cancel(&fixed.id).is_err());
+        assert!(registry.cancel("missing").is_err());
+        registry.cancel(&task.id).unwrap();
+        assert!(cancel.load(Ordering::SeqCst));
+
+        registry.update(&task.id, |t| t.status = TaskStatus::Cancelled);
+        assert!(registry.cancel(&task.id).is_err());
+        // Running tasks are listed first
+        assert_eq!(registry.list()[0].id, fixed.id);
+
+        for _ in 0..MAX_FINISHED_TASKS + 5 {
+            let (done, _) = registry.insert("history-export", "Export history".into(), true);
+            registry.update(&done.id, |t| t.status = TaskStatus::Completed);
+        }
+        let tasks = registry.list();
+        assert_eq!(tasks.len(), MAX_FINISHED_TASKS + 1);
+        assert!(tasks.iter().any(|t| t.id == fixed.id));
+        assert!(!tasks.iter().any(|t| t.id == task.id));
+    }
+}
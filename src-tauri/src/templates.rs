@@ -0,0 +1,152 @@
+//! Download templates: named option presets ("Private tracker", "Linux
+//! ISOs", "Work VPN") bundling a save directory or location, category,
+//! priority, speed caps and trackers. A download added with
+//! `DownloadOptions.template` starts from the template's options; anything
+//! given with the download itself wins. The proxy is engine-wide, so
+//! templates don't carry one.
+
+use crate::types::{DownloadOptions, DownloadTemplate};
+use crate::{Error, Result};
+
+/// Check a template from the frontend: a trimmed name, no nested
+/// template, and tracker URLs the engine can announce to.
+pub fn normalize_template(template: DownloadTemplate) -> Result<DownloadTemplate> {
+    let name = template.name.trim().to_string();
+    if name.is_empty() {
+        return Err(Error::InvalidInput("Template name cannot be empty".into()));
+    }
+    if template.options.template.is_some() {
+        return Err(Error::InvalidInput(
+            "A template cannot use another template".into(),
+        ));
+    }
+    let mut trackers: Vec<String> = Vec::new();
+    for tracker in template
+        .trackers
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+    {
+        let scheme = tracker
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+        if !matches!(scheme.as_deref(), Some("http" | "https" | "udp" | "wss")) {
+            return Err(Error::InvalidInput(format!(
+                "Not a tracker URL: {}",
+                tracker
+            )));
+        }
+        if !trackers.iter().any(|t| t == tracker) {
+            trackers.push(tracker.to_string());
+        }
+    }
+    Ok(DownloadTemplate {
+        name,
+        options: template.options,
+        trackers,
+    })
+}
+
+/// `options` on top of the template's: fields set in `options` are kept,
+/// the rest come from the template.
+pub fn apply(options: DownloadOptions, template: &DownloadTemplate) -> Result<DownloadOptions> {
+    let mut merged = serde_json::to_value(&template.options)?;
+    if let (Some(base), serde_json::Value::Object(overrides)) =
+        (merged.as_object_mut(), serde_json::to_value(&options)?)
+    {
+        base.extend(overrides.into_iter().filter(|(_, value)| !value.is_null()));
+    }
+    let mut merged: DownloadOptions = serde_json::from_value(merged)?;
+    merged.template = None;
+    Ok(merged)
+}
+
+/// `magnet` with each of `trackers` it doesn't list yet added as a `tr`
+/// parameter.
+pub fn with_trackers(magnet: &str, trackers: &[String]) -> String {
+    let mut uri = magnet.to_string();
+    for tracker in trackers {
+        let encoded: String = url::form_urlencoded::byte_serialize(tracker.as_bytes()).collect();
+        if magnet.contains(&format!("tr={}", encoded))
+            || magnet.contains(&format!("tr={}", tracker))
+        {
+            continue;
+        }
+        uri.push_str(if uri.contains('?') { "&tr=" } else { "?tr=" });
+        uri.push_str(&encoded);
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str, trackers: &[&str]) -> DownloadTemplate {
+        DownloadTemplate {
+            name: name.into(),
+            options: DownloadOptions {
+                dir: Some("/srv/isos".into()),
+                priority: Some("low".into()),
+                max_download_limit: Some("1048576".into()),
+                ..Default::default()
+            },
+            trackers: trackers.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_template() {
+        let normalized = normalize_template(template(
+            "  Linux ISOs ",
+            &[
+                "udp://tracker.example:6969/announce",
+                " udp://tracker.example:6969/announce",
+                "",
+            ],
+        ))
+        .unwrap();
+        assert_eq!(normalized.name, "Linux ISOs");
+        assert_eq!(
+            normalized.trackers,
+            vec!["udp://tracker.example:6969/announce"]
+        );
+
+        assert!(normalize_template(template(" ", &[])).is_err());
+        assert!(normalize_template(template("Bad", &["tracker.example"])).is_err());
+        let mut nested = template("Nested", &[]);
+        nested.options.template = Some("Linux ISOs".into());
+        assert!(normalize_template(nested).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let options = DownloadOptions {
+            priority: Some("high".into()),
+            out: Some("debian.iso".into()),
+            template: Some("Linux ISOs".into()),
+            ..Default::default()
+        };
+        let merged = apply(options, &template("Linux ISOs", &[])).unwrap();
+        assert_eq!(merged.dir.as_deref(), Some("/srv/isos"));
+        assert_eq!(merged.max_download_limit.as_deref(), Some("1048576"));
+        // Given with the download, so it wins
+        assert_eq!(merged.priority.as_deref(), Some("high"));
+        assert_eq!(merged.out.as_deref(), Some("debian.iso"));
+        assert_eq!(merged.template, None);
+    }
+
+    #[test]
+    fn test_with_trackers() {
+        let magnet = "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567\
+                      &tr=udp%3A%2F%2Fa.example%3A6969";
+        let trackers = vec![
+            "udp://a.example:6969".to_string(),
+            "https://b.example/announce".to_string(),
+        ];
+        assert_eq!(
+            with_trackers(magnet, &trackers),
+            format!("{}&tr=https%3A%2F%2Fb.example%2Fannounce", magnet)
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Editing a .torrent's metainfo before it is added, for cross-seeding.
+//! The top-level `comment` and `creation date` can be replaced or
+//! stripped without touching the info hash. `source` and `private` live in
+//! the info dictionary, so changing them makes a different torrent with an
+//! info hash of its own: the point when re-adding a torrent for another
+//! tracker, though the copies no longer share a swarm.
+
+use crate::types::TorrentEdits;
+use crate::{Error, Result};
+use gosh_dl::torrent::BencodeValue as Value;
+use std::collections::BTreeMap;
+
+impl TorrentEdits {
+    /// Whether the edits touch the info dictionary, and so the info hash.
+    pub fn changes_info(&self) -> bool {
+        self.source.is_some() || self.private.is_some()
+    }
+}
+
+/// `torrent_data` with `edits` applied, re-encoded.
+pub fn edit(torrent_data: &[u8], edits: &TorrentEdits) -> Result<Vec<u8>> {
+    let mut root = Value::parse_exact(torrent_data)
+        .map_err(|e| Error::InvalidInput(format!("Failed to parse torrent: {}", e)))?;
+    let Some(metainfo) = root.as_dict_mut() else {
+        return Err(Error::InvalidInput(
+            "Torrent metainfo is not a dictionary".into(),
+        ));
+    };
+    if let Some(comment) = &edits.comment {
+        set_text(metainfo, "comment", comment);
+    }
+    match edits.creation_date {
+        Some(0) => {
+            metainfo.remove(b"creation date".as_slice());
+        }
+        Some(date) => {
+            metainfo.insert(b"creation date".to_vec(), Value::Integer(date));
+        }
+        None => {}
+    }
+
+    let Some(Value::Dict(info)) = metainfo.get_mut(b"info".as_slice()) else {
+        return Err(Error::InvalidInput("Torrent has no info dictionary".into()));
+    };
+    if let Some(source) = &edits.source {
+        set_text(info, "source", source);
+    }
+    match edits.private {
+        Some(true) => {
+            info.insert(b"private".to_vec(), Value::Integer(1));
+        }
+        Some(false) => {
+            info.remove(b"private".as_slice());
+        }
+        None => {}
+    }
+    Ok(root.encode())
+}
+
+/// Set a text field, or remove it when `text` is empty.
+fn set_text(dict: &mut BTreeMap<Vec<u8>, Value>, key: &str, text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        dict.remove(key.as_bytes());
+    } else {
+        dict.insert(
+            key.as_bytes().to_vec(),
+            Value::Bytes(text.as_bytes().to_vec()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TORRENT: &[u8] = b"d7:comment5:hello13:creation datei1700000000e4:infod6:lengthi3e\
+        4:name5:a.iso12:piece lengthi16384e6:pieces20:012345678901234567897:privatei1eee";
+
+    fn info(data: &[u8]) -> Value {
+        Value::parse_exact(data)
+            .unwrap()
+            .get("info")
+            .unwrap()
+            .clone()
+    }
+
+    fn is_private(data: &[u8]) -> bool {
+        gosh_dl::torrent::Metainfo::parse(data)
+            .unwrap()
+            .info
+            .private
+    }
+
+    #[test]
+    fn test_top_level_edits_keep_info() {
+        let edits = TorrentEdits {
+            comment: Some(String::new()),
+            creation_date: Some(0),
+            ..Default::default()
+        };
+        assert!(!edits.changes_info());
+        let edited = edit(TORRENT, &edits).unwrap();
+        let root = Value::parse_exact(&edited).unwrap();
+        assert!(root.get("comment").is_none() && root.get("creation date").is_none());
+        assert_eq!(info(&edited), info(TORRENT));
+
+        let edits = TorrentEdits {
+            comment: Some(" cross-seed ".into()),
+            ..Default::default()
+        };
+        let root = Value::parse_exact(&edit(TORRENT, &edits).unwrap()).unwrap();
+        assert_eq!(
+            root.get("comment"),
+            Some(&Value::Bytes(b"cross-seed".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_info_edits() {
+        let edits = TorrentEdits {
+            source: Some("OTHER".into()),
+            private: Some(false),
+            ..Default::default()
+        };
+        assert!(edits.changes_info());
+        let edited = edit(TORRENT, &edits).unwrap();
+        assert!(!is_private(&edited));
+        assert_eq!(
+            info(&edited).get("source"),
+            Some(&Value::Bytes(b"OTHER".to_vec()))
+        );
+
+        let private = edit(
+            &edited,
+            &TorrentEdits {
+                private: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(is_private(&private));
+        assert!(edit(b"d7:comment1:xe", &TorrentEdits::default()).is_err());
+    }
+}
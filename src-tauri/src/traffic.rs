@@ -0,0 +1,182 @@
+//! Download traffic per download, kept across restarts. The engine reports
+//! current download speeds and how much of each download is complete,
+//! nothing about bytes thrown away, so the app samples both alongside the
+//! seeding totals and tells the bytes that became part of a file from the
+//! ones that didn't: data received but never counted complete (pieces that
+//! failed their hash check, blocks fetched twice in endgame, partial pieces
+//! dropped on pause) and data counted complete and later discarded (a
+//! resume that didn't match, a download restarted from scratch). Protocol
+//! overhead isn't reported by the engine and isn't included.
+
+use crate::types::{Download, TrafficStats};
+use std::collections::{HashMap, HashSet};
+
+/// Totals of one download as stored in the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficTotals {
+    /// Bytes received, from sampled download speeds
+    pub received: u64,
+    /// Growth of the completed size
+    pub payload: u64,
+    /// Completed bytes later thrown away
+    pub discarded: u64,
+    /// Completed size at the last sample
+    pub completed: u64,
+}
+
+impl TrafficTotals {
+    /// Received bytes that never counted as complete.
+    fn unverified(&self) -> u64 {
+        self.received.saturating_sub(self.payload)
+    }
+
+    pub fn stats(&self) -> TrafficStats {
+        TrafficStats {
+            received: self.received,
+            payload: self.payload.saturating_sub(self.discarded),
+            wasted: self.discarded + self.unverified(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TrafficLedger {
+    totals: HashMap<String, TrafficTotals>,
+    /// GIDs changed since the last `take_dirty`
+    dirty: HashSet<String>,
+}
+
+impl TrafficLedger {
+    /// Start from the totals saved by an earlier session.
+    pub fn load(&mut self, totals: HashMap<String, TrafficTotals>) {
+        self.totals = totals;
+        self.dirty.clear();
+    }
+
+    /// Add `elapsed_secs` at the downloads' current speeds and account for
+    /// how their completed sizes moved. A download seen for the first time
+    /// counts from its current size.
+    pub fn sample(&mut self, downloads: &[Download], elapsed_secs: u64) {
+        for download in downloads {
+            let totals = self
+                .totals
+                .entry(download.gid.clone())
+                .or_insert_with(|| TrafficTotals {
+                    completed: download.completed_size,
+                    ..Default::default()
+                });
+            let before = *totals;
+            totals.received += download.download_speed * elapsed_secs;
+            if download.completed_size >= totals.completed {
+                totals.payload += download.completed_size - totals.completed;
+            } else {
+                totals.discarded += totals.completed - download.completed_size;
+            }
+            totals.completed = download.completed_size;
+            if *totals != before {
+                self.dirty.insert(download.gid.clone());
+            }
+        }
+    }
+
+    pub fn get(&self, gid: &str) -> Option<TrafficTotals> {
+        self.totals.get(gid).copied()
+    }
+
+    /// Traffic of every download seen, removed ones included.
+    pub fn overall(&self) -> TrafficStats {
+        self.totals
+            .values()
+            .fold(TrafficStats::default(), |sum, totals| {
+                let stats = totals.stats();
+                TrafficStats {
+                    received: sum.received + stats.received,
+                    payload: sum.payload + stats.payload,
+                    wasted: sum.wasted + stats.wasted,
+                }
+            })
+    }
+
+    /// Totals of every download seen, removed ones included.
+    pub fn all(&self) -> Vec<(String, TrafficTotals)> {
+        self.totals
+            .iter()
+            .map(|(gid, totals)| (gid.clone(), *totals))
+            .collect()
+    }
+
+    /// Totals changed since the last call, for saving.
+    pub fn take_dirty(&mut self) -> Vec<(String, TrafficTotals)> {
+        self.dirty
+            .drain()
+            .filter_map(|gid| self.totals.get(&gid).map(|totals| (gid, *totals)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DownloadState, DownloadType};
+
+    fn download(gid: &str, completed_size: u64, download_speed: u64) -> Download {
+        Download {
+            gid: gid.into(),
+            name: gid.into(),
+            download_type: DownloadType::Torrent,
+            status: DownloadState::Active,
+            total_size: 10_000,
+            completed_size,
+            download_speed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut ledger = TrafficLedger::default();
+        ledger.sample(&[download("a", 500, 0)], 5);
+        assert_eq!(ledger.get("a").unwrap().stats(), TrafficStats::default());
+
+        // 1000 received, 800 of it verified
+        ledger.sample(&[download("a", 1300, 200)], 5);
+        assert_eq!(
+            ledger.get("a").unwrap().stats(),
+            TrafficStats {
+                received: 1000,
+                payload: 800,
+                wasted: 200
+            }
+        );
+
+        // Resume data didn't match and the download started over
+        ledger.sample(&[download("a", 100, 20)], 5);
+        assert_eq!(
+            ledger.get("a").unwrap().stats(),
+            TrafficStats {
+                received: 1100,
+                payload: 0,
+                wasted: 1500
+            }
+        );
+
+        ledger.sample(&[download("b", 0, 100)], 1);
+        assert_eq!(
+            ledger.overall(),
+            TrafficStats {
+                received: 1200,
+                payload: 0,
+                wasted: 1600
+            }
+        );
+        let mut dirty: Vec<String> = ledger
+            .take_dirty()
+            .into_iter()
+            .map(|(gid, _)| gid)
+            .collect();
+        dirty.sort();
+        assert_eq!(dirty, vec!["a", "b"]);
+        ledger.sample(&[download("a", 100, 0)], 5);
+        assert!(ledger.take_dirty().is_empty());
+    }
+}
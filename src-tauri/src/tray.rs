@@ -36,7 +36,11 @@ pub fn create_tray(app: &AppHandle) -> tauri::Result<()> {
     )?;
 
     let mut builder = TrayIconBuilder::with_id("main-tray")
-        .icon(app.default_window_icon().cloned().expect("app icon missing"))
+        .icon(
+            app.default_window_icon()
+                .cloned()
+                .expect("app icon missing"),
+        )
         .tooltip("Gosh-Fetch")
         .menu(&menu)
         .on_menu_event(|app, event| handle_tray_action(app, event.id.as_ref()))
@@ -84,8 +88,8 @@ fn handle_tray_action(app: &AppHandle, action: &str) {
         "resume-all" => {
             let state = app.state::<AppState>().inner().clone();
             tauri::async_runtime::spawn(async move {
-                if let Ok(adapter) = state.get_adapter().await {
-                    adapter.resume_all().await;
+                if let Err(e) = crate::commands::resume_all(&state).await {
+                    log::warn!("Failed to resume all downloads: {}", e);
                 }
             });
         }
@@ -45,7 +45,8 @@ pub struct DownloadOptions {
     /// Download priority (low, normal, high, critical)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<String>,
-    /// Checksum for verification (format: "sha256:hex" or "md5:hex")
+    /// Checksum for verification ("sha256:hex", "md5:hex", "sha1:hex" or
+    /// "blake3:hex"; the last two are checked by the app, not the engine)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub checksum: Option<String>,
     /// Mirror/failover URLs
@@ -54,6 +55,177 @@ pub struct DownloadOptions {
     /// Sequential download mode (for torrents)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sequential: Option<bool>,
+    /// What to do with an existing file of the same name (HTTP downloads)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<FileRotation>,
+    /// Pattern-based file selection (for torrents), applied on top of `select_file`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_filter: Option<FileFilter>,
+    /// Ask the server for uncompressed content (HTTP downloads); defaults
+    /// to the `http_identity_encoding` setting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity_encoding: Option<bool>,
+    /// Category whose directory to save into; when absent (and no `dir` is
+    /// given) the category rules pick one from the file's extension or type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Named download location to save into (`Default` for the download
+    /// directory); when absent the one last used for the category applies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Redirects this download may follow (HTTP downloads); defaults to
+    /// the `max_redirects` setting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_redirects: Option<u32>,
+    /// Download template whose options this download starts from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Changes to a .torrent's metainfo before it is added
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub torrent_edits: Option<TorrentEdits>,
+    /// Leave a magnet link's trackers as they are instead of adding ones
+    /// from the global list (see the `bt_magnet_merge_trackers` setting)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_global_trackers: Option<bool>,
+    /// Add the download paused. The engine has no such option, so the app
+    /// pauses it right after adding it, in the same call; it may be
+    /// scheduled for a moment before that
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<bool>,
+}
+
+/// Named option preset, picked with `DownloadOptions.template`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadTemplate {
+    pub name: String,
+    /// Options the download starts from; ones given with it win
+    #[serde(default)]
+    pub options: DownloadOptions,
+    /// Trackers added to magnet links
+    #[serde(default)]
+    pub trackers: Vec<String>,
+}
+
+/// Policy for a download whose target file already exists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "camelCase")]
+pub enum FileRotation {
+    /// Replace the existing file
+    Overwrite,
+    /// Move the existing file aside with a timestamp suffix
+    TimestampSuffix,
+    /// Like `TimestampSuffix`, pruning all but the newest `count` old copies
+    KeepLast { count: u32 },
+}
+
+/// Include/exclude patterns for choosing torrent files by path.
+/// Globs by default; prefix a pattern with `re:` for a regular expression.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileFilter {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Metainfo edits for cross-seeding. Fields left out are kept; an empty
+/// string removes the field. `source` and `private` are part of the info
+/// dictionary, so changing them gives the torrent a new info hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TorrentEdits {
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Unix seconds; 0 removes it
+    #[serde(default)]
+    pub creation_date: Option<i64>,
+    /// Tag trackers use to tell cross-seeded copies apart
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Set or clear the private flag (BEP 27)
+    #[serde(default)]
+    pub private: Option<bool>,
+}
+
+/// Automatic categorization rule. Rules are checked in order; the first
+/// listing a download's extension (or, failing that, its MIME type) wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryRule {
+    pub name: String,
+    /// Save directory; relative paths are taken from the download directory
+    pub directory: String,
+    /// Extensions without the dot, e.g. `mkv`
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// MIME types; `video/*` matches every video type
+    #[serde(default)]
+    pub mime_types: Vec<String>,
+    /// Days after which finished downloads are archived; None follows the
+    /// archive setting, 0 never archives this category
+    #[serde(default)]
+    pub archive_after_days: Option<u32>,
+    /// Archive folder; empty uses the archive setting's, relative paths are
+    /// taken from it
+    #[serde(default)]
+    pub archive_directory: String,
+}
+
+/// Renames a finished HTTP download's file, see `set_rename_rules`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameRule {
+    pub name: String,
+    /// Only downloads saved under this category; empty for any
+    #[serde(default)]
+    pub category: String,
+    /// Only downloads from this host or its subdomains; empty for any
+    #[serde(default)]
+    pub host: String,
+    /// Regular expression searched in the file name, ignoring case;
+    /// empty matches the whole name
+    #[serde(default)]
+    pub find: String,
+    /// What each match becomes, with `$1`-style groups and `{name}`,
+    /// `{ext}`, `{title}`, `{date}`, `{host}` and `{category}` tokens
+    pub replace: String,
+}
+
+/// Name a rename rule gives a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamePreview {
+    /// The rule that matched
+    pub rule: String,
+    pub name: String,
+}
+
+/// A named place downloads can be saved to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadLocation {
+    pub name: String,
+    /// Absolute path; `~` is expanded
+    pub path: String,
+}
+
+/// A download location as offered when adding a download
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadLocationInfo {
+    pub name: String,
+    pub path: String,
+    /// The download directory from settings
+    pub is_default: bool,
+    /// False until the first download creates the directory
+    pub exists: bool,
+    /// Bytes free/total on the drive holding the path; None if unreadable
+    pub free_space: Option<u64>,
+    pub total_space: Option<u64>,
+    /// Categories that last saved here; `""` stands for uncategorized
+    pub last_used_for: Vec<String>,
 }
 
 /// Global download statistics
@@ -66,6 +238,261 @@ pub struct GlobalStat {
     pub num_waiting: u32,
     pub num_stopped: u32,
     pub num_stopped_total: u32,
+    /// Bytes uploaded by all torrents, kept across restarts
+    pub total_uploaded: u64,
+    /// Uploaded over downloaded bytes across all torrents
+    pub share_ratio: f64,
+    /// Bytes received by all downloads, kept across restarts
+    pub total_received: u64,
+    /// Of those, bytes that ended up in no file
+    pub total_wasted: u64,
+}
+
+/// Timing aggregates over completed download history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryStats {
+    /// Completed downloads with recorded timing
+    pub downloads: u32,
+    pub total_bytes: u64,
+    /// Bytes per second across all timed downloads
+    pub average_speed: u64,
+    pub median_elapsed_secs: u64,
+    /// Time to first byte of recently probed HTTP servers; None before any
+    pub ttfb: Option<LatencyPercentiles>,
+    /// Per-host figures for HTTP downloads, fastest first
+    pub hosts: Vec<HostStats>,
+}
+
+/// Spread of a latency, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentiles {
+    pub samples: u32,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+}
+
+/// Size of the app database on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStorageInfo {
+    pub db_size: u64,
+    pub wal_size: u64,
+    /// Space inside the database file that vacuuming can give back
+    pub free_bytes: u64,
+    /// Rows per table
+    pub rows: std::collections::BTreeMap<String, u64>,
+}
+
+/// How serious a `SettingIssue` is: an error stops the settings from
+/// being saved, a warning doesn't
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueLevel {
+    Error,
+    Warning,
+}
+
+/// Something wrong with one field of a proposed `Settings`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingIssue {
+    /// The `Settings` field, as named there (e.g. "download_path")
+    pub field: String,
+    pub level: IssueLevel,
+    pub message: String,
+}
+
+/// Outcome of `db_maintenance`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbMaintenanceReport {
+    pub before: DbStorageInfo,
+    /// None when only a report was asked for
+    pub after: Option<DbStorageInfo>,
+    /// History entries removed by the retention period
+    pub pruned: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostStats {
+    pub host: String,
+    pub downloads: u32,
+    pub total_bytes: u64,
+    pub average_speed: u64,
+    pub average_elapsed_secs: u64,
+    /// Median time to first byte of the host's recent probes
+    pub median_ttfb_ms: Option<u64>,
+}
+
+/// Live figures for one host's running HTTP downloads, from `get_host_stats`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostActivity {
+    pub host: String,
+    pub downloads: u32,
+    /// Connections they hold open
+    pub connections: u32,
+    /// Connections they may open: one for a download whose server ignores
+    /// byte ranges, the engine's per-download limit for the rest
+    pub connection_limit: u32,
+    /// Combined speed in bytes per second
+    pub download_speed: u64,
+    /// Downloads from the host that finished or failed in the last 15
+    /// minutes, and how many of those failed
+    pub recent_finished: u32,
+    pub recent_failed: u32,
+    /// `recent_failed` over `recent_finished`; None without any
+    pub error_rate: Option<f64>,
+    /// What holds it back: `no-ranges`, `connection-limit`,
+    /// `global-limit`, `battery-saver` or `load-guard`
+    pub throttled: Vec<String>,
+    /// Connections auto-tuning would give the next download from it
+    pub suggested_connections: Option<u32>,
+}
+
+/// What `get_host_profiles` has learned about a host's connection count
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostProfile {
+    pub host: String,
+    /// Measurements by connection count, fewest connections first
+    pub connections: Vec<ConnectionProfile>,
+    /// Connections the next download from this host gets when
+    /// auto-tuning is on; None until a count has been measured enough
+    pub suggested_connections: Option<u32>,
+    /// The host sent whole files for ranges past the first byte, so its
+    /// downloads use one connection
+    #[serde(default)]
+    pub broken_ranges: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionProfile {
+    pub connections: u32,
+    /// Finished downloads measured
+    pub samples: u32,
+    /// Bytes per second while transferring
+    pub average_speed: u64,
+    pub updated_at: String,
+}
+
+/// Open file descriptors the engine may use, reported by
+/// `get_engine_health`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FdBudget {
+    /// Soft limit on open files; None if unlimited or unknown (Windows)
+    pub limit: Option<u64>,
+    /// Descriptors the engine's caps allow it to use at worst
+    pub worst_case: u64,
+    /// Descriptors open now, where the OS tells (Linux)
+    pub open: Option<u64>,
+    /// What was lowered to fit the limit
+    pub clamped: Option<String>,
+}
+
+/// Where a torrent's connected peers are, from `get_swarm_geography`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwarmGeography {
+    /// Most peers first
+    pub countries: Vec<CountryPeers>,
+    /// Peers the GeoIP database doesn't know
+    pub unknown: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountryPeers {
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    pub peers: u32,
+    pub download_speed: u64,
+    pub upload_speed: u64,
+}
+
+/// What the system load guard sees and does, from `get_load_guard_status`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadGuardStatus {
+    pub enabled: bool,
+    /// Whether the load has been high long enough to ease off
+    pub active: bool,
+    /// Latest readings; None where the OS doesn't report them
+    pub cpu_percent: Option<u32>,
+    pub disk_queue: Option<u64>,
+    /// When the guard last switched on, RFC 3339
+    pub active_since: Option<String>,
+    /// Low-priority downloads it paused
+    pub paused: Vec<String>,
+}
+
+/// Which download events a `subscribe_download_events` subscription gets.
+/// An empty list matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventFilter {
+    #[serde(default)]
+    pub gids: Vec<String>,
+    /// Event names, with or without the `download:` prefix
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// Download list filters currently applied in the UI, mirrored to the
+/// backend so `get_ui_snapshot` can report what the user is looking at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiFilters {
+    /// "all" | "active" | "paused" | "completed"
+    pub status: String,
+    #[serde(default)]
+    pub search: String,
+}
+
+impl Default for UiFilters {
+    fn default() -> Self {
+        Self {
+            status: "all".to_string(),
+            search: String::new(),
+        }
+    }
+}
+
+impl UiFilters {
+    /// Same rules as the Downloads page: status chip, then a
+    /// case-insensitive search over name and URL.
+    pub fn matches(&self, download: &Download) -> bool {
+        let status_ok = match self.status.as_str() {
+            "active" => matches!(
+                download.status,
+                DownloadState::Active | DownloadState::Waiting | DownloadState::Error
+            ),
+            "paused" => matches!(
+                download.status,
+                DownloadState::Paused | DownloadState::WaitingForNetwork
+            ),
+            "completed" => matches!(
+                download.status,
+                DownloadState::Complete | DownloadState::Warning
+            ),
+            _ => true,
+        };
+        let query = self.search.trim().to_lowercase();
+        status_ok
+            && (query.is_empty()
+                || download.name.to_lowercase().contains(&query)
+                || download
+                    .url
+                    .as_ref()
+                    .is_some_and(|u| u.to_lowercase().contains(&query)))
+    }
 }
 
 /// Torrent file information (for display before adding)
@@ -118,7 +545,7 @@ pub struct FileUri {
 }
 
 /// Frontend-facing download model
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Download {
     pub id: i64,
@@ -138,14 +565,319 @@ pub struct Download {
     pub completed_at: Option<String>,
     pub error_message: Option<String>,
     pub connections: u32,
+    /// Connected peers that have the whole torrent
     pub seeders: u32,
+    /// Seeders in the whole swarm, as last reported by a tracker (the
+    /// `complete` count of an announce reply); 0 until one reports
+    #[serde(default)]
+    pub swarm_seeders: u32,
+    /// Connected peers of a torrent, seeders included
+    #[serde(default)]
+    pub peers: u32,
+    /// Connected peers that don't have the whole torrent yet
+    #[serde(default)]
+    pub leechers: u32,
     pub selected_files: Option<Vec<usize>>,
+    /// Pinned in history by the user; exempt from retention pruning
+    #[serde(default)]
+    pub favorite: bool,
+    /// False for HTTP downloads whose server ignores byte ranges: they use
+    /// a single connection and restart from the beginning when resumed.
+    /// None when unknown
+    #[serde(default)]
+    pub resumable: Option<bool>,
+    /// Place among waiting downloads in the order they start (priority,
+    /// then `added_order`), from 1; None unless waiting
+    #[serde(default)]
+    pub queue_position: Option<u32>,
+    /// low, normal, high or critical; None for history entries
+    #[serde(default)]
+    pub priority: Option<String>,
+    /// Category it was saved under; empty for none
+    #[serde(default)]
+    pub category: String,
+    /// Grows with each download added or restored this session; 0 for
+    /// history entries
+    #[serde(default)]
+    pub added_order: u64,
+    /// Upload totals of a torrent; None for HTTP downloads and history
+    #[serde(default)]
+    pub seeding: Option<SeedingStats>,
+    /// Bytes received and wasted; None for history
+    #[serde(default)]
+    pub traffic: Option<TrafficStats>,
+}
+
+/// What a torrent has given back, kept across restarts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedingStats {
+    pub uploaded: u64,
+    /// Uploaded over downloaded bytes
+    pub ratio: f64,
+    /// Time spent seeding once complete
+    pub seed_time_secs: u64,
+}
+
+/// What a download received and how much of it ended up in its files,
+/// kept across restarts. Estimated from sampled speeds and sizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficStats {
+    pub received: u64,
+    /// Received bytes that are part of the files
+    pub payload: u64,
+    /// Bytes that failed verification, were fetched twice or were
+    /// discarded after being counted complete
+    pub wasted: u64,
+}
+
+/// One torrent's traffic on one day (UTC), as `export_stats` writes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedStatsDay {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub gid: String,
+    /// Empty when the torrent is gone from both the list and the history
+    pub name: String,
+    pub info_hash: Option<String>,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub seed_time_secs: u64,
+}
+
+/// A download with the diagnostics `get_download_details` adds
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadDetails {
+    #[serde(flatten)]
+    pub download: Download,
+    /// URLs the server was reached through, starting with the one added;
+    /// empty if it didn't redirect or wasn't probed
+    pub redirect_chain: Vec<String>,
+    /// Why a redirect was refused, if one was
+    pub redirect_error: Option<String>,
+    /// Time to the server's response headers when probed, DNS, connecting,
+    /// TLS and redirects included
+    pub ttfb_ms: Option<u64>,
+    /// Part of `ttfb_ms` spent on redirects
+    pub redirect_ms: Option<u64>,
+}
+
+/// The first bytes of a download's file, from `preview_download`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadPreview {
+    pub gid: String,
+    /// File the bytes were read from
+    pub path: String,
+    /// The bytes, base64-encoded
+    pub data: String,
+    /// MIME type sniffed from the bytes, if recognised
+    pub mime: Option<String>,
+    /// False while the download is still running: later parts of `data`
+    /// may not have been written yet (torrents fill out of order unless
+    /// added with `sequential`)
+    pub complete: bool,
+}
+
+/// What adding a download would do, from `plan_download` and
+/// `plan_torrent_file`; nothing is created
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddPlan {
+    /// URL as given; None for a torrent file
+    pub url: Option<String>,
+    /// File name, or a torrent's folder; None when neither the URL nor
+    /// the server suggest one
+    pub name: Option<String>,
+    /// Directory the download is saved to
+    pub directory: String,
+    /// `directory` joined with `name`
+    pub path: Option<String>,
+    /// Temp directory an HTTP download is written to before being moved
+    /// to `directory`, if one is configured
+    pub temp_directory: Option<String>,
+    pub category: Option<String>,
+    /// Name the file gets once finished, if a rename rule matches it
+    pub renamed: Option<RenamePreview>,
+    /// Options after the template, settings defaults, category and what
+    /// the server reported
+    pub options: DownloadOptions,
+    pub warnings: Vec<String>,
+}
+
+/// One URL from `import_url_list`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedUrl {
+    pub url: String,
+    /// Per-row file name and directory from a CSV list
+    pub filename: Option<String>,
+    pub dir: Option<String>,
+    /// Set when the URL is already in the download list or history
+    pub warning: Option<String>,
+}
+
+/// Preview of a URL list, from `import_url_list`; nothing is added
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlListImport {
+    /// "text", "csv" or "html"
+    pub format: String,
+    pub entries: Vec<ImportedUrl>,
+    /// Repeats of a URL already listed, left out
+    pub duplicates: u32,
+    /// Lines and links that aren't an addable URL or didn't pass the
+    /// extension filter
+    pub skipped: u32,
+}
+
+/// Outcome of `add_urls`: one URL failing doesn't stop the rest
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddUrlsResult {
+    /// GIDs of the downloads added, in the order their URLs were given
+    pub gids: Vec<String>,
+    pub failed: Vec<FailedUrl>,
+}
+
+/// A URL `add_urls` couldn't add, and why
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedUrl {
+    pub url: String,
+    pub error: String,
+}
+
+/// A download's last full progress report, from `get_progress_snapshots`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressSnapshot {
+    pub gid: String,
+    /// Sequence number of the last `download:progress` sent for it
+    pub seq: u64,
+    /// As in a full `download:progress`
+    pub progress: serde_json::Value,
+}
+
+/// Result of `verify_download`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityCheck {
+    pub gid: String,
+    /// The file's hash now, "kind:hex"
+    pub hash: String,
+    /// The hash recorded when the download finished, if there was one
+    pub recorded: Option<String>,
+    /// None when there was nothing to compare with; the hash is recorded now
+    pub matches: Option<bool>,
+}
+
+/// Downloads changed since a version, from `get_changes`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadChanges {
+    /// Pass this back as `since_version` next time
+    pub version: u64,
+    pub downloads: Vec<Download>,
+    /// GIDs no longer in the list
+    pub removed: Vec<String>,
+    /// `downloads` is the whole list; drop anything not in it
+    pub full: bool,
+}
+
+/// Downloads that were running when the previous session ended without a
+/// clean shutdown, from `get_crash_recovery_info`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashRecoveryInfo {
+    /// When the interrupted session started, if its marker was readable
+    pub session_started_at: Option<String>,
+    pub downloads: Vec<InterruptedDownload>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterruptedDownload {
+    pub gid: String,
+    pub name: String,
+    pub download_type: DownloadType,
+    pub total_size: u64,
+    pub completed_size: u64,
+    pub save_path: String,
+    /// The engine restored it (held paused until the user decides);
+    /// otherwise restoring adds it again from its URL or magnet
+    pub in_engine: bool,
+}
+
+/// A `.part` file in a download folder that no download claims, from
+/// `list_orphaned_partials`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanedPartial {
+    pub path: String,
+    /// File name without the `.part` suffix
+    pub name: String,
+    pub size: u64,
+    pub modified_at: Option<String>,
+    /// Full size the server reported, if its sidecar recorded one
+    pub total_size: Option<u64>,
+    /// Where it was downloaded from, if its sidecar survived; without it
+    /// the file can only be deleted
+    pub url: Option<String>,
+}
+
+/// Result of `get_capabilities`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub app_version: String,
+    pub engine: String,
+    pub engine_version: String,
+    /// Command and event API version, see `constants::API_VERSION`
+    pub api_version: u32,
+    pub protocols: Vec<ProtocolSupport>,
+    /// Feature names, e.g. `dht` or `sequential`
+    pub features: Vec<String>,
+}
+
+/// A URL scheme or source a download can be added from
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolSupport {
+    pub name: String,
+    /// Protocol version spoken, where there is a choice
+    pub version: Option<String>,
+}
+
+/// Result of `get_update_info`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub current_version: String,
+    /// Newest release on the channel, even when it isn't newer
+    pub latest_version: String,
+    pub channel: String,
+    pub update_available: bool,
+    pub prerelease: bool,
+    pub release_name: Option<String>,
+    /// Release notes (Markdown)
+    pub changelog: String,
+    /// Release page
+    pub release_url: String,
+    pub published_at: Option<String>,
+    /// Installer for this platform, if the release has one
+    pub download_url: Option<String>,
+    pub asset_name: Option<String>,
+    pub asset_size: Option<u64>,
 }
 
 /// Type of download
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DownloadType {
+    #[default]
     Http,
     Torrent,
     Magnet,
@@ -162,13 +894,20 @@ impl std::fmt::Display for DownloadType {
 }
 
 /// Download state
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DownloadState {
     Active,
+    #[default]
     Waiting,
     Paused,
+    /// Paused by the app because the network went away; resumed when it returns
+    #[serde(rename = "waiting_for_network")]
+    WaitingForNetwork,
     Complete,
+    /// Finished, but the content looks wrong (e.g. an HTML error page
+    /// saved in place of the file); `error_message` says why
+    Warning,
     Error,
     Removed,
 }
@@ -179,7 +918,9 @@ impl From<&str> for DownloadState {
             "active" => DownloadState::Active,
             "waiting" => DownloadState::Waiting,
             "paused" => DownloadState::Paused,
+            "waiting_for_network" => DownloadState::WaitingForNetwork,
             "complete" => DownloadState::Complete,
+            "warning" => DownloadState::Warning,
             "error" => DownloadState::Error,
             "removed" => DownloadState::Removed,
             _ => DownloadState::Waiting,
@@ -193,7 +934,9 @@ impl std::fmt::Display for DownloadState {
             DownloadState::Active => write!(f, "active"),
             DownloadState::Waiting => write!(f, "waiting"),
             DownloadState::Paused => write!(f, "paused"),
+            DownloadState::WaitingForNetwork => write!(f, "waiting_for_network"),
             DownloadState::Complete => write!(f, "complete"),
+            DownloadState::Warning => write!(f, "warning"),
             DownloadState::Error => write!(f, "error"),
             DownloadState::Removed => write!(f, "removed"),
         }
@@ -238,12 +981,15 @@ mod tests {
             DownloadState::Active,
             DownloadState::Waiting,
             DownloadState::Paused,
+            DownloadState::WaitingForNetwork,
             DownloadState::Complete,
+            DownloadState::Warning,
             DownloadState::Error,
             DownloadState::Removed,
         ] {
             let s = state.to_string();
             assert_eq!(DownloadState::from(s.as_str()), state);
+            assert_eq!(serde_json::to_value(state).unwrap(), s.as_str());
         }
     }
 
@@ -256,11 +1002,40 @@ mod tests {
             num_waiting: 1,
             num_stopped: 0,
             num_stopped_total: 2,
+            total_uploaded: 4096,
+            share_ratio: 0.5,
+            total_received: 8192,
+            total_wasted: 128,
         };
         let json = serde_json::to_value(&stat).unwrap();
         assert_eq!(json["downloadSpeed"], 1024);
         assert_eq!(json["uploadSpeed"], 512);
         assert_eq!(json["numActive"], 3);
+        assert_eq!(json["shareRatio"], 0.5);
+        assert_eq!(json["totalWasted"], 128);
+    }
+
+    #[test]
+    fn test_file_rotation_serialization() {
+        let opts: DownloadOptions = serde_json::from_value(serde_json::json!({
+            "rotation": { "policy": "keepLast", "count": 3 }
+        }))
+        .unwrap();
+        assert_eq!(opts.rotation, Some(FileRotation::KeepLast { count: 3 }));
+
+        let json = serde_json::to_value(FileRotation::TimestampSuffix).unwrap();
+        assert_eq!(json["policy"], "timestampSuffix");
+    }
+
+    #[test]
+    fn test_file_filter_deserialization() {
+        let opts: DownloadOptions = serde_json::from_value(serde_json::json!({
+            "fileFilter": { "include": ["*.mkv"] }
+        }))
+        .unwrap();
+        let filter = opts.file_filter.unwrap();
+        assert_eq!(filter.include, vec!["*.mkv"]);
+        assert!(filter.exclude.is_empty());
     }
 
     #[test]
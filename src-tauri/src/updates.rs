@@ -0,0 +1,333 @@
+//! Update check against the project's GitHub releases. Releases are read
+//! from the API feed, filtered by channel (`stable` skips pre-releases,
+//! `beta` includes them) and compared with the running version by semver
+//! precedence. The installer for this platform is picked from the release
+//! assets so the app can fetch it with its own engine.
+
+use crate::types::UpdateInfo;
+use crate::{Error, Result};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::time::Duration;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/goshitsarch-eng/Gosh-Fetch/releases";
+
+const CHECK_TIMEOUT_SECS: u64 = 15;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// A semantic version. Build metadata (`+...`) is dropped, as it doesn't
+/// take part in precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<String>,
+}
+
+impl Version {
+    /// Parse `1.2.3`, `v1.2.3-beta.1` or a shortened `1.2`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().trim_start_matches('v');
+        let s = s.split('+').next()?;
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').map(str::to_string).collect()),
+            None => (s, Vec::new()),
+        };
+        let mut parts = core.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        let patch = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Version {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                // A pre-release comes before its release
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_pre(&self.pre, &other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Identifiers compare numerically when both are numbers; numbers sort
+/// before words; a longer list wins when one is a prefix of the other.
+fn compare_pre(a: &[String], b: &[String]) -> Ordering {
+    for (x, y) in a.iter().zip(b) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Newest published release on `channel`, with its parsed version.
+pub fn latest_release<'a>(
+    releases: &'a [Release],
+    channel: &str,
+) -> Option<(&'a Release, Version)> {
+    releases
+        .iter()
+        .filter(|r| !r.draft && (channel == "beta" || !r.prerelease))
+        .filter_map(|r| Version::parse(&r.tag_name).map(|v| (r, v)))
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+}
+
+fn installer_extensions(os: &str) -> &'static [&'static str] {
+    match os {
+        "windows" => &[".msi", ".exe"],
+        "macos" => &[".dmg"],
+        _ => &[".appimage", ".deb", ".rpm"],
+    }
+}
+
+const X86_64: &[&str] = &["x86_64", "x64", "amd64"];
+const AARCH64: &[&str] = &["aarch64", "arm64"];
+
+fn arch_aliases(arch: &str) -> &'static [&'static str] {
+    match arch {
+        "x86_64" => X86_64,
+        "aarch64" => AARCH64,
+        _ => &[],
+    }
+}
+
+/// Installer for `os`/`arch` (as in `std::env::consts`), by preferred
+/// package format: a build naming this architecture, else a universal
+/// one, else the only build in that format if it names no architecture.
+pub fn installer_asset<'a>(
+    assets: &'a [ReleaseAsset],
+    os: &str,
+    arch: &str,
+) -> Option<&'a ReleaseAsset> {
+    let aliases = arch_aliases(arch);
+    installer_extensions(os).iter().find_map(|ext| {
+        let candidates: Vec<&ReleaseAsset> = assets
+            .iter()
+            .filter(|a| a.name.to_lowercase().ends_with(ext))
+            .collect();
+        let named = |words: &[&str]| {
+            candidates
+                .iter()
+                .copied()
+                .find(|a| words.iter().any(|w| a.name.to_lowercase().contains(w)))
+        };
+        named(aliases)
+            .or_else(|| named(&["universal"]))
+            .or_else(|| match candidates[..] {
+                [only] if named(X86_64).or_else(|| named(AARCH64)).is_none() => Some(only),
+                _ => None,
+            })
+    })
+}
+
+/// Compare `current` with the newest release on `channel`.
+pub fn update_info(releases: &[Release], current: &str, channel: &str) -> Result<UpdateInfo> {
+    let (release, latest) = latest_release(releases, channel)
+        .ok_or_else(|| Error::NotFound(format!("No releases on the {} channel", channel)))?;
+    let update_available = Version::parse(current).is_none_or(|current| latest > current);
+    let asset = installer_asset(
+        &release.assets,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    );
+    Ok(UpdateInfo {
+        current_version: current.to_string(),
+        latest_version: latest.to_string(),
+        channel: channel.to_string(),
+        update_available,
+        prerelease: release.prerelease,
+        release_name: release.name.clone().filter(|n| !n.is_empty()),
+        changelog: release.body.clone().unwrap_or_default(),
+        release_url: release.html_url.clone(),
+        published_at: release.published_at.clone(),
+        download_url: asset.map(|a| a.browser_download_url.clone()),
+        asset_name: asset.map(|a| a.name.clone()),
+        asset_size: asset.map(|a| a.size),
+    })
+}
+
+/// Fetch the release feed and compare it with `current`.
+pub async fn check(current: &str, channel: &str, proxy_url: &str) -> Result<UpdateInfo> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(CHECK_TIMEOUT_SECS))
+        .user_agent(format!("Gosh-Fetch/{}", current));
+    if !proxy_url.is_empty() {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| Error::InvalidInput(format!("Invalid proxy URL: {}", e)))?;
+        builder = builder.proxy(proxy);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))?;
+    let response = client
+        .get(RELEASES_URL)
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to check for updates: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Network(format!(
+            "Failed to check for updates: HTTP {}",
+            response.status()
+        )));
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to read response: {}", e)))?;
+    let releases: Vec<Release> = serde_json::from_str(&text)?;
+    update_info(&releases, current, channel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, prerelease: bool) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            name: None,
+            body: Some(format!("Changes in {}", tag)),
+            html_url: format!("https://example.com/releases/{}", tag),
+            draft: false,
+            prerelease,
+            published_at: None,
+            assets: Vec::new(),
+        }
+    }
+
+    fn asset(name: &str) -> ReleaseAsset {
+        ReleaseAsset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn test_version_precedence() {
+        let v = |s| Version::parse(s).unwrap();
+        assert!(v("3.0.1") > v("3.0.0"));
+        assert!(v("v3.1") > v("3.0.9"));
+        assert!(v("3.1.0") > v("3.1.0-rc.1"));
+        assert!(v("3.1.0-rc.1") > v("3.1.0-beta.11"));
+        assert!(v("3.1.0-beta.11") > v("3.1.0-beta.2"));
+        assert!(v("3.1.0-beta.1") > v("3.1.0-beta"));
+        assert_eq!(v("3.0.0+build.5"), v("3.0.0"));
+        assert_eq!(v("v3.1.0-beta.2").to_string(), "3.1.0-beta.2");
+        assert!(Version::parse("latest").is_none());
+        assert!(Version::parse("1.2.3.4").is_none());
+    }
+
+    #[test]
+    fn test_channels() {
+        let releases = vec![
+            release("v3.0.0", false),
+            release("v3.1.0-beta.1", true),
+            release("v3.0.1", false),
+        ];
+        let info = update_info(&releases, "3.0.0", "stable").unwrap();
+        assert_eq!(info.latest_version, "3.0.1");
+        assert!(info.update_available);
+        assert_eq!(info.changelog, "Changes in v3.0.1");
+
+        let info = update_info(&releases, "3.0.1", "beta").unwrap();
+        assert_eq!(info.latest_version, "3.1.0-beta.1");
+        assert!(info.prerelease);
+
+        assert!(
+            !update_info(&releases, "3.0.1", "stable")
+                .unwrap()
+                .update_available
+        );
+        assert!(update_info(&releases[1..2], "3.0.0", "stable").is_err());
+    }
+
+    #[test]
+    fn test_installer_asset() {
+        let assets = vec![
+            asset("Gosh-Fetch_3.0.1_amd64.AppImage"),
+            asset("Gosh-Fetch_3.0.1_amd64.AppImage.sig"),
+            asset("Gosh-Fetch_3.0.1_arm64.deb"),
+            asset("Gosh-Fetch_3.0.1_x64_en-US.msi"),
+            asset("Gosh-Fetch_3.0.1_universal.dmg"),
+            asset("latest.json"),
+        ];
+        let name = |os, arch| installer_asset(&assets, os, arch).map(|a| a.name.as_str());
+        assert_eq!(
+            name("linux", "x86_64"),
+            Some("Gosh-Fetch_3.0.1_amd64.AppImage")
+        );
+        assert_eq!(
+            name("windows", "x86_64"),
+            Some("Gosh-Fetch_3.0.1_x64_en-US.msi")
+        );
+        assert_eq!(
+            name("macos", "aarch64"),
+            Some("Gosh-Fetch_3.0.1_universal.dmg")
+        );
+        // An AppImage for another architecture is no good; fall back to the .deb
+        assert_eq!(name("linux", "aarch64"), Some("Gosh-Fetch_3.0.1_arm64.deb"));
+        assert_eq!(name("windows", "aarch64"), None);
+    }
+}
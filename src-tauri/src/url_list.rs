@@ -0,0 +1,359 @@
+//! Reading a list of URLs to add in bulk: plain text with one URL per line,
+//! CSV with optional per-row `filename` and `dir` columns, or an HTML page
+//! whose links are taken. Nothing is added here; the entries are shown to
+//! the user first and only the ones kept are added.
+
+use crate::types::ImportedUrl;
+use crate::validation::validate_download_url;
+use std::collections::HashSet;
+
+/// How a list is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Text,
+    Csv,
+    Html,
+}
+
+impl ListFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ListFormat::Text => "text",
+            ListFormat::Csv => "csv",
+            ListFormat::Html => "html",
+        }
+    }
+}
+
+/// The entries of a list, first occurrence of each URL only.
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedList {
+    pub entries: Vec<ImportedUrl>,
+    /// URLs listed more than once, counted once per repeat
+    pub duplicates: u32,
+    /// Rows and links that aren't a URL the app can add, or that the
+    /// extension filter left out
+    pub skipped: u32,
+}
+
+/// The format of a list, from its file name if there is one, otherwise
+/// from its content.
+pub fn detect_format(file_name: Option<&str>, text: &str) -> ListFormat {
+    let extension = file_name
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("csv") => return ListFormat::Csv,
+        Some("htm" | "html") => return ListFormat::Html,
+        Some("txt" | "list") => return ListFormat::Text,
+        _ => {}
+    }
+    let start = text
+        .trim_start()
+        .get(..256)
+        .unwrap_or(text.trim_start())
+        .to_ascii_lowercase();
+    if start.starts_with("<!doctype html")
+        || start.starts_with("<html")
+        || text.to_ascii_lowercase().contains("<a ")
+    {
+        return ListFormat::Html;
+    }
+    let header = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    if split_csv_row(header)
+        .iter()
+        .any(|field| field.eq_ignore_ascii_case("url"))
+    {
+        return ListFormat::Csv;
+    }
+    ListFormat::Text
+}
+
+/// Read the entries of `text`. `extensions` (without the dot, any case)
+/// limits the links taken from HTML; empty takes them all.
+pub fn parse(text: &str, format: ListFormat, extensions: &[String]) -> ParsedList {
+    let mut list = ParsedList::default();
+    let mut seen = HashSet::new();
+    let mut push =
+        |list: &mut ParsedList, url: &str, filename: Option<String>, dir: Option<String>| {
+            let url = url.trim();
+            if validate_download_url(url).is_err() {
+                list.skipped += 1;
+            } else if !seen.insert(url.to_string()) {
+                list.duplicates += 1;
+            } else {
+                list.entries.push(ImportedUrl {
+                    url: url.to_string(),
+                    filename,
+                    dir,
+                    warning: None,
+                });
+            }
+        };
+
+    match format {
+        ListFormat::Text => {
+            for line in text.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                push(&mut list, line, None, None);
+            }
+        }
+        ListFormat::Csv => {
+            let mut rows = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(split_csv_row)
+                .peekable();
+            // Without a header row the columns are url, filename, dir
+            let mut columns = (0, Some(1), Some(2));
+            if let Some(header) =
+                rows.next_if(|row| row.iter().any(|f| f.eq_ignore_ascii_case("url")))
+            {
+                let find = |names: &[&str]| {
+                    header
+                        .iter()
+                        .position(|f| names.iter().any(|n| f.eq_ignore_ascii_case(n)))
+                };
+                columns = (
+                    find(&["url"]).unwrap_or(0),
+                    find(&["filename", "name", "out"]),
+                    find(&["dir", "directory", "folder"]),
+                );
+            }
+            let (url, filename, dir) = columns;
+            for row in rows {
+                let field = |i: Option<usize>| {
+                    i.and_then(|i| row.get(i))
+                        .map(|f| f.trim())
+                        .filter(|f| !f.is_empty())
+                        .map(str::to_string)
+                };
+                let Some(row_url) = field(Some(url)) else {
+                    list.skipped += 1;
+                    continue;
+                };
+                push(&mut list, &row_url, field(filename), field(dir));
+            }
+        }
+        ListFormat::Html => {
+            let extensions: Vec<String> = extensions
+                .iter()
+                .map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase())
+                .collect();
+            let extensions: Vec<&str> = extensions
+                .iter()
+                .map(String::as_str)
+                .filter(|e| !e.is_empty())
+                .collect();
+            let mut base = None;
+            for (tag, href) in html_links(text) {
+                if tag == "base" {
+                    base = url::Url::parse(&href).ok();
+                    continue;
+                }
+                let resolved = match &base {
+                    Some(base) => base.join(&href).ok(),
+                    None => url::Url::parse(&href).ok(),
+                };
+                let Some(resolved) =
+                    resolved.filter(|u| matches!(u.scheme(), "http" | "https" | "magnet"))
+                else {
+                    list.skipped += 1;
+                    continue;
+                };
+                if !extensions.is_empty() && !has_extension(&resolved, &extensions) {
+                    list.skipped += 1;
+                    continue;
+                }
+                push(&mut list, resolved.as_str(), None, None);
+            }
+        }
+    }
+    list
+}
+
+fn has_extension(url: &url::Url, extensions: &[&str]) -> bool {
+    let name = url
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .unwrap_or_default();
+    let name = name.to_ascii_lowercase();
+    extensions
+        .iter()
+        .any(|ext| name.ends_with(&format!(".{}", ext)))
+}
+
+/// `(tag, href)` of each `<a>` and `<base>` with an `href`, in order.
+fn html_links(html: &str) -> Vec<(String, String)> {
+    let mut links = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix("!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        let end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        if name != "a" && name != "base" {
+            continue;
+        }
+        if let Some(href) = attribute(&tag[name_end..], "href") {
+            links.push((name, href));
+        }
+    }
+    links
+}
+
+/// The value of attribute `key` in the attribute part of a tag, quoted or
+/// not, with the common entities decoded.
+fn attribute(mut attributes: &str, key: &str) -> Option<String> {
+    loop {
+        attributes = attributes.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if attributes.is_empty() {
+            return None;
+        }
+        let name_end = attributes
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(attributes.len());
+        let name = &attributes[..name_end];
+        attributes = attributes[name_end..].trim_start();
+        let Some(after) = attributes.strip_prefix('=') else {
+            continue;
+        };
+        let after = after.trim_start();
+        let (value, remainder) = match after.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                (&after[1..end], after.get(end + 1..).unwrap_or_default())
+            }
+            _ => {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        if name.eq_ignore_ascii_case(key) {
+            let value = value
+                .trim()
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&#39;", "'")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&");
+            return Some(value);
+        }
+        attributes = remainder;
+    }
+}
+
+/// The fields of one CSV row: comma separated, `"` quoting with `""` for
+/// a quote inside. Quoted fields spanning lines aren't supported.
+fn split_csv_row(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("at least one field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(list: &ParsedList) -> Vec<&str> {
+        list.entries.iter().map(|e| e.url.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_text_and_csv() {
+        let text = "# mirrors\nhttps://example.com/a.iso\n\n  https://example.com/b.iso \nnot a url\nhttps://example.com/a.iso\nmagnet:?xt=urn:btih:abc\n";
+        assert_eq!(detect_format(None, text), ListFormat::Text);
+        let list = parse(text, ListFormat::Text, &[]);
+        assert_eq!(
+            urls(&list),
+            vec![
+                "https://example.com/a.iso",
+                "https://example.com/b.iso",
+                "magnet:?xt=urn:btih:abc"
+            ]
+        );
+        assert_eq!((list.duplicates, list.skipped), (1, 1));
+
+        let csv = "Dir,URL,Filename\n/tmp/isos,https://example.com/a.iso,\"debian, netinst.iso\"\n,https://example.com/b.iso,\n";
+        assert_eq!(detect_format(None, csv), ListFormat::Csv);
+        let list = parse(csv, ListFormat::Csv, &[]);
+        assert_eq!(
+            list.entries[0].filename.as_deref(),
+            Some("debian, netinst.iso")
+        );
+        assert_eq!(list.entries[0].dir.as_deref(), Some("/tmp/isos"));
+        assert_eq!(list.entries[1].filename, None);
+
+        // Headerless rows are url, filename, dir
+        let list = parse(
+            "https://example.com/c.iso,c.iso",
+            detect_format(Some("list.CSV"), ""),
+            &[],
+        );
+        assert_eq!(list.entries[0].filename.as_deref(), Some("c.iso"));
+        assert_eq!(
+            split_csv_row(r#"a,"say ""hi""",b"#),
+            vec!["a", "say \"hi\"", "b"]
+        );
+    }
+
+    #[test]
+    fn test_parse_html() {
+        let html = r#"<!DOCTYPE html><html><head><BASE HREF="https://example.com/pub/"></head><body>
+            <!-- <a href="hidden.zip"> -->
+            <a href="../index.html">Up</a>
+            <a class=file href=one.zip>one</a>
+            <A HREF='two.ZIP?x=1&amp;y=2'>two</A>
+            <a href="https://other.example/one.zip">again</a>
+            <a href="one.zip">dup</a>
+            <a href="mailto:me@example.com">mail</a>
+        </body></html>"#;
+        assert_eq!(detect_format(Some("index.htm"), html), ListFormat::Html);
+        assert_eq!(detect_format(None, html), ListFormat::Html);
+        let list = parse(html, ListFormat::Html, &[".zip".to_string()]);
+        assert_eq!(
+            urls(&list),
+            vec![
+                "https://example.com/pub/one.zip",
+                "https://example.com/pub/two.ZIP?x=1&y=2",
+                "https://other.example/one.zip",
+            ]
+        );
+        assert_eq!((list.duplicates, list.skipped), (1, 2));
+
+        // Relative links need a <base>
+        let list = parse(
+            r#"<a href="a.zip"></a><a href="https://example.com/b.zip"></a>"#,
+            ListFormat::Html,
+            &[],
+        );
+        assert_eq!(urls(&list), vec!["https://example.com/b.zip"]);
+    }
+}
@@ -1,4 +1,5 @@
-//! Utility modules for Gosh-Fetch
+//! Utility modules for Gosh-Fetch: tracker list fetching, user-facing
+//! number formatting, internet route probing, and battery state.
 
 use crate::{Error, Result};
 use chrono::{DateTime, Utc};
@@ -79,3 +80,529 @@ impl Default for TrackerUpdater {
         Self::new()
     }
 }
+
+/// A magnet listing this many trackers already has enough to find peers.
+const MAGNET_TRACKERS_ENOUGH: usize = 5;
+/// Trackers from the global list added to one magnet at most.
+const MAX_MERGED_TRACKERS: usize = 20;
+
+/// Trackers from `global` worth adding to `magnet`: none if it already
+/// lists enough, or if one of its trackers carries a passkey (a magnet
+/// doesn't say whether the torrent is private, so this is the tell);
+/// otherwise the first ones it doesn't list yet.
+pub fn trackers_to_merge(magnet: &str, global: &[String]) -> Vec<String> {
+    let listed = magnet_trackers(magnet);
+    if listed.len() >= MAGNET_TRACKERS_ENOUGH
+        || listed.iter().any(|tracker| is_private_tracker(tracker))
+    {
+        return Vec::new();
+    }
+    let mut merged: Vec<String> = Vec::new();
+    for tracker in global.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if merged.len() == MAX_MERGED_TRACKERS {
+            break;
+        }
+        if !listed
+            .iter()
+            .chain(&merged)
+            .any(|t| t.eq_ignore_ascii_case(tracker))
+        {
+            merged.push(tracker.to_string());
+        }
+    }
+    merged
+}
+
+/// Whether one of a magnet link's trackers carries a passkey.
+pub fn lists_private_tracker(magnet: &str) -> bool {
+    magnet_trackers(magnet)
+        .iter()
+        .any(|tracker| is_private_tracker(tracker))
+}
+
+/// The trackers a magnet link lists (`tr` and `tr.N` parameters).
+fn magnet_trackers(magnet: &str) -> Vec<String> {
+    let query = magnet.split_once('?').map_or("", |(_, query)| query);
+    url::form_urlencoded::parse(query.as_bytes())
+        .filter(|(key, _)| key == "tr" || key.starts_with("tr."))
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+/// Whether an announce URL identifies the user, as private trackers' do.
+fn is_private_tracker(tracker: &str) -> bool {
+    let lower = tracker.to_ascii_lowercase();
+    ["passkey=", "authkey=", "torrent_pass=", "/pk/"]
+        .iter()
+        .any(|marker| lower.contains(marker))
+        || lower
+            .split(['/', '?', '&', '='])
+            .any(|part| part.len() >= 32 && part.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Which unit family byte counts are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnitSystem {
+    /// Powers of 1024 with KB/MB/GB labels (the historical display)
+    #[default]
+    Binary,
+    /// Powers of 1024 with KiB/MiB/GiB labels
+    Iec,
+    /// Powers of 1000 with kB/MB/GB labels
+    Si,
+}
+
+impl UnitSystem {
+    /// Parse the `unit_system` setting; unknown values fall back to `Binary`.
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "iec" => UnitSystem::Iec,
+            "si" => UnitSystem::Si,
+            _ => UnitSystem::Binary,
+        }
+    }
+
+    fn base(self) -> f64 {
+        match self {
+            UnitSystem::Si => 1000.0,
+            _ => 1024.0,
+        }
+    }
+
+    fn labels(self) -> [&'static str; 4] {
+        match self {
+            UnitSystem::Binary => ["KB", "MB", "GB", "TB"],
+            UnitSystem::Iec => ["KiB", "MiB", "GiB", "TiB"],
+            UnitSystem::Si => ["kB", "MB", "GB", "TB"],
+        }
+    }
+}
+
+/// Formatting for user-facing sizes, speeds and ETAs (tray tooltip,
+/// formatted API fields), matching the frontend's `format.ts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub units: UnitSystem,
+    pub decimal_separator: char,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            units: UnitSystem::default(),
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Use the given units and the decimal separator of the system locale.
+    pub fn with_system_locale(units: UnitSystem) -> Self {
+        Self {
+            units,
+            decimal_separator: system_decimal_separator(),
+        }
+    }
+
+    /// e.g. "1.50 MB"
+    pub fn bytes(&self, bytes: u64) -> String {
+        self.scaled(bytes, 2, "")
+    }
+
+    /// e.g. "1.5 MB/s"
+    pub fn speed(&self, bytes_per_sec: u64) -> String {
+        self.scaled(bytes_per_sec, 1, "/s")
+    }
+
+    /// Remaining time at the current speed, e.g. "4m 10s"; "--" if unknown.
+    pub fn eta(&self, bytes_remaining: u64, bytes_per_sec: u64) -> String {
+        if bytes_per_sec == 0 || bytes_remaining == 0 {
+            return "--".to_string();
+        }
+        let secs = bytes_remaining / bytes_per_sec;
+        match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+            3600..=86399 => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+            _ => format!("{}d {}h", secs / 86400, (secs % 86400) / 3600),
+        }
+    }
+
+    fn scaled(&self, value: u64, decimals: usize, suffix: &str) -> String {
+        let base = self.units.base();
+        let labels = self.units.labels();
+        let mut scaled = value as f64;
+        let mut unit = None;
+        for label in labels {
+            if scaled < base {
+                break;
+            }
+            scaled /= base;
+            unit = Some(label);
+        }
+        match unit {
+            None => format!("{} B{}", value, suffix),
+            Some(label) => {
+                let number = format!("{:.*}", decimals, scaled);
+                let number = if self.decimal_separator == '.' {
+                    number
+                } else {
+                    number.replace('.', &self.decimal_separator.to_string())
+                };
+                format!("{} {}{}", number, label, suffix)
+            }
+        }
+    }
+}
+
+/// Decimal separator for the process locale (LC_ALL, LC_NUMERIC, LANG).
+/// Platforms without these variables get '.'.
+fn system_decimal_separator() -> char {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|locale| decimal_separator_for_locale(&locale))
+        .unwrap_or('.')
+}
+
+/// Decimal separator conventionally used by a POSIX locale name such as
+/// "de_DE.UTF-8".
+pub fn decimal_separator_for_locale(locale: &str) -> char {
+    const COMMA_LANGUAGES: &[&str] = &[
+        "bg", "ca", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "it", "lt",
+        "lv", "nb", "nl", "nn", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk", "vi",
+    ];
+    let language = locale
+        .split(['_', '.', '@', '-'])
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if COMMA_LANGUAGES.contains(&language.as_str()) {
+        ','
+    } else {
+        '.'
+    }
+}
+
+/// Source addresses the OS would use to reach the internet, one per address
+/// family that has a route. Connecting a UDP socket only consults the
+/// routing table, so nothing is sent. Empty when there is no route, e.g. a
+/// pulled cable or dropped Wi-Fi (a captive portal still counts as a route).
+pub fn internet_route_sources() -> Vec<std::net::IpAddr> {
+    const PROBES: [(&str, &str); 2] = [
+        ("0.0.0.0:0", "1.1.1.1:53"),
+        ("[::]:0", "[2606:4700:4700::1111]:53"),
+    ];
+    PROBES
+        .iter()
+        .filter_map(|(bind, target)| {
+            let socket = std::net::UdpSocket::bind(bind).ok()?;
+            socket.connect(target).ok()?;
+            socket.local_addr().ok().map(|addr| addr.ip())
+        })
+        .collect()
+}
+
+/// Battery state as reported by the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerState {
+    /// Running from the battery rather than AC power
+    pub on_battery: bool,
+    /// Remaining charge, 0-100
+    pub percent: u8,
+}
+
+/// Current battery state, or None on machines without a battery or where
+/// the OS doesn't report one.
+pub fn power_state() -> Option<PowerState> {
+    platform_power_state()
+}
+
+#[cfg(target_os = "linux")]
+fn platform_power_state() -> Option<PowerState> {
+    let supplies: Vec<PowerSupply> = std::fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            let read = |name: &str| {
+                std::fs::read_to_string(path.join(name))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default()
+            };
+            PowerSupply {
+                kind: read("type"),
+                online: read("online") == "1",
+                status: read("status"),
+                capacity: read("capacity").parse().ok(),
+                device_scope: read("scope") == "Device",
+            }
+        })
+        .collect();
+    linux_power_state(&supplies)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_power_state() -> Option<PowerState> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_power_state() -> Option<PowerState> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // SAFETY: GetSystemPowerStatus only writes into the struct we pass
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    // Flag bit 128: no system battery (also set by 255, unknown)
+    if status.BatteryFlag & 128 != 0 || status.BatteryLifePercent > 100 {
+        return None;
+    }
+    Some(PowerState {
+        on_battery: status.ACLineStatus == 0,
+        percent: status.BatteryLifePercent,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn platform_power_state() -> Option<PowerState> {
+    None
+}
+
+/// One entry under /sys/class/power_supply.
+#[cfg(any(target_os = "linux", test))]
+struct PowerSupply {
+    kind: String,
+    online: bool,
+    status: String,
+    capacity: Option<u8>,
+    /// Batteries of peripherals (mice, headsets) rather than the system
+    device_scope: bool,
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn linux_power_state(supplies: &[PowerSupply]) -> Option<PowerState> {
+    let batteries: Vec<&PowerSupply> = supplies
+        .iter()
+        .filter(|s| s.kind == "Battery" && !s.device_scope)
+        .collect();
+    let capacities: Vec<u32> = batteries
+        .iter()
+        .filter_map(|b| b.capacity)
+        .map(u32::from)
+        .collect();
+    if capacities.is_empty() {
+        return None;
+    }
+    let ac_online = supplies.iter().any(|s| s.kind != "Battery" && s.online);
+    Some(PowerState {
+        on_battery: !ac_online && batteries.iter().any(|b| b.status == "Discharging"),
+        percent: (capacities.iter().sum::<u32>() / capacities.len() as u32).min(100) as u8,
+    })
+}
+
+/// Parse the output of `pmset -g batt`: the first line names the power
+/// source, the InternalBattery line carries the charge ("85%; discharging;").
+#[cfg(any(target_os = "macos", test))]
+fn parse_pmset(output: &str) -> Option<PowerState> {
+    let line = output.lines().find(|l| l.contains("InternalBattery"))?;
+    let percent = line
+        .split_whitespace()
+        .find_map(|word| word.strip_suffix("%;")?.parse::<u8>().ok())?;
+    Some(PowerState {
+        on_battery: output.contains("'Battery Power'"),
+        percent: percent.min(100),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trackers_to_merge() {
+        let global: Vec<String> = [
+            "udp://a.example:6969/announce",
+            "udp://b.example:1337/announce",
+            " ",
+            "UDP://A.EXAMPLE:6969/announce",
+        ]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+        let bare = "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a&dn=file";
+        assert_eq!(
+            trackers_to_merge(bare, &global),
+            vec![
+                "udp://a.example:6969/announce",
+                "udp://b.example:1337/announce"
+            ]
+        );
+
+        let listed = format!("{}&tr=udp%3A%2F%2Fa.example%3A6969%2Fannounce", bare);
+        assert_eq!(
+            trackers_to_merge(&listed, &global),
+            vec!["udp://b.example:1337/announce"]
+        );
+
+        let private = format!(
+            "{}&tr=https%3A%2F%2Ftracker.example%2Fannounce%3Fpasskey%3Dabc",
+            bare
+        );
+        assert!(trackers_to_merge(&private, &global).is_empty());
+        assert!(lists_private_tracker(&private));
+        assert!(!lists_private_tracker(&listed));
+        let hex_path = format!(
+            "{}&tr=https://tracker.example/0123456789abcdef0123456789abcdef/announce",
+            bare
+        );
+        assert!(trackers_to_merge(&hex_path, &global).is_empty());
+
+        let many = (0..5).fold(bare.to_string(), |uri, i| {
+            format!("{}&tr=udp://t{}.example/announce", uri, i)
+        });
+        assert!(trackers_to_merge(&many, &global).is_empty());
+
+        let long: Vec<String> = (0..50)
+            .map(|i| format!("udp://t{}.example/announce", i))
+            .collect();
+        assert_eq!(trackers_to_merge(bare, &long).len(), MAX_MERGED_TRACKERS);
+    }
+
+    #[test]
+    fn test_format_binary_units() {
+        let fmt = NumberFormat::default();
+        assert_eq!(fmt.speed(512), "512 B/s");
+        assert_eq!(fmt.speed(2048), "2.0 KB/s");
+        assert_eq!(fmt.speed(3 * 1024 * 1024), "3.0 MB/s");
+        assert_eq!(fmt.speed(2 * 1024 * 1024 * 1024), "2.0 GB/s");
+        assert_eq!(fmt.bytes(1536), "1.50 KB");
+        assert_eq!(fmt.bytes(0), "0 B");
+    }
+
+    #[test]
+    fn test_format_iec_and_si_units() {
+        let iec = NumberFormat {
+            units: UnitSystem::Iec,
+            ..Default::default()
+        };
+        assert_eq!(iec.bytes(1024 * 1024), "1.00 MiB");
+
+        let si = NumberFormat {
+            units: UnitSystem::Si,
+            ..Default::default()
+        };
+        assert_eq!(si.bytes(1000), "1.00 kB");
+        assert_eq!(si.bytes(1024), "1.02 kB");
+        assert_eq!(si.speed(999), "999 B/s");
+        assert_eq!(si.bytes(5_000_000_000_000_000), "5000.00 TB");
+    }
+
+    #[test]
+    fn test_format_decimal_separator() {
+        let fmt = NumberFormat {
+            decimal_separator: ',',
+            ..Default::default()
+        };
+        assert_eq!(fmt.bytes(1536), "1,50 KB");
+        assert_eq!(decimal_separator_for_locale("de_DE.UTF-8"), ',');
+        assert_eq!(decimal_separator_for_locale("pt_BR"), ',');
+        assert_eq!(decimal_separator_for_locale("en_US.UTF-8"), '.');
+        assert_eq!(decimal_separator_for_locale("C"), '.');
+    }
+
+    #[test]
+    fn test_format_eta() {
+        let fmt = NumberFormat::default();
+        assert_eq!(fmt.eta(100, 0), "--");
+        assert_eq!(fmt.eta(45, 1), "45s");
+        assert_eq!(fmt.eta(250, 1), "4m 10s");
+        assert_eq!(fmt.eta(7260, 1), "2h 1m");
+        assert_eq!(fmt.eta(90000, 1), "1d 1h");
+    }
+
+    #[test]
+    fn test_unit_system_from_setting() {
+        assert_eq!(UnitSystem::from_setting("si"), UnitSystem::Si);
+        assert_eq!(UnitSystem::from_setting("iec"), UnitSystem::Iec);
+        assert_eq!(UnitSystem::from_setting("binary"), UnitSystem::Binary);
+        assert_eq!(UnitSystem::from_setting("bogus"), UnitSystem::Binary);
+    }
+
+    fn supply(kind: &str, online: bool, status: &str, capacity: Option<u8>) -> PowerSupply {
+        PowerSupply {
+            kind: kind.into(),
+            online,
+            status: status.into(),
+            capacity,
+            device_scope: false,
+        }
+    }
+
+    #[test]
+    fn test_linux_power_state() {
+        let on_battery = [
+            supply("Mains", false, "", None),
+            supply("Battery", false, "Discharging", Some(42)),
+        ];
+        assert_eq!(
+            linux_power_state(&on_battery),
+            Some(PowerState {
+                on_battery: true,
+                percent: 42
+            })
+        );
+
+        let charging = [
+            supply("Mains", true, "", None),
+            supply("Battery", false, "Charging", Some(80)),
+        ];
+        assert_eq!(
+            linux_power_state(&charging),
+            Some(PowerState {
+                on_battery: false,
+                percent: 80
+            })
+        );
+
+        // A wireless mouse battery doesn't make a desktop a laptop
+        let mut mouse = supply("Battery", false, "Discharging", Some(10));
+        mouse.device_scope = true;
+        assert_eq!(
+            linux_power_state(&[supply("Mains", true, "", None), mouse]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_pmset() {
+        let battery = "Now drawing from 'Battery Power'\n \
+                       -InternalBattery-0 (id=4653155)\t85%; discharging; 4:12 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(battery),
+            Some(PowerState {
+                on_battery: true,
+                percent: 85
+            })
+        );
+
+        let ac = "Now drawing from 'AC Power'\n \
+                  -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(ac),
+            Some(PowerState {
+                on_battery: false,
+                percent: 100
+            })
+        );
+
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+    }
+}
@@ -1,11 +1,12 @@
-//! Input validation for URLs and torrent paths, shared by all commands.
+//! Input validation for URLs, torrent paths and settings values, shared by
+//! all commands.
 
 use crate::Error;
 use std::net::IpAddr;
 
 const MAX_URL_LENGTH: usize = 8192;
 
-/// Validate a download URL: must be http://, https://, or magnet:
+/// Validate a download URL: must be http://, https://, s3:// or magnet:
 /// Rejects file:// scheme, empty URLs, overly long URLs, and private IP addresses.
 pub fn validate_download_url(url: &str) -> crate::Result<()> {
     if url.is_empty() {
@@ -22,9 +23,12 @@ pub fn validate_download_url(url: &str) -> crate::Result<()> {
     if lower.starts_with("magnet:") {
         return Ok(());
     }
+    if crate::s3::is_s3_url(url) {
+        return crate::s3::parse_s3_url(url).map(|_| ());
+    }
     if !lower.starts_with("http://") && !lower.starts_with("https://") {
         return Err(Error::InvalidInput(format!(
-            "URL must use http://, https://, or magnet: scheme, got: {}",
+            "URL must use http://, https://, s3:// or magnet: scheme, got: {}",
             url.split("://").next().unwrap_or("unknown")
         )));
     }
@@ -45,23 +49,36 @@ pub fn validate_download_url(url: &str) -> crate::Result<()> {
     Ok(())
 }
 
-/// Validate a URL for recursive HTTP mirroring: http/https only (no magnet).
+/// Validate a URL fetched by the app or mirrored recursively: http/https
+/// only (no magnet or s3).
 pub fn validate_http_url(url: &str) -> crate::Result<()> {
-    if url.to_lowercase().starts_with("magnet:") {
+    if url.to_lowercase().starts_with("magnet:") || crate::s3::is_s3_url(url) {
         return Err(Error::InvalidInput(
-            "Recursive mirroring requires an http:// or https:// URL".into(),
+            "An http:// or https:// URL is required here".into(),
         ));
     }
     validate_download_url(url)
 }
 
+/// Validate a time of day in 24-hour `HH:MM` form, as used by quiet hours.
+pub fn validate_time_of_day(value: &str) -> crate::Result<()> {
+    let valid = value.len() == 5 && chrono::NaiveTime::parse_from_str(value, "%H:%M").is_ok();
+    if !valid {
+        return Err(Error::InvalidInput(format!(
+            "Expected a time as HH:MM (e.g. 22:00), got: {}",
+            value
+        )));
+    }
+    Ok(())
+}
+
 fn is_private_ip(ip: &IpAddr) -> bool {
     match ip {
         IpAddr::V4(v4) => {
             v4.is_loopback()             // 127.0.0.0/8
                 || v4.is_private()       // 10.0.0.0/8, 172.16.0.0/12, 192.168.0.0/16
                 || v4.is_link_local()    // 169.254.0.0/16
-                || v4.is_unspecified()   // 0.0.0.0
+                || v4.is_unspecified() // 0.0.0.0
         }
         IpAddr::V6(v6) => {
             v6.is_loopback()             // ::1
@@ -75,7 +92,9 @@ fn is_private_ip(ip: &IpAddr) -> bool {
 /// Validate a torrent file path: must end with .torrent and exist on disk.
 pub fn validate_torrent_path(file_path: &str) -> crate::Result<()> {
     if file_path.is_empty() {
-        return Err(Error::InvalidInput("Torrent file path cannot be empty".into()));
+        return Err(Error::InvalidInput(
+            "Torrent file path cannot be empty".into(),
+        ));
     }
     if !file_path.to_lowercase().ends_with(".torrent") {
         return Err(Error::InvalidInput(
@@ -91,6 +110,24 @@ pub fn validate_torrent_path(file_path: &str) -> crate::Result<()> {
     Ok(())
 }
 
+/// Validate a metalink file path: must end with .meta4 or .metalink and
+/// exist on disk.
+pub fn validate_metalink_path(file_path: &str) -> crate::Result<()> {
+    let lower = file_path.to_lowercase();
+    if !lower.ends_with(".meta4") && !lower.ends_with(".metalink") {
+        return Err(Error::InvalidInput(
+            "File must have a .meta4 or .metalink extension".into(),
+        ));
+    }
+    if !std::path::Path::new(file_path).exists() {
+        return Err(Error::InvalidInput(format!(
+            "Metalink file does not exist: {}",
+            file_path
+        )));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,6 +137,9 @@ mod tests {
         assert!(validate_download_url("https://example.com/file.zip").is_ok());
         assert!(validate_download_url("http://example.com/file.zip").is_ok());
         assert!(validate_download_url("magnet:?xt=urn:btih:abc123").is_ok());
+        assert!(validate_download_url("s3://my-bucket/images/disk.img").is_ok());
+        assert!(validate_download_url("s3://my-bucket").is_err());
+        assert!(validate_http_url("s3://my-bucket/images/disk.img").is_err());
     }
 
     #[test]
@@ -135,6 +175,17 @@ mod tests {
         assert!(validate_http_url("https://example.com/dir/").is_ok());
     }
 
+    #[test]
+    fn test_validate_time_of_day() {
+        assert!(validate_time_of_day("22:00").is_ok());
+        assert!(validate_time_of_day("07:30").is_ok());
+        assert!(validate_time_of_day("00:00").is_ok());
+        assert!(validate_time_of_day("24:00").is_err());
+        assert!(validate_time_of_day("7:30").is_err());
+        assert!(validate_time_of_day("22:60").is_err());
+        assert!(validate_time_of_day("").is_err());
+    }
+
     #[test]
     fn test_is_private_ip() {
         assert!(is_private_ip(&"127.0.0.1".parse().unwrap()));
@@ -0,0 +1,326 @@
+//! Verification of finished HTTP downloads. The file is hashed off the
+//! runtime with its progress sent as `download:verifying`, compared with the
+//! checksum it was added with, and the hash is kept with the download so
+//! the file can be checked again later. The engine itself only checks
+//! SHA-256 and MD5 checksums; SHA-1 and BLAKE3 ones are checked here.
+
+use crate::events::FailedDownload;
+use crate::types::{Download, DownloadType, IntegrityCheck};
+use crate::{AppState, Error, Result};
+use sha2::Digest;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Hashed per read.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashKind {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl HashKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha256" => Some(HashKind::Sha256),
+            "sha1" => Some(HashKind::Sha1),
+            "md5" => Some(HashKind::Md5),
+            "blake3" => Some(HashKind::Blake3),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashKind::Sha256 => "sha256",
+            HashKind::Sha1 => "sha1",
+            HashKind::Md5 => "md5",
+            HashKind::Blake3 => "blake3",
+        }
+    }
+
+    /// Kinds the engine checks itself before reporting the download done.
+    pub fn checked_by_engine(self) -> bool {
+        matches!(self, HashKind::Sha256 | HashKind::Md5)
+    }
+}
+
+/// A checksum in "kind:hex" form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub kind: HashKind,
+    /// Lowercase hex
+    pub digest: String,
+}
+
+impl Checksum {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (kind, digest) = value.trim().split_once(':')?;
+        let kind = HashKind::parse(kind)?;
+        let digest = digest.trim().to_ascii_lowercase();
+        let expected_len = match kind {
+            HashKind::Sha256 | HashKind::Blake3 => 64,
+            HashKind::Sha1 => 40,
+            HashKind::Md5 => 32,
+        };
+        (digest.len() == expected_len && digest.bytes().all(|b| b.is_ascii_hexdigit()))
+            .then_some(Checksum { kind, digest })
+    }
+}
+
+impl std::fmt::Display for Checksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.kind.as_str(), self.digest)
+    }
+}
+
+enum Hasher {
+    Sha256(sha2::Sha256),
+    Sha1(sha1::Sha1),
+    Md5(md5::Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(kind: HashKind) -> Self {
+        match kind {
+            HashKind::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashKind::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            HashKind::Md5 => Hasher::Md5(md5::Md5::new()),
+            HashKind::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(h) => hex::encode(h.finalize()),
+            Hasher::Sha1(h) => hex::encode(h.finalize()),
+            Hasher::Md5(h) => hex::encode(h.finalize()),
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Hash the file at `path`, calling `progress(done, total)` after each
+/// chunk. Blocking; run it with `spawn_blocking`.
+pub fn hash_file(
+    path: &Path,
+    kind: HashKind,
+    mut progress: impl FnMut(u64, u64),
+) -> std::io::Result<Checksum> {
+    let mut file = std::fs::File::open(path)?;
+    let total = file.metadata()?.len();
+    let mut hasher = Hasher::new(kind);
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut done = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        done += read as u64;
+        progress(done, total);
+    }
+    Ok(Checksum {
+        kind,
+        digest: hasher.finish(),
+    })
+}
+
+/// Hash a download's file, sending `download:verifying` as whole percents
+/// go by.
+async fn hash_download(
+    app: &AppHandle,
+    download: &Download,
+    path: PathBuf,
+    kind: HashKind,
+) -> Result<Checksum> {
+    let (app, gid, name) = (app.clone(), download.gid.clone(), download.name.clone());
+    tokio::task::spawn_blocking(move || {
+        let mut reported = None;
+        hash_file(&path, kind, |done, total| {
+            let percent = (done * 100).checked_div(total).unwrap_or(100);
+            if reported != Some(percent) {
+                reported = Some(percent);
+                let _ = app.emit(
+                    "download:verifying",
+                    serde_json::json!({
+                        "gid": gid,
+                        "name": name,
+                        "progress": percent as f64 / 100.0
+                    }),
+                );
+            }
+        })
+    })
+    .await
+    .map_err(|e| Error::Io(std::io::Error::other(e)))?
+    .map_err(Error::from)
+}
+
+/// Verify a download that just finished against `expected` (its checksum
+/// as added) and record the file's hash. Returns false if the file doesn't
+/// match, after failing the download; it should then stay where it is.
+pub async fn verify_completed(
+    state: &AppState,
+    app: &AppHandle,
+    gid: &str,
+    expected: Option<String>,
+) -> Result<bool> {
+    let adapter = state.get_adapter().await?;
+    let Some(download) = adapter
+        .get_status(gid)
+        .filter(|d| d.download_type == DownloadType::Http)
+    else {
+        return Ok(true);
+    };
+    // Gone if it was an error page deleted by the content check
+    let Some(path) = crate::preview::locate_file(&download, &[]) else {
+        return Ok(true);
+    };
+    let expected = expected.as_deref().and_then(Checksum::parse);
+
+    let actual = match &expected {
+        // The engine would have failed the download on a mismatch
+        Some(expected) if expected.kind.checked_by_engine() => expected.clone(),
+        _ => {
+            let kind = expected.as_ref().map_or(HashKind::Sha256, |e| e.kind);
+            hash_download(app, &download, path, kind).await?
+        }
+    };
+
+    if let Some(expected) = expected.filter(|expected| *expected != actual) {
+        let error = format!("Checksum mismatch: expected {}, got {}", expected, actual);
+        log::warn!("{}: {}", download.name, error);
+        adapter.fail(gid, error.clone());
+        let _ = app.emit(
+            "download:verification-failed",
+            serde_json::json!({
+                "gid": gid,
+                "name": download.name,
+                "expected": expected.to_string(),
+                "actual": actual.to_string(),
+            }),
+        );
+        crate::events::queue_failure(
+            app,
+            state,
+            FailedDownload {
+                gid: gid.to_string(),
+                name: download.name,
+                error,
+            },
+        );
+        return Ok(false);
+    }
+
+    state
+        .get_db()
+        .await?
+        .record_file_hash_async(download, actual.to_string())
+        .await?;
+    Ok(true)
+}
+
+/// Hash a finished download's file again and compare it with the hash
+/// recorded when it finished. A download without one gets it recorded now.
+pub async fn recheck(state: &AppState, app: &AppHandle, gid: String) -> Result<IntegrityCheck> {
+    let db = state.get_db().await?;
+    let saved = db.get_file_hash_async(gid.clone()).await?;
+    let live = state
+        .get_adapter()
+        .await
+        .ok()
+        .and_then(|adapter| adapter.get_status(&gid));
+    let (download, recorded) = match (live, saved) {
+        (Some(live), saved) => (live, saved.and_then(|(_, hash)| hash)),
+        (None, Some((saved, hash))) => (saved, hash),
+        (None, None) => return Err(Error::NotFound(format!("Download not found: {}", gid))),
+    };
+    if download.download_type != DownloadType::Http {
+        return Err(Error::InvalidInput(
+            "Torrents are checked piece by piece by the engine".into(),
+        ));
+    }
+    let path = crate::preview::locate_file(&download, &[])
+        .ok_or_else(|| Error::NotFound(format!("File of {} not found", download.name)))?;
+
+    let recorded = recorded.as_deref().and_then(Checksum::parse);
+    let kind = recorded.as_ref().map_or(HashKind::Sha256, |r| r.kind);
+    let actual = hash_download(app, &download, path, kind).await?;
+    let matches = recorded.as_ref().map(|recorded| *recorded == actual);
+    if recorded.is_none() {
+        db.record_file_hash_async(download, actual.to_string())
+            .await?;
+    }
+    Ok(IntegrityCheck {
+        gid,
+        hash: actual.to_string(),
+        recorded: recorded.map(|r| r.to_string()),
+        matches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checksum() {
+        let sha1 = Checksum::parse(" SHA-1:A9993E364706816ABA3E25717850C26C9CD0D89D ").unwrap();
+        assert_eq!(
+            sha1.to_string(),
+            "sha1:a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(Checksum::parse("md5:abc"), None);
+        assert_eq!(Checksum::parse("crc32:0badf00d"), None);
+        assert_eq!(
+            Checksum::parse("a9993e364706816aba3e25717850c26c9cd0d89d"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let path = std::env::temp_dir().join(format!("gosh-fetch-verify-{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        let hash = |kind| hash_file(&path, kind, |_, _| {}).unwrap().to_string();
+        assert_eq!(
+            hash(HashKind::Sha256),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash(HashKind::Sha1),
+            "sha1:a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(hash(HashKind::Md5), "md5:900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            hash(HashKind::Blake3),
+            "blake3:6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+
+        let mut reports = Vec::new();
+        hash_file(&path, HashKind::Md5, |done, total| {
+            reports.push((done, total))
+        })
+        .unwrap();
+        assert_eq!(reports, vec![(3, 3)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
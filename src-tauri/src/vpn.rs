@@ -0,0 +1,64 @@
+//! VPN binding check for the torrent kill switch.
+//!
+//! The VPN counts as up when the named interface exists and the OS routes
+//! internet traffic out through it: for every address family that has a
+//! route at all, the source address the kernel picks must belong to the
+//! interface. A VPN that carries IPv4 while IPv6 still leaves through the
+//! physical NIC is treated as down.
+
+use crate::utils::internet_route_sources;
+use std::net::IpAddr;
+
+/// True if internet-bound traffic is routed through `interface`.
+pub fn routes_through_interface(interface: &str) -> bool {
+    let interface_addrs: Vec<IpAddr> = match local_ip_address::list_afinet_netifas() {
+        Ok(list) => list
+            .into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(interface))
+            .map(|(_, addr)| addr)
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to list network interfaces: {}", e);
+            Vec::new()
+        }
+    };
+    all_routes_within(&interface_addrs, &internet_route_sources())
+}
+
+fn all_routes_within(interface_addrs: &[IpAddr], route_sources: &[IpAddr]) -> bool {
+    !interface_addrs.is_empty()
+        && !route_sources.is_empty()
+        && route_sources
+            .iter()
+            .all(|src| interface_addrs.contains(src))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_all_routes_within() {
+        let vpn = [ip("10.8.0.2"), ip("fd00:8::2")];
+        assert!(all_routes_within(&vpn, &[ip("10.8.0.2")]));
+        assert!(all_routes_within(&vpn, &[ip("10.8.0.2"), ip("fd00:8::2")]));
+        // IPv6 leaking out of the physical interface
+        assert!(!all_routes_within(
+            &vpn,
+            &[ip("10.8.0.2"), ip("2001:db8::5")]
+        ));
+        assert!(!all_routes_within(&vpn, &[ip("192.168.1.20")]));
+        // Interface missing, or no route at all
+        assert!(!all_routes_within(&[], &[ip("10.8.0.2")]));
+        assert!(!all_routes_within(&vpn, &[]));
+    }
+
+    #[test]
+    fn test_missing_interface_is_down() {
+        assert!(!routes_through_interface("gosh-fetch-no-such-if0"));
+    }
+}
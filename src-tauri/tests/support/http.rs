@@ -0,0 +1,174 @@
+//! Minimal HTTP/1.1 file server for one in-memory file.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How the server behaves; set before or while a download runs.
+#[derive(Debug, Clone, Default)]
+pub struct HttpMode {
+    /// Ignore Range headers and always send the whole body with 200
+    pub no_ranges: bool,
+    /// Cap each response at this many bytes per second
+    pub throttle: Option<u64>,
+    /// Cut the connection halfway through the body for this many GETs
+    pub fail_first: usize,
+}
+
+/// A request the server saw, for asserting on engine behavior.
+#[derive(Debug, Clone)]
+pub struct SeenRequest {
+    pub method: String,
+    pub range_start: Option<u64>,
+}
+
+pub struct HttpFixture {
+    pub url: String,
+    mode: Arc<Mutex<HttpMode>>,
+    seen: Arc<Mutex<Vec<SeenRequest>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl HttpFixture {
+    /// Serve `body` at `http://127.0.0.1:<port>/<name>`.
+    pub async fn start(name: &str, body: Vec<u8>, mode: HttpMode) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/{}", listener.local_addr().unwrap(), name);
+        let body = Arc::new(body);
+        let mode = Arc::new(Mutex::new(mode));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let failures = Arc::new(AtomicUsize::new(0));
+
+        let task = {
+            let (mode, seen) = (mode.clone(), seen.clone());
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let (body, mode, seen, failures) =
+                        (body.clone(), mode.clone(), seen.clone(), failures.clone());
+                    tokio::spawn(async move {
+                        let _ = serve(stream, &body, &mode, &seen, &failures).await;
+                    });
+                }
+            })
+        };
+
+        Self {
+            url,
+            mode,
+            seen,
+            task,
+        }
+    }
+
+    pub fn set_mode(&self, mode: HttpMode) {
+        *self.mode.lock().unwrap() = mode;
+    }
+
+    pub fn requests(&self) -> Vec<SeenRequest> {
+        self.seen.lock().unwrap().clone()
+    }
+}
+
+impl Drop for HttpFixture {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    body: &[u8],
+    mode: &Mutex<HttpMode>,
+    seen: &Mutex<Vec<SeenRequest>>,
+    failures: &AtomicUsize,
+) -> std::io::Result<()> {
+    let head = read_head(&mut stream).await?;
+    let mut lines = head.lines();
+    let method = lines
+        .next()
+        .and_then(|l| l.split_whitespace().next())
+        .unwrap_or("GET")
+        .to_string();
+    let range = lines
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("range"))
+        .and_then(|(_, v)| parse_range(v.trim(), body.len() as u64));
+
+    let mode = mode.lock().unwrap().clone();
+    let range = if mode.no_ranges { None } else { range };
+    seen.lock().unwrap().push(SeenRequest {
+        method: method.clone(),
+        range_start: range.map(|(start, _)| start),
+    });
+
+    let (status, start, end) = match range {
+        Some((start, end)) => ("206 Partial Content", start, end),
+        None => ("200 OK", 0, body.len() as u64),
+    };
+    let mut headers = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nContent-Type: application/octet-stream\r\nConnection: close\r\n",
+        status,
+        end - start
+    );
+    if !mode.no_ranges {
+        headers.push_str("Accept-Ranges: bytes\r\n");
+    }
+    if range.is_some() {
+        headers.push_str(&format!(
+            "Content-Range: bytes {}-{}/{}\r\n",
+            start,
+            end - 1,
+            body.len()
+        ));
+    }
+    headers.push_str("\r\n");
+    stream.write_all(headers.as_bytes()).await?;
+    if method == "HEAD" {
+        return Ok(());
+    }
+
+    let mut slice = &body[start as usize..end as usize];
+    if failures.fetch_add(1, Ordering::SeqCst) < mode.fail_first {
+        slice = &slice[..slice.len() / 2];
+        stream.write_all(slice).await?;
+        return Ok(()); // dropping the stream cuts the transfer short
+    }
+
+    match mode.throttle {
+        None => stream.write_all(slice).await?,
+        Some(rate) => {
+            // 10 writes per second at the configured rate
+            let chunk = (rate / 10).max(1) as usize;
+            for part in slice.chunks(chunk) {
+                stream.write_all(part).await?;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+    stream.flush().await
+}
+
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut buf = Vec::new();
+    let mut byte = [0u8; 1];
+    while !buf.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).await? == 0 {
+            break;
+        }
+        buf.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// Parse "bytes=a-b" / "bytes=a-" into a half-open [start, end) range.
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let start: u64 = start.parse().ok()?;
+    let end = match end {
+        "" => len,
+        e => (e.parse::<u64>().ok()? + 1).min(len),
+    };
+    (start < end).then_some((start, end))
+}
@@ -0,0 +1,160 @@
+//! A one-seeder torrent swarm: an HTTP tracker that always returns the
+//! local seeder, and a seeder speaking just enough of the peer wire
+//! protocol (handshake, bitfield, unchoke, request/piece) to serve a file.
+
+use sha1::Digest;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const PIECE_LENGTH: usize = 16 * 1024;
+
+pub struct SwarmFixture {
+    /// Bencoded single-file .torrent announcing to the local tracker
+    pub torrent: Vec<u8>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl SwarmFixture {
+    pub async fn start(name: &str, content: Vec<u8>) -> Self {
+        let content = Arc::new(content);
+
+        let seeder = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let seeder_port = seeder.local_addr().unwrap().port();
+        let tracker = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+
+        let info = info_dict(name, &content);
+        let info_hash = sha1(&info);
+        let torrent = [
+            b"d8:announce".to_vec(),
+            bytes(announce.as_bytes()),
+            b"4:info".to_vec(),
+            info,
+            b"e".to_vec(),
+        ]
+        .concat();
+
+        let tracker_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = tracker.accept().await {
+                tokio::spawn(answer_announce(stream, seeder_port));
+            }
+        });
+        let seeder_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = seeder.accept().await {
+                let content = content.clone();
+                tokio::spawn(async move {
+                    let _ = seed(stream, info_hash, &content).await;
+                });
+            }
+        });
+
+        Self {
+            torrent,
+            tasks: vec![tracker_task, seeder_task],
+        }
+    }
+}
+
+impl Drop for SwarmFixture {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Reply to any tracker request with the seeder as the only (compact) peer.
+async fn answer_announce(mut stream: TcpStream, seeder_port: u16) {
+    let mut buf = [0u8; 4096];
+    let _ = stream.read(&mut buf).await;
+    let mut peers = vec![127, 0, 0, 1];
+    peers.extend_from_slice(&seeder_port.to_be_bytes());
+    let body = [
+        b"d8:completei1e10:incompletei0e8:intervali60e5:peers".to_vec(),
+        bytes(&peers),
+        b"e".to_vec(),
+    ]
+    .concat();
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+}
+
+async fn seed(mut stream: TcpStream, info_hash: [u8; 20], content: &[u8]) -> std::io::Result<()> {
+    // Handshake: pstrlen, pstr, 8 reserved bytes, info hash, peer id
+    let mut handshake = [0u8; 68];
+    stream.read_exact(&mut handshake).await?;
+    if handshake[28..48] != info_hash {
+        return Ok(());
+    }
+    let mut reply = vec![19u8];
+    reply.extend_from_slice(b"BitTorrent protocol");
+    reply.extend_from_slice(&[0u8; 8]);
+    reply.extend_from_slice(&info_hash);
+    reply.extend_from_slice(b"-GF0000-fixtureseed0");
+    stream.write_all(&reply).await?;
+
+    let pieces = content.len().div_ceil(PIECE_LENGTH);
+    let mut bitfield = vec![0u8; pieces.div_ceil(8)];
+    for i in 0..pieces {
+        bitfield[i / 8] |= 0x80 >> (i % 8);
+    }
+    send(&mut stream, 5, &bitfield).await?;
+    send(&mut stream, 1, &[]).await?; // unchoke
+
+    loop {
+        let len = stream.read_u32().await? as usize;
+        if len == 0 {
+            continue; // keep-alive
+        }
+        let mut msg = vec![0u8; len];
+        stream.read_exact(&mut msg).await?;
+        match msg[0] {
+            2 => send(&mut stream, 1, &[]).await?, // interested -> unchoke
+            6 if len == 13 => {
+                let index = u32::from_be_bytes(msg[1..5].try_into().unwrap()) as usize;
+                let begin = u32::from_be_bytes(msg[5..9].try_into().unwrap()) as usize;
+                let length = u32::from_be_bytes(msg[9..13].try_into().unwrap()) as usize;
+                let offset = index * PIECE_LENGTH + begin;
+                let end = (offset + length).min(content.len());
+                let mut payload = msg[1..9].to_vec();
+                payload.extend_from_slice(&content[offset..end]);
+                send(&mut stream, 7, &payload).await?;
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn send(stream: &mut TcpStream, id: u8, payload: &[u8]) -> std::io::Result<()> {
+    stream.write_u32(payload.len() as u32 + 1).await?;
+    stream.write_u8(id).await?;
+    stream.write_all(payload).await
+}
+
+fn info_dict(name: &str, content: &[u8]) -> Vec<u8> {
+    let hashes: Vec<u8> = content.chunks(PIECE_LENGTH).flat_map(sha1).collect();
+    [
+        format!("d6:lengthi{}e4:name", content.len()).into_bytes(),
+        bytes(name.as_bytes()),
+        format!("12:piece lengthi{}e6:pieces", PIECE_LENGTH).into_bytes(),
+        bytes(&hashes),
+        b"e".to_vec(),
+    ]
+    .concat()
+}
+
+/// Bencode a byte string.
+fn bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = format!("{}:", data.len()).into_bytes();
+    out.extend_from_slice(data);
+    out
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    sha1::Sha1::digest(data).into()
+}
@@ -3,14 +3,132 @@
 // Rust snake_case automatically by Tauri).
 import { invoke } from '@tauri-apps/api/core';
 import type {
+  AddPlan,
+  AddUrlsResult,
+  CrashRecoveryInfo,
+  DbMaintenanceReport,
   Download,
+  DownloadChanges,
+  DownloadDetails,
   DownloadOptions,
+  DownloadPreview,
+  FileFilter,
   GlobalStats,
+  HistoryStats,
+  HostActivity,
+  HostProfile,
+  IntegrityCheck,
+  OrphanedPartial,
+  PeerInfo,
+  ProgressSnapshot,
+  RenamePreview,
+  SwarmGeography,
   TorrentInfo,
   MagnetInfo,
+  UrlListImport,
 } from '../types/download';
-import type { Settings } from '../types/settings';
+import type {
+  CategoryRule,
+  DownloadLocation,
+  DownloadLocationInfo,
+  DownloadTemplate,
+  RenameRule,
+  SettingIssue,
+  Settings,
+} from '../types/settings';
 import type { MirrorJob, MirrorManifest, MirrorOptions } from '../types/mirror';
+import type { TaskProgress } from '../types/task';
+import type { UpdateInfo } from '../types/update';
+
+export interface EngineHealth {
+  running: boolean;
+  restartCount: number;
+  lastError: string | null;
+  startedAt: string | null;
+  uptimeSecs: number | null;
+  fdBudget: FdBudget;
+}
+
+/** Open file descriptors the engine may use */
+export interface FdBudget {
+  /** Soft open file limit; null if unlimited or unknown */
+  limit: number | null;
+  /** Most the engine's caps let it open */
+  worstCase: number;
+  /** Open now (Linux only) */
+  open: number | null;
+  /** Why the engine's caps were lowered below the settings */
+  clamped: string | null;
+}
+
+/** Which download events a subscription gets; empty lists match everything */
+export interface EventFilter {
+  gids?: string[];
+  /** Event names, with or without the `download:` prefix */
+  events?: string[];
+}
+
+/** Event delivered on `download-events:<subscription id>` */
+export interface SubscribedEvent {
+  event: string;
+  gid: string;
+  payload: unknown;
+}
+
+/** Protocols and optional features of this build, from `get_capabilities` */
+export interface Capabilities {
+  appVersion: string;
+  engine: string;
+  engineVersion: string;
+  /** Command and event API version; bumped on incompatible changes */
+  apiVersion: number;
+  protocols: { name: string; version: string | null }[];
+  features: string[];
+}
+
+/** What the system load guard sees and does */
+export interface LoadGuardStatus {
+  enabled: boolean;
+  /** Whether the load has been high long enough to ease off */
+  active: boolean;
+  /** Latest readings; null where the OS doesn't report them */
+  cpuPercent: number | null;
+  diskQueue: number | null;
+  activeSince: string | null;
+  /** GIDs of the low-priority downloads it paused */
+  paused: string[];
+}
+
+/** True for the error the open commands return when a download's file was moved or deleted */
+export function isFileMissing(error: unknown): boolean {
+  return String(error).startsWith('file missing:');
+}
+
+export interface UiFilters {
+  status: 'all' | 'active' | 'paused' | 'completed';
+  search: string;
+}
+
+/** get_ui_snapshot result; only `version` and `changed` when nothing changed. */
+export interface UiSnapshot {
+  version: number;
+  changed: boolean;
+  downloads?: (Download & {
+    formatted: {
+      totalSize: string;
+      completedSize: string;
+      downloadSpeed: string;
+      uploadSpeed: string;
+      eta: string;
+      progress: string;
+    };
+    visible: boolean;
+  })[];
+  filters?: UiFilters;
+  stats?: GlobalStats;
+  formattedStats?: { downloadSpeed: string; uploadSpeed: string };
+  health?: EngineHealth;
+}
 
 export interface BatchResult {
   succeeded: string[];
@@ -23,7 +141,9 @@ export const api = {
   addDownload: (url: string, options?: DownloadOptions) =>
     invoke<string>('add_download', { url, options }),
   addUrls: (urls: string[], options?: DownloadOptions) =>
-    invoke<string[]>('add_urls', { urls, options }),
+    invoke<AddUrlsResult>('add_urls', { urls, options }),
+  planDownload: (url: string, options?: DownloadOptions) =>
+    invoke<AddPlan>('plan_download', { url, options }),
   pauseDownload: (gid: string) => invoke<void>('pause_download', { gid }),
   pauseAll: () => invoke<BatchResult>('pause_all'),
   resumeDownload: (gid: string) => invoke<void>('resume_download', { gid }),
@@ -33,25 +153,44 @@ export const api = {
   removeDownload: (gid: string, deleteFiles: boolean = false) =>
     invoke<void>('remove_download', { gid, deleteFiles }),
   getDownloadStatus: (gid: string) => invoke<Download>('get_download_status', { gid }),
+  getDownloadDetails: (gid: string) => invoke<DownloadDetails>('get_download_details', { gid }),
+  verifyDownload: (gid: string) => invoke<IntegrityCheck>('verify_download', { gid }),
   getAllDownloads: () => invoke<Download[]>('get_all_downloads'),
+  getChanges: (sinceVersion?: number, waitMs?: number) =>
+    invoke<DownloadChanges>('get_changes', { sinceVersion, waitMs }),
   getActiveDownloads: () => invoke<Download[]>('get_active_downloads'),
   getGlobalStats: () => invoke<GlobalStats>('get_global_stats'),
+  getHostStats: () => invoke<HostActivity[]>('get_host_stats'),
   setSpeedLimit: (downloadLimit?: number, uploadLimit?: number) =>
     invoke<void>('set_speed_limit', { downloadLimit, uploadLimit }),
+  addMetalinkFile: (filePath: string, options?: DownloadOptions) =>
+    invoke<string[]>('add_metalink_file', { filePath, options }),
+  /** `source` is a .txt/.csv/.html file path or the list itself */
+  importUrlList: (source: string, extensions?: string[]) =>
+    invoke<UrlListImport>('import_url_list', { source, extensions }),
+  previewDownload: (gid: string, bytes?: number) =>
+    invoke<DownloadPreview>('preview_download', { gid, bytes }),
 
   // Torrent commands
   addTorrentFile: (filePath: string, options?: DownloadOptions) =>
     invoke<string>('add_torrent_file', { filePath, options }),
+  planTorrentFile: (filePath: string, options?: DownloadOptions) =>
+    invoke<AddPlan>('plan_torrent_file', { filePath, options }),
+  addTorrentUrl: (url: string, options?: DownloadOptions) =>
+    invoke<string>('add_torrent_url', { url, options }),
   addMagnet: (magnetUri: string, options?: DownloadOptions) =>
     invoke<string>('add_magnet', { magnetUri, options }),
   getTorrentFiles: (gid: string) => invoke<any[]>('get_torrent_files', { gid }),
   selectTorrentFiles: (gid: string, fileIndices: number[]) =>
     invoke<void>('select_torrent_files', { gid, fileIndices }),
+  applyFileFilter: (gid: string, rules: FileFilter) =>
+    invoke<number[]>('apply_file_filter', { gid, rules }),
   parseTorrentFile: (filePath: string) =>
     invoke<TorrentInfo>('parse_torrent_file', { filePath }),
   parseMagnetUri: (magnetUri: string) =>
     invoke<MagnetInfo>('parse_magnet_uri', { magnetUri }),
-  getPeers: (gid: string) => invoke<any[]>('get_peers', { gid }),
+  getPeers: (gid: string) => invoke<PeerInfo[]>('get_peers', { gid }),
+  getSwarmGeography: (gid: string) => invoke<SwarmGeography>('get_swarm_geography', { gid }),
 
   // Recursive mirroring commands
   discoverRecursive: (url: string, options?: DownloadOptions, recursive?: MirrorOptions) =>
@@ -74,7 +213,23 @@ export const api = {
   updateTrackerList: () => invoke<string[]>('update_tracker_list'),
   applySettingsToEngine: (settings: Settings) =>
     invoke<void>('apply_settings_to_engine', { settings }),
+  validateSettings: (settings: Settings) =>
+    invoke<SettingIssue[]>('validate_settings', { settings }),
   getUserAgentPresets: () => invoke<[string, string][]>('get_user_agent_presets'),
+  getCategories: () => invoke<CategoryRule[]>('get_categories'),
+  setCategories: (rules: CategoryRule[]) => invoke<CategoryRule[]>('set_categories', { rules }),
+  resetCategories: () => invoke<CategoryRule[]>('reset_categories'),
+  listDownloadLocations: () => invoke<DownloadLocationInfo[]>('list_download_locations'),
+  getDownloadTemplates: () => invoke<DownloadTemplate[]>('get_download_templates'),
+  saveDownloadTemplate: (template: DownloadTemplate) =>
+    invoke<DownloadTemplate>('save_download_template', { template }),
+  deleteDownloadTemplate: (name: string) => invoke<void>('delete_download_template', { name }),
+  getRenameRules: () => invoke<RenameRule[]>('get_rename_rules'),
+  setRenameRules: (rules: RenameRule[]) => invoke<RenameRule[]>('set_rename_rules', { rules }),
+  previewRename: (name: string, url?: string, category?: string, rules?: RenameRule[]) =>
+    invoke<RenamePreview | null>('preview_rename', { name, url, category, rules }),
+  setDownloadLocations: (locations: DownloadLocation[]) =>
+    invoke<DownloadLocationInfo[]>('set_download_locations', { locations }),
 
   // Priority and scheduling
   setPriority: (gid: string, priority: string) =>
@@ -85,11 +240,29 @@ export const api = {
   // System commands
   getEngineVersion: () =>
     invoke<{ name: string; version: string; running: boolean }>('get_engine_version'),
+  getEngineHealth: () => invoke<EngineHealth>('get_engine_health'),
+  restartEngine: () => invoke<void>('restart_engine'),
+  listTasks: () => invoke<TaskProgress[]>('list_tasks'),
+  cancelTask: (id: string) => invoke<void>('cancel_task', { id }),
+  subscribeDownloadEvents: (filter: EventFilter) =>
+    invoke<string>('subscribe_download_events', { filter }),
+  unsubscribeDownloadEvents: (id: string) => invoke<void>('unsubscribe_download_events', { id }),
+  getLoadGuardStatus: () => invoke<LoadGuardStatus>('get_load_guard_status'),
+  getUiSnapshot: (sinceVersion?: number) =>
+    invoke<UiSnapshot>('get_ui_snapshot', { sinceVersion }),
+  getProgressSnapshots: (gids?: string[]) =>
+    invoke<ProgressSnapshot[]>('get_progress_snapshots', { gids }),
+  setUiFilters: (filters: UiFilters) =>
+    invoke<void>('set_ui_filters', { filters }),
   openDownloadFolder: (path: string) => invoke<void>('open_download_folder', { path }),
   openFileLocation: (filePath: string) => invoke<void>('open_file_location', { filePath }),
+  openDownloadFile: (gid: string) => invoke<void>('open_download_file', { gid }),
+  revealDownload: (gid: string) => invoke<void>('reveal_download', { gid }),
+  getDownloadPath: (gid: string) => invoke<string>('get_download_path', { gid }),
   getDefaultDownloadPath: () => invoke<string>('get_default_download_path'),
   getAppVersion: () => invoke<string>('get_app_version'),
   getAppInfo: () => invoke<any>('get_app_info'),
+  getCapabilities: () => invoke<Capabilities>('get_capabilities'),
   getDiskSpace: (path?: string) =>
     invoke<{ total: number; free: number }>('get_disk_space', { path }),
   performSystemAction: (action: 'sleep' | 'shutdown' | 'close', forceCloseApps: boolean = false) =>
@@ -99,15 +272,35 @@ export const api = {
     invoke<({ kind: 'magnet'; uri: string } | { kind: 'torrentFile'; path: string })[]>(
       'get_pending_open_requests'
     ),
+  getUpdateInfo: () => invoke<UpdateInfo>('get_update_info'),
+  getCrashRecoveryInfo: () => invoke<CrashRecoveryInfo | null>('get_crash_recovery_info'),
+  restoreSession: (selection: string[]) => invoke<string[]>('restore_session', { selection }),
+  listOrphanedPartials: () => invoke<OrphanedPartial[]>('list_orphaned_partials'),
+  resumeOrphanedPartial: (path: string) => invoke<string>('resume_orphaned_partial', { path }),
+  deleteOrphanedPartials: (paths: string[]) => invoke<number>('delete_orphaned_partials', { paths }),
 
   // Database commands
   dbGetCompletedHistory: () => invoke<Download[]>('db_get_completed_history'),
   dbSaveDownload: (download: Download) => invoke<void>('db_save_download', { download }),
   dbRemoveDownload: (gid: string) => invoke<void>('db_remove_download', { gid }),
+  dbSetFavorite: (gid: string, favorite: boolean) =>
+    invoke<void>('db_set_favorite', { gid, favorite }),
   dbClearHistory: () => invoke<void>('db_clear_history'),
+  /** Archive the finished downloads that are due now; returns how many moved */
+  archiveDownloads: () => invoke<number>('archive_downloads'),
+  dbGetHistoryStats: () => invoke<HistoryStats>('db_get_history_stats'),
+  getHostProfiles: () => invoke<HostProfile[]>('get_host_profiles'),
   dbGetSettings: () => invoke<Settings>('db_get_settings'),
   dbSaveSettings: (settings: Settings) => invoke<void>('db_save_settings', { settings }),
   dbLoadIncomplete: () => invoke<Download[]>('db_load_incomplete'),
+  restoreIncomplete: () => invoke<string[]>('restore_incomplete'),
+  dbMaintenance: (reportOnly?: boolean) =>
+    invoke<DbMaintenanceReport>('db_maintenance', { reportOnly }),
+  dbExportHistory: (path: string, format: 'csv' | 'json') =>
+    invoke<string>('db_export_history', { path, format }),
+  /** Daily seeding statistics from `from` to `to` (YYYY-MM-DD, inclusive); resolves to the row count */
+  exportStats: (path: string, format: 'csv' | 'json', from?: string, to?: string) =>
+    invoke<number>('export_stats', { path, format, from, to }),
 };
 
 export type { Settings };
@@ -9,8 +9,11 @@ import { stats } from '../stores/stats.svelte';
 import { notifications } from '../stores/notifications.svelte';
 import { mirror } from '../stores/mirror.svelte';
 import { ui } from '../stores/ui.svelte';
-import type { GlobalStats } from '../types/download';
+import { tasks } from '../stores/tasks.svelte';
+import type { FailureDigest, GlobalStats } from '../types/download';
 import type { MirrorJob } from '../types/mirror';
+import type { TaskProgress } from '../types/task';
+import type { UpdateInfo } from '../types/update';
 
 let refreshTimer: ReturnType<typeof setTimeout> | null = null;
 let onCompletionTriggered = false;
@@ -241,6 +244,12 @@ export async function startEventBridge(): Promise<() => void> {
     listen<{ connected: boolean; restarting: boolean }>('engine-status', (e) => {
       if (!e.payload.connected && !e.payload.restarting) {
         stats.setDisconnected();
+        void api
+          .getEngineHealth()
+          .then((health) => stats.setDisconnected(health.lastError))
+          .catch(() => {
+            // Health is informational; keep the plain disconnected strip
+          });
       }
     }),
 
@@ -262,8 +271,29 @@ export async function startEventBridge(): Promise<() => void> {
     listen('download:failed', (e) => {
       scheduleDownloadsRefresh();
       persistDownloadSnapshot(e.payload);
-      const name = extractName(e.payload);
-      if (name) notifications.add('failed', name);
+    }),
+    listen<{ gid: string; progress: number }>('download:verifying', (e) => {
+      downloads.applyVerifying(e.payload.gid, e.payload.progress);
+    }),
+    // The failure itself is notified with the batched failures below
+    listen<{ gid: string }>('download:verification-failed', (e) => {
+      downloads.applyVerifying(e.payload.gid, 1);
+      scheduleDownloadsRefresh();
+      persistDownloadSnapshot(e.payload);
+    }),
+    // Failures are notified in batches so a network drop doesn't produce one
+    // notification per download
+    listen<FailureDigest>('download:failure-digest', (e) => {
+      const { count, grouped, reason, failures } = e.payload;
+      if (grouped) {
+        notifications.add(
+          'failures',
+          `${count} downloads failed: ${reason}`,
+          failures.map((f) => `${f.name}: ${f.error}`)
+        );
+      } else {
+        for (const f of failures) notifications.add('failed', f.name);
+      }
     }),
     listen('download:removed', (e) => {
       scheduleDownloadsRefresh();
@@ -288,11 +318,61 @@ export async function startEventBridge(): Promise<() => void> {
       scheduleDownloadsRefresh();
       persistDownloadSnapshot(e.payload);
     }),
+    listen<{ online: boolean; gids: string[] }>('network:status', (e) => {
+      scheduleDownloadsRefresh();
+      const { online, gids } = e.payload;
+      if (gids.length === 0) return;
+      const count = gids.length === 1 ? '1 download' : `${gids.length} downloads`;
+      if (online) {
+        notifications.add('resumed', `Network restored: ${count} resumed`);
+      } else {
+        notifications.add('paused', `Network lost: ${count} waiting for network`);
+      }
+    }),
+    listen<{ interface: string; gids: string[] }>('vpn:down', (e) => {
+      scheduleDownloadsRefresh();
+      const { interface: iface, gids } = e.payload;
+      notifications.add('paused', `VPN ${iface} is down: ${gids.length} torrent(s) paused`);
+    }),
+    listen<{ interface: string; gids: string[] }>('vpn:up', (e) => {
+      scheduleDownloadsRefresh();
+      const { interface: iface, gids } = e.payload;
+      notifications.add('resumed', `VPN ${iface} is back: ${gids.length} torrent(s) resumed`);
+    }),
+    listen<{ active: boolean; percent: number | null }>('power:battery-saver', (e) => {
+      const { active, percent } = e.payload;
+      const charge = percent === null ? '' : ` (${percent}%)`;
+      notifications.add('power', active ? `Battery saver on${charge}` : 'Battery saver off: full speed restored');
+    }),
+    listen<{ active: boolean; cpuPercent: number | null; diskQueue: number | null; gids: string[] }>(
+      'load-guard:changed',
+      (e) => {
+        scheduleDownloadsRefresh();
+        const { active, gids } = e.payload;
+        const count = gids.length === 1 ? '1 download' : `${gids.length} downloads`;
+        if (active) {
+          notifications.add('paused', `System busy: easing off${gids.length ? `, ${count} paused` : ''}`);
+        } else {
+          notifications.add('resumed', `System load back to normal${gids.length ? `: ${count} resumed` : ''}`);
+        }
+      }
+    ),
+    listen<UpdateInfo>('update:available', (e) => {
+      notifications.add('update', `Gosh-Fetch ${e.payload.latestVersion}`);
+    }),
+    listen<{ gid: string; name: string; reason: string }>('download:auto-stopped', (e) => {
+      scheduleDownloadsRefresh();
+      persistDownloadSnapshot(e.payload);
+      notifications.add('paused', e.payload.name);
+    }),
 
     // Recursive mirroring job lifecycle
     listen<MirrorJob>('recursive:added', (e) => mirror.applyUpsert(e.payload)),
     listen<MirrorJob>('recursive:updated', (e) => mirror.applyUpsert(e.payload)),
     listen<{ id: string }>('recursive:removed', (e) => mirror.applyRemoved(e.payload.id)),
+
+    // Background tasks (exports, maintenance)
+    listen<TaskProgress>('task:progress', (e) => tasks.applyProgress(e.payload)),
   ]);
 
   // Tell the backend we're ready; it returns magnet/.torrent open requests
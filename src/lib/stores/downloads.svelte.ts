@@ -2,7 +2,7 @@
 // coupled through fetchDownloads.fulfilled).
 import { SvelteMap } from 'svelte/reactivity';
 import { api } from '../api/commands';
-import type { Download, DownloadOptions } from '../types/download';
+import type { AddUrlsResult, Download, DownloadOptions } from '../types/download';
 
 const ORDER_KEY = 'gosh-fetch-queue-order';
 
@@ -30,18 +30,24 @@ function getBucket(index: number, total: number): string {
 
 class DownloadStore {
   byGid = new SvelteMap<string, Download>();
+  /** Finished downloads whose file is being hashed, progress 0-1 */
+  verifying = new SvelteMap<string, number>();
   completedHistory = $state<Download[]>([]);
   isLoading = $state(false);
   error = $state<string | null>(null);
   gidOrder = $state<string[]>(loadOrder());
   isDragging = $state(false);
+  /** Version of the last `getChanges` applied, so only what moved comes back */
+  private changesVersion: number | undefined;
 
   all = $derived([...this.byGid.values()]);
   active = $derived(this.all.filter((d) => d.status === 'active' || d.status === 'waiting'));
-  paused = $derived(this.all.filter((d) => d.status === 'paused'));
+  paused = $derived(
+    this.all.filter((d) => d.status === 'paused' || d.status === 'waiting_for_network')
+  );
   errored = $derived(this.all.filter((d) => d.status === 'error'));
   completed = $derived.by(() => {
-    const engineCompleted = this.all.filter((d) => d.status === 'complete');
+    const engineCompleted = this.all.filter((d) => d.status === 'complete' || d.status === 'warning');
     const engineGids = new Set(engineCompleted.map((d) => d.gid));
     const historyOnly = this.completedHistory.filter((d) => !engineGids.has(d.gid));
     return [...engineCompleted, ...historyOnly];
@@ -51,10 +57,12 @@ class DownloadStore {
     this.isLoading = true;
     this.error = null;
     try {
-      const downloads = await api.getAllDownloads();
-      this.byGid.clear();
-      for (const d of downloads) this.byGid.set(d.gid, d);
-      this.reconcileOrder(downloads);
+      const changes = await api.getChanges(this.changesVersion);
+      if (changes.full) this.byGid.clear();
+      for (const gid of changes.removed) this.byGid.delete(gid);
+      for (const d of changes.downloads) this.byGid.set(d.gid, d);
+      this.changesVersion = changes.version;
+      this.reconcileOrder([...this.byGid.values()]);
     } catch (e) {
       this.error = e instanceof Error ? e.message : 'Failed to fetch downloads';
     } finally {
@@ -62,15 +70,21 @@ class DownloadStore {
     }
   }
 
+  /** Record hashing progress from `download:verifying`. */
+  applyVerifying(gid: string, progress: number) {
+    if (progress >= 1) this.verifying.delete(gid);
+    else this.verifying.set(gid, progress);
+  }
+
   /** Drop order entries for removed downloads, append newcomers. */
   private reconcileOrder(downloads: Download[]) {
     if (this.isDragging) return;
     const currentGids = new Set(downloads.map((d) => d.gid));
     const filtered = this.gidOrder.filter((gid) => currentGids.has(gid));
     const ordered = new Set(filtered);
-    for (const d of downloads) {
-      if (!ordered.has(d.gid)) filtered.push(d.gid);
-    }
+    const newcomers = downloads.filter((d) => !ordered.has(d.gid));
+    newcomers.sort((a, b) => (a.addedOrder ?? 0) - (b.addedOrder ?? 0));
+    for (const d of newcomers) filtered.push(d.gid);
     this.gidOrder = filtered;
     saveOrder(filtered);
   }
@@ -79,11 +93,21 @@ class DownloadStore {
     this.completedHistory = await api.dbGetCompletedHistory();
   }
 
+  /** Favorites are stored with the history, not reported by the engine. */
+  isFavorite(gid: string): boolean {
+    return this.completedHistory.some((d) => d.gid === gid && d.favorite);
+  }
+
+  async setFavorite(gid: string, favorite: boolean): Promise<void> {
+    await api.dbSetFavorite(gid, favorite);
+    this.completedHistory = this.completedHistory.map((d) => (d.gid === gid ? { ...d, favorite } : d));
+  }
+
   async addDownload(url: string, options?: DownloadOptions): Promise<string> {
     return api.addDownload(url, options);
   }
 
-  async addUrls(urls: string[], options?: DownloadOptions): Promise<string[]> {
+  async addUrls(urls: string[], options?: DownloadOptions): Promise<AddUrlsResult> {
     return api.addUrls(urls, options);
   }
 
@@ -95,6 +119,14 @@ class DownloadStore {
     return api.addTorrentFile(filePath, options);
   }
 
+  async addMetalinkFile(filePath: string, options?: DownloadOptions): Promise<string[]> {
+    return api.addMetalinkFile(filePath, options);
+  }
+
+  async addTorrentUrl(url: string, options?: DownloadOptions): Promise<string> {
+    return api.addTorrentUrl(url, options);
+  }
+
   async pause(gid: string): Promise<void> {
     await api.pauseDownload(gid);
   }
@@ -169,26 +201,16 @@ class DownloadStore {
     }
   }
 
-  /** Re-add downloads recorded as incomplete in the app DB (startup restore). */
+  /**
+   * Re-add downloads recorded as incomplete in the app DB that the engine
+   * didn't bring back (startup restore). Folders, names, priorities and
+   * paused state are kept.
+   */
   async restoreIncomplete(): Promise<void> {
-    const incompleteDownloads = await api.dbLoadIncomplete();
-    for (const download of incompleteDownloads) {
-      try {
-        if (download.downloadType === 'magnet' && download.magnetUri) {
-          await api.addMagnet(download.magnetUri);
-        } else if (download.downloadType === 'magnet' && download.infoHash) {
-          await api.addMagnet(`magnet:?xt=urn:btih:${download.infoHash}`);
-        } else if (download.downloadType === 'torrent' && download.magnetUri) {
-          await api.addMagnet(download.magnetUri);
-        } else if (download.downloadType === 'torrent' && download.infoHash) {
-          await api.addMagnet(`magnet:?xt=urn:btih:${download.infoHash}`);
-        } else if (download.url) {
-          await api.addDownload(download.url);
-        }
-        await api.dbRemoveDownload(download.gid);
-      } catch (e) {
-        console.error(`Failed to restore download ${download.name}:`, e);
-      }
+    try {
+      await api.restoreIncomplete();
+    } catch (e) {
+      console.error('Failed to restore incomplete downloads:', e);
     }
   }
 }
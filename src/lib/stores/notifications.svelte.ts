@@ -1,29 +1,61 @@
+import { isWithinQuietHours } from '../utils/quietHours';
+
 export interface AppNotification {
   id: string;
-  type: 'completed' | 'failed' | 'added' | 'paused' | 'resumed';
+  type: 'completed' | 'failed' | 'failures' | 'added' | 'paused' | 'resumed' | 'digest' | 'power' | 'update';
   downloadName: string;
+  /** Per-download lines behind a grouped notification */
+  details?: string[];
   timestamp: number;
   read: boolean;
 }
 
+export interface QuietHours {
+  enabled: boolean;
+  /** Local time, HH:MM */
+  start: string;
+  end: string;
+}
+
 const MAX_NOTIFICATIONS = 50;
 
+// Notifications held back during quiet hours; everything else still shows
+const QUIET_TYPES: AppNotification['type'][] = ['completed', 'failed', 'failures'];
+
+const QUIET_CHECK_INTERVAL_MS = 60_000;
+
 class NotificationStore {
   items = $state<AppNotification[]>([]);
 
   unreadCount = $derived(this.items.filter((n) => !n.read).length);
 
-  add(type: AppNotification['type'], downloadName: string) {
+  private quietHours: QuietHours = { enabled: false, start: '22:00', end: '07:00' };
+  private held: Omit<AppNotification, 'id' | 'read'>[] = [];
+  private quietTimer: ReturnType<typeof setInterval> | null = null;
+
+  add(type: AppNotification['type'], downloadName: string, details?: string[]) {
+    if (QUIET_TYPES.includes(type) && this.isQuiet()) {
+      this.held.push({ type, downloadName, details, timestamp: Date.now() });
+      this.startQuietTimer();
+      return;
+    }
     const notification: AppNotification = {
       id: `${Date.now()}-${Math.random().toString(36).slice(2, 8)}`,
       type,
       downloadName,
+      details,
       timestamp: Date.now(),
       read: false,
     };
     this.items = [notification, ...this.items].slice(0, MAX_NOTIFICATIONS);
   }
 
+  /** Apply the quiet-hours settings; held notifications are released if the window no longer applies. */
+  setQuietHours(quietHours: QuietHours) {
+    this.quietHours = quietHours;
+    if (!this.isQuiet()) this.flushDigest();
+  }
+
   markAllRead() {
     this.items = this.items.map((n) => ({ ...n, read: true }));
   }
@@ -35,6 +67,43 @@ class NotificationStore {
   clearAll() {
     this.items = [];
   }
+
+  private isQuiet(): boolean {
+    const { enabled, start, end } = this.quietHours;
+    return enabled && isWithinQuietHours(new Date(), start, end);
+  }
+
+  private startQuietTimer() {
+    if (this.quietTimer) return;
+    this.quietTimer = setInterval(() => {
+      if (!this.isQuiet()) this.flushDigest();
+    }, QUIET_CHECK_INTERVAL_MS);
+  }
+
+  /** Replace everything held during quiet hours with a single summary. */
+  private flushDigest() {
+    if (this.quietTimer) {
+      clearInterval(this.quietTimer);
+      this.quietTimer = null;
+    }
+    const held = this.held;
+    this.held = [];
+    if (held.length === 0) return;
+
+    const completed = held.filter((n) => n.type === 'completed').length;
+    // A grouped failure notification stands for one download per detail line
+    const failed = held
+      .filter((n) => n.type !== 'completed')
+      .reduce((sum, n) => sum + (n.details?.length || 1), 0);
+    const parts: string[] = [];
+    if (completed > 0) parts.push(`${completed} completed`);
+    if (failed > 0) parts.push(`${failed} failed`);
+    const details = held.flatMap((n) => {
+      const prefix = n.type === 'completed' ? 'Completed' : 'Failed';
+      return n.details?.length ? n.details.map((d) => `${prefix}: ${d}`) : [`${prefix}: ${n.downloadName}`];
+    });
+    this.add('digest', `While quiet: ${parts.join(', ')}`, details);
+  }
 }
 
 export const notifications = new NotificationStore();
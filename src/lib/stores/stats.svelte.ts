@@ -6,7 +6,15 @@ class StatsStore {
   numActive = $state(0);
   numWaiting = $state(0);
   numStopped = $state(0);
+  /** All torrents, across restarts */
+  totalUploaded = $state(0);
+  shareRatio = $state(0);
+  /** All downloads, across restarts */
+  totalReceived = $state(0);
+  totalWasted = $state(0);
   isConnected = $state(false);
+  /** Why the engine last failed to start, when it is down. */
+  engineError = $state<string | null>(null);
 
   update(s: GlobalStats) {
     this.downloadSpeed = s.downloadSpeed;
@@ -14,11 +22,17 @@ class StatsStore {
     this.numActive = s.numActive;
     this.numWaiting = s.numWaiting;
     this.numStopped = s.numStopped;
+    this.totalUploaded = s.totalUploaded;
+    this.shareRatio = s.shareRatio;
+    this.totalReceived = s.totalReceived;
+    this.totalWasted = s.totalWasted;
     this.isConnected = true;
+    this.engineError = null;
   }
 
-  setDisconnected() {
+  setDisconnected(error: string | null = null) {
     this.isConnected = false;
+    this.engineError = error;
   }
 }
 
@@ -0,0 +1,37 @@
+// Background tasks (history export, maintenance) for the tasks popover.
+import { SvelteMap } from 'svelte/reactivity';
+import { api } from '../api/commands';
+import type { TaskProgress } from '../types/task';
+
+class TaskStore {
+  tasks = new SvelteMap<string, TaskProgress>();
+
+  all = $derived(
+    [...this.tasks.values()].sort((a, b) => b.startedAt.localeCompare(a.startedAt))
+  );
+  running = $derived(this.all.filter((t) => t.status === 'running'));
+
+  async fetchTasks(): Promise<void> {
+    const tasks = await api.listTasks();
+    this.tasks.clear();
+    for (const t of tasks) this.tasks.set(t.id, t);
+  }
+
+  async cancel(id: string): Promise<void> {
+    await api.cancelTask(id);
+  }
+
+  /** Upsert from task:progress events. */
+  applyProgress(payload: TaskProgress) {
+    if (payload?.id) this.tasks.set(payload.id, payload);
+  }
+
+  /** Forget finished tasks. */
+  clearFinished() {
+    for (const t of this.all) {
+      if (t.status !== 'running') this.tasks.delete(t.id);
+    }
+  }
+}
+
+export const tasks = new TaskStore();
@@ -1,6 +1,14 @@
 export type DownloadType = 'http' | 'torrent' | 'magnet';
 
-export type DownloadState = 'active' | 'waiting' | 'paused' | 'complete' | 'error' | 'removed';
+export type DownloadState =
+  | 'active'
+  | 'waiting'
+  | 'paused'
+  | 'waiting_for_network'
+  | 'complete'
+  | 'warning'
+  | 'error'
+  | 'removed';
 
 export type AppDownloadStateType = 'queued' | 'downloading' | 'stalled' | 'paused' | 'completed' | 'error' | 'retrying';
 
@@ -41,8 +49,69 @@ export interface Download {
   completedAt: string | null;
   errorMessage: string | null;
   connections: number;
+  /** Connected peers that have the whole torrent */
   seeders: number;
+  /** Seeders in the whole swarm, as last reported by a tracker */
+  swarmSeeders?: number;
+  /** Connected peers of a torrent, seeders included */
+  peers?: number;
+  /** Connected peers without the whole torrent */
+  leechers?: number;
   selectedFiles: number[] | null;
+  favorite?: boolean;
+  /** False when the server ignores byte ranges; null when unknown */
+  resumable?: boolean | null;
+  /** 1-based place among waiting downloads; null unless waiting */
+  queuePosition?: number | null;
+  /** 'low' | 'normal' | 'high' | 'critical'; null for history entries */
+  priority?: string | null;
+  /** Category it was saved under; '' for none */
+  category?: string;
+  /** Grows with each download added this session; 0 for history entries */
+  addedOrder?: number;
+  /** Upload totals of a torrent; null for HTTP downloads and history */
+  seeding?: SeedingStats | null;
+  /** Bytes received and wasted; null for history */
+  traffic?: TrafficStats | null;
+}
+
+export interface SeedingStats {
+  uploaded: number;
+  ratio: number;
+  seedTimeSecs: number;
+}
+
+/** Estimated from sampled speeds and sizes, across restarts */
+export interface TrafficStats {
+  received: number;
+  /** Received bytes that are part of the files */
+  payload: number;
+  /** Failed verification, fetched twice, or discarded after completing */
+  wasted: number;
+}
+
+/** One torrent's traffic on one day (UTC), as exportStats writes it */
+export interface SeedStatsDay {
+  /** YYYY-MM-DD */
+  date: string;
+  gid: string;
+  /** Empty when the torrent is gone from both the list and the history */
+  name: string;
+  infoHash: string | null;
+  uploaded: number;
+  downloaded: number;
+  seedTimeSecs: number;
+}
+
+export interface DownloadDetails extends Download {
+  /** URLs the server was reached through, starting with the one added */
+  redirectChain: string[];
+  /** Why a redirect was refused, if one was */
+  redirectError: string | null;
+  /** Time to the server's response headers when probed; null if not probed */
+  ttfbMs: number | null;
+  /** Part of ttfbMs spent on redirects */
+  redirectMs: number | null;
 }
 
 export interface DownloadOptions {
@@ -62,6 +131,47 @@ export interface DownloadOptions {
   checksum?: string;
   mirrors?: string[];
   sequential?: boolean;
+  rotation?: FileRotation;
+  fileFilter?: FileFilter;
+  identityEncoding?: boolean;
+  /** Category to save into; picked from the category rules when omitted */
+  category?: string;
+  /** Named location to save into; the one last used for the category when omitted */
+  location?: string;
+  /** Redirects to follow; the max_redirects setting when omitted */
+  maxRedirects?: number;
+  /** Download template to start from; options given here win */
+  template?: string;
+  /** Metainfo changes for a .torrent before it is added */
+  torrentEdits?: TorrentEdits;
+  /** Add a magnet link without trackers from the global list */
+  skipGlobalTrackers?: boolean;
+  /** Add the download paused */
+  paused?: boolean;
+}
+
+/** What to do when an HTTP download's target file already exists. */
+export type FileRotation =
+  | { policy: 'overwrite' }
+  | { policy: 'timestampSuffix' }
+  | { policy: 'keepLast'; count: number };
+
+/**
+ * Torrent file selection by path. Patterns are case-insensitive globs;
+ * prefix one with `re:` for a regular expression.
+ */
+/** Left out = kept, empty string = removed. source and private change the info hash */
+export interface TorrentEdits {
+  comment?: string;
+  /** Unix seconds; 0 removes it */
+  creationDate?: number;
+  source?: string;
+  private?: boolean;
+}
+
+export interface FileFilter {
+  include?: string[];
+  exclude?: string[];
 }
 
 export interface TorrentFile {
@@ -93,4 +203,249 @@ export interface GlobalStats {
   numActive: number;
   numWaiting: number;
   numStopped: number;
+  /** Bytes uploaded by all torrents, across restarts */
+  totalUploaded: number;
+  /** Uploaded over downloaded bytes across all torrents */
+  shareRatio: number;
+  /** Bytes received by all downloads, across restarts */
+  totalReceived: number;
+  /** Of those, bytes that ended up in no file */
+  totalWasted: number;
+}
+
+export interface FailureDigest {
+  count: number;
+  grouped: boolean;                // Show one notification rather than one per failure
+  reason: string;                  // Most common error in the batch
+  failures: { gid: string; name: string; error: string }[];
+}
+
+export interface HostStats {
+  host: string;
+  downloads: number;
+  totalBytes: number;
+  averageSpeed: number;
+  averageElapsedSecs: number;
+  /** Median time to first byte of the host's recent probes */
+  medianTtfbMs: number | null;
+}
+
+/** Connection counts learned for a host, from getHostProfiles */
+export interface HostProfile {
+  host: string;
+  /** Fewest connections first */
+  connections: ConnectionProfile[];
+  /** What the next download from the host gets when auto-tuning is on */
+  suggestedConnections: number | null;
+  /** Sent whole files for ranges past the first byte; downloads use one connection */
+  brokenRanges: boolean;
+}
+
+export interface ConnectionProfile {
+  connections: number;
+  /** Finished downloads measured */
+  samples: number;
+  averageSpeed: number;
+  updatedAt: string;
+}
+
+/** Live figures for one host's running HTTP downloads, from getHostStats */
+export interface HostActivity {
+  host: string;
+  downloads: number;
+  connections: number;
+  /** One per download whose server ignores ranges, the engine limit for the rest */
+  connectionLimit: number;
+  downloadSpeed: number;
+  /** Finished or failed in the last 15 minutes */
+  recentFinished: number;
+  recentFailed: number;
+  /** 0-1; null when none finished recently */
+  errorRate: number | null;
+  /** no-ranges, connection-limit, global-limit, battery-saver, load-guard */
+  throttled: string[];
+  suggestedConnections: number | null;
+}
+
+export interface PeerInfo {
+  ip: string;
+  port: number;
+  client: string | null;
+  downloadSpeed: number;
+  uploadSpeed: number;
+  /** ISO country code; null when GeoIP lookup is off or the address unknown */
+  country: string | null;
+}
+
+/** Connected peers of a torrent by country, from getSwarmGeography */
+export interface SwarmGeography {
+  /** Most peers first */
+  countries: CountryPeers[];
+  /** Peers the GeoIP database doesn't know */
+  unknown: number;
+}
+
+export interface CountryPeers {
+  country: string;
+  peers: number;
+  downloadSpeed: number;
+  uploadSpeed: number;
+}
+
+export interface DownloadPreview {
+  gid: string;
+  path: string;
+  /** The bytes, base64-encoded */
+  data: string;
+  /** Sniffed from magic bytes */
+  mime: string | null;
+  /** False while downloading: later bytes may not be written yet */
+  complete: boolean;
+}
+
+/** What adding a download would do; nothing is created */
+export interface AddPlan {
+  /** Null for a torrent file */
+  url: string | null;
+  /** Null when neither the URL nor the server suggest a name */
+  name: string | null;
+  directory: string;
+  path: string | null;
+  /** Where an HTTP download is written before being moved to `directory` */
+  tempDirectory: string | null;
+  category: string | null;
+  /** Name the file gets once finished, if a rename rule matches it */
+  renamed: RenamePreview | null;
+  /** After the template, settings defaults, category and server probe */
+  options: DownloadOptions;
+  warnings: string[];
+}
+
+/** Name a rename rule gives a file */
+export interface RenamePreview {
+  /** The rule that matched */
+  rule: string;
+  name: string;
+}
+
+/** One URL from a list read by `importUrlList` */
+export interface ImportedUrl {
+  url: string;
+  /** Per-row file name and directory from a CSV list */
+  filename: string | null;
+  dir: string | null;
+  /** Set when the URL is already in the download list or history */
+  warning: string | null;
+}
+
+/** Preview of a URL list; nothing is added */
+export interface UrlListImport {
+  format: 'text' | 'csv' | 'html';
+  entries: ImportedUrl[];
+  /** Repeats of a URL already listed */
+  duplicates: number;
+  /** Lines and links that aren't an addable URL or didn't pass the filter */
+  skipped: number;
+}
+
+/** Outcome of `addUrls`: one URL failing doesn't stop the rest */
+export interface AddUrlsResult {
+  /** Downloads added, in the order their URLs were given */
+  gids: string[];
+  failed: { url: string; error: string }[];
+}
+
+/** A download's last full progress, from `getProgressSnapshots` */
+export interface ProgressSnapshot {
+  gid: string;
+  /** Sequence number of the last `download:progress` sent for it */
+  seq: number;
+  progress: Record<string, unknown>;
+}
+
+/** Result of `verifyDownload` */
+export interface IntegrityCheck {
+  gid: string;
+  /** The file's hash now, "kind:hex" */
+  hash: string;
+  /** The hash recorded when the download finished */
+  recorded: string | null;
+  /** null when there was nothing to compare with; the hash is recorded now */
+  matches: boolean | null;
+}
+
+/** Downloads changed since a version, from `getChanges` */
+export interface DownloadChanges {
+  /** Pass back as `sinceVersion` next time */
+  version: number;
+  downloads: Download[];
+  /** GIDs no longer in the list */
+  removed: string[];
+  /** `downloads` is the whole list; drop anything not in it */
+  full: boolean;
+}
+
+export interface DbStorageInfo {
+  dbSize: number;
+  walSize: number;
+  /** Space vacuuming can give back */
+  freeBytes: number;
+  /** Rows per table */
+  rows: Record<string, number>;
+}
+
+/** Downloads a crashed session had running, from getCrashRecoveryInfo */
+export interface CrashRecoveryInfo {
+  sessionStartedAt: string | null;
+  downloads: InterruptedDownload[];
+}
+
+export interface InterruptedDownload {
+  gid: string;
+  name: string;
+  downloadType: DownloadType;
+  totalSize: number;
+  completedSize: number;
+  savePath: string;
+  /** Kept (paused) by the engine; otherwise restoring adds it again */
+  inEngine: boolean;
+}
+
+/** A .part file no download claims, from listOrphanedPartials */
+export interface OrphanedPartial {
+  path: string;
+  /** File name without .part */
+  name: string;
+  size: number;
+  modifiedAt: string | null;
+  /** Full size the server reported, from its sidecar */
+  totalSize: number | null;
+  /** Where it came from, if recorded; without it the file can only be deleted */
+  url: string | null;
+}
+
+export interface DbMaintenanceReport {
+  before: DbStorageInfo;
+  /** Null when only a report was asked for */
+  after: DbStorageInfo | null;
+  pruned: number;
+  durationMs: number;
+}
+
+export interface HistoryStats {
+  downloads: number;
+  totalBytes: number;
+  averageSpeed: number;
+  medianElapsedSecs: number;
+  /** Time to first byte of recently probed servers; null before any */
+  ttfb: LatencyPercentiles | null;
+  hosts: HostStats[];
+}
+
+/** Spread of a latency, in milliseconds */
+export interface LatencyPercentiles {
+  samples: number;
+  p50Ms: number;
+  p90Ms: number;
+  p99Ms: number;
 }
@@ -1,3 +1,5 @@
+import type { DownloadOptions } from './download';
+
 // Settings keys are snake_case on purpose: they mirror the SQLite settings
 // table and the Rust Settings struct.
 export interface Settings {
@@ -23,4 +25,127 @@ export interface Settings {
   read_timeout: number;
   max_retries: number;
   allocation_mode: string;
+  bt_strict_private_mode: boolean;
+  bt_magnet_merge_trackers: boolean;
+  bt_auto_stop_seeders: number;
+  unit_system: string;
+  failure_digest_threshold: number;
+  pause_on_network_loss: boolean;
+  bt_vpn_interface: string;
+  quiet_hours_enabled: boolean;
+  quiet_hours_start: string;
+  quiet_hours_end: string;
+  battery_mode_enabled: boolean;
+  battery_threshold: number;
+  battery_max_connections: number;
+  battery_download_limit: number;
+  battery_upload_limit: number;
+  http_identity_encoding: boolean;
+  delete_error_pages: boolean;
+  history_retention: string;
+  history_retention_days: number;
+  history_retention_count: number;
+  auto_db_maintenance: boolean;
+  update_check_enabled: boolean;
+  update_channel: string;
+  /** Where unfinished HTTP downloads are written; '' writes them in place */
+  temp_download_path: string;
+  retry_size_mismatch: boolean;
+  filename_priority: string;
+  max_redirects: number;
+  allow_insecure_redirects: boolean;
+  accept_invalid_certs: boolean;
+  s3_endpoint: string;
+  s3_region: string;
+  s3_access_key: string;
+  s3_secret_key: string;
+  auto_tune_connections: boolean;
+  bt_seed_time_limit: number;
+  bt_webseed_enabled: boolean;
+  bt_webseed_max_connections: number;
+  bt_encryption: string;
+  peer_geoip: boolean;
+  geoip_db_path: string;
+  load_guard_enabled: boolean;
+  load_guard_cpu_percent: number;
+  load_guard_disk_queue: number;
+  load_guard_max_connections: number;
+  load_guard_pause_low_priority: boolean;
+  stats_report_enabled: boolean;
+  stats_report_path: string;
+  stats_report_format: 'csv' | 'json';
+  stats_report_interval_hours: number;
+  shutdown_timeout_secs: number;
+  /** Move finished downloads older than archive_after_days to archive_path */
+  archive_enabled: boolean;
+  archive_after_days: number;
+  archive_path: string;
+  /** `download:progress` carries only the fields that changed */
+  delta_progress_events: boolean;
+}
+
+/** Automatic categorization rule; rules are checked in order. */
+export interface CategoryRule {
+  name: string;
+  /** Save directory; relative paths are under the download directory */
+  directory: string;
+  /** Extensions without the dot, e.g. "mkv" */
+  extensions: string[];
+  /** MIME types; "video/*" matches every video type */
+  mimeTypes: string[];
+  /** Days until finished downloads are archived; null follows the archive setting, 0 never */
+  archiveAfterDays?: number | null;
+  /** Archive folder; empty uses the archive setting's, relative paths are under it */
+  archiveDirectory?: string;
+}
+
+/** A problem with one field of proposed settings, from `validateSettings`. */
+export interface SettingIssue {
+  /** Settings key, e.g. "download_path" */
+  field: string;
+  /** Errors stop a save; warnings don't */
+  level: 'error' | 'warning';
+  message: string;
+}
+
+/** Named option preset, picked with `DownloadOptions.template`. */
+export interface DownloadTemplate {
+  name: string;
+  options: DownloadOptions;
+  /** Trackers added to magnet links */
+  trackers: string[];
+}
+
+/**
+ * Renames a finished HTTP download's file. `replace` takes `$1`-style groups
+ * and {name}, {ext}, {title}, {date}, {host} and {category} tokens.
+ */
+export interface RenameRule {
+  name: string;
+  /** Only this category; empty for any */
+  category?: string;
+  /** Only this host or its subdomains; empty for any */
+  host?: string;
+  /** Regex searched in the file name, ignoring case; empty matches it all */
+  find?: string;
+  replace: string;
+}
+
+/** A named place downloads can be saved to. */
+export interface DownloadLocation {
+  name: string;
+  /** Absolute path; "~" is expanded */
+  path: string;
+}
+
+/** A download location with the space on its drive, as listed when adding. */
+export interface DownloadLocationInfo extends DownloadLocation {
+  /** The download directory from settings, listed as "Default" */
+  isDefault: boolean;
+  /** False until the first download creates the directory */
+  exists: boolean;
+  freeSpace: number | null;
+  totalSpace: number | null;
+  /** Categories that last saved here; "" stands for uncategorized */
+  lastUsedFor: string[];
 }
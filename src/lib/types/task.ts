@@ -0,0 +1,17 @@
+export type TaskStatus = 'running' | 'completed' | 'failed' | 'cancelled';
+
+/** A long-running background operation, as reported by `task:progress`. */
+export interface TaskProgress {
+  id: string;
+  /** Machine-readable operation, e.g. "history-export" */
+  kind: string;
+  label: string;
+  status: TaskStatus;
+  /** 0-100, or null while the amount of work is unknown */
+  percent: number | null;
+  /** Current step while running, the error after a failure */
+  message: string | null;
+  cancellable: boolean;
+  startedAt: string;
+  finishedAt: string | null;
+}
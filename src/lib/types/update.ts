@@ -0,0 +1,18 @@
+/** Release feed check result, from `get_update_info` and `update:available`. */
+export interface UpdateInfo {
+  currentVersion: string;
+  /** Newest release on the channel, even when it isn't newer */
+  latestVersion: string;
+  channel: string;
+  updateAvailable: boolean;
+  prerelease: boolean;
+  releaseName: string | null;
+  /** Release notes (Markdown) */
+  changelog: string;
+  releaseUrl: string;
+  publishedAt: string | null;
+  /** Installer for this platform, if the release has one */
+  downloadUrl: string | null;
+  assetName: string | null;
+  assetSize: number | null;
+}
@@ -1,5 +1,5 @@
-import { describe, it, expect } from 'vitest';
-import { formatBytes, formatSpeed, formatProgress, formatEta, getStatusColor, getStatusText } from './format';
+import { describe, it, expect, afterEach } from 'vitest';
+import { formatBytes, formatSpeed, formatProgress, formatEta, getStatusColor, getStatusText, setUnitSystem } from './format';
 
 describe('formatBytes', () => {
   it('returns "0 B" for zero bytes', () => {
@@ -51,6 +51,27 @@ describe('formatSpeed', () => {
   });
 });
 
+describe('unit systems', () => {
+  afterEach(() => setUnitSystem('binary'));
+
+  it('uses KiB labels for iec', () => {
+    setUnitSystem('iec');
+    expect(formatBytes(1048576)).toBe('1.00 MiB');
+    expect(formatSpeed(1024)).toBe('1.0 KiB/s');
+  });
+
+  it('uses powers of 1000 for si', () => {
+    setUnitSystem('si');
+    expect(formatBytes(1000)).toBe('1.00 kB');
+    expect(formatSpeed(999)).toBe('999 B/s');
+  });
+
+  it('falls back to binary for unknown values', () => {
+    setUnitSystem('bogus');
+    expect(formatBytes(1024)).toBe('1.00 KB');
+  });
+});
+
 describe('formatProgress', () => {
   it('returns 0 when total is 0', () => {
     expect(formatProgress(0, 0)).toBe(0);
@@ -126,6 +147,10 @@ describe('getStatusColor', () => {
     expect(getStatusColor('retrying')).toBe('var(--color-warning)');
   });
 
+  it('returns warning color for suspect completed files', () => {
+    expect(getStatusColor('warning')).toBe('var(--color-warning)');
+  });
+
   it('returns success color for completed status', () => {
     expect(getStatusColor('complete')).toBe('var(--color-success)');
     expect(getStatusColor('completed')).toBe('var(--color-success)');
@@ -162,11 +187,19 @@ describe('getStatusText', () => {
     expect(getStatusText('paused')).toBe('Paused');
   });
 
+  it('returns "Waiting for network" for downloads paused while offline', () => {
+    expect(getStatusText('waiting_for_network')).toBe('Waiting for network');
+  });
+
   it('returns "Completed" for complete status', () => {
     expect(getStatusText('complete')).toBe('Completed');
     expect(getStatusText('completed')).toBe('Completed');
   });
 
+  it('returns "Suspect file" for warning status', () => {
+    expect(getStatusText('warning')).toBe('Suspect file');
+  });
+
   it('returns "Error" for error status', () => {
     expect(getStatusText('error')).toBe('Error');
   });
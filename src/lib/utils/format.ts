@@ -1,23 +1,47 @@
-const KB = 1024;
-const MB = KB * 1024;
-const GB = MB * 1024;
-const TB = GB * 1024;
+/**
+ * Unit family for sizes and speeds, from the `unit_system` setting:
+ * binary = powers of 1024 labeled KB/MB (the historical display),
+ * iec = powers of 1024 labeled KiB/MiB, si = powers of 1000 labeled kB/MB.
+ */
+export type UnitSystem = 'binary' | 'iec' | 'si';
+
+const UNIT_LABELS: Record<UnitSystem, string[]> = {
+  binary: ['KB', 'MB', 'GB', 'TB'],
+  iec: ['KiB', 'MiB', 'GiB', 'TiB'],
+  si: ['kB', 'MB', 'GB', 'TB'],
+};
+
+let unitSystem: UnitSystem = 'binary';
+
+export function setUnitSystem(system: string): void {
+  unitSystem = system === 'iec' || system === 'si' ? system : 'binary';
+}
+
+/** Scale a value into the largest unit below it, using the user's locale decimals. */
+function formatScaled(value: number, decimals: number, suffix: string): string {
+  const base = unitSystem === 'si' ? 1000 : 1024;
+  const labels = UNIT_LABELS[unitSystem];
+  let scaled = value;
+  let unit = -1;
+  while (unit < labels.length - 1 && scaled >= base) {
+    scaled /= base;
+    unit++;
+  }
+  if (unit < 0) return `${value} B${suffix}`;
+  const number = scaled.toLocaleString(undefined, {
+    minimumFractionDigits: decimals,
+    maximumFractionDigits: decimals,
+    useGrouping: false,
+  });
+  return `${number} ${labels[unit]}${suffix}`;
+}
 
 export function formatBytes(bytes: number): string {
-  if (bytes === 0) return '0 B';
-  if (bytes >= TB) return `${(bytes / TB).toFixed(2)} TB`;
-  if (bytes >= GB) return `${(bytes / GB).toFixed(2)} GB`;
-  if (bytes >= MB) return `${(bytes / MB).toFixed(2)} MB`;
-  if (bytes >= KB) return `${(bytes / KB).toFixed(2)} KB`;
-  return `${bytes} B`;
+  return formatScaled(bytes, 2, '');
 }
 
 export function formatSpeed(bytesPerSec: number): string {
-  if (bytesPerSec === 0) return '0 B/s';
-  if (bytesPerSec >= GB) return `${(bytesPerSec / GB).toFixed(1)} GB/s`;
-  if (bytesPerSec >= MB) return `${(bytesPerSec / MB).toFixed(1)} MB/s`;
-  if (bytesPerSec >= KB) return `${(bytesPerSec / KB).toFixed(1)} KB/s`;
-  return `${bytesPerSec} B/s`;
+  return formatScaled(bytesPerSec, 1, '/s');
 }
 
 export function formatProgress(completed: number, total: number): number {
@@ -27,7 +51,10 @@ export function formatProgress(completed: number, total: number): number {
 
 export function formatEta(bytesRemaining: number, speed: number): string {
   if (speed === 0 || bytesRemaining === 0) return '--';
-  const seconds = Math.floor(bytesRemaining / speed);
+  return formatDuration(Math.floor(bytesRemaining / speed));
+}
+
+export function formatDuration(seconds: number): string {
   if (seconds < 60) return `${seconds}s`;
   if (seconds < 3600) {
     const mins = Math.floor(seconds / 60);
@@ -79,7 +106,9 @@ export function getStatusColor(status: string): string {
     case 'retrying': return 'var(--color-warning)';
     case 'active': return 'var(--color-success)';
     case 'waiting': return 'var(--color-info)';
-    case 'paused': return 'var(--color-warning)';
+    case 'paused':
+    case 'waiting_for_network':
+    case 'warning': return 'var(--color-warning)';
     case 'completed':
     case 'complete': return 'var(--color-success)';
     case 'error':
@@ -93,8 +122,10 @@ export function getStatusText(status: string, downloadSpeed?: number): string {
     case 'active': return downloadSpeed && downloadSpeed > 0 ? 'Downloading' : 'Stalled';
     case 'waiting': return 'Queued';
     case 'paused': return 'Paused';
+    case 'waiting_for_network': return 'Waiting for network';
     case 'complete':
     case 'completed': return 'Completed';
+    case 'warning': return 'Suspect file';
     case 'error': return 'Error';
     case 'removed': return 'Removed';
     case 'downloading': return 'Downloading';
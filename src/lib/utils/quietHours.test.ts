@@ -0,0 +1,39 @@
+import { describe, it, expect } from 'vitest';
+import { isWithinQuietHours, parseClock } from './quietHours';
+
+function at(time: string): Date {
+  const [h, m] = time.split(':').map(Number);
+  return new Date(2026, 0, 15, h, m);
+}
+
+describe('parseClock', () => {
+  it('parses HH:MM into minutes since midnight', () => {
+    expect(parseClock('00:00')).toBe(0);
+    expect(parseClock('22:30')).toBe(1350);
+  });
+
+  it('rejects malformed times', () => {
+    expect(parseClock('7:30')).toBeNull();
+    expect(parseClock('24:00')).toBeNull();
+    expect(parseClock('')).toBeNull();
+  });
+});
+
+describe('isWithinQuietHours', () => {
+  it('handles a window that crosses midnight', () => {
+    expect(isWithinQuietHours(at('23:15'), '22:00', '07:00')).toBe(true);
+    expect(isWithinQuietHours(at('03:00'), '22:00', '07:00')).toBe(true);
+    expect(isWithinQuietHours(at('07:00'), '22:00', '07:00')).toBe(false);
+    expect(isWithinQuietHours(at('12:00'), '22:00', '07:00')).toBe(false);
+  });
+
+  it('handles a same-day window', () => {
+    expect(isWithinQuietHours(at('13:30'), '13:00', '14:00')).toBe(true);
+    expect(isWithinQuietHours(at('14:00'), '13:00', '14:00')).toBe(false);
+  });
+
+  it('treats an empty or invalid window as never quiet', () => {
+    expect(isWithinQuietHours(at('22:00'), '22:00', '22:00')).toBe(false);
+    expect(isWithinQuietHours(at('22:00'), 'late', '07:00')).toBe(false);
+  });
+});
@@ -0,0 +1,22 @@
+/** Minutes since midnight for an `HH:MM` string, or null if malformed. */
+export function parseClock(value: string): number | null {
+  const match = /^(\d{2}):(\d{2})$/.exec(value);
+  if (!match) return null;
+  const hours = Number(match[1]);
+  const minutes = Number(match[2]);
+  if (hours > 23 || minutes > 59) return null;
+  return hours * 60 + minutes;
+}
+
+/**
+ * Whether `now` (local time) falls in the quiet window from `start` to `end`.
+ * A window whose end is earlier than its start runs past midnight; equal
+ * start and end mean no quiet time at all.
+ */
+export function isWithinQuietHours(now: Date, start: string, end: string): boolean {
+  const from = parseClock(start);
+  const to = parseClock(end);
+  if (from === null || to === null || from === to) return false;
+  const minute = now.getHours() * 60 + now.getMinutes();
+  return from < to ? minute >= from && minute < to : minute >= from || minute < to;
+}